@@ -0,0 +1,552 @@
+//! Command-line tool for offline odometry, path generation, planning and
+//! path tracking. Run `diff-drive help` for the subcommands and options.
+use anyhow::{self, bail};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use diff_drive::ddrive::{DiffDrive, DiffDriveConfig, EncoderTicks, WheelState};
+use diff_drive::kinematics::{Ackermann, Mecanum, Omni3, SkidSteer};
+use diff_drive::mapping::OccupancyGrid;
+use diff_drive::planning::{path_length, GridPlanner};
+use diff_drive::plot::Plot;
+use diff_drive::rigid2d::{Pose2D, Vector2D};
+use diff_drive::tracking::{track, PurePursuit};
+use diff_drive::trajectory::Path;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+
+#[derive(Parser)]
+#[command(
+    name = "diff-drive",
+    version,
+    about = "Tools for differential drive robots"
+)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Replays an encoder CSV (time,left,right) through the odometry and
+    /// writes the poses as CSV (time,x,y,theta), starting at the origin
+    Odom {
+        /// robot config file (TOML, YAML or JSON)
+        #[arg(long)]
+        config: String,
+
+        /// encoder CSV, with wheel angles in radians unless --ticks is given
+        #[arg(long)]
+        input: String,
+
+        /// read the encoder columns as ticks
+        #[arg(long)]
+        ticks: bool,
+
+        /// pose CSV to write, standard output if not given
+        #[arg(long)]
+        output: Option<String>,
+
+        /// also plot the trace to an .svg or .png file
+        #[arg(long)]
+        plot: Option<String>,
+    },
+
+    /// Writes one of the built-in paths as CSV (x,y) or JSON
+    GenPath {
+        shape: Shape,
+
+        /// length of a line or radius of a circle in meters
+        #[arg(long, default_value_t = 1.0)]
+        size: f32,
+
+        /// number of waypoints
+        #[arg(long, default_value_t = 50)]
+        points: usize,
+
+        /// output format, taken from the output file name if not given
+        #[arg(long)]
+        format: Option<Format>,
+
+        /// file to write, standard output if not given
+        #[arg(long)]
+        output: Option<String>,
+    },
+
+    /// Plans a path on a PGM map with A* and writes it as CSV (x,y)
+    Plan {
+        /// map image, e.g. written by OccupancyGrid::write_pgm
+        #[arg(long)]
+        map: String,
+
+        /// side length of a map pixel in meters
+        #[arg(long)]
+        resolution: f64,
+
+        /// world coordinates x,y of the lower left corner of the map
+        #[arg(long, value_parser = parse_point, allow_hyphen_values = true, default_value = "0,0")]
+        origin: (f64, f64),
+
+        /// start position x,y
+        #[arg(long, value_parser = parse_point, allow_hyphen_values = true)]
+        start: (f64, f64),
+
+        /// goal position x,y
+        #[arg(long, value_parser = parse_point, allow_hyphen_values = true)]
+        goal: (f64, f64),
+
+        /// radius of the robot in meters
+        #[arg(long, default_value_t = 0.1)]
+        radius: f64,
+
+        /// allow the path through unknown cells
+        #[arg(long)]
+        allow_unknown: bool,
+
+        /// path CSV to write, standard output if not given
+        #[arg(long)]
+        output: Option<String>,
+
+        /// also plot the map and path to an .svg or .png file
+        #[arg(long)]
+        plot: Option<String>,
+    },
+
+    /// Simulates pure pursuit following a path CSV (x,y) and reports the
+    /// tracking error. Fails if the goal is not reached.
+    Track {
+        /// robot config file (TOML, YAML or JSON)
+        #[arg(long)]
+        config: String,
+
+        /// path CSV, e.g. written by gen-path or plan
+        #[arg(long)]
+        path: String,
+
+        #[command(flatten)]
+        base: BaseOptions,
+
+        /// lookahead distance in meters
+        #[arg(long, default_value_t = 0.3)]
+        lookahead: f64,
+
+        /// forward speed in m/s
+        #[arg(long, default_value_t = 0.2)]
+        speed: f64,
+
+        /// largest turn rate in rad/s
+        #[arg(long)]
+        max_angular_speed: Option<f64>,
+
+        /// simulation time step in seconds
+        #[arg(long, default_value_t = 0.02)]
+        dt: f64,
+
+        /// simulated time after which to give up, in seconds
+        #[arg(long, default_value_t = 300.0)]
+        timeout: f64,
+
+        /// start pose x,y,theta, the start of the path facing along it if
+        /// not given
+        #[arg(long, value_parser = parse_pose, allow_hyphen_values = true)]
+        start: Option<Pose2D<f64>>,
+
+        /// pose CSV (time,x,y,theta) to write
+        #[arg(long)]
+        output: Option<String>,
+
+        /// also plot the path and the driven trace to an .svg or .png file
+        #[arg(long)]
+        plot: Option<String>,
+    },
+}
+
+/// Kind of mobile base to track the path with. Its wheel radius and width
+/// come from the robot config
+#[derive(Args)]
+struct BaseOptions {
+    /// kind of base
+    #[arg(long, value_enum, default_value_t = Base::DiffDrive)]
+    base: Base,
+
+    /// distance between the front and rear axles of a mecanum or Ackermann
+    /// base, the wheel separation if not given
+    #[arg(long)]
+    wheelbase: Option<f64>,
+
+    /// ratio of the effective to the nominal track width of a skid-steer base
+    #[arg(long, default_value_t = 1.0)]
+    slip_factor: f64,
+
+    /// largest steering angle of an Ackermann base in rad
+    #[arg(long, default_value_t = 0.6)]
+    max_steering_angle: f64,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Base {
+    DiffDrive,
+    SkidSteer,
+    Mecanum,
+    Omni3,
+    Ackermann,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Shape {
+    SemiCircle,
+    Line,
+    Circle,
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum Format {
+    Csv,
+    Json,
+}
+
+fn parse_numbers(s: &str, count: usize) -> Result<Vec<f64>, String> {
+    let values: Vec<f64> = s
+        .split(',')
+        .map(|v| v.trim().parse::<f64>())
+        .collect::<Result<_, _>>()
+        .map_err(|e| e.to_string())?;
+    if values.len() != count {
+        return Err(format!("expected {} comma separated numbers", count));
+    }
+    Ok(values)
+}
+
+fn parse_point(s: &str) -> Result<(f64, f64), String> {
+    let v = parse_numbers(s, 2)?;
+    Ok((v[0], v[1]))
+}
+
+fn parse_pose(s: &str) -> Result<Pose2D<f64>, String> {
+    let v = parse_numbers(s, 3)?;
+    Ok(Pose2D::new(v[0], v[1], v[2]))
+}
+
+/// opens the output file, or standard output if there is none
+fn output(path: &Option<String>) -> anyhow::Result<Box<dyn Write>> {
+    Ok(match path {
+        Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+        None => Box::new(BufWriter::new(io::stdout())),
+    })
+}
+
+fn load_robot(config: &str) -> anyhow::Result<DiffDrive<f64>> {
+    Ok(DiffDriveConfig::<f64>::load(config)?.to_diff_drive())
+}
+
+fn write_poses(out: &mut dyn Write, poses: &[(f64, Pose2D<f64>)]) -> anyhow::Result<()> {
+    writeln!(out, "time,x,y,theta")?;
+    for (time, pose) in poses {
+        writeln!(out, "{},{},{},{}", time, pose.x, pose.y, pose.theta)?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
+fn odom(
+    config: &str,
+    input: &str,
+    ticks: bool,
+    output_path: &Option<String>,
+    plot: &Option<String>,
+) -> anyhow::Result<()> {
+    let mut robot = load_robot(config)?;
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .from_path(input)?;
+    let mut poses = vec![];
+    for (line, record) in reader.records().enumerate() {
+        let record = record?;
+        let fields: Vec<&str> = record.iter().map(|f| f.trim()).collect();
+        // skip a header line
+        if line == 0 && fields.first().is_none_or(|f| f.parse::<f64>().is_err()) {
+            continue;
+        }
+        if fields.len() < 3 {
+            bail!("expected time,left,right on line {}", line + 1);
+        }
+        let values: Vec<f64> = fields[..3]
+            .iter()
+            .map(|f| f.parse::<f64>())
+            .collect::<Result<_, _>>()?;
+        let pose = if ticks {
+            let ticks = EncoderTicks::new(values[1].round() as i64, values[2].round() as i64);
+            robot.forward_kinematics_ticks(ticks)
+        } else {
+            robot.forward_kinematics(WheelState::new(values[1], values[2]))
+        };
+        if poses.is_empty() {
+            // the first reading is where the encoders started, not a motion
+            robot.set_pose(Pose2D::new(0.0, 0.0, 0.0));
+            poses.push((values[0], robot.pose()));
+        } else {
+            poses.push((values[0], pose));
+        }
+    }
+    write_poses(&mut *output(output_path)?, &poses)?;
+    if let Some(plot_path) = plot {
+        let trace: Vec<Pose2D<f64>> = poses.iter().map(|(_, p)| *p).collect();
+        let mut plot = Plot::new(800, 600).with_title("odometry");
+        plot.add_pose_trace(&trace, 20, "odometry");
+        plot.save(plot_path)?;
+    }
+    Ok(())
+}
+
+fn gen_path(
+    shape: Shape,
+    size: f32,
+    points: usize,
+    format: Option<Format>,
+    output_path: &Option<String>,
+) -> anyhow::Result<()> {
+    if points < 2 {
+        bail!("a path needs at least 2 points");
+    }
+    let path = match shape {
+        Shape::SemiCircle => Path::semi_circle(size, points),
+        Shape::Line => Path::line(size, points),
+        Shape::Circle => Path::circle(size, points),
+    };
+    let format = format.unwrap_or(match output_path {
+        Some(p) if p.to_ascii_lowercase().ends_with(".json") => Format::Json,
+        _ => Format::Csv,
+    });
+    let mut out = output(output_path)?;
+    match format {
+        Format::Csv => {
+            for p in path.to_vec() {
+                writeln!(out, "{},{}", p.x, p.y)?;
+            }
+        }
+        Format::Json => {
+            serde_json::to_writer_pretty(&mut out, &path.to_vec())?;
+            writeln!(out)?;
+        }
+    }
+    out.flush()?;
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+fn plan(
+    map: &str,
+    resolution: f64,
+    origin: (f64, f64),
+    start: (f64, f64),
+    goal: (f64, f64),
+    radius: f64,
+    allow_unknown: bool,
+    output_path: &Option<String>,
+    plot: &Option<String>,
+) -> anyhow::Result<()> {
+    let grid = OccupancyGrid::read_pgm(map, resolution, Vector2D::new(origin.0, origin.1))?;
+    let path = GridPlanner::new(radius)
+        .with_unknown_allowed(allow_unknown)
+        .plan(
+            &grid,
+            Vector2D::new(start.0, start.1),
+            Vector2D::new(goal.0, goal.1),
+        )?;
+    let mut out = output(output_path)?;
+    for p in &path {
+        writeln!(out, "{},{}", p.x, p.y)?;
+    }
+    out.flush()?;
+    eprintln!(
+        "planned {} waypoints, {:.3} m",
+        path.len(),
+        path_length(&path)
+    );
+    if let Some(plot_path) = plot {
+        let mut plot = Plot::new(800, 600).with_title("plan");
+        plot.add_grid(&grid);
+        let poses: Vec<Pose2D<f64>> = path.iter().map(|p| Pose2D::new(p.x, p.y, 0.0)).collect();
+        plot.add_pose_trace(&poses, 0, "path");
+        plot.save(plot_path)?;
+    }
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+fn track_path(
+    config: &str,
+    path: &str,
+    base: &BaseOptions,
+    lookahead: f64,
+    speed: f64,
+    max_angular_speed: Option<f64>,
+    dt: f64,
+    timeout: f64,
+    start: Option<Pose2D<f64>>,
+    output_path: &Option<String>,
+    plot: &Option<String>,
+) -> anyhow::Result<()> {
+    if dt <= 0.0 {
+        bail!("the time step must be positive");
+    }
+    let config = DiffDriveConfig::<f64>::load(config)?;
+    let reference = Path::read_csv(path)?;
+    let waypoints: Vec<Vector2D<f64>> = reference
+        .to_vec()
+        .iter()
+        .map(|p| Vector2D::new(p.x as f64, p.y as f64))
+        .collect();
+    if waypoints.is_empty() {
+        bail!("{} has no waypoints", path);
+    }
+    let start = start.unwrap_or_else(|| {
+        let heading = match waypoints.get(1) {
+            Some(next) => (next.y - waypoints[0].y).atan2(next.x - waypoints[0].x),
+            None => 0.0,
+        };
+        Pose2D::new(waypoints[0].x, waypoints[0].y, heading)
+    });
+
+    let mut controller = PurePursuit::new(lookahead, speed);
+    if let Some(max) = max_angular_speed {
+        controller = controller.with_max_angular_speed(max);
+    }
+    let radius = config.left.radius;
+    let width = config.wheel_separation;
+    let wheelbase = base.wheelbase.unwrap_or(width);
+    let report = match base.base {
+        Base::DiffDrive => track(
+            &config.to_diff_drive(),
+            start,
+            &mut controller,
+            &waypoints,
+            dt,
+            timeout,
+        ),
+        Base::SkidSteer => track(
+            &SkidSteer::new(radius, width, base.slip_factor),
+            start,
+            &mut controller,
+            &waypoints,
+            dt,
+            timeout,
+        ),
+        Base::Mecanum => track(
+            &Mecanum::new(radius, wheelbase, width),
+            start,
+            &mut controller,
+            &waypoints,
+            dt,
+            timeout,
+        ),
+        Base::Omni3 => track(
+            &Omni3::new(radius, width / 2.0),
+            start,
+            &mut controller,
+            &waypoints,
+            dt,
+            timeout,
+        ),
+        Base::Ackermann => track(
+            &Ackermann::new(radius, wheelbase, width, base.max_steering_angle),
+            start,
+            &mut controller,
+            &waypoints,
+            dt,
+            timeout,
+        ),
+    }?;
+
+    println!(
+        "reached goal: {}",
+        if report.reached_goal { "yes" } else { "no" }
+    );
+    println!("time: {:.2} s", report.time);
+    println!("rms error: {:.4} m", report.rms_error);
+    println!("max error: {:.4} m", report.max_error);
+    println!("goal error: {:.4} m", report.goal_error);
+
+    if output_path.is_some() {
+        let poses: Vec<(f64, Pose2D<f64>)> = report
+            .poses
+            .iter()
+            .enumerate()
+            .map(|(i, p)| (i as f64 * dt, *p))
+            .collect();
+        write_poses(&mut *output(output_path)?, &poses)?;
+    }
+    if let Some(plot_path) = plot {
+        let mut plot = Plot::new(800, 600).with_title("tracking");
+        plot.add_path(&reference, "reference");
+        plot.add_pose_trace(&report.poses, 20, "robot");
+        plot.save(plot_path)?;
+    }
+    if !report.reached_goal {
+        bail!("the goal was not reached within {} s", timeout);
+    }
+    Ok(())
+}
+
+fn main() -> anyhow::Result<()> {
+    match Cli::parse().command {
+        Command::Odom {
+            config,
+            input,
+            ticks,
+            output,
+            plot,
+        } => odom(&config, &input, ticks, &output, &plot),
+        Command::GenPath {
+            shape,
+            size,
+            points,
+            format,
+            output,
+        } => gen_path(shape, size, points, format, &output),
+        Command::Plan {
+            map,
+            resolution,
+            origin,
+            start,
+            goal,
+            radius,
+            allow_unknown,
+            output,
+            plot,
+        } => plan(
+            &map,
+            resolution,
+            origin,
+            start,
+            goal,
+            radius,
+            allow_unknown,
+            &output,
+            &plot,
+        ),
+        Command::Track {
+            config,
+            path,
+            base,
+            lookahead,
+            speed,
+            max_angular_speed,
+            dt,
+            timeout,
+            start,
+            output,
+            plot,
+        } => track_path(
+            &config,
+            &path,
+            &base,
+            lookahead,
+            speed,
+            max_angular_speed,
+            dt,
+            timeout,
+            start,
+            &output,
+            &plot,
+        ),
+    }
+}
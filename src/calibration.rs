@@ -0,0 +1,240 @@
+//! Wheel odometry calibration.
+//!
+//! Estimates the effective left and right wheel radii and the effective wheel
+//! separation of a differential drive robot, either by least squares from
+//! encoder logs with ground-truth poses or with the UMBmark bidirectional
+//! square test (Borenstein and Feng, 1996).
+use crate::ddrive::{DiffDrive, WheelState};
+use crate::rigid2d::{Pose2D, Transform2D, Twist2D, Vector2D};
+use crate::utils::normalize_angle;
+use anyhow::{self, bail};
+use num_traits::Float;
+
+/// Effective kinematic parameters of a differential drive robot
+#[derive(Debug, Clone, Copy)]
+pub struct OdometryCalibration<T: Float> {
+    /// effective radius of the left wheel
+    pub left_wheel_radius: T,
+
+    /// effective radius of the right wheel
+    pub right_wheel_radius: T,
+
+    /// effective distance between the wheel contact points
+    pub wheel_separation: T,
+}
+
+impl<T: Float + Default> OdometryCalibration<T> {
+    /// constructs a new OdometryCalibration
+    pub fn new(left_wheel_radius: T, right_wheel_radius: T, wheel_separation: T) -> Self {
+        OdometryCalibration {
+            left_wheel_radius,
+            right_wheel_radius,
+            wheel_separation,
+        }
+    }
+
+    /// returns the parameters a DiffDrive was constructed with
+    pub fn from_diff_drive(robot: &DiffDrive<T>) -> Self {
+        OdometryCalibration::new(
+            robot.left_wheel_radius(),
+            robot.right_wheel_radius(),
+            robot.wheel_separation(),
+        )
+    }
+
+    /// constructs a DiffDrive with the calibrated parameters
+    pub fn to_diff_drive(&self) -> DiffDrive<T> {
+        DiffDrive::with_wheel_radii(
+            self.left_wheel_radius,
+            self.right_wheel_radius,
+            self.wheel_separation,
+        )
+    }
+}
+
+/// An encoder reading together with the ground-truth pose of the robot
+/// (e.g. from a motion capture system) at the same instant
+#[derive(Debug, Clone, Copy)]
+pub struct CalibrationSample<T: Float + Default> {
+    /// accumulated wheel angles in radians
+    pub wheel_angles: WheelState<T>,
+
+    /// ground-truth pose of the robot
+    pub pose: Pose2D<T>,
+}
+
+impl<T: Float + Default> CalibrationSample<T> {
+    /// constructs a new CalibrationSample
+    pub fn new(wheel_angles: WheelState<T>, pose: Pose2D<T>) -> Self {
+        CalibrationSample { wheel_angles, pose }
+    }
+}
+
+/// Estimates the wheel radii and separation by least squares from a log of
+/// encoder readings with ground-truth poses. Consecutive samples should be
+/// close enough that the robot follows a constant-curvature arc between them.
+///
+/// The distance travelled on each arc is linear in the wheel radii, and once
+/// the radii are known the heading change is linear in the inverse separation.
+pub fn calibrate_from_ground_truth<T: Float + Default>(
+    samples: &[CalibrationSample<T>],
+) -> anyhow::Result<OdometryCalibration<T>> {
+    if samples.len() < 3 {
+        bail!("calibration needs at least 3 samples");
+    }
+    let half = T::from(0.5).unwrap();
+
+    // arc length and heading change of every segment from the ground truth
+    let segments: Vec<(T, T, T, T)> = samples
+        .windows(2)
+        .map(|w| {
+            let dl = w[1].wheel_angles.left - w[0].wheel_angles.left;
+            let dr = w[1].wheel_angles.right - w[0].wheel_angles.right;
+            let delta = Transform2D::from_pose(w[0].pose).inv() * Transform2D::from_pose(w[1].pose);
+            let dtheta = normalize_angle(delta.rotation());
+            let chord = delta.translation().magnitude();
+            let arc = if dtheta.abs() < T::from(1e-9).unwrap() {
+                chord
+            } else {
+                chord * (dtheta * half) / (dtheta * half).sin()
+            };
+            let ds = if delta.translation().x < T::zero() {
+                -arc
+            } else {
+                arc
+            };
+            (dl, dr, ds, dtheta)
+        })
+        .collect();
+
+    // ds = 0.5 r_l dphi_l + 0.5 r_r dphi_r
+    let (mut a11, mut a12, mut a22, mut b1, mut b2) =
+        (T::zero(), T::zero(), T::zero(), T::zero(), T::zero());
+    for (dl, dr, ds, _) in &segments {
+        let (u, v) = (half * *dl, half * *dr);
+        a11 = a11 + u * u;
+        a12 = a12 + u * v;
+        a22 = a22 + v * v;
+        b1 = b1 + u * *ds;
+        b2 = b2 + v * *ds;
+    }
+    let det = a11 * a22 - a12 * a12;
+    if det.abs() < T::epsilon() * (a11 * a22).max(T::min_positive_value()) {
+        bail!("the log must contain turns as well as straight driving to separate the wheel radii");
+    }
+    let left = (a22 * b1 - a12 * b2) / det;
+    let right = (a11 * b2 - a12 * b1) / det;
+
+    // dtheta = (r_r dphi_r - r_l dphi_l) / b
+    let (mut uu, mut ut) = (T::zero(), T::zero());
+    for (dl, dr, _, dtheta) in &segments {
+        let u = right * *dr - left * *dl;
+        uu = uu + u * u;
+        ut = ut + u * *dtheta;
+    }
+    if ut.abs() < T::epsilon() {
+        bail!("the log must contain turns to estimate the wheel separation");
+    }
+
+    Ok(OdometryCalibration::new(left, right, uu / ut))
+}
+
+/// Direction the robot travels around the square in the UMBmark test
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SquareDirection {
+    Clockwise,
+    CounterClockwise,
+}
+
+/// Returns the wheel angle increments that drive the nominal robot once around
+/// a square with the given side length, starting at the origin facing +x and
+/// turning in place at each corner. Feed these to the real robot and record
+/// where it stops relative to where it started.
+pub fn umbmark_square<T: Float + Default>(
+    nominal: &DiffDrive<T>,
+    side: T,
+    direction: SquareDirection,
+) -> Vec<WheelState<T>> {
+    let quarter = T::from(std::f64::consts::FRAC_PI_2).unwrap();
+    let turn = match direction {
+        SquareDirection::CounterClockwise => quarter,
+        SquareDirection::Clockwise => -quarter,
+    };
+    let mut script = vec![];
+    for _ in 0..4 {
+        script.push(nominal.speeds_from_twist(Twist2D::new(T::zero(), side, T::zero())));
+        script.push(nominal.speeds_from_twist(Twist2D::new(turn, T::zero(), T::zero())));
+    }
+    script
+}
+
+/// The systematic errors found by the UMBmark test
+#[derive(Debug, Clone, Copy)]
+pub struct UmbmarkResult<T: Float> {
+    /// extra rotation at each 90 degree turn caused by the wheel separation error (radians)
+    pub alpha: T,
+
+    /// heading change over each straight leg caused by unequal wheel radii (radians)
+    pub beta: T,
+
+    /// ratio of the right wheel radius to the left wheel radius
+    pub radius_ratio: T,
+
+    /// corrected parameters
+    pub calibration: OdometryCalibration<T>,
+}
+
+/// Evaluates the UMBmark test. `clockwise` and `counter_clockwise` hold the
+/// final positions of the real robot after each run of the corresponding
+/// `umbmark_square`, in the frame of its starting pose. The corrected radii
+/// keep the average radius of the nominal robot, which the test can not observe.
+pub fn umbmark<T: Float + Default>(
+    nominal: &DiffDrive<T>,
+    side: T,
+    clockwise: &[Vector2D<T>],
+    counter_clockwise: &[Vector2D<T>],
+) -> anyhow::Result<UmbmarkResult<T>> {
+    if clockwise.is_empty() || counter_clockwise.is_empty() {
+        bail!("UMBmark needs at least one run in each direction");
+    }
+    let centroid = |points: &[Vector2D<T>]| {
+        let n = T::from(points.len()).unwrap();
+        let sum = points
+            .iter()
+            .fold(Vector2D::new(T::zero(), T::zero()), |a, b| a + *b);
+        Vector2D::new(sum.x / n, sum.y / n)
+    };
+    let cw = centroid(clockwise);
+    let ccw = centroid(counter_clockwise);
+
+    // to first order the return errors are
+    //   ccw = 2 L (alpha + beta) (1, -1)
+    //   cw  = 2 L (alpha - beta) (1, 1)
+    let eight_l = T::from(8.0).unwrap() * side;
+    let alpha = (cw.x + ccw.x + cw.y - ccw.y) / eight_l;
+    let beta = (ccw.x - cw.x - ccw.y - cw.y) / eight_l;
+
+    let two = T::from(2.0).unwrap();
+    let b = nominal.wheel_separation();
+    let radius_ratio = if beta.abs() < T::epsilon() {
+        T::one()
+    } else {
+        let r = side / two / (beta / two).sin();
+        (r + b / two) / (r - b / two)
+    };
+
+    let quarter = T::from(std::f64::consts::FRAC_PI_2).unwrap();
+    let mean_radius = (nominal.left_wheel_radius() + nominal.right_wheel_radius()) / two;
+    let calibration = OdometryCalibration::new(
+        two / (radius_ratio + T::one()) * mean_radius,
+        two / (T::one() / radius_ratio + T::one()) * mean_radius,
+        b * quarter / (quarter + alpha),
+    );
+
+    Ok(UmbmarkResult {
+        alpha,
+        beta,
+        radius_ratio,
+        calibration,
+    })
+}
@@ -0,0 +1,244 @@
+//! Arbitration and smoothing of velocity commands.
+//!
+//! Several sources command the robot, e.g. teleop, navigation and a safety
+//! stop. `CommandMux` keeps the latest timestamped `Twist2D` of every source
+//! and passes on the command of the highest priority source that has not
+//! timed out, so a source hands control back by going silent or clearing
+//! its command. `VelocitySmoother` follows the mux and limits the
+//! acceleration and jerk of the selected command before it is turned into
+//! wheel speeds with `speeds_from_twist`.
+use crate::rigid2d::Twist2D;
+use anyhow::{self, bail};
+use num_traits::Float;
+
+/// A source of commands
+#[derive(Debug, Clone)]
+struct Source<T: Float> {
+    name: String,
+    priority: u32,
+
+    /// age in seconds after which the command of the source is ignored
+    timeout: T,
+
+    /// latest command and its timestamp
+    latest: Option<(T, Twist2D<T>)>,
+}
+
+/// Selects the command of the highest priority source
+#[derive(Debug, Clone, Default)]
+pub struct CommandMux<T: Float> {
+    sources: Vec<Source<T>>,
+
+    /// index of the source selected last
+    active: Option<usize>,
+}
+
+impl<T: Float> CommandMux<T> {
+    /// constructs a mux without sources
+    pub fn new() -> Self {
+        CommandMux {
+            sources: vec![],
+            active: None,
+        }
+    }
+
+    /// Adds a source, replacing any source of the same name. Higher
+    /// priorities win and sources of equal priority win in the order they
+    /// were added. Commands older than the timeout in seconds are ignored,
+    /// an infinite timeout keeps a command until it is cleared.
+    pub fn with_source(mut self, name: &str, priority: u32, timeout: T) -> Self {
+        self.sources.retain(|source| source.name != name);
+        self.sources.push(Source {
+            name: name.to_string(),
+            priority,
+            timeout,
+            latest: None,
+        });
+        self.active = None;
+        self
+    }
+
+    fn source_mut(&mut self, name: &str) -> anyhow::Result<&mut Source<T>> {
+        match self.sources.iter_mut().find(|source| source.name == name) {
+            Some(source) => Ok(source),
+            None => bail!("unknown command source {}", name),
+        }
+    }
+
+    /// records a command of a source, stamped with the time it was issued
+    pub fn submit(&mut self, name: &str, twist: Twist2D<T>, stamp: T) -> anyhow::Result<()> {
+        self.source_mut(name)?.latest = Some((stamp, twist));
+        Ok(())
+    }
+
+    /// drops the command of a source, handing control to the sources below it
+    pub fn clear(&mut self, name: &str) -> anyhow::Result<()> {
+        self.source_mut(name)?.latest = None;
+        Ok(())
+    }
+
+    /// Returns the command of the highest priority source whose command is
+    /// no older than its timeout at the given time, or None when there is
+    /// no such source and the robot should stop
+    pub fn select(&mut self, now: T) -> Option<Twist2D<T>> {
+        let mut best: Option<usize> = None;
+        for (i, source) in self.sources.iter().enumerate() {
+            let fresh = source
+                .latest
+                .is_some_and(|(stamp, _)| now - stamp <= source.timeout);
+            if fresh && best.is_none_or(|b| source.priority > self.sources[b].priority) {
+                best = Some(i);
+            }
+        }
+        self.active = best;
+        best.and_then(|i| self.sources[i].latest.map(|(_, twist)| twist))
+    }
+
+    /// returns the name of the source picked by the last `select`
+    pub fn active(&self) -> Option<&str> {
+        self.active.map(|i| self.sources[i].name.as_str())
+    }
+}
+
+/// Limits of one velocity component
+#[derive(Debug, Clone, Copy)]
+struct AxisLimits<T: Float> {
+    speed: T,
+    acceleration: T,
+
+    /// acceleration limit while slowing down towards zero speed
+    deceleration: T,
+    jerk: T,
+}
+
+impl<T: Float> AxisLimits<T> {
+    fn new(acceleration: T) -> Self {
+        AxisLimits {
+            speed: T::infinity(),
+            acceleration,
+            deceleration: acceleration,
+            jerk: T::infinity(),
+        }
+    }
+
+    /// Steps a velocity and acceleration towards the target velocity. With
+    /// a jerk limit the acceleration is kept low enough to be ramped back
+    /// to zero by the time the target is reached. Without time passing
+    /// nothing changes.
+    fn step(&self, velocity: T, acceleration: T, target: T, dt: T) -> (T, T) {
+        if dt <= T::zero() {
+            return (velocity, acceleration);
+        }
+        let target = target.max(-self.speed).min(self.speed);
+        let error = target - velocity;
+        if error == T::zero() {
+            return (target, T::zero());
+        }
+
+        let slowing = velocity != T::zero() && error.signum() != velocity.signum();
+        let limit = if slowing {
+            self.deceleration
+        } else {
+            self.acceleration
+        };
+        // largest acceleration from which ramping down to zero at the jerk
+        // limit, one step at a time, ends at the target
+        let two = T::from(2.0).unwrap();
+        let half_step = dt / two;
+        let reachable = if self.jerk.is_finite() {
+            self.jerk * ((half_step * half_step + two * error.abs() / self.jerk).sqrt() - half_step)
+        } else {
+            T::infinity()
+        };
+        let desired = error.signum() * limit.min(reachable).min(error.abs() / dt);
+
+        let max_change = self.jerk * dt;
+        let change = (desired - acceleration).max(-max_change).min(max_change);
+        let mut acceleration = acceleration + change;
+        // never step past the target
+        if (acceleration * dt - error) * error.signum() > T::zero() {
+            acceleration = error / dt;
+        }
+        (velocity + acceleration * dt, acceleration)
+    }
+}
+
+/// Limits the speed, acceleration and jerk of a stream of twists. The
+/// linear limits apply to both translational components.
+#[derive(Debug, Clone, Copy)]
+pub struct VelocitySmoother<T: Float> {
+    linear: AxisLimits<T>,
+    angular: AxisLimits<T>,
+
+    /// smoothed twist and its rate of change
+    twist: Twist2D<T>,
+    acceleration: Twist2D<T>,
+}
+
+impl<T: Float> VelocitySmoother<T> {
+    /// constructs a smoother with the given acceleration limits in m/s^2
+    /// and rad/s^2, without speed or jerk limits
+    pub fn new(max_linear_acceleration: T, max_angular_acceleration: T) -> Self {
+        let zero = Twist2D::new(T::zero(), T::zero(), T::zero());
+        VelocitySmoother {
+            linear: AxisLimits::new(max_linear_acceleration),
+            angular: AxisLimits::new(max_angular_acceleration),
+            twist: zero,
+            acceleration: zero,
+        }
+    }
+
+    /// sets separate acceleration limits for slowing down, e.g. to stop
+    /// harder than the robot speeds up
+    pub fn with_deceleration_limits(mut self, linear: T, angular: T) -> Self {
+        self.linear.deceleration = linear;
+        self.angular.deceleration = angular;
+        self
+    }
+
+    /// limits the jerk in m/s^3 and rad/s^3
+    pub fn with_jerk_limits(mut self, linear: T, angular: T) -> Self {
+        self.linear.jerk = linear;
+        self.angular.jerk = angular;
+        self
+    }
+
+    /// limits the speeds in m/s and rad/s
+    pub fn with_speed_limits(mut self, linear: T, angular: T) -> Self {
+        self.linear.speed = linear;
+        self.angular.speed = angular;
+        self
+    }
+
+    /// returns the smoothed twist of the last update
+    pub fn twist(&self) -> Twist2D<T> {
+        self.twist
+    }
+
+    /// sets the current twist, e.g. to the measured one, with zero
+    /// acceleration
+    pub fn reset(&mut self, twist: Twist2D<T>) {
+        self.twist = twist;
+        self.acceleration = Twist2D::new(T::zero(), T::zero(), T::zero());
+    }
+
+    /// moves the smoothed twist towards the target over a time step and
+    /// returns it. Pass a zero twist when the mux selects nothing.
+    pub fn update(&mut self, target: Twist2D<T>, dt: T) -> Twist2D<T> {
+        let (thetadot, alpha) = self.angular.step(
+            self.twist.thetadot,
+            self.acceleration.thetadot,
+            target.thetadot,
+            dt,
+        );
+        let (xdot, ax) = self
+            .linear
+            .step(self.twist.xdot, self.acceleration.xdot, target.xdot, dt);
+        let (ydot, ay) = self
+            .linear
+            .step(self.twist.ydot, self.acceleration.ydot, target.ydot, dt);
+        self.twist = Twist2D::new(thetadot, xdot, ydot);
+        self.acceleration = Twist2D::new(alpha, ax, ay);
+        self.twist
+    }
+}
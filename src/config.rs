@@ -0,0 +1,110 @@
+//! Loading and saving robot configs as TOML, YAML or JSON.
+//!
+//! A config file holds a `DiffDriveConfig`, e.g. in TOML
+//!
+//! ```toml
+//! wheel_separation = 0.16
+//!
+//! [left]
+//! radius = 0.033
+//! ticks_per_rev = 4096.0
+//! gear_ratio = 1.0
+//!
+//! [right]
+//! radius = 0.033
+//! ticks_per_rev = 4096.0
+//! gear_ratio = 1.0
+//! ```
+use crate::ddrive::DiffDriveConfig;
+use crate::scalar::Scalar;
+use anyhow::{self, bail};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fs;
+use std::path::Path;
+
+/// Text format of a config file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigFormat {
+    Toml,
+    Yaml,
+    Json,
+}
+
+impl ConfigFormat {
+    /// picks the format from the extension of a file name
+    pub fn from_path<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
+        let extension = path
+            .as_ref()
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_ascii_lowercase());
+        match extension.as_deref() {
+            Some("toml") => Ok(ConfigFormat::Toml),
+            Some("yaml") | Some("yml") => Ok(ConfigFormat::Yaml),
+            Some("json") => Ok(ConfigFormat::Json),
+            _ => bail!(
+                "can not tell the config format of {}",
+                path.as_ref().display()
+            ),
+        }
+    }
+}
+
+/// parses a value from a string in the given format
+pub fn from_str<C: DeserializeOwned>(text: &str, format: ConfigFormat) -> anyhow::Result<C> {
+    Ok(match format {
+        ConfigFormat::Toml => toml::from_str(text)?,
+        ConfigFormat::Yaml => serde_yaml::from_str(text)?,
+        ConfigFormat::Json => serde_json::from_str(text)?,
+    })
+}
+
+/// writes a value to a string in the given format
+pub fn to_string<C: Serialize>(value: &C, format: ConfigFormat) -> anyhow::Result<String> {
+    Ok(match format {
+        ConfigFormat::Toml => toml::to_string_pretty(value)?,
+        ConfigFormat::Yaml => serde_yaml::to_string(value)?,
+        ConfigFormat::Json => serde_json::to_string_pretty(value)?,
+    })
+}
+
+/// reads a value from a file, in the format given by its extension
+pub fn load<C: DeserializeOwned, P: AsRef<Path>>(path: P) -> anyhow::Result<C> {
+    let format = ConfigFormat::from_path(&path)?;
+    from_str(&fs::read_to_string(path)?, format)
+}
+
+/// writes a value to a file, in the format given by its extension
+pub fn save<C: Serialize, P: AsRef<Path>>(value: &C, path: P) -> anyhow::Result<()> {
+    let format = ConfigFormat::from_path(&path)?;
+    fs::write(path, to_string(value, format)?)?;
+    Ok(())
+}
+
+impl<T: Scalar + Default + Serialize + DeserializeOwned> DiffDriveConfig<T> {
+    /// parses a config from TOML
+    pub fn from_toml(text: &str) -> anyhow::Result<Self> {
+        from_str(text, ConfigFormat::Toml)
+    }
+
+    /// parses a config from YAML
+    pub fn from_yaml(text: &str) -> anyhow::Result<Self> {
+        from_str(text, ConfigFormat::Yaml)
+    }
+
+    /// parses a config from JSON
+    pub fn from_json(text: &str) -> anyhow::Result<Self> {
+        from_str(text, ConfigFormat::Json)
+    }
+
+    /// reads a config from a .toml, .yaml, .yml or .json file
+    pub fn load<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
+        load(path)
+    }
+
+    /// writes the config to a .toml, .yaml, .yml or .json file
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> anyhow::Result<()> {
+        save(self, path)
+    }
+}
@@ -0,0 +1,700 @@
+//! Coverage path planning for cleaning and mowing robots.
+//!
+//! `CoveragePlanner` covers a polygonal `Region` with holes in lawnmower
+//! passes spaced by the tool width less the overlap. The passes run along
+//! the sweep angle, x by default. The region is split into cells by a
+//! boustrophedon decomposition: a cell ends wherever the free part of a
+//! pass splits or merges around a hole or a bend of the boundary, so each
+//! cell can be covered back and forth without leaving it.
+//!
+//! The tool is taken to be a disc of the tool width. Turnarounds respect
+//! the minimum turning radius: passes at least two radii apart are joined
+//! by a U-turn and closer passes by an omega turn that swings out and back.
+//! Passes keep the whole disc inside the region, their ends are pulled in
+//! far enough for the turnarounds to stay inside too, and the outermost
+//! passes move in when an omega turn would swing out past the edge.
+//! Cells are joined by the shortest straight-line route around the holes.
+use crate::geometry::{segments_intersect, Polygon};
+use crate::rigid2d::{Transform2D, Vector2D};
+use crate::trajectory::Path;
+use anyhow::{self, bail};
+use num_traits::Float;
+
+/// A polygonal area to cover, less its holes
+#[derive(Debug, Clone)]
+pub struct Region<T: Float> {
+    pub outer: Polygon<T>,
+    pub holes: Vec<Polygon<T>>,
+}
+
+impl<T: Float> Region<T> {
+    /// constructs a region without holes
+    pub fn new(outer: Polygon<T>) -> Self {
+        Region {
+            outer,
+            holes: vec![],
+        }
+    }
+
+    /// adds a hole, e.g. an obstacle or a flower bed
+    pub fn with_hole(mut self, hole: Polygon<T>) -> Self {
+        self.holes.push(hole);
+        self
+    }
+
+    /// returns the area of the region
+    pub fn area(&self) -> T {
+        self.holes
+            .iter()
+            .fold(self.outer.area(), |area, hole| area - hole.area())
+    }
+
+    /// returns true if the point is inside the outer boundary and outside
+    /// the holes
+    pub fn contains(&self, p: Vector2D<T>) -> bool {
+        self.outer.contains(p) && !self.holes.iter().any(|hole| hole.contains(p))
+    }
+
+    fn polygons(&self) -> impl Iterator<Item = &Polygon<T>> {
+        core::iter::once(&self.outer).chain(self.holes.iter())
+    }
+
+    fn transform(&self, transform: &Transform2D<T>) -> Self {
+        Region {
+            outer: self.outer.transform(transform),
+            holes: self.holes.iter().map(|h| h.transform(transform)).collect(),
+        }
+    }
+}
+
+/// A cell of the decomposition, covered by parallel passes
+#[derive(Debug, Clone)]
+pub struct CoverageCell<T: Float> {
+    /// start and end of every pass, in order across the cell and all in
+    /// the sweep direction
+    pub passes: Vec<(Vector2D<T>, Vector2D<T>)>,
+}
+
+/// A coverage path and how good it is
+#[derive(Debug, Clone)]
+pub struct CoveragePlan<T: Float> {
+    pub waypoints: Vec<Vector2D<T>>,
+    pub cells: Vec<CoverageCell<T>>,
+
+    /// length of the path in meters
+    pub length: T,
+
+    /// percentage of the region's area swept by the tool
+    pub coverage: T,
+}
+
+impl<T: Float> CoveragePlan<T> {
+    /// returns the path through the waypoints
+    pub fn to_path(&self) -> Path {
+        Path::from_waypoints(
+            self.waypoints
+                .iter()
+                .map(|p| Vector2D::new(p.x.to_f32().unwrap(), p.y.to_f32().unwrap()))
+                .collect(),
+        )
+    }
+}
+
+/// Plans lawnmower coverage of a region
+#[derive(Debug, Clone, Copy)]
+pub struct CoveragePlanner<T: Float> {
+    /// width of the swept strip in meters
+    tool_width: T,
+
+    /// fraction of the tool width neighboring passes overlap by
+    overlap: T,
+
+    /// smallest radius the robot can turn with
+    turning_radius: T,
+
+    /// direction of the passes in radians from the x axis
+    sweep_angle: T,
+}
+
+/// a pass at height y from x0 to x1, in the frame where passes run along x
+#[derive(Debug, Clone, Copy)]
+struct Span<T> {
+    y: T,
+    x0: T,
+    x1: T,
+}
+
+impl<T: Float> CoveragePlanner<T> {
+    /// constructs a planner for a tool of the given width, with 10 %
+    /// overlap, passes along the x axis and turns on the spot
+    pub fn new(tool_width: T) -> Self {
+        CoveragePlanner {
+            tool_width,
+            overlap: T::from(0.1).unwrap(),
+            turning_radius: T::zero(),
+            sweep_angle: T::zero(),
+        }
+    }
+
+    /// sets the fraction of the tool width neighboring passes overlap by
+    pub fn with_overlap(mut self, overlap: T) -> Self {
+        self.overlap = overlap;
+        self
+    }
+
+    /// sets the smallest radius in meters the robot can turn with
+    pub fn with_turning_radius(mut self, radius: T) -> Self {
+        self.turning_radius = radius;
+        self
+    }
+
+    /// sets the direction of the passes in radians from the x axis
+    pub fn with_sweep_angle(mut self, angle: T) -> Self {
+        self.sweep_angle = angle;
+        self
+    }
+
+    /// Splits the region into cells and places the passes in them. Passes
+    /// are at most the tool width less the overlap apart and run along the
+    /// sweep angle.
+    pub fn decompose(&self, region: &Region<T>) -> anyhow::Result<Vec<CoverageCell<T>>> {
+        let to_world = self.sweep_frame();
+        Ok(self
+            .cells(region)?
+            .iter()
+            .map(|spans| CoverageCell {
+                passes: spans
+                    .iter()
+                    .map(|s| {
+                        (
+                            to_world.apply(Vector2D::new(s.x0, s.y)),
+                            to_world.apply(Vector2D::new(s.x1, s.y)),
+                        )
+                    })
+                    .collect(),
+            })
+            .collect())
+    }
+
+    /// Plans a path that covers the region cell by cell, and measures its
+    /// length and coverage
+    pub fn plan(&self, region: &Region<T>) -> anyhow::Result<CoveragePlan<T>> {
+        let cells = self.cells(region)?;
+        if cells.is_empty() {
+            bail!("the region is too narrow for the tool");
+        }
+        let rotated = region.transform(&self.sweep_frame().inv());
+
+        // visit the nearest cell next, entering it at its nearest corner
+        let mut waypoints: Vec<Vector2D<T>> = vec![];
+        let mut remaining: Vec<Vec<Span<T>>> = cells;
+        while !remaining.is_empty() {
+            let position = waypoints.last().copied();
+            let mut best: Option<(T, usize, bool, bool)> = None;
+            for (i, spans) in remaining.iter().enumerate() {
+                for reverse in [false, true] {
+                    for leftward in [false, true] {
+                        let first = if reverse {
+                            spans[spans.len() - 1]
+                        } else {
+                            spans[0]
+                        };
+                        let x = if leftward { first.x1 } else { first.x0 };
+                        let d =
+                            position.map_or(T::zero(), |p| distance(p, Vector2D::new(x, first.y)));
+                        if best.is_none_or(|(b, ..)| d < b) {
+                            best = Some((d, i, reverse, leftward));
+                        }
+                    }
+                }
+            }
+            let (_, i, reverse, leftward) = best.unwrap();
+            let mut spans = remaining.remove(i);
+            if reverse {
+                spans.reverse();
+            }
+            let cell = self.sweep_cell(&spans, leftward);
+            if let Some(&from) = waypoints.last() {
+                waypoints.extend(route(&rotated, from, cell[0]).into_iter().skip(1));
+                waypoints.pop();
+            }
+            waypoints.extend(cell);
+        }
+
+        let to_world = self.sweep_frame();
+        let waypoints: Vec<Vector2D<T>> = waypoints.iter().map(|p| to_world.apply(*p)).collect();
+        let length = waypoints
+            .windows(2)
+            .fold(T::zero(), |a, w| a + distance(w[0], w[1]));
+        let coverage = self.coverage(region, &waypoints);
+        Ok(CoveragePlan {
+            waypoints,
+            cells: self.decompose(region)?,
+            length,
+            coverage,
+        })
+    }
+
+    /// transform from the frame where passes run along x to the world
+    fn sweep_frame(&self) -> Transform2D<T> {
+        Transform2D::new(Vector2D::new(T::zero(), T::zero()), self.sweep_angle)
+    }
+
+    /// how far a turnaround reaches past the end of the passes it joins
+    fn turn_extent(&self, spacing: T) -> T {
+        let r = self.turning_radius;
+        let two = T::from(2.0).unwrap();
+        if spacing >= two * r {
+            r
+        } else {
+            omega_offset(spacing, r) + r
+        }
+    }
+
+    /// how far an omega turn swings out sideways past the passes it joins
+    fn turn_bulge(&self, spacing: T) -> T {
+        let two = T::from(2.0).unwrap();
+        if spacing >= two * self.turning_radius {
+            T::zero()
+        } else {
+            self.turning_radius - spacing / two
+        }
+    }
+
+    /// Returns the number of passes across the given height, their spacing
+    /// and the margin from the outermost passes to the edges. Passes are
+    /// spread evenly, no farther apart than the tool width less the overlap,
+    /// and the margin is half the tool width or more when omega turns swing
+    /// out farther than that.
+    fn pass_layout(&self, height: T) -> (usize, T, T) {
+        let two = T::from(2.0).unwrap();
+        let half = self.tool_width / two;
+        let max_spacing = self.tool_width * (T::one() - self.overlap);
+        if height <= self.tool_width {
+            return (1, T::zero(), half);
+        }
+        let mut n = (((height - self.tool_width) / max_spacing).ceil())
+            .to_usize()
+            .unwrap_or(0)
+            .max(1)
+            + 1;
+        loop {
+            let spacing = (height - two * half) / T::from(n - 1).unwrap();
+            if self.turn_bulge(spacing) <= half {
+                return (n, spacing, half);
+            }
+            // with margin r - s / 2 on both sides, (n - 1) s = h - 2r + s
+            if n > 2 {
+                let spacing = (height - two * self.turning_radius) / T::from(n - 2).unwrap();
+                if spacing > T::zero() && spacing <= max_spacing {
+                    return (n, spacing, self.turn_bulge(spacing).max(half));
+                }
+                if spacing <= T::zero() {
+                    // too narrow for an omega turn, cover it in one pass
+                    return (1, T::zero(), half);
+                }
+            }
+            n += 1;
+        }
+    }
+
+    /// the passes of every cell, in the frame where passes run along x
+    fn cells(&self, region: &Region<T>) -> anyhow::Result<Vec<Vec<Span<T>>>> {
+        let two = T::from(2.0).unwrap();
+        let w = self.tool_width;
+        if w <= T::zero() {
+            bail!("the tool width must be positive");
+        }
+        if self.overlap < T::zero() || self.overlap >= T::one() {
+            bail!("the overlap must be at least 0 and less than 1");
+        }
+        if self.turning_radius < T::zero() {
+            bail!("the turning radius must not be negative");
+        }
+        let rotated = region.transform(&self.sweep_frame().inv());
+        let vertices = rotated.outer.vertices();
+        if vertices.len() < 3 {
+            bail!("the region needs at least three corners");
+        }
+        let (y_min, y_max) = vertices
+            .iter()
+            .fold((T::infinity(), T::neg_infinity()), |(lo, hi), v| {
+                (lo.min(v.y), hi.max(v.y))
+            });
+
+        let (count, spacing, margin) = self.pass_layout(y_max - y_min);
+        let inset = (w / two).max(self.turn_extent(spacing));
+        // keep the strip checks just inside edges the tool runs along
+        let eps = w * T::from(1e-6).unwrap();
+
+        let mut cells: Vec<Vec<Span<T>>> = vec![];
+        // intervals of the previous pass and the cells they belong to
+        let mut previous: Vec<(T, T, usize)> = vec![];
+        for k in 0..count {
+            let y = if count == 1 {
+                (y_min + y_max) / two
+            } else {
+                y_min + margin + spacing * T::from(k).unwrap()
+            };
+            let intervals: Vec<(T, T)> =
+                strip_intervals(&rotated, y - margin + eps, y + margin - eps)
+                    .into_iter()
+                    .map(|(x0, x1)| (x0 + inset, x1 - inset))
+                    .filter(|(x0, x1)| x1 > x0)
+                    .collect();
+
+            let overlaps = |a: (T, T), b: (T, T)| a.0 <= b.1 && b.0 <= a.1;
+            let mut current = vec![];
+            for &interval in &intervals {
+                let below: Vec<&(T, T, usize)> = previous
+                    .iter()
+                    .filter(|p| overlaps((p.0, p.1), interval))
+                    .collect();
+                let continues = below.len() == 1
+                    && intervals
+                        .iter()
+                        .filter(|i| overlaps(**i, (below[0].0, below[0].1)))
+                        .count()
+                        == 1;
+                let cell = if continues {
+                    below[0].2
+                } else {
+                    cells.push(vec![]);
+                    cells.len() - 1
+                };
+                cells[cell].push(Span {
+                    y,
+                    x0: interval.0,
+                    x1: interval.1,
+                });
+                current.push((interval.0, interval.1, cell));
+            }
+            previous = current;
+        }
+        Ok(cells)
+    }
+
+    /// Returns the waypoints that sweep a cell back and forth, starting at
+    /// the left end of the first pass, or at its right end when leftward
+    fn sweep_cell(&self, spans: &[Span<T>], leftward: bool) -> Vec<Vector2D<T>> {
+        let mut points = vec![];
+        let mut rightward = !leftward;
+        let mut entry = if rightward { spans[0].x0 } else { spans[0].x1 };
+        for (j, span) in spans.iter().enumerate() {
+            points.push(Vector2D::new(entry, span.y));
+            let next = match spans.get(j + 1) {
+                Some(next) => next,
+                None => {
+                    let exit = if rightward { span.x1 } else { span.x0 };
+                    points.push(Vector2D::new(exit, span.y));
+                    break;
+                }
+            };
+            // turn where both passes still reach
+            let exit = if rightward {
+                span.x1.min(next.x1)
+            } else {
+                span.x0.max(next.x0)
+            };
+            let end = Vector2D::new(exit, span.y);
+            points.push(end);
+            let heading = if rightward {
+                T::zero()
+            } else {
+                T::from(core::f64::consts::PI).unwrap()
+            };
+            let turn = self.turnaround(next.y - span.y);
+            let transform = Transform2D::new(end, heading);
+            let mirror =
+                (next.y - span.y) * if rightward { T::one() } else { -T::one() } < T::zero();
+            for p in turn.into_iter().skip(1) {
+                let p = if mirror { Vector2D::new(p.x, -p.y) } else { p };
+                points.push(transform.apply(p));
+            }
+            points.pop();
+            entry = exit;
+            rightward = !rightward;
+        }
+        points
+    }
+
+    /// Returns the points of a turnaround from the origin heading along x
+    /// to a pass offset by the distance to the left heading back, which
+    /// ends at (0, offset)
+    fn turnaround(&self, offset: T) -> Vec<Vector2D<T>> {
+        let d = offset.abs();
+        let r = self.turning_radius;
+        let zero = T::zero();
+        let two = T::from(2.0).unwrap();
+        let half_pi = T::from(core::f64::consts::FRAC_PI_2).unwrap();
+        let origin = Vector2D::new(zero, zero);
+        if r <= zero {
+            return vec![origin, Vector2D::new(zero, d)];
+        }
+        let mut points = vec![origin];
+        if d >= two * r {
+            arc(&mut points, Vector2D::new(zero, r), r, -half_pi, zero);
+            arc(&mut points, Vector2D::new(zero, d - r), r, zero, half_pi);
+        } else {
+            // swing right, around to the left and right again onto the pass,
+            // along three circles that touch each other
+            let c1 = Vector2D::new(zero, -r);
+            let c2 = Vector2D::new(omega_offset(d, r), d / two);
+            let c3 = Vector2D::new(zero, d + r);
+            let t1 = Vector2D::new(c2.x / two, (c1.y + c2.y) / two);
+            let t2 = Vector2D::new(c2.x / two, (c2.y + c3.y) / two);
+            let angle = |c: Vector2D<T>, p: Vector2D<T>| (p.y - c.y).atan2(p.x - c.x);
+            arc(&mut points, c1, r, half_pi, angle(c1, t1));
+            let start = angle(c2, t1);
+            let mut end = angle(c2, t2);
+            while end < start {
+                end = end + two * two * half_pi;
+            }
+            arc(&mut points, c2, r, start, end);
+            arc(&mut points, c3, r, angle(c3, t2), -half_pi);
+        }
+        points.pop();
+        points.push(Vector2D::new(zero, d));
+        points
+    }
+
+    /// percentage of the region's area within half the tool width of the
+    /// path, measured on a grid of a tenth of the tool width
+    fn coverage(&self, region: &Region<T>, path: &[Vector2D<T>]) -> T {
+        let two = T::from(2.0).unwrap();
+        let resolution = self.tool_width / T::from(10.0).unwrap();
+        let radius = self.tool_width / two;
+        let (min, max) = region.outer.vertices().iter().fold(
+            (
+                Vector2D::new(T::infinity(), T::infinity()),
+                Vector2D::new(T::neg_infinity(), T::neg_infinity()),
+            ),
+            |(lo, hi), v| {
+                (
+                    Vector2D::new(lo.x.min(v.x), lo.y.min(v.y)),
+                    Vector2D::new(hi.x.max(v.x), hi.y.max(v.y)),
+                )
+            },
+        );
+        let width = ((max.x - min.x) / resolution)
+            .ceil()
+            .to_usize()
+            .unwrap_or(0);
+        let height = ((max.y - min.y) / resolution)
+            .ceil()
+            .to_usize()
+            .unwrap_or(0);
+        let center = |ix: usize, iy: usize| {
+            Vector2D::new(
+                min.x + (T::from(ix).unwrap() + T::from(0.5).unwrap()) * resolution,
+                min.y + (T::from(iy).unwrap() + T::from(0.5).unwrap()) * resolution,
+            )
+        };
+        let index = |v: T, lo: T, n: usize| {
+            ((v - lo) / resolution)
+                .floor()
+                .max(T::zero())
+                .to_usize()
+                .unwrap_or(0)
+                .min(n.saturating_sub(1))
+        };
+
+        let mut covered = vec![false; width * height];
+        for segment in path.windows(2) {
+            let (a, b) = (segment[0], segment[1]);
+            let (x0, x1) = (a.x.min(b.x) - radius, a.x.max(b.x) + radius);
+            let (y0, y1) = (a.y.min(b.y) - radius, a.y.max(b.y) + radius);
+            for iy in index(y0, min.y, height)..=index(y1, min.y, height) {
+                for ix in index(x0, min.x, width)..=index(x1, min.x, width) {
+                    let p = center(ix, iy);
+                    if crate::geometry::segment_distance(p, a, b) <= radius {
+                        covered[iy * width + ix] = true;
+                    }
+                }
+            }
+        }
+
+        let (mut free, mut swept) = (0usize, 0usize);
+        for iy in 0..height {
+            for ix in 0..width {
+                if region.contains(center(ix, iy)) {
+                    free += 1;
+                    if covered[iy * width + ix] {
+                        swept += 1;
+                    }
+                }
+            }
+        }
+        if free == 0 {
+            return T::zero();
+        }
+        T::from(100.0).unwrap() * T::from(swept).unwrap() / T::from(free).unwrap()
+    }
+}
+
+/// Returns the x intervals free for the whole height of a horizontal strip.
+/// The free intervals change linearly between corners, so they are checked
+/// at both edges of the strip and at every corner in between.
+fn strip_intervals<T: Float>(region: &Region<T>, y0: T, y1: T) -> Vec<(T, T)> {
+    let mut heights = vec![y0, y1];
+    for polygon in region.polygons() {
+        heights.extend(
+            polygon
+                .vertices()
+                .iter()
+                .map(|v| v.y)
+                .filter(|y| *y > y0 && *y < y1),
+        );
+    }
+    let mut free = line_intervals(region, y0);
+    for y in heights.into_iter().skip(1) {
+        free = intersect(&free, &line_intervals(region, y));
+    }
+    free
+}
+
+/// Returns the x intervals of a horizontal line inside the region. Corners
+/// exactly on the line count as below it.
+fn line_intervals<T: Float>(region: &Region<T>, y: T) -> Vec<(T, T)> {
+    let mut crossings = vec![];
+    for polygon in region.polygons() {
+        for (a, b) in polygon.edges() {
+            if (a.y > y) != (b.y > y) {
+                crossings.push(a.x + (y - a.y) / (b.y - a.y) * (b.x - a.x));
+            }
+        }
+    }
+    crossings.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    crossings.chunks_exact(2).map(|c| (c[0], c[1])).collect()
+}
+
+/// returns the intersection of two sorted lists of disjoint intervals
+fn intersect<T: Float>(a: &[(T, T)], b: &[(T, T)]) -> Vec<(T, T)> {
+    let mut out = vec![];
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        let lo = a[i].0.max(b[j].0);
+        let hi = a[i].1.min(b[j].1);
+        if lo < hi {
+            out.push((lo, hi));
+        }
+        if a[i].1 < b[j].1 {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    out
+}
+
+/// distance along the passes from the turning point to the center of the
+/// middle circle of an omega turn between passes the spacing apart
+fn omega_offset<T: Float>(spacing: T, radius: T) -> T {
+    let two = T::from(2.0).unwrap();
+    let half_span = (spacing + two * radius) / two;
+    (two * two * radius * radius - half_span * half_span)
+        .max(T::zero())
+        .sqrt()
+}
+
+/// appends the points of an arc after its start, one every 10 degrees
+fn arc<T: Float>(points: &mut Vec<Vector2D<T>>, center: Vector2D<T>, radius: T, start: T, end: T) {
+    let step = T::from(10.0f64.to_radians()).unwrap();
+    let n = ((end - start).abs() / step)
+        .ceil()
+        .to_usize()
+        .unwrap_or(1)
+        .max(1);
+    for k in 1..=n {
+        let a = start + (end - start) * T::from(k).unwrap() / T::from(n).unwrap();
+        points.push(Vector2D::new(
+            center.x + radius * a.cos(),
+            center.y + radius * a.sin(),
+        ));
+    }
+}
+
+fn distance<T: Float>(a: Vector2D<T>, b: Vector2D<T>) -> T {
+    (b.x - a.x).hypot(b.y - a.y)
+}
+
+/// Returns the shortest straight-line route between two points inside the
+/// region, through corners of the boundary and the holes, or the direct
+/// segment when there is none
+fn route<T: Float>(region: &Region<T>, from: Vector2D<T>, to: Vector2D<T>) -> Vec<Vector2D<T>> {
+    let visible = |a: Vector2D<T>, b: Vector2D<T>| {
+        let middle = Vector2D::new(
+            (a.x + b.x) / T::from(2.0).unwrap(),
+            (a.y + b.y) / T::from(2.0).unwrap(),
+        );
+        region.contains(middle)
+            && !region
+                .polygons()
+                .any(|polygon| polygon.edges().any(|(c, d)| segments_intersect(a, b, c, d)))
+    };
+    if visible(from, to) {
+        return vec![from, to];
+    }
+
+    // corners nudged off the boundary into the free space
+    let nudge = T::from(1e-3).unwrap();
+    let mut nodes = vec![from, to];
+    for polygon in region.polygons() {
+        let v = polygon.vertices();
+        for i in 0..v.len() {
+            let (prev, next) = (v[(i + v.len() - 1) % v.len()], v[(i + 1) % v.len()]);
+            let along = |p: Vector2D<T>| {
+                let d = distance(v[i], p);
+                Vector2D::new((p.x - v[i].x) / d, (p.y - v[i].y) / d)
+            };
+            let (u, w) = (along(prev), along(next));
+            for sign in [T::one(), -T::one()] {
+                let candidate = Vector2D::new(
+                    v[i].x + sign * nudge * (u.x + w.x),
+                    v[i].y + sign * nudge * (u.y + w.y),
+                );
+                if region.contains(candidate) {
+                    nodes.push(candidate);
+                }
+            }
+        }
+    }
+
+    // Dijkstra over the visibility graph
+    let n = nodes.len();
+    let mut cost = vec![T::infinity(); n];
+    let mut parent = vec![usize::MAX; n];
+    let mut done = vec![false; n];
+    cost[0] = T::zero();
+    loop {
+        let current = (0..n)
+            .filter(|&i| !done[i] && cost[i].is_finite())
+            .min_by(|&a, &b| cost[a].partial_cmp(&cost[b]).unwrap());
+        let current = match current {
+            Some(current) => current,
+            None => return vec![from, to],
+        };
+        if current == 1 {
+            break;
+        }
+        done[current] = true;
+        for next in 0..n {
+            if done[next] || !visible(nodes[current], nodes[next]) {
+                continue;
+            }
+            let c = cost[current] + distance(nodes[current], nodes[next]);
+            if c < cost[next] {
+                cost[next] = c;
+                parent[next] = current;
+            }
+        }
+    }
+    let mut path = vec![to];
+    let mut node = 1;
+    while parent[node] != usize::MAX {
+        node = parent[node];
+        path.push(nodes[node]);
+    }
+    path.reverse();
+    path
+}
@@ -1,16 +1,15 @@
 #![allow(non_snake_case)]
 
 use crate::rigid2d::{Pose2D, Transform2D, Twist2D, Vector2D};
+use crate::scalar::Scalar;
 use crate::utils;
-use anyhow;
-use num_traits::Float;
-use std::default::Default;
-use std::error::Error;
-use std::fmt::Display;
+use core::default::Default;
+use core::fmt::Display;
 
 /// State of the left and right wheels which could be position, velocity, etc.
-#[derive(Debug, Clone, Copy, Default)]
-pub struct WheelState<T: Float + Default> {
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WheelState<T: Scalar + Default> {
     /// State of the left wheel
     pub left: T,
 
@@ -18,7 +17,7 @@ pub struct WheelState<T: Float + Default> {
     pub right: T,
 }
 
-impl<T: Float + Default> WheelState<T> {
+impl<T: Scalar + Default> WheelState<T> {
     /// construcs a new WheelState from (left,right) which are floats
     pub fn new(left: T, right: T) -> Self {
         WheelState { left, right }
@@ -47,21 +46,148 @@ impl<T: Float + Default> WheelState<T> {
 
 impl<T> Display for WheelState<T>
 where
-    T: Float + Display + Default,
+    T: Scalar + Display + Default,
 {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         write!(f, "left:{} right:{}", self.left, self.right)
     }
 }
 
+/// Raw encoder counts of the left and right wheels
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EncoderTicks {
+    /// Encoder count of the left wheel
+    pub left: i64,
+
+    /// Encoder count of the right wheel
+    pub right: i64,
+}
+
+impl EncoderTicks {
+    /// constructs a new EncoderTicks from (left,right) counts
+    pub fn new(left: i64, right: i64) -> Self {
+        EncoderTicks { left, right }
+    }
+}
+
+/// Geometry, encoder and gearbox parameters of a single wheel
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WheelParams<T: Scalar> {
+    /// effective radius of the wheel
+    pub radius: T,
+
+    /// encoder ticks per revolution of the motor shaft
+    pub ticks_per_rev: T,
+
+    /// motor shaft revolutions per wheel revolution
+    pub gear_ratio: T,
+}
+
+impl<T: Scalar> WheelParams<T> {
+    /// constructs a new WheelParams
+    pub fn new(radius: T, ticks_per_rev: T, gear_ratio: T) -> Self {
+        WheelParams {
+            radius,
+            ticks_per_rev,
+            gear_ratio,
+        }
+    }
+
+    /// constructs a directly driven wheel whose encoder counts one tick per radian
+    pub fn from_radius(radius: T) -> Self {
+        WheelParams::new(radius, T::tau(), T::one())
+    }
+
+    /// converts encoder ticks to a wheel angle in radians
+    pub fn ticks_to_wheel(&self, ticks: T) -> T {
+        self.motor_to_wheel(ticks / self.ticks_per_rev * T::tau())
+    }
+
+    /// Converts an encoder count to a wheel angle in radians. The count is
+    /// split into blocks of about a motor revolution with integer math, so
+    /// that a fixed-point angle wraps around instead of saturating.
+    pub fn count_to_wheel(&self, ticks: i64) -> T {
+        let block = self
+            .ticks_per_rev
+            .to_i64()
+            .unwrap_or(1)
+            .clamp(1, i16::MAX as i64);
+        let rest = ticks.rem_euclid(block);
+        let per_block = self.ticks_to_wheel(T::from_int(block as i32));
+        per_block
+            .wrapping_mul_int(ticks.div_euclid(block))
+            .wrapping_add(self.ticks_to_wheel(T::from_int(rest as i32)))
+    }
+
+    /// converts a wheel angle in radians to encoder ticks
+    pub fn wheel_to_ticks(&self, angle: T) -> T {
+        self.wheel_to_motor(angle) * self.ticks_per_rev / T::tau()
+    }
+
+    /// converts a motor shaft angle to a wheel angle
+    pub fn motor_to_wheel(&self, angle: T) -> T {
+        angle / self.gear_ratio
+    }
+
+    /// converts a wheel angle to a motor shaft angle
+    pub fn wheel_to_motor(&self, angle: T) -> T {
+        angle * self.gear_ratio
+    }
+}
+
+/// Kinematic parameters of a differential drive robot, which can be stored
+/// and loaded with the `serde` feature
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DiffDriveConfig<T: Scalar> {
+    /// Parameters of the left wheel
+    pub left: WheelParams<T>,
+
+    /// Parameters of the right wheel
+    pub right: WheelParams<T>,
+
+    /// Nominal distance between the wheel centers
+    pub wheel_separation: T,
+
+    /// Ratio of the effective to the nominal wheel separation, 1 if omitted
+    #[cfg_attr(feature = "serde", serde(default = "T::one"))]
+    pub separation_scale: T,
+}
+
+impl<T: Scalar + Default> DiffDriveConfig<T> {
+    /// constructs a DiffDriveConfig with the same parameters for both wheels
+    pub fn new(wheel: WheelParams<T>, wheel_separation: T) -> Self {
+        DiffDriveConfig {
+            left: wheel,
+            right: wheel,
+            wheel_separation,
+            separation_scale: T::one(),
+        }
+    }
+
+    /// constructs a DiffDrive at the origin with these parameters
+    pub fn to_diff_drive(&self) -> DiffDrive<T> {
+        DiffDrive::from_wheel_params(self.left, self.right, self.wheel_separation)
+            .with_separation_scale(self.separation_scale)
+    }
+}
+
 // #[derive(Debug, Clone)]
-pub struct DiffDrive<T: Float + Default> {
-    /// Radius of the robot's wheels
-    wheel_radius: T,
+pub struct DiffDrive<T: Scalar + Default> {
+    /// Parameters of the robot's left wheel
+    left: WheelParams<T>,
+
+    /// Parameters of the robot's right wheel
+    right: WheelParams<T>,
 
-    /// Distance between the wheel centers
+    /// Nominal distance between the wheel centers
     wheel_separation: T,
 
+    /// Ratio of the effective to the nominal wheel separation
+    separation_scale: T,
+
     /// x,y,theta position in meters, radians
     pose: Pose2D<T>,
 
@@ -72,31 +198,117 @@ pub struct DiffDrive<T: Float + Default> {
     phidot: WheelState<T>,
 }
 
-impl<T: Float + Default> DiffDrive<T> {
+impl<T: Scalar + Default> DiffDrive<T> {
     pub fn new(wheel_radius: T, wheel_separation: T) -> Self {
+        Self::with_wheel_radii(wheel_radius, wheel_radius, wheel_separation)
+    }
+
+    /// constructs a DiffDrive whose left and right wheels have different radii
+    pub fn with_wheel_radii(
+        left_wheel_radius: T,
+        right_wheel_radius: T,
+        wheel_separation: T,
+    ) -> Self {
+        Self::from_wheel_params(
+            WheelParams::from_radius(left_wheel_radius),
+            WheelParams::from_radius(right_wheel_radius),
+            wheel_separation,
+        )
+    }
+
+    /// constructs a DiffDrive with independent radius, encoder and gearbox parameters per wheel
+    pub fn from_wheel_params(
+        left: WheelParams<T>,
+        right: WheelParams<T>,
+        wheel_separation: T,
+    ) -> Self {
         Self {
-            wheel_radius,
+            left,
+            right,
             wheel_separation,
+            separation_scale: T::one(),
             pose: Pose2D::default(),
             phi: WheelState::default(),
             phidot: WheelState::default(),
         }
     }
 
+    /// scales the nominal wheel separation to correct for the effective
+    /// contact points of the wheels differing from their centers
+    pub fn with_separation_scale(mut self, scale: T) -> Self {
+        self.separation_scale = scale;
+        self
+    }
+
+    /// returns the kinematic parameters of the robot
+    pub fn config(&self) -> DiffDriveConfig<T> {
+        DiffDriveConfig {
+            left: self.left,
+            right: self.right,
+            wheel_separation: self.wheel_separation,
+            separation_scale: self.separation_scale,
+        }
+    }
+
+    /// returns the parameters of the left wheel
+    pub fn left_wheel(&self) -> WheelParams<T> {
+        self.left
+    }
+
+    /// returns the parameters of the right wheel
+    pub fn right_wheel(&self) -> WheelParams<T> {
+        self.right
+    }
+
+    /// returns the radius of the left wheel
+    pub fn left_wheel_radius(&self) -> T {
+        self.left.radius
+    }
+
+    /// returns the radius of the right wheel
+    pub fn right_wheel_radius(&self) -> T {
+        self.right.radius
+    }
+
+    /// returns the effective distance between the wheels, i.e. the nominal
+    /// separation times the separation scale
+    pub fn wheel_separation(&self) -> T {
+        self.wheel_separation * self.separation_scale
+    }
+
+    /// returns the ratio of the effective to the nominal wheel separation
+    pub fn separation_scale(&self) -> T {
+        self.separation_scale
+    }
+
+    /// returns the current pose of the robot
+    pub fn pose(&self) -> Pose2D<T> {
+        self.pose
+    }
+
+    /// returns the wheel angles of the last forward kinematics update
+    pub fn wheel_angles(&self) -> WheelState<T> {
+        self.phi
+    }
+
+    /// sets the current pose of the robot
+    pub fn set_pose(&mut self, pose: Pose2D<T>) {
+        self.pose = pose;
+    }
+
     /// Computes the wheel speeds needed to obtain the given twist.
     /// this can also be considered inverse kinematics
     /// TODO: do not mutate self here
     pub fn speeds_from_twist(&self, v: Twist2D<T>) -> WheelState<T> {
-        if !utils::almost_equal(v.ydot, T::from(0.0).unwrap(), T::from(0.0001).unwrap()) {
+        if !utils::almost_equal(v.ydot, T::zero(), T::from_ratio(1, 10_000)) {
             panic!("Non-zero y component of twist is not possible");
         }
 
-        let d = self.wheel_separation / T::from(2.0).unwrap();
-        let r = self.wheel_radius;
+        let d = self.wheel_separation() / T::from_int(2);
 
         WheelState::new(
-            (T::from(1.0).unwrap() / r) * (-d * v.thetadot + v.xdot),
-            (T::from(1.0).unwrap() / r) * (d * v.thetadot + v.xdot),
+            (T::one() / self.left.radius) * (-d * v.thetadot + v.xdot),
+            (T::one() / self.right.radius) * (d * v.thetadot + v.xdot),
         )
         // self.phidot.left = (T::from(1.0).unwrap() / r) * (-d * v.thetadot + v.xdot);
         // self.phidot.right = (T::from(1.0).unwrap() / r) * (d * v.thetadot + v.xdot);
@@ -105,22 +317,25 @@ impl<T: Float + Default> DiffDrive<T> {
 
     /// Computes the body twist for the given wheel speeds
     pub fn twist_from_speeds(&self, phidot: WheelState<T>) -> Twist2D<T> {
+        let left = self.left.radius * phidot.left;
+        let right = self.right.radius * phidot.right;
         Twist2D::new(
-            (self.wheel_radius / self.wheel_separation) * (phidot.right - phidot.left),
-            (self.wheel_radius / T::from(2.0).unwrap()) * (phidot.left + phidot.right),
-            T::from(0.0).unwrap(),
+            (right - left) / self.wheel_separation(),
+            (left + right) / T::from_int(2),
+            T::zero(),
         )
     }
 
     /// computes the forward kinematics to find
-    /// the new pose of robot given new wheel angles
+    /// the new pose of robot given new wheel angles.
+    /// The angles may wrap around at the ends of a fixed-point range.
     pub fn forward_kinematics(&mut self, phi_new: WheelState<T>) -> Pose2D<T> {
         // update the pose with the provided pose
         // self.pose = pose;
 
         // Compute the new wheel speeds for a single timestep (t=1)
-        self.phidot.left = phi_new.left - self.phi.left;
-        self.phidot.right = phi_new.right - self.phi.right;
+        self.phidot.left = phi_new.left.wrapping_sub(self.phi.left);
+        self.phidot.right = phi_new.right.wrapping_sub(self.phi.right);
 
         // Update the wheel angles with the provided ones
         self.phi = phi_new;
@@ -148,4 +363,65 @@ impl<T: Float + Default> DiffDrive<T> {
 
         self.pose
     }
+
+    /// converts encoder counts to wheel angles in radians
+    pub fn wheel_angles_from_ticks(&self, ticks: EncoderTicks) -> WheelState<T> {
+        WheelState::new(
+            self.left.count_to_wheel(ticks.left),
+            self.right.count_to_wheel(ticks.right),
+        )
+    }
+
+    /// converts motor shaft angles (or speeds) to wheel angles (or speeds)
+    pub fn wheel_from_motor(&self, motor: WheelState<T>) -> WheelState<T> {
+        WheelState::new(
+            self.left.motor_to_wheel(motor.left),
+            self.right.motor_to_wheel(motor.right),
+        )
+    }
+
+    /// converts wheel angles (or speeds) to motor shaft angles (or speeds)
+    pub fn motor_from_wheel(&self, wheel: WheelState<T>) -> WheelState<T> {
+        WheelState::new(
+            self.left.wheel_to_motor(wheel.left),
+            self.right.wheel_to_motor(wheel.right),
+        )
+    }
+
+    /// computes the forward kinematics from raw encoder counts
+    pub fn forward_kinematics_ticks(&mut self, ticks: EncoderTicks) -> Pose2D<T> {
+        self.forward_kinematics(self.wheel_angles_from_ticks(ticks))
+    }
+
+    /// computes the forward kinematics from motor shaft angles in radians
+    pub fn forward_kinematics_motor(&mut self, motor_angles: WheelState<T>) -> Pose2D<T> {
+        self.forward_kinematics(self.wheel_from_motor(motor_angles))
+    }
+
+    /// Computes the body twist for the given encoder rates in ticks per second
+    pub fn twist_from_tick_rates(&self, rates: WheelState<T>) -> Twist2D<T> {
+        self.twist_from_speeds(WheelState::new(
+            self.left.ticks_to_wheel(rates.left),
+            self.right.ticks_to_wheel(rates.right),
+        ))
+    }
+
+    /// Computes the encoder rates in ticks per second needed to obtain the given twist
+    pub fn tick_rates_from_twist(&self, v: Twist2D<T>) -> WheelState<T> {
+        let speeds = self.speeds_from_twist(v);
+        WheelState::new(
+            self.left.wheel_to_ticks(speeds.left),
+            self.right.wheel_to_ticks(speeds.right),
+        )
+    }
+
+    /// Computes the body twist for the given motor shaft speeds in radians per second
+    pub fn twist_from_motor_speeds(&self, motor_speeds: WheelState<T>) -> Twist2D<T> {
+        self.twist_from_speeds(self.wheel_from_motor(motor_speeds))
+    }
+
+    /// Computes the motor shaft speeds in radians per second needed to obtain the given twist
+    pub fn motor_speeds_from_twist(&self, v: Twist2D<T>) -> WheelState<T> {
+        self.motor_from_wheel(self.speeds_from_twist(v))
+    }
 }
@@ -0,0 +1,404 @@
+//! Second-order (dynamic) model of a differential drive robot.
+//!
+//! Each wheel is driven through a gearbox by a DC motor whose electrical time
+//! constant is neglected, so the motor current follows the applied voltage
+//! and the back-EMF instantly. Wheel axles see Coulomb and viscous friction,
+//! and each wheel transmits at most mu * m * g / 2 of force to the ground
+//! before it slips. The kinematic `DiffDrive` is the limit of this model as
+//! the masses and inertias go to zero.
+use crate::ddrive::{DiffDrive, WheelState};
+use crate::rigid2d::{Pose2D, Transform2D, Twist2D};
+use num_traits::Float;
+
+/// Parameters of a DC motor and its gearbox
+#[derive(Debug, Clone, Copy)]
+pub struct MotorParams<T: Float> {
+    /// torque per ampere of current (N m / A)
+    pub torque_constant: T,
+
+    /// back-EMF per radian per second of shaft speed (V s / rad)
+    pub back_emf_constant: T,
+
+    /// winding resistance (ohm)
+    pub resistance: T,
+
+    /// inertia of the rotor (kg m^2)
+    pub rotor_inertia: T,
+
+    /// motor shaft revolutions per wheel revolution
+    pub gear_ratio: T,
+}
+
+impl<T: Float> MotorParams<T> {
+    /// constructs a new MotorParams
+    pub fn new(
+        torque_constant: T,
+        back_emf_constant: T,
+        resistance: T,
+        rotor_inertia: T,
+        gear_ratio: T,
+    ) -> Self {
+        MotorParams {
+            torque_constant,
+            back_emf_constant,
+            resistance,
+            rotor_inertia,
+            gear_ratio,
+        }
+    }
+
+    /// computes the torque at the wheel for the applied voltage and wheel speed
+    pub fn wheel_torque(&self, voltage: T, wheel_speed: T) -> T {
+        let current =
+            (voltage - self.back_emf_constant * self.gear_ratio * wheel_speed) / self.resistance;
+        self.gear_ratio * self.torque_constant * current
+    }
+
+    /// computes the voltage that produces the torque at the wheel at the given wheel speed
+    pub fn voltage(&self, wheel_torque: T, wheel_speed: T) -> T {
+        let current = wheel_torque / (self.gear_ratio * self.torque_constant);
+        current * self.resistance + self.back_emf_constant * self.gear_ratio * wheel_speed
+    }
+}
+
+/// Physical parameters of a differential drive robot
+#[derive(Debug, Clone, Copy)]
+pub struct DynamicsParams<T: Float> {
+    /// mass of the robot (kg)
+    pub mass: T,
+
+    /// moment of inertia of the robot about its center (kg m^2)
+    pub inertia: T,
+
+    /// radius of the wheels (m)
+    pub wheel_radius: T,
+
+    /// distance between the wheels (m)
+    pub wheel_separation: T,
+
+    /// inertia of each wheel about its axle (kg m^2)
+    pub wheel_inertia: T,
+
+    /// motor driving each wheel
+    pub motor: MotorParams<T>,
+
+    /// Coulomb friction torque at each wheel axle (N m)
+    pub coulomb_friction: T,
+
+    /// viscous friction at each wheel axle (N m s / rad)
+    pub viscous_friction: T,
+
+    /// coefficient of friction between the wheels and the ground
+    pub traction_coefficient: T,
+
+    /// gravitational acceleration (m / s^2)
+    pub gravity: T,
+}
+
+/// Input applied to the motors
+#[derive(Debug, Clone, Copy)]
+pub enum DriveInput<T: Float + Default> {
+    /// terminal voltage of each motor (V)
+    Voltage(WheelState<T>),
+
+    /// torque at each motor shaft (N m)
+    Torque(WheelState<T>),
+}
+
+/// A differential drive robot simulated with its dynamics
+pub struct DynamicDiffDrive<T: Float + Default> {
+    params: DynamicsParams<T>,
+
+    /// x,y,theta position in meters, radians
+    pose: Pose2D<T>,
+
+    /// forward speed (m/s)
+    v: T,
+
+    /// angular speed (rad/s)
+    omega: T,
+
+    /// wheel speeds (rad/s), which differ from the body motion while slipping
+    wheel_speeds: WheelState<T>,
+
+    /// forward and angular acceleration during the last step
+    acceleration: (T, T),
+
+    /// whether each wheel slipped during the last step
+    slipping: (bool, bool),
+}
+
+impl<T: Float + Default> DynamicDiffDrive<T> {
+    /// constructs a robot at rest at the origin
+    pub fn new(params: DynamicsParams<T>) -> Self {
+        DynamicDiffDrive {
+            params,
+            pose: Pose2D::default(),
+            v: T::zero(),
+            omega: T::zero(),
+            wheel_speeds: WheelState::default(),
+            acceleration: (T::zero(), T::zero()),
+            slipping: (false, false),
+        }
+    }
+
+    /// returns the physical parameters
+    pub fn params(&self) -> &DynamicsParams<T> {
+        &self.params
+    }
+
+    /// returns the current pose
+    pub fn pose(&self) -> Pose2D<T> {
+        self.pose
+    }
+
+    /// sets the current pose
+    pub fn set_pose(&mut self, pose: Pose2D<T>) {
+        self.pose = pose;
+    }
+
+    /// returns the current body twist
+    pub fn twist(&self) -> Twist2D<T> {
+        Twist2D::new(self.omega, self.v, T::zero())
+    }
+
+    /// returns the current wheel speeds (rad/s)
+    pub fn wheel_speeds(&self) -> WheelState<T> {
+        self.wheel_speeds
+    }
+
+    /// returns the forward and angular acceleration of the last step
+    pub fn acceleration(&self) -> (T, T) {
+        self.acceleration
+    }
+
+    /// returns whether the (left, right) wheels slipped during the last step
+    pub fn slipping(&self) -> (bool, bool) {
+        self.slipping
+    }
+
+    /// returns the kinematic model of the robot, the zero-mass limit of the dynamics
+    pub fn kinematic(&self) -> DiffDrive<T> {
+        DiffDrive::new(self.params.wheel_radius, self.params.wheel_separation)
+    }
+
+    /// computes the inertia of each wheel including the rotor reflected through the gearbox
+    pub fn effective_wheel_inertia(&self) -> T {
+        let m = &self.params.motor;
+        self.params.wheel_inertia + m.gear_ratio * m.gear_ratio * m.rotor_inertia
+    }
+
+    /// returns the largest forward acceleration before the wheels slip
+    pub fn traction_limit(&self) -> T {
+        self.params.traction_coefficient * self.params.gravity
+    }
+
+    /// Returns the largest forward acceleration from rest with the given
+    /// voltage on both motors, limited by the motors or by traction
+    pub fn max_acceleration(&self, voltage: T) -> T {
+        let p = &self.params;
+        let two = T::from(2.0).unwrap();
+        let torque = p.motor.wheel_torque(voltage, T::zero()) - p.coulomb_friction;
+        let r = p.wheel_radius;
+        let motor_limit =
+            two * torque.max(T::zero()) / (r * p.mass + two * self.effective_wheel_inertia() / r);
+        motor_limit.min(self.traction_limit())
+    }
+
+    /// Computes the motor voltages that produce the given forward and angular
+    /// accelerations at the given twist without slipping, for use as a
+    /// feed-forward term
+    pub fn feedforward_voltage(
+        &self,
+        twist: &Twist2D<T>,
+        accel: T,
+        angular_accel: T,
+    ) -> WheelState<T> {
+        let p = &self.params;
+        let two = T::from(2.0).unwrap();
+        let (r, d) = (p.wheel_radius, p.wheel_separation / two);
+        let jw = self.effective_wheel_inertia();
+
+        let speeds =
+            self.kinematic()
+                .speeds_from_twist(Twist2D::new(twist.thetadot, twist.xdot, T::zero()));
+        let wheel_accel = [
+            (accel - d * angular_accel) / r,
+            (accel + d * angular_accel) / r,
+        ];
+        // ground forces that produce the body accelerations
+        let total = p.mass * accel;
+        let diff = p.inertia * angular_accel / d;
+        let forces = [(total - diff) / two, (total + diff) / two];
+
+        let voltage = |i: usize, speed: T| {
+            let torque = forces[i] * r + jw * wheel_accel[i] + self.friction(speed);
+            p.motor.voltage(torque, speed)
+        };
+        WheelState::new(voltage(0, speeds.left), voltage(1, speeds.right))
+    }
+
+    /// advances the simulation by dt seconds and returns the new pose
+    pub fn step(&mut self, input: DriveInput<T>, dt: T) -> Pose2D<T> {
+        let p = self.params;
+        let two = T::from(2.0).unwrap();
+        let (r, d) = (p.wheel_radius, p.wheel_separation / two);
+        let jw = self.effective_wheel_inertia();
+        let normal = p.mass * p.gravity / two;
+        let max_force = p.traction_coefficient * normal;
+
+        let speeds = [self.wheel_speeds.left, self.wheel_speeds.right];
+        let drive = match input {
+            DriveInput::Voltage(v) => [
+                p.motor.wheel_torque(v.left, speeds[0]),
+                p.motor.wheel_torque(v.right, speeds[1]),
+            ],
+            DriveInput::Torque(t) => [t.left * p.motor.gear_ratio, t.right * p.motor.gear_ratio],
+        };
+        let torque = [
+            drive[0] - self.stiction(drive[0], speeds[0]),
+            drive[1] - self.stiction(drive[1], speeds[1]),
+        ];
+
+        // speed of the ground under each wheel and how fast the wheel slides over it
+        let contact = [self.v - d * self.omega, self.v + d * self.omega];
+        let slip_speed = [r * speeds[0] - contact[0], r * speeds[1] - contact[1]];
+        let tolerance = T::from(1e-6).unwrap();
+
+        // a wheel that is not sliding sticks unless that needs more force than traction allows
+        let mut stuck = [
+            slip_speed[0].abs() < tolerance,
+            slip_speed[1].abs() < tolerance,
+        ];
+        // direction of the friction force on each sliding wheel
+        let mut direction = [slip_speed[0].signum(), slip_speed[1].signum()];
+        let forces = loop {
+            let forces = self.contact_forces(&torque, &stuck, &direction, max_force, jw);
+            let mut changed = false;
+            for i in 0..2 {
+                if stuck[i] && forces[i].abs() > max_force {
+                    stuck[i] = false;
+                    direction[i] = forces[i].signum();
+                    changed = true;
+                }
+            }
+            if !changed {
+                break forces;
+            }
+        };
+
+        let accel = (forces[0] + forces[1]) / p.mass;
+        let angular_accel = d * (forces[1] - forces[0]) / p.inertia;
+        let wheel_accel = [
+            (torque[0] - r * forces[0]) / jw,
+            (torque[1] - r * forces[1]) / jw,
+        ];
+
+        // integrate the pose with the mean twist over the step
+        let v_next = self.v + accel * dt;
+        let omega_next = self.omega + angular_accel * dt;
+        let mean = Twist2D::new(
+            (self.omega + omega_next) / two * dt,
+            (self.v + v_next) / two * dt,
+            T::zero(),
+        );
+        let twb = Transform2D::from_pose(self.pose);
+        self.pose = (twb * twb.integrate_twist(mean)).to_pose();
+        self.v = v_next;
+        self.omega = omega_next;
+
+        // wheels that stuck roll with the ground, sliding wheels stop sliding
+        // once their slip changes sign
+        let contact = [self.v - d * self.omega, self.v + d * self.omega];
+        let mut next = [T::zero(); 2];
+        for i in 0..2 {
+            next[i] = speeds[i] + wheel_accel[i] * dt;
+            let slip = r * next[i] - contact[i];
+            if stuck[i] || slip * direction[i] < T::zero() {
+                next[i] = contact[i] / r;
+            }
+        }
+        self.wheel_speeds = WheelState::new(next[0], next[1]);
+        self.acceleration = (accel, angular_accel);
+        self.slipping = (!stuck[0], !stuck[1]);
+        self.pose
+    }
+
+    /// Solves for the ground force on each wheel. Stuck wheels take whatever
+    /// force keeps them rolling with the body, sliding wheels get kinetic friction.
+    fn contact_forces(
+        &self,
+        torque: &[T; 2],
+        stuck: &[bool; 2],
+        direction: &[T; 2],
+        max_force: T,
+        jw: T,
+    ) -> [T; 2] {
+        let p = &self.params;
+        let d = p.wheel_separation / T::from(2.0).unwrap();
+        let r = p.wheel_radius;
+        let sign = [-T::one(), T::one()];
+
+        let mut forces = [T::zero(); 2];
+        for i in 0..2 {
+            if !stuck[i] {
+                forces[i] = max_force * direction[i];
+            }
+        }
+
+        // rolling condition for stuck wheel i:
+        //   r (tau_i - r F_i) / jw = (F_l + F_r) / m + sign_i d^2 (F_r - F_l) / I
+        let coeff = |i: usize, j: usize| {
+            let body = T::one() / p.mass + sign[i] * sign[j] * d * d / p.inertia;
+            if i == j {
+                body + r * r / jw
+            } else {
+                body
+            }
+        };
+        let rhs = |i: usize, forces: &[T; 2]| {
+            let j = 1 - i;
+            let known = if stuck[j] {
+                T::zero()
+            } else {
+                coeff(i, j) * forces[j]
+            };
+            r * torque[i] / jw - known
+        };
+        match (stuck[0], stuck[1]) {
+            (true, true) => {
+                let (a, b, c, e) = (coeff(0, 0), coeff(0, 1), coeff(1, 0), coeff(1, 1));
+                let (r0, r1) = (r * torque[0] / jw, r * torque[1] / jw);
+                let det = a * e - b * c;
+                forces[0] = (e * r0 - b * r1) / det;
+                forces[1] = (a * r1 - c * r0) / det;
+            }
+            (true, false) => forces[0] = rhs(0, &forces) / coeff(0, 0),
+            (false, true) => forces[1] = rhs(1, &forces) / coeff(1, 1),
+            (false, false) => {}
+        }
+        forces
+    }
+
+    /// friction torque at a wheel axle turning at the given speed
+    fn friction(&self, speed: T) -> T {
+        let coulomb = if speed == T::zero() {
+            T::zero()
+        } else {
+            self.params.coulomb_friction * speed.signum()
+        };
+        coulomb + self.params.viscous_friction * speed
+    }
+
+    /// friction torque opposing the drive torque, which holds a wheel at rest
+    /// until the drive torque exceeds the Coulomb friction
+    fn stiction(&self, drive: T, speed: T) -> T {
+        if speed.abs() < T::from(1e-9).unwrap() {
+            drive
+                .max(-self.params.coulomb_friction)
+                .min(self.params.coulomb_friction)
+        } else {
+            self.friction(speed)
+        }
+    }
+}
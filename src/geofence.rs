@@ -0,0 +1,429 @@
+//! Geofences that keep the robot in allowed areas.
+//!
+//! A `Geofence` holds polygon zones in the map frame. The robot footprint
+//! must stay inside one of the inclusion zones, when there are any, and out
+//! of the exclusion zones, and it may only drive as fast as the tightest speed
+//! limit zone it touches. `Geofence::filter` projects the footprint along a
+//! command for a lookahead time and clamps the command, or rejects it,
+//! when the robot would otherwise leave the allowed area.
+//!
+//! With the `geojson` feature, zones can be loaded from a GeoJSON
+//! FeatureCollection whose coordinates are meters in the map frame rather
+//! than longitudes and latitudes. Every Polygon or MultiPolygon feature
+//! becomes a zone, described by the properties
+//!
+//! ```text
+//! "kind": "inclusion", "exclusion" or "speed_limit"
+//! "name": optional name, reported when the zone limits a command
+//! "max_linear", "max_angular": speed limits in m/s and rad/s
+//! ```
+//!
+//! Holes in polygons are not supported.
+use crate::geometry::Polygon;
+use crate::rigid2d::{Pose2D, Transform2D, Twist2D, Vector2D};
+use num_traits::Float;
+
+#[cfg(feature = "geojson")]
+use anyhow::{self, bail};
+#[cfg(feature = "geojson")]
+use std::path::Path;
+
+/// What a zone does
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ZoneKind<T: Float> {
+    /// the footprint must stay inside one of the inclusion zones
+    Inclusion,
+
+    /// the footprint must stay out of the zone
+    Exclusion,
+
+    /// speeds are limited while the footprint touches the zone
+    SpeedLimit { linear: T, angular: T },
+}
+
+/// A named polygon zone in the map frame
+#[derive(Debug, Clone)]
+pub struct GeoZone<T: Float> {
+    pub name: String,
+    pub kind: ZoneKind<T>,
+    pub polygon: Polygon<T>,
+}
+
+impl<T: Float> GeoZone<T> {
+    /// constructs a zone
+    pub fn new(name: &str, kind: ZoneKind<T>, polygon: Polygon<T>) -> Self {
+        GeoZone {
+            name: name.to_string(),
+            kind,
+            polygon,
+        }
+    }
+}
+
+/// How a footprint breaks the fence
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Violation {
+    /// the footprint does not fit inside any single inclusion zone
+    OutsideInclusion,
+
+    /// the footprint overlaps the named exclusion zone
+    InsideExclusion(String),
+}
+
+/// State of the robot at a pose
+#[derive(Debug, Clone)]
+pub struct FenceStatus<T: Float> {
+    pub violation: Option<Violation>,
+
+    /// tightest linear and angular speed limits of the zones the footprint
+    /// touches, infinite when there are none
+    pub speed_limit: (T, T),
+}
+
+impl<T: Float> FenceStatus<T> {
+    /// returns true if the footprint is in the allowed area
+    pub fn is_allowed(&self) -> bool {
+        self.violation.is_none()
+    }
+}
+
+/// What `Geofence::filter` did to a command
+#[derive(Debug, Clone, PartialEq)]
+pub enum FenceOutcome<T: Float> {
+    /// the command stays in the allowed area at its speed
+    Allowed,
+
+    /// the command was slowed down to the limit of a speed zone
+    SpeedLimited { zone: String },
+
+    /// the command was slowed down to the given fraction so that the robot
+    /// does not leave the allowed area within the lookahead
+    Clamped { scale: T, violation: Violation },
+
+    /// the command would leave the allowed area and was replaced by a stop
+    Rejected { violation: Violation },
+}
+
+/// What to do with commands that would leave the allowed area
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FenceAction {
+    /// slow them down so that the robot stays inside within the lookahead
+    Clamp,
+
+    /// replace them with a stop
+    Reject,
+}
+
+/// Polygon zones checked against the robot footprint
+#[derive(Debug, Clone)]
+pub struct Geofence<T: Float> {
+    zones: Vec<GeoZone<T>>,
+
+    /// robot outline in the robot frame
+    footprint: Polygon<T>,
+
+    /// time in seconds commands are projected ahead
+    lookahead: T,
+
+    /// largest distance a footprint point moves between checks
+    resolution: T,
+
+    action: FenceAction,
+}
+
+impl<T: Float> Geofence<T> {
+    /// Constructs a fence without zones for a robot with the given
+    /// footprint. Commands are projected 2 s ahead and clamped.
+    pub fn new(footprint: Polygon<T>) -> Self {
+        Geofence {
+            zones: vec![],
+            footprint,
+            lookahead: T::from(2.0).unwrap(),
+            resolution: T::from(0.05).unwrap(),
+            action: FenceAction::Clamp,
+        }
+    }
+
+    /// adds a zone
+    pub fn with_zone(mut self, zone: GeoZone<T>) -> Self {
+        self.zones.push(zone);
+        self
+    }
+
+    /// adds zones
+    pub fn with_zones(mut self, zones: Vec<GeoZone<T>>) -> Self {
+        self.zones.extend(zones);
+        self
+    }
+
+    /// sets how many seconds ahead commands are checked
+    pub fn with_lookahead(mut self, seconds: T) -> Self {
+        self.lookahead = seconds;
+        self
+    }
+
+    /// sets the largest distance in meters a footprint point moves between
+    /// checks, 5 cm by default
+    pub fn with_resolution(mut self, resolution: T) -> Self {
+        self.resolution = resolution;
+        self
+    }
+
+    /// sets whether commands that would leave the allowed area are clamped
+    /// or rejected
+    pub fn with_action(mut self, action: FenceAction) -> Self {
+        self.action = action;
+        self
+    }
+
+    /// returns the zones
+    pub fn zones(&self) -> &[GeoZone<T>] {
+        &self.zones
+    }
+
+    /// checks the footprint at a pose against the zones
+    pub fn check(&self, pose: Pose2D<T>) -> FenceStatus<T> {
+        let footprint = self.footprint.transform(&Transform2D::from_pose(pose));
+        let (violation, speed_limit, _) = self.check_footprint(&footprint);
+        FenceStatus {
+            violation,
+            speed_limit,
+        }
+    }
+
+    /// returns the violation, speed limits and the name of the zone with the
+    /// tightest linear limit for a footprint in the map frame
+    fn check_footprint(&self, footprint: &Polygon<T>) -> (Option<Violation>, (T, T), Option<&str>) {
+        let mut violation = None;
+        let mut limit = (T::infinity(), T::infinity());
+        let mut limiting = None;
+        let mut has_inclusion = false;
+        for zone in &self.zones {
+            match zone.kind {
+                ZoneKind::Inclusion => has_inclusion = true,
+                ZoneKind::Exclusion => {
+                    if violation.is_none() && zone.polygon.intersects(footprint) {
+                        violation = Some(Violation::InsideExclusion(zone.name.clone()));
+                    }
+                }
+                ZoneKind::SpeedLimit { linear, angular } => {
+                    if zone.polygon.intersects(footprint) {
+                        if linear < limit.0 {
+                            limiting = Some(zone.name.as_str());
+                        }
+                        limit = (limit.0.min(linear), limit.1.min(angular));
+                    }
+                }
+            }
+        }
+        if violation.is_none() && has_inclusion {
+            // the whole footprint has to fit in a single inclusion zone
+            let inside = self
+                .zones
+                .iter()
+                .any(|z| z.kind == ZoneKind::Inclusion && z.polygon.contains_polygon(footprint));
+            if !inside {
+                violation = Some(Violation::OutsideInclusion);
+            }
+        }
+        (violation, limit, limiting)
+    }
+
+    /// Returns the first violation of the footprint moving from the pose
+    /// with the twist for the lookahead, and the tightest speed limits and
+    /// limiting zone along the way
+    fn project(
+        &self,
+        pose: Pose2D<T>,
+        twist: Twist2D<T>,
+    ) -> (Option<Violation>, (T, T), Option<&str>) {
+        let start = Transform2D::from_pose(pose);
+        let speed = twist.xdot.hypot(twist.ydot);
+        let travel = (speed * self.lookahead)
+            .max(twist.thetadot.abs() * self.lookahead * self.footprint.bounding_radius());
+        let steps = (travel / self.resolution)
+            .ceil()
+            .to_usize()
+            .unwrap_or(1)
+            .clamp(1, 10_000);
+
+        let mut limit = (T::infinity(), T::infinity());
+        let mut limiting = None;
+        let origin = Transform2D::new(Vector2D::new(T::zero(), T::zero()), T::zero());
+        for k in 0..=steps {
+            let t = self.lookahead * T::from(k).unwrap() / T::from(steps).unwrap();
+            let moved = origin.integrate_twist(Twist2D::new(
+                twist.thetadot * t,
+                twist.xdot * t,
+                twist.ydot * t,
+            ));
+            let footprint = self.footprint.transform(&compose(&start, &moved));
+            let (violation, step_limit, step_limiting) = self.check_footprint(&footprint);
+            if violation.is_some() {
+                return (violation, limit, limiting);
+            }
+            if step_limit.0 < limit.0 {
+                limiting = step_limiting;
+            }
+            limit = (limit.0.min(step_limit.0), limit.1.min(step_limit.1));
+        }
+        (None, limit, limiting)
+    }
+
+    /// Limits a command from the pose so that the footprint stays in the
+    /// allowed area for the lookahead and keeps to the speed limits. A robot
+    /// that is already outside the allowed area may only take commands that
+    /// bring it back in within the lookahead.
+    pub fn filter(&self, pose: Pose2D<T>, twist: Twist2D<T>) -> (Twist2D<T>, FenceOutcome<T>) {
+        let stop = Twist2D::new(T::zero(), T::zero(), T::zero());
+        let scaled = |s: T| Twist2D::new(twist.thetadot * s, twist.xdot * s, twist.ydot * s);
+
+        let (violation, _, _) = self.project(pose, twist);
+        let mut scale = T::one();
+        let mut outcome = FenceOutcome::Allowed;
+        if let Some(violation) = violation {
+            if let Some(start) = self.check(pose).violation {
+                if !self.recovers(pose, twist) {
+                    return (stop, FenceOutcome::Rejected { violation: start });
+                }
+            } else {
+                if self.action == FenceAction::Reject {
+                    return (stop, FenceOutcome::Rejected { violation });
+                }
+                // largest scale whose projection stays inside
+                let (mut low, mut high) = (T::zero(), T::one());
+                for _ in 0..20 {
+                    let mid = (low + high) / T::from(2.0).unwrap();
+                    if self.project(pose, scaled(mid)).0.is_none() {
+                        low = mid;
+                    } else {
+                        high = mid;
+                    }
+                }
+                scale = low;
+                outcome = FenceOutcome::Clamped { scale, violation };
+            }
+        }
+
+        // speed limits of the zones touched along the way
+        let command = scaled(scale);
+        let (_, (linear, angular), zone) = self.project(pose, command);
+        let speed = command.xdot.hypot(command.ydot);
+        let mut factor = T::one();
+        if speed > linear {
+            factor = linear / speed;
+        }
+        if command.thetadot.abs() > angular {
+            factor = factor.min(angular / command.thetadot.abs());
+        }
+        if factor < T::one() {
+            if outcome == FenceOutcome::Allowed {
+                outcome = FenceOutcome::SpeedLimited {
+                    zone: zone.unwrap_or_default().to_string(),
+                };
+            }
+            return (scaled(scale * factor), outcome);
+        }
+        (command, outcome)
+    }
+
+    /// true if the footprint moving with the twist is allowed at some point
+    /// within the lookahead
+    fn recovers(&self, pose: Pose2D<T>, twist: Twist2D<T>) -> bool {
+        let steps = 20;
+        (1..=steps).any(|k| {
+            let t = self.lookahead * T::from(k).unwrap() / T::from(steps).unwrap();
+            let moved =
+                Transform2D::new(Vector2D::new(T::zero(), T::zero()), T::zero()).integrate_twist(
+                    Twist2D::new(twist.thetadot * t, twist.xdot * t, twist.ydot * t),
+                );
+            let at = compose(&Transform2D::from_pose(pose), &moved).to_pose();
+            self.check(at).is_allowed()
+        })
+    }
+}
+
+/// composes two transforms, applying b first
+fn compose<T: Float>(a: &Transform2D<T>, b: &Transform2D<T>) -> Transform2D<T> {
+    Transform2D::new(a.apply(b.translation()), a.rotation() + b.rotation())
+}
+
+#[cfg(feature = "geojson")]
+impl Geofence<f64> {
+    /// reads zones from a GeoJSON file, see the module documentation
+    pub fn read_geojson<P: AsRef<Path>>(path: P) -> anyhow::Result<Vec<GeoZone<f64>>> {
+        Geofence::parse_geojson(&std::fs::read_to_string(path)?)
+    }
+
+    /// parses zones from a GeoJSON FeatureCollection, see the module
+    /// documentation
+    pub fn parse_geojson(text: &str) -> anyhow::Result<Vec<GeoZone<f64>>> {
+        use serde_json::Value;
+
+        let root: Value = serde_json::from_str(text)?;
+        if root["type"] != "FeatureCollection" {
+            bail!("GeoJSON zones must be a FeatureCollection");
+        }
+        let features = match root["features"].as_array() {
+            Some(features) => features,
+            None => bail!("GeoJSON FeatureCollection has no features"),
+        };
+
+        let mut zones = vec![];
+        for (i, feature) in features.iter().enumerate() {
+            let properties = &feature["properties"];
+            let name = match properties["name"].as_str() {
+                Some(name) => name.to_string(),
+                None => format!("zone {}", i),
+            };
+            let limit = |key: &str| properties[key].as_f64().unwrap_or(f64::INFINITY);
+            let kind = match properties["kind"].as_str() {
+                Some("inclusion") => ZoneKind::Inclusion,
+                Some("exclusion") => ZoneKind::Exclusion,
+                Some("speed_limit") => ZoneKind::SpeedLimit {
+                    linear: limit("max_linear"),
+                    angular: limit("max_angular"),
+                },
+                Some(other) => bail!("{} has the unknown kind {}", name, other),
+                None => bail!("{} has no kind", name),
+            };
+
+            let geometry = &feature["geometry"];
+            let polygons = match geometry["type"].as_str() {
+                Some("Polygon") => vec![&geometry["coordinates"]],
+                Some("MultiPolygon") => match geometry["coordinates"].as_array() {
+                    Some(polygons) => polygons.iter().collect(),
+                    None => bail!("{} has no coordinates", name),
+                },
+                _ => bail!("{} is not a Polygon or MultiPolygon", name),
+            };
+            for rings in polygons {
+                let rings = rings.as_array().map(Vec::as_slice).unwrap_or_default();
+                let exterior = match rings {
+                    [] => bail!("{} has no coordinates", name),
+                    [exterior] => exterior,
+                    _ => bail!("{} has holes, which are not supported", name),
+                };
+                let mut vertices = vec![];
+                for position in exterior.as_array().map(Vec::as_slice).unwrap_or_default() {
+                    match (position[0].as_f64(), position[1].as_f64()) {
+                        (Some(x), Some(y)) => vertices.push(Vector2D::new(x, y)),
+                        _ => bail!("{} has a position that is not a pair of numbers", name),
+                    }
+                }
+                // GeoJSON rings repeat the first position at the end
+                if vertices.len() > 1 {
+                    let (first, last) = (vertices[0], vertices[vertices.len() - 1]);
+                    if first.x == last.x && first.y == last.y {
+                        vertices.pop();
+                    }
+                }
+                if vertices.len() < 3 {
+                    bail!("{} has fewer than three corners", name);
+                }
+                zones.push(GeoZone::new(&name, kind, Polygon::new(vertices)));
+            }
+        }
+        Ok(zones)
+    }
+}
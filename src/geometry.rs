@@ -0,0 +1,163 @@
+//! Planar polygons, used for robot footprints and zones.
+use crate::rigid2d::{Transform2D, Vector2D};
+use num_traits::Float;
+
+/// A simple polygon given by its vertices in order, without repeating the
+/// first vertex at the end
+#[derive(Debug, Clone)]
+pub struct Polygon<T: Float> {
+    vertices: Vec<Vector2D<T>>,
+}
+
+impl<T: Float> Polygon<T> {
+    /// constructs a polygon from its vertices, in either winding order
+    pub fn new(vertices: Vec<Vector2D<T>>) -> Self {
+        Polygon { vertices }
+    }
+
+    /// constructs a rectangle of the given length along x and width along
+    /// y, centered at the origin
+    pub fn rectangle(length: T, width: T) -> Self {
+        let (hx, hy) = (
+            length / T::from(2.0).unwrap(),
+            width / T::from(2.0).unwrap(),
+        );
+        Polygon::new(vec![
+            Vector2D::new(hx, hy),
+            Vector2D::new(-hx, hy),
+            Vector2D::new(-hx, -hy),
+            Vector2D::new(hx, -hy),
+        ])
+    }
+
+    /// returns the vertices
+    pub fn vertices(&self) -> &[Vector2D<T>] {
+        &self.vertices
+    }
+
+    /// returns the edges as pairs of consecutive vertices
+    pub fn edges(&self) -> impl Iterator<Item = (Vector2D<T>, Vector2D<T>)> + '_ {
+        let n = self.vertices.len();
+        (0..n).map(move |i| (self.vertices[i], self.vertices[(i + 1) % n]))
+    }
+
+    /// returns the enclosed area
+    pub fn area(&self) -> T {
+        let twice = self
+            .edges()
+            .fold(T::zero(), |acc, (a, b)| acc + a.x * b.y - b.x * a.y);
+        twice.abs() / T::from(2.0).unwrap()
+    }
+
+    /// returns the largest distance of a vertex from the origin
+    pub fn bounding_radius(&self) -> T {
+        self.vertices
+            .iter()
+            .fold(T::zero(), |acc, v| acc.max(v.x.hypot(v.y)))
+    }
+
+    /// returns true if the point is inside the polygon or on its boundary
+    pub fn contains(&self, p: Vector2D<T>) -> bool {
+        let mut inside = false;
+        for (a, b) in self.edges() {
+            if segment_distance(p, a, b) == T::zero() {
+                return true;
+            }
+            if (a.y > p.y) != (b.y > p.y) {
+                let x = a.x + (p.y - a.y) / (b.y - a.y) * (b.x - a.x);
+                if p.x < x {
+                    inside = !inside;
+                }
+            }
+        }
+        inside
+    }
+
+    /// returns the distance from the point to the polygon, zero inside
+    pub fn distance(&self, p: Vector2D<T>) -> T {
+        if self.contains(p) {
+            return T::zero();
+        }
+        self.boundary_distance(p)
+    }
+
+    /// returns the distance from the point to the nearest edge, inside or
+    /// outside
+    pub fn boundary_distance(&self, p: Vector2D<T>) -> T {
+        self.edges()
+            .map(|(a, b)| segment_distance(p, a, b))
+            .fold(T::infinity(), T::min)
+    }
+
+    /// returns true if the polygons overlap or touch
+    pub fn intersects(&self, other: &Polygon<T>) -> bool {
+        if self.vertices.is_empty() || other.vertices.is_empty() {
+            return false;
+        }
+        self.edges()
+            .any(|(a, b)| other.edges().any(|(c, d)| segments_intersect(a, b, c, d)))
+            || self.contains(other.vertices[0])
+            || other.contains(self.vertices[0])
+    }
+
+    /// returns true if the other polygon lies inside this one, touching its
+    /// boundary at most
+    pub fn contains_polygon(&self, other: &Polygon<T>) -> bool {
+        other.vertices.iter().all(|v| self.contains(*v))
+            && !self
+                .edges()
+                .any(|(a, b)| other.edges().any(|(c, d)| segments_cross(a, b, c, d)))
+    }
+
+    /// returns the polygon moved by a transform
+    pub fn transform(&self, transform: &Transform2D<T>) -> Self {
+        Polygon::new(self.vertices.iter().map(|v| transform.apply(*v)).collect())
+    }
+}
+
+/// returns the distance from a point to the segment ab
+pub fn segment_distance<T: Float>(p: Vector2D<T>, a: Vector2D<T>, b: Vector2D<T>) -> T {
+    let (dx, dy) = (b.x - a.x, b.y - a.y);
+    let length2 = dx * dx + dy * dy;
+    let t = if length2 > T::zero() {
+        (((p.x - a.x) * dx + (p.y - a.y) * dy) / length2)
+            .max(T::zero())
+            .min(T::one())
+    } else {
+        T::zero()
+    };
+    (a.x + dx * t - p.x).hypot(a.y + dy * t - p.y)
+}
+
+/// returns true if the segments ab and cd touch or cross
+pub fn segments_intersect<T: Float>(
+    a: Vector2D<T>,
+    b: Vector2D<T>,
+    c: Vector2D<T>,
+    d: Vector2D<T>,
+) -> bool {
+    if segments_cross(a, b, c, d) {
+        return true;
+    }
+    // collinear and touching cases
+    segment_distance(a, c, d) == T::zero()
+        || segment_distance(b, c, d) == T::zero()
+        || segment_distance(c, a, b) == T::zero()
+        || segment_distance(d, a, b) == T::zero()
+}
+
+/// returns true if the segments ab and cd cross at a point inside both
+fn segments_cross<T: Float>(
+    a: Vector2D<T>,
+    b: Vector2D<T>,
+    c: Vector2D<T>,
+    d: Vector2D<T>,
+) -> bool {
+    let cross = |o: Vector2D<T>, p: Vector2D<T>, q: Vector2D<T>| {
+        (p.x - o.x) * (q.y - o.y) - (p.y - o.y) * (q.x - o.x)
+    };
+    let (d1, d2) = (cross(c, d, a), cross(c, d, b));
+    let (d3, d4) = (cross(a, b, c), cross(a, b, d));
+    ((d1 > T::zero() && d2 < T::zero()) || (d1 < T::zero() && d2 > T::zero()))
+        && ((d3 > T::zero() && d4 < T::zero()) || (d3 < T::zero() && d4 > T::zero()))
+}
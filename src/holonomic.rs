@@ -0,0 +1,182 @@
+//! Holonomic bases with an arbitrary number of omni or mecanum wheels.
+//!
+//! Each wheel is described by its position, the direction it drives in and
+//! the angle of its rollers. Together these form a wheel-geometry matrix J
+//! mapping a full (thetadot, xdot, ydot) twist to the wheel speeds. With more
+//! wheels than degrees of freedom, odometry uses the least-squares inverse
+//! (J^T J)^-1 J^T.
+use crate::kinematics::Kinematics;
+use crate::linalg::{self, Matrix3};
+use crate::rigid2d::{Twist2D, Vector2D};
+use anyhow::{self, bail};
+use num_traits::Float;
+
+/// Placement and geometry of a single omni or mecanum wheel
+#[derive(Debug, Clone, Copy)]
+pub struct WheelGeometry<T: Float> {
+    /// position of the wheel contact point in the body frame
+    pub position: Vector2D<T>,
+
+    /// direction the wheel drives in, measured from the body x axis (radians)
+    pub orientation: T,
+
+    /// angle of the free-rolling direction of the rollers away from the
+    /// direction perpendicular to the wheel, 0 for omni wheels and +/- 45
+    /// degrees for mecanum wheels (radians)
+    pub roller_angle: T,
+
+    /// radius of the wheel
+    pub radius: T,
+}
+
+impl<T: Float> WheelGeometry<T> {
+    /// constructs a new WheelGeometry
+    pub fn new(position: Vector2D<T>, orientation: T, roller_angle: T, radius: T) -> Self {
+        WheelGeometry {
+            position,
+            orientation,
+            roller_angle,
+            radius,
+        }
+    }
+
+    /// returns the row of the wheel-geometry matrix, ordered (thetadot, xdot, ydot)
+    pub fn jacobian_row(&self) -> [T; 3] {
+        let angle = self.orientation + self.roller_angle;
+        let (s, c) = (angle.sin(), angle.cos());
+        let k = T::one() / (self.radius * self.roller_angle.cos());
+        [
+            k * (self.position.x * s - self.position.y * c),
+            k * c,
+            k * s,
+        ]
+    }
+}
+
+/// A holonomic base driven by any number of omni or mecanum wheels
+#[derive(Debug, Clone)]
+pub struct HolonomicDrive<T: Float> {
+    wheels: Vec<WheelGeometry<T>>,
+
+    /// wheel-geometry matrix, one row per wheel
+    jacobian: Vec<[T; 3]>,
+
+    /// (J^T J)^-1
+    normal_inverse: Matrix3<T>,
+}
+
+impl<T: Float> HolonomicDrive<T> {
+    /// constructs a base from its wheels, or returns an error if the wheels
+    /// can not produce every planar twist
+    pub fn new(wheels: Vec<WheelGeometry<T>>) -> anyhow::Result<Self> {
+        if wheels.len() < 3 {
+            bail!("a holonomic base needs at least 3 wheels");
+        }
+        let jacobian: Vec<[T; 3]> = wheels.iter().map(|w| w.jacobian_row()).collect();
+        let mut normal: Matrix3<T> = linalg::zeros3();
+        for row in &jacobian {
+            for a in 0..3 {
+                for b in 0..3 {
+                    normal[a][b] = normal[a][b] + row[a] * row[b];
+                }
+            }
+        }
+        let normal_inverse = match linalg::inverse3(&normal) {
+            Some(inv) => inv,
+            None => bail!("the wheel geometry does not span all three degrees of freedom"),
+        };
+        Ok(HolonomicDrive {
+            wheels,
+            jacobian,
+            normal_inverse,
+        })
+    }
+
+    /// constructs a 4-wheel mecanum base with 45 degree rollers in an X
+    /// configuration, wheels ordered front left, front right, rear left, rear right
+    pub fn mecanum(wheel_radius: T, wheelbase: T, track_width: T) -> anyhow::Result<Self> {
+        let two = T::from(2.0).unwrap();
+        let (x, y) = (wheelbase / two, track_width / two);
+        let quarter = T::from(std::f64::consts::FRAC_PI_4).unwrap();
+        HolonomicDrive::new(vec![
+            WheelGeometry::new(Vector2D::new(x, y), T::zero(), -quarter, wheel_radius),
+            WheelGeometry::new(Vector2D::new(x, -y), T::zero(), quarter, wheel_radius),
+            WheelGeometry::new(Vector2D::new(-x, y), T::zero(), quarter, wheel_radius),
+            WheelGeometry::new(Vector2D::new(-x, -y), T::zero(), -quarter, wheel_radius),
+        ])
+    }
+
+    /// constructs a base with n omni wheels evenly spaced on a circle, each
+    /// driving tangentially, with the first wheel on the +y axis
+    pub fn omni(wheel_radius: T, base_radius: T, n: usize) -> anyhow::Result<Self> {
+        let step = T::from(2.0 * std::f64::consts::PI).unwrap() / T::from(n).unwrap();
+        let half_pi = T::from(std::f64::consts::FRAC_PI_2).unwrap();
+        let wheels = (0..n)
+            .map(|i| {
+                let angle = half_pi + T::from(i).unwrap() * step;
+                WheelGeometry::new(
+                    Vector2D::from_polar(base_radius, angle),
+                    angle + half_pi,
+                    T::zero(),
+                    wheel_radius,
+                )
+            })
+            .collect();
+        HolonomicDrive::new(wheels)
+    }
+
+    /// returns the wheels of the base
+    pub fn wheels(&self) -> &[WheelGeometry<T>] {
+        &self.wheels
+    }
+
+    /// returns the wheel-geometry matrix, one (thetadot, xdot, ydot) row per wheel
+    pub fn jacobian(&self) -> &[[T; 3]] {
+        &self.jacobian
+    }
+
+    /// returns the norm of the part of the wheel speeds that no rigid body
+    /// twist explains, which grows when wheels slip
+    pub fn residual(&self, wheels: &[T]) -> T {
+        let twist = self.twist_from_wheels(&wheels.to_vec());
+        let fitted = self.speeds(&twist);
+        fitted
+            .iter()
+            .zip(wheels)
+            .fold(T::zero(), |acc, (a, b)| acc + (*a - *b) * (*a - *b))
+            .sqrt()
+    }
+
+    fn speeds(&self, twist: &Twist2D<T>) -> Vec<T> {
+        self.jacobian
+            .iter()
+            .map(|row| row[0] * twist.thetadot + row[1] * twist.xdot + row[2] * twist.ydot)
+            .collect()
+    }
+}
+
+impl<T: Float> Kinematics<T> for HolonomicDrive<T> {
+    /// one speed per wheel, in the order the wheels were given
+    type Wheels = Vec<T>;
+
+    /// least-squares estimate of the twist from the wheel speeds
+    fn twist_from_wheels(&self, wheels: &Vec<T>) -> Twist2D<T> {
+        let mut jt_w = [T::zero(); 3];
+        for (row, w) in self.jacobian.iter().zip(wheels) {
+            for a in 0..3 {
+                jt_w[a] = jt_w[a] + row[a] * *w;
+            }
+        }
+        let mut v = [T::zero(); 3];
+        for (a, row) in self.normal_inverse.iter().enumerate() {
+            for b in 0..3 {
+                v[a] = v[a] + row[b] * jt_w[b];
+            }
+        }
+        Twist2D::new(v[0], v[1], v[2])
+    }
+
+    fn wheels_from_twist(&self, twist: &Twist2D<T>) -> anyhow::Result<Vec<T>> {
+        Ok(self.speeds(twist))
+    }
+}
@@ -0,0 +1,319 @@
+//! Kinematics of mobile bases.
+//!
+//! The `Kinematics` trait maps between the wheel state of a base and its body
+//! twist, and updates odometry from wheel displacements, so planners and
+//! controllers can be written once for every base type. Implementations are
+//! provided for differential drive, skid-steer, 4-wheel mecanum, 3-wheel omni
+//! and Ackermann (bicycle) bases.
+use crate::ddrive::{DiffDrive, WheelState};
+use crate::rigid2d::{Pose2D, Transform2D, Twist2D};
+use anyhow::{self, bail};
+use num_traits::Float;
+
+/// Maps between the wheel state of a mobile base and its body twist
+pub trait Kinematics<T: Float> {
+    /// wheel speeds (or displacements) and steering state of the base
+    type Wheels;
+
+    /// computes the body twist produced by the given wheel speeds
+    fn twist_from_wheels(&self, wheels: &Self::Wheels) -> Twist2D<T>;
+
+    /// computes the wheel speeds that produce the given body twist, or an
+    /// error if the base can not follow the twist
+    fn wheels_from_twist(&self, twist: &Twist2D<T>) -> anyhow::Result<Self::Wheels>;
+
+    /// computes the new pose of the base after the wheels moved by the given
+    /// displacements, assuming a constant twist over the step
+    fn odometry_update(&self, pose: Pose2D<T>, wheel_deltas: &Self::Wheels) -> Pose2D<T> {
+        let twist = self.twist_from_wheels(wheel_deltas);
+        let twb = Transform2D::from_pose(pose);
+        (twb * twb.integrate_twist(twist)).to_pose()
+    }
+}
+
+fn tolerance<T: Float>() -> T {
+    T::from(0.0001).unwrap()
+}
+
+impl<T: Float + Default> Kinematics<T> for DiffDrive<T> {
+    type Wheels = WheelState<T>;
+
+    fn twist_from_wheels(&self, wheels: &WheelState<T>) -> Twist2D<T> {
+        self.twist_from_speeds(*wheels)
+    }
+
+    fn wheels_from_twist(&self, twist: &Twist2D<T>) -> anyhow::Result<WheelState<T>> {
+        if twist.ydot.abs() >= tolerance() {
+            bail!("a differential drive can not move sideways");
+        }
+        Ok(self.speeds_from_twist(Twist2D::new(twist.thetadot, twist.xdot, T::zero())))
+    }
+}
+
+/// A skid-steer base whose left and right wheels are driven together. Wheel
+/// slip while turning is modeled by the instantaneous centers of rotation
+/// (ICR) of the left and right treads, which lie further out than the wheels.
+#[derive(Debug, Clone, Copy)]
+pub struct SkidSteer<T: Float> {
+    /// radius of the wheels
+    wheel_radius: T,
+
+    /// y coordinate of the ICR of the left tread (positive)
+    y_icr_left: T,
+
+    /// y coordinate of the ICR of the right tread (negative)
+    y_icr_right: T,
+
+    /// x coordinate of the ICR of the body
+    x_icr: T,
+}
+
+impl<T: Float> SkidSteer<T> {
+    /// constructs a symmetric SkidSteer. The slip factor (>= 1) scales the
+    /// track width to the effective distance between the tread ICRs.
+    pub fn new(wheel_radius: T, track_width: T, slip_factor: T) -> Self {
+        let half = track_width * slip_factor / T::from(2.0).unwrap();
+        SkidSteer::from_icr(wheel_radius, half, -half, T::zero())
+    }
+
+    /// constructs a SkidSteer from identified ICR coordinates
+    pub fn from_icr(wheel_radius: T, y_icr_left: T, y_icr_right: T, x_icr: T) -> Self {
+        SkidSteer {
+            wheel_radius,
+            y_icr_left,
+            y_icr_right,
+            x_icr,
+        }
+    }
+}
+
+impl<T: Float + Default> Kinematics<T> for SkidSteer<T> {
+    type Wheels = WheelState<T>;
+
+    fn twist_from_wheels(&self, wheels: &WheelState<T>) -> Twist2D<T> {
+        let vl = self.wheel_radius * wheels.left;
+        let vr = self.wheel_radius * wheels.right;
+        let span = self.y_icr_left - self.y_icr_right;
+        Twist2D::new(
+            (vr - vl) / span,
+            (self.y_icr_left * vr - self.y_icr_right * vl) / span,
+            self.x_icr * (vl - vr) / span,
+        )
+    }
+
+    fn wheels_from_twist(&self, twist: &Twist2D<T>) -> anyhow::Result<WheelState<T>> {
+        if (twist.ydot + self.x_icr * twist.thetadot).abs() >= tolerance() {
+            bail!("a skid-steer base can only move sideways as its ICR offset allows");
+        }
+        Ok(WheelState::new(
+            (twist.xdot - self.y_icr_left * twist.thetadot) / self.wheel_radius,
+            (twist.xdot - self.y_icr_right * twist.thetadot) / self.wheel_radius,
+        ))
+    }
+}
+
+/// A 4-wheel mecanum base with 45 degree rollers in an X configuration.
+/// Wheels are ordered front left, front right, rear left, rear right.
+#[derive(Debug, Clone, Copy)]
+pub struct Mecanum<T: Float> {
+    /// radius of the wheels
+    wheel_radius: T,
+
+    /// half the distance between the front and rear axles
+    half_wheelbase: T,
+
+    /// half the distance between the left and right wheels
+    half_track: T,
+}
+
+impl<T: Float> Mecanum<T> {
+    /// constructs a new Mecanum base
+    pub fn new(wheel_radius: T, wheelbase: T, track_width: T) -> Self {
+        let two = T::from(2.0).unwrap();
+        Mecanum {
+            wheel_radius,
+            half_wheelbase: wheelbase / two,
+            half_track: track_width / two,
+        }
+    }
+}
+
+impl<T: Float> Kinematics<T> for Mecanum<T> {
+    type Wheels = [T; 4];
+
+    fn twist_from_wheels(&self, wheels: &[T; 4]) -> Twist2D<T> {
+        let [fl, fr, rl, rr] = *wheels;
+        let k = self.wheel_radius / T::from(4.0).unwrap();
+        Twist2D::new(
+            k * (-fl + fr - rl + rr) / (self.half_wheelbase + self.half_track),
+            k * (fl + fr + rl + rr),
+            k * (-fl + fr + rl - rr),
+        )
+    }
+
+    fn wheels_from_twist(&self, twist: &Twist2D<T>) -> anyhow::Result<[T; 4]> {
+        let w = (self.half_wheelbase + self.half_track) * twist.thetadot;
+        let (vx, vy, r) = (twist.xdot, twist.ydot, self.wheel_radius);
+        Ok([
+            (vx - vy - w) / r,
+            (vx + vy + w) / r,
+            (vx + vy - w) / r,
+            (vx - vy + w) / r,
+        ])
+    }
+}
+
+/// A 3-wheel omni base with the wheels spaced 120 degrees apart, each
+/// driving tangentially to the circle they sit on
+#[derive(Debug, Clone, Copy)]
+pub struct Omni3<T: Float> {
+    /// radius of the wheels
+    wheel_radius: T,
+
+    /// distance from the center of the base to each wheel
+    base_radius: T,
+
+    /// angle of the first wheel from the x axis, the others follow counter clockwise
+    first_wheel_angle: T,
+}
+
+impl<T: Float> Omni3<T> {
+    /// constructs a new Omni3 base with the first wheel on the +y axis
+    pub fn new(wheel_radius: T, base_radius: T) -> Self {
+        Omni3 {
+            wheel_radius,
+            base_radius,
+            first_wheel_angle: T::from(std::f64::consts::FRAC_PI_2).unwrap(),
+        }
+    }
+
+    /// returns the angular positions of the wheels
+    pub fn wheel_angles(&self) -> [T; 3] {
+        let step = T::from(2.0 * std::f64::consts::PI / 3.0).unwrap();
+        [0, 1, 2].map(|i| self.first_wheel_angle + T::from(i).unwrap() * step)
+    }
+}
+
+impl<T: Float> Kinematics<T> for Omni3<T> {
+    type Wheels = [T; 3];
+
+    fn twist_from_wheels(&self, wheels: &[T; 3]) -> Twist2D<T> {
+        let (mut vx, mut vy, mut w) = (T::zero(), T::zero(), T::zero());
+        for (angle, speed) in self.wheel_angles().iter().zip(wheels) {
+            vx = vx - angle.sin() * *speed;
+            vy = vy + angle.cos() * *speed;
+            w = w + *speed;
+        }
+        let k = T::from(2.0 / 3.0).unwrap() * self.wheel_radius;
+        Twist2D::new(
+            self.wheel_radius * w / (T::from(3.0).unwrap() * self.base_radius),
+            k * vx,
+            k * vy,
+        )
+    }
+
+    fn wheels_from_twist(&self, twist: &Twist2D<T>) -> anyhow::Result<[T; 3]> {
+        Ok(self.wheel_angles().map(|angle| {
+            (-angle.sin() * twist.xdot
+                + angle.cos() * twist.ydot
+                + self.base_radius * twist.thetadot)
+                / self.wheel_radius
+        }))
+    }
+}
+
+/// Wheel state of an Ackermann base
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AckermannState<T: Float> {
+    /// speed (or displacement) of the rear wheels in radians
+    pub wheel_speed: T,
+
+    /// angle of the virtual front wheel at the center of the front axle
+    pub steering_angle: T,
+}
+
+impl<T: Float> AckermannState<T> {
+    /// constructs a new AckermannState
+    pub fn new(wheel_speed: T, steering_angle: T) -> Self {
+        AckermannState {
+            wheel_speed,
+            steering_angle,
+        }
+    }
+}
+
+/// A car-like base modeled as a bicycle, with the body frame at the center
+/// of the rear axle
+#[derive(Debug, Clone, Copy)]
+pub struct Ackermann<T: Float> {
+    /// radius of the rear wheels
+    wheel_radius: T,
+
+    /// distance between the front and rear axles
+    wheelbase: T,
+
+    /// distance between the front wheels
+    track_width: T,
+
+    /// largest steering angle the base can reach
+    max_steering_angle: T,
+}
+
+impl<T: Float> Ackermann<T> {
+    /// constructs a new Ackermann base
+    pub fn new(wheel_radius: T, wheelbase: T, track_width: T, max_steering_angle: T) -> Self {
+        Ackermann {
+            wheel_radius,
+            wheelbase,
+            track_width,
+            max_steering_angle,
+        }
+    }
+
+    /// returns the (left, right) front wheel angles that realize the steering
+    /// angle of the virtual center wheel without scrubbing
+    pub fn front_wheel_angles(&self, steering_angle: T) -> (T, T) {
+        if steering_angle.abs() < T::epsilon() {
+            return (T::zero(), T::zero());
+        }
+        let radius = self.wheelbase / steering_angle.tan();
+        let half = self.track_width / T::from(2.0).unwrap();
+        (
+            (self.wheelbase / (radius - half)).atan(),
+            (self.wheelbase / (radius + half)).atan(),
+        )
+    }
+}
+
+impl<T: Float> Kinematics<T> for Ackermann<T> {
+    type Wheels = AckermannState<T>;
+
+    fn twist_from_wheels(&self, wheels: &AckermannState<T>) -> Twist2D<T> {
+        let v = self.wheel_radius * wheels.wheel_speed;
+        Twist2D::new(
+            v * wheels.steering_angle.tan() / self.wheelbase,
+            v,
+            T::zero(),
+        )
+    }
+
+    fn wheels_from_twist(&self, twist: &Twist2D<T>) -> anyhow::Result<AckermannState<T>> {
+        if twist.ydot.abs() >= tolerance() {
+            bail!("an Ackermann base can not move sideways");
+        }
+        if twist.xdot.abs() < tolerance() {
+            if twist.thetadot.abs() >= tolerance() {
+                bail!("an Ackermann base can not turn in place");
+            }
+            return Ok(AckermannState::new(T::zero(), T::zero()));
+        }
+        let steering_angle = (twist.thetadot * self.wheelbase / twist.xdot).atan();
+        if steering_angle.abs() > self.max_steering_angle {
+            bail!("the twist needs a steering angle beyond the steering limit");
+        }
+        Ok(AckermannState::new(
+            twist.xdot / self.wheel_radius,
+            steering_angle,
+        ))
+    }
+}
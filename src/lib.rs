@@ -25,10 +25,88 @@
 //! }
 //! ```
 //! `
+//!
+//! # Features
+//! - `std` (default): everything, including the modules that need the
+//!   standard library and CSV I/O
+//! - `alloc`: `Path`, `linspace`, `arange` and `Vector2D::to_vec` without
+//!   the standard library
+//! - `serde`: Serialize and Deserialize for the rigid body types, the wheel
+//!   types and `DiffDriveConfig`
+//! - `config`: loading and saving configs as TOML, YAML or JSON
+//! - `cli`: the `diff-drive` command-line tool
+//! - `mcap`: exporting and importing pose, twist, wheel and path streams
+//!   as MCAP files
+//! - `geojson`: loading geofence zones from GeoJSON
+//!
+//! With default features off, `rigid2d`, `ddrive`, `utils` and `scalar`
+//! build under `no_std`, using `libm` for the float math. These modules are
+//! generic over `scalar::Scalar`, which is implemented for every float and
+//! for the `scalar::Q16` fixed-point type.
+#![cfg_attr(not(feature = "std"), no_std)]
 #![allow(unused_imports)]
 
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+#[cfg(feature = "std")]
+pub mod calibration;
+#[cfg(feature = "std")]
+pub mod cmd_mux;
+#[cfg(feature = "config")]
+pub mod config;
+#[cfg(feature = "std")]
+pub mod coverage;
 pub mod ddrive;
+#[cfg(feature = "std")]
+pub mod dynamics;
+#[cfg(feature = "std")]
+pub mod geofence;
+#[cfg(feature = "std")]
+pub mod geometry;
+#[cfg(feature = "std")]
+pub mod holonomic;
+#[cfg(feature = "std")]
+pub mod kinematics;
+#[cfg(feature = "std")]
+pub mod linalg;
+#[cfg(feature = "std")]
+pub mod mapping;
+#[cfg(feature = "mcap")]
+pub mod mcap;
+#[cfg(feature = "std")]
+pub mod motor_link;
+#[cfg(feature = "std")]
+pub mod net;
+#[cfg(feature = "std")]
+pub mod orca;
+#[cfg(feature = "std")]
+pub mod pid;
+#[cfg(feature = "std")]
+pub mod planning;
+#[cfg(feature = "std")]
+pub mod plot;
+#[cfg(feature = "std")]
+pub mod pose_graph;
 pub mod rigid2d;
+#[cfg(feature = "std")]
+pub mod safety;
+pub mod scalar;
+#[cfg(feature = "std")]
+pub mod scan;
+#[cfg(feature = "std")]
+pub mod scan_matching;
+#[cfg(feature = "std")]
+pub mod sim;
+#[cfg(feature = "std")]
+pub mod telemetry;
+#[cfg(feature = "std")]
+pub mod teleop;
+#[cfg(feature = "std")]
+pub mod tracking;
+#[cfg(feature = "std")]
+pub mod trailer;
+#[cfg(feature = "alloc")]
 pub mod trajectory;
 pub mod utils;
 
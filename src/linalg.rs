@@ -0,0 +1,212 @@
+//! Linear algebra helpers for 3-dof (x, y, theta) problems
+#![allow(clippy::needless_range_loop)]
+
+use num_traits::Float;
+use std::collections::BTreeMap;
+
+/// A 3x3 matrix stored row major
+pub type Matrix3<T> = [[T; 3]; 3];
+
+/// returns the 3x3 zero matrix
+pub fn zeros3<T: Float>() -> Matrix3<T> {
+    [[T::zero(); 3]; 3]
+}
+
+/// returns the 3x3 identity matrix
+pub fn identity3<T: Float>() -> Matrix3<T> {
+    let mut m = zeros3();
+    for (i, row) in m.iter_mut().enumerate() {
+        row[i] = T::one();
+    }
+    m
+}
+
+/// computes the product of two 3x3 matrices
+pub fn mul3<T: Float>(a: &Matrix3<T>, b: &Matrix3<T>) -> Matrix3<T> {
+    let mut m = zeros3();
+    for i in 0..3 {
+        for j in 0..3 {
+            for k in 0..3 {
+                m[i][j] = m[i][j] + a[i][k] * b[k][j];
+            }
+        }
+    }
+    m
+}
+
+/// computes the transpose of a 3x3 matrix
+pub fn transpose3<T: Float>(a: &Matrix3<T>) -> Matrix3<T> {
+    let mut m = zeros3();
+    for i in 0..3 {
+        for j in 0..3 {
+            m[i][j] = a[j][i];
+        }
+    }
+    m
+}
+
+/// computes the determinant of a 3x3 matrix
+pub fn det3<T: Float>(a: &Matrix3<T>) -> T {
+    a[0][0] * (a[1][1] * a[2][2] - a[1][2] * a[2][1])
+        - a[0][1] * (a[1][0] * a[2][2] - a[1][2] * a[2][0])
+        + a[0][2] * (a[1][0] * a[2][1] - a[1][1] * a[2][0])
+}
+
+/// Computes the inverse of a 3x3 matrix, or None if it is singular. The
+/// determinant is compared to the product of the row norms, which bounds
+/// it, so that matrices with small entries still invert.
+pub fn inverse3<T: Float>(a: &Matrix3<T>) -> Option<Matrix3<T>> {
+    let det = det3(a);
+    let scale = a.iter().fold(T::one(), |acc, row| {
+        acc * row.iter().fold(T::zero(), |n, v| n.hypot(*v))
+    });
+    if !det.is_finite() || det.abs() <= T::epsilon() * scale {
+        return None;
+    }
+    let mut m = zeros3();
+    for i in 0..3 {
+        for j in 0..3 {
+            // cofactor of a[j][i]
+            let (r0, r1) = ((j + 1) % 3, (j + 2) % 3);
+            let (c0, c1) = ((i + 1) % 3, (i + 2) % 3);
+            m[i][j] = (a[r0][c0] * a[r1][c1] - a[r0][c1] * a[r1][c0]) / det;
+        }
+    }
+    Some(m)
+}
+
+/// solves a x = b for a 3x3 matrix a, or returns None if it is singular
+pub fn solve3<T: Float>(a: &Matrix3<T>, b: &[T; 3]) -> Option<[T; 3]> {
+    let inv = inverse3(a)?;
+    let mut x = [T::zero(); 3];
+    for i in 0..3 {
+        for j in 0..3 {
+            x[i] = x[i] + inv[i][j] * b[j];
+        }
+    }
+    Some(x)
+}
+
+/// A symmetric matrix made of 3x3 blocks, of which only the non-zero blocks are stored
+#[derive(Debug, Clone)]
+pub struct BlockSparseMatrix<T: Float> {
+    /// non-zero blocks of every block row, keyed by block column
+    rows: Vec<BTreeMap<usize, Matrix3<T>>>,
+}
+
+impl<T: Float> BlockSparseMatrix<T> {
+    /// constructs an empty matrix of n x n blocks
+    pub fn new(n: usize) -> Self {
+        BlockSparseMatrix {
+            rows: vec![BTreeMap::new(); n],
+        }
+    }
+
+    /// returns the number of block rows
+    pub fn block_rows(&self) -> usize {
+        self.rows.len()
+    }
+
+    /// returns the number of stored blocks
+    pub fn num_blocks(&self) -> usize {
+        self.rows.iter().map(|r| r.len()).sum()
+    }
+
+    /// returns the block at (i, j), or None if it is zero
+    pub fn block(&self, i: usize, j: usize) -> Option<&Matrix3<T>> {
+        self.rows[i].get(&j)
+    }
+
+    /// adds m to the block at (i, j)
+    pub fn add_block(&mut self, i: usize, j: usize, m: &Matrix3<T>) {
+        let block = self.rows[i].entry(j).or_insert_with(zeros3);
+        for a in 0..3 {
+            for b in 0..3 {
+                block[a][b] = block[a][b] + m[a][b];
+            }
+        }
+    }
+
+    /// computes the product of the matrix with a vector of 3 * block_rows elements
+    pub fn mul_vec(&self, x: &[T]) -> Vec<T> {
+        let mut y = vec![T::zero(); x.len()];
+        for (i, row) in self.rows.iter().enumerate() {
+            for (j, m) in row {
+                for a in 0..3 {
+                    for b in 0..3 {
+                        y[3 * i + a] = y[3 * i + a] + m[a][b] * x[3 * j + b];
+                    }
+                }
+            }
+        }
+        y
+    }
+
+    /// Solves A x = b for a symmetric positive definite matrix with the block
+    /// Jacobi preconditioned conjugate gradient method. The variables of the
+    /// blocks marked as fixed are held at zero.
+    pub fn solve_cg(&self, b: &[T], fixed: &[bool], tolerance: T, max_iterations: usize) -> Vec<T> {
+        let n = b.len();
+        let free = |i: usize| !fixed.get(i / 3).copied().unwrap_or(false);
+        let precond: Vec<Matrix3<T>> = (0..self.rows.len())
+            .map(|i| {
+                self.block(i, i)
+                    .and_then(inverse3)
+                    .unwrap_or_else(identity3)
+            })
+            .collect();
+        let apply_precond = |r: &[T]| {
+            let mut z = vec![T::zero(); n];
+            for (i, m) in precond.iter().enumerate() {
+                for a in 0..3 {
+                    if !free(3 * i + a) {
+                        continue;
+                    }
+                    for c in 0..3 {
+                        z[3 * i + a] = z[3 * i + a] + m[a][c] * r[3 * i + c];
+                    }
+                }
+            }
+            z
+        };
+        let dot = |u: &[T], v: &[T]| u.iter().zip(v).fold(T::zero(), |acc, (a, b)| acc + *a * *b);
+
+        let mut x = vec![T::zero(); n];
+        let mut r: Vec<T> = (0..n)
+            .map(|i| if free(i) { b[i] } else { T::zero() })
+            .collect();
+        let mut z = apply_precond(&r);
+        let mut p = z.clone();
+        let mut rz = dot(&r, &z);
+        let threshold = tolerance * dot(&r, &r).sqrt();
+
+        for _ in 0..max_iterations {
+            if dot(&r, &r).sqrt() <= threshold {
+                break;
+            }
+            let mut ap = self.mul_vec(&p);
+            for (i, v) in ap.iter_mut().enumerate() {
+                if !free(i) {
+                    *v = T::zero();
+                }
+            }
+            let pap = dot(&p, &ap);
+            if pap <= T::zero() {
+                break;
+            }
+            let alpha = rz / pap;
+            for i in 0..n {
+                x[i] = x[i] + alpha * p[i];
+                r[i] = r[i] - alpha * ap[i];
+            }
+            z = apply_precond(&r);
+            let rz_new = dot(&r, &z);
+            let beta = rz_new / rz;
+            rz = rz_new;
+            for i in 0..n {
+                p[i] = z[i] + beta * p[i];
+            }
+        }
+        x
+    }
+}
@@ -0,0 +1,413 @@
+//! Occupancy grid mapping with known poses.
+//!
+//! Range scans taken at poses from `DiffDrive` odometry are integrated into
+//! a grid of log-odds values using an inverse sensor model.
+use crate::rigid2d::{Pose2D, Vector2D};
+use crate::scan::LaserScan;
+use anyhow::{self, bail};
+use num_traits::Float;
+use std::fs::File;
+use std::io::Write;
+
+/// Converts a probability to log-odds
+pub fn log_odds<T: Float>(p: T) -> T {
+    (p / (T::one() - p)).ln()
+}
+
+/// Converts log-odds to a probability
+pub fn probability<T: Float>(l: T) -> T {
+    T::one() - T::one() / (T::one() + l.exp())
+}
+
+/// Classification of a single grid cell
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CellState {
+    Free,
+    Occupied,
+    Unknown,
+}
+
+/// Inverse sensor model for a range beam: the probability that a cell is
+/// occupied given that the beam ended in it (p_hit) or passed through it (p_miss)
+#[derive(Debug, Clone, Copy)]
+pub struct InverseSensorModel<T: Float> {
+    /// occupancy probability of the cell a beam ended in
+    pub p_hit: T,
+
+    /// occupancy probability of a cell a beam passed through
+    pub p_miss: T,
+}
+
+impl<T: Float> InverseSensorModel<T> {
+    /// constructs a new InverseSensorModel from the hit and miss probabilities
+    pub fn new(p_hit: T, p_miss: T) -> Self {
+        InverseSensorModel { p_hit, p_miss }
+    }
+}
+
+impl<T: Float> Default for InverseSensorModel<T> {
+    fn default() -> Self {
+        InverseSensorModel::new(T::from(0.7).unwrap(), T::from(0.4).unwrap())
+    }
+}
+
+/// A 2D probabilistic occupancy grid stored as log-odds
+#[derive(Debug, Clone)]
+pub struct OccupancyGrid<T: Float> {
+    /// number of cells along x
+    width: usize,
+
+    /// number of cells along y
+    height: usize,
+
+    /// side length of a cell in meters
+    resolution: T,
+
+    /// world coordinates of the lower left corner of cell (0, 0)
+    origin: Vector2D<T>,
+
+    /// log-odds of every cell, row major starting at the origin
+    cells: Vec<T>,
+
+    /// inverse sensor model used when integrating scans
+    model: InverseSensorModel<T>,
+
+    /// lower and upper clamping bounds on the log-odds of a cell
+    clamp: (T, T),
+
+    /// probabilities above/below which a cell is considered occupied/free
+    thresholds: (T, T),
+}
+
+impl<T: Float> OccupancyGrid<T> {
+    /// constructs an unknown grid of width x height cells. Panics if the
+    /// number of cells overflows usize.
+    pub fn new(width: usize, height: usize, resolution: T, origin: Vector2D<T>) -> Self {
+        let cells = width
+            .checked_mul(height)
+            .expect("the grid has too many cells");
+        OccupancyGrid {
+            width,
+            height,
+            resolution,
+            origin,
+            cells: vec![T::zero(); cells],
+            model: InverseSensorModel::default(),
+            clamp: (T::from(-2.0).unwrap(), T::from(3.5).unwrap()),
+            thresholds: (T::from(0.35).unwrap(), T::from(0.65).unwrap()),
+        }
+    }
+
+    /// sets the inverse sensor model
+    pub fn with_sensor_model(mut self, model: InverseSensorModel<T>) -> Self {
+        self.model = model;
+        self
+    }
+
+    /// sets the bounds the log-odds of each cell are clamped to, which keeps
+    /// the map able to react to changes in the environment
+    pub fn with_clamping(mut self, l_min: T, l_max: T) -> Self {
+        self.clamp = (l_min, l_max);
+        self
+    }
+
+    /// sets the probabilities below which a cell is free and above which a cell is occupied
+    pub fn with_thresholds(mut self, free: T, occupied: T) -> Self {
+        self.thresholds = (free, occupied);
+        self
+    }
+
+    /// returns the number of cells along x
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// returns the number of cells along y
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// returns the side length of a cell in meters
+    pub fn resolution(&self) -> T {
+        self.resolution
+    }
+
+    /// returns the world coordinates of the lower left corner of the grid
+    pub fn origin(&self) -> Vector2D<T> {
+        self.origin
+    }
+
+    /// returns the cell containing the world point, or None if it is off the grid
+    pub fn world_to_cell(&self, p: Vector2D<T>) -> Option<(usize, usize)> {
+        let (ix, iy) = self.world_to_index(p)?;
+        if self.in_bounds(ix, iy) {
+            Some((ix as usize, iy as usize))
+        } else {
+            None
+        }
+    }
+
+    /// returns the world coordinates of the center of the cell
+    pub fn cell_to_world(&self, ix: usize, iy: usize) -> Vector2D<T> {
+        let half = T::from(0.5).unwrap();
+        Vector2D::new(
+            self.origin.x + (T::from(ix).unwrap() + half) * self.resolution,
+            self.origin.y + (T::from(iy).unwrap() + half) * self.resolution,
+        )
+    }
+
+    /// returns the log-odds of the cell
+    pub fn log_odds(&self, ix: usize, iy: usize) -> T {
+        self.cells[iy * self.width + ix]
+    }
+
+    /// returns the occupancy probability of the cell
+    pub fn probability(&self, ix: usize, iy: usize) -> T {
+        probability(self.log_odds(ix, iy))
+    }
+
+    /// classifies the cell as free, occupied or unknown
+    pub fn state(&self, ix: usize, iy: usize) -> CellState {
+        let p = self.probability(ix, iy);
+        if p < self.thresholds.0 {
+            CellState::Free
+        } else if p > self.thresholds.1 {
+            CellState::Occupied
+        } else {
+            CellState::Unknown
+        }
+    }
+
+    /// returns the state of every cell, row major starting at the origin
+    pub fn states(&self) -> Vec<CellState> {
+        (0..self.height)
+            .flat_map(|iy| (0..self.width).map(move |ix| (ix, iy)))
+            .map(|(ix, iy)| self.state(ix, iy))
+            .collect()
+    }
+
+    /// adds the log-odds update to the cell and clamps the result
+    pub fn update_cell(&mut self, ix: usize, iy: usize, delta: T) {
+        let l = &mut self.cells[iy * self.width + ix];
+        *l = (*l + delta).max(self.clamp.0).min(self.clamp.1);
+    }
+
+    /// integrates a range scan taken by a sensor at the given pose in the world frame.
+    /// Cells traversed by a beam are updated as free and the cell a beam ended
+    /// in is updated as occupied. Beams with no return only clear cells. Beams
+    /// are clipped to the grid, and a scan from a pose that is not finite is
+    /// ignored.
+    pub fn integrate_scan(&mut self, pose: Pose2D<T>, scan: &LaserScan<T>) {
+        if !(pose.x.is_finite() && pose.y.is_finite() && pose.theta.is_finite()) {
+            return;
+        }
+        let l_hit = log_odds(self.model.p_hit);
+        let l_miss = log_odds(self.model.p_miss);
+        let origin = Vector2D::new(pose.x, pose.y);
+
+        for (angle, range) in scan.beams() {
+            if range.is_nan() || range < scan.range_min {
+                continue;
+            }
+            let hit = scan.is_hit(range);
+            let length = if hit { range } else { scan.range_max };
+            let (s, c) = (pose.theta + angle).sin_cos();
+            let (start, end, clipped) = match self.clip(origin, Vector2D::new(c, s), length) {
+                Some(segment) => segment,
+                None => continue,
+            };
+            let (start, end) = match (self.world_to_index(start), self.world_to_index(end)) {
+                (Some(start), Some(end)) => (start, end),
+                _ => continue,
+            };
+
+            let ray = bresenham(start, end);
+            let last = ray.len().saturating_sub(1);
+            for (i, (ix, iy)) in ray.into_iter().enumerate() {
+                if !self.in_bounds(ix, iy) {
+                    continue;
+                }
+                let delta = if hit && !clipped && i == last {
+                    l_hit
+                } else {
+                    l_miss
+                };
+                self.update_cell(ix as usize, iy as usize, delta);
+            }
+        }
+    }
+
+    /// Clips the beam from the origin along the unit direction, possibly of
+    /// infinite length, to the grid. Returns the ends of the part on the
+    /// grid and whether the far end was cut off, or None if the beam misses
+    /// the grid.
+    fn clip(
+        &self,
+        origin: Vector2D<T>,
+        direction: Vector2D<T>,
+        length: T,
+    ) -> Option<(Vector2D<T>, Vector2D<T>, bool)> {
+        let max = Vector2D::new(
+            self.origin.x + T::from(self.width).unwrap() * self.resolution,
+            self.origin.y + T::from(self.height).unwrap() * self.resolution,
+        );
+        let (mut t0, mut t1) = (T::zero(), length);
+        for (p, d, lo, hi) in [
+            (origin.x, direction.x, self.origin.x, max.x),
+            (origin.y, direction.y, self.origin.y, max.y),
+        ] {
+            if d == T::zero() {
+                if p < lo || p > hi {
+                    return None;
+                }
+                continue;
+            }
+            let (a, b) = ((lo - p) / d, (hi - p) / d);
+            t0 = t0.max(a.min(b));
+            t1 = t1.min(a.max(b));
+        }
+        if t0 > t1 {
+            return None;
+        }
+        let at = |t: T| Vector2D::new(origin.x + t * direction.x, origin.y + t * direction.y);
+        Some((at(t0), at(t1), t1 < length))
+    }
+
+    /// encodes the grid as a binary PGM image with free cells white,
+    /// occupied cells black and unknown cells gray. The top row of the
+    /// image is the row of the grid with the largest y.
+    pub fn to_pgm(&self) -> Vec<u8> {
+        let mut bytes = format!("P5\n{} {}\n255\n", self.width, self.height).into_bytes();
+        for iy in (0..self.height).rev() {
+            for ix in 0..self.width {
+                bytes.push(match self.state(ix, iy) {
+                    CellState::Free => 254,
+                    CellState::Occupied => 0,
+                    CellState::Unknown => 205,
+                });
+            }
+        }
+        bytes
+    }
+
+    /// writes the grid to a PGM image file
+    pub fn write_pgm(&self, filename: &str) -> anyhow::Result<()> {
+        let mut file = File::create(filename)?;
+        file.write_all(&self.to_pgm())?;
+        Ok(())
+    }
+
+    /// Decodes a binary PGM image, like the ones `to_pgm` writes, into a
+    /// grid with the given resolution and origin. As in ROS map files, a
+    /// pixel is occupied if its darkness is above 0.65, free if it is below
+    /// 0.196 and unknown otherwise.
+    pub fn from_pgm(bytes: &[u8], resolution: T, origin: Vector2D<T>) -> anyhow::Result<Self> {
+        // the header is the magic number and three integers, separated by
+        // whitespace and possibly comments
+        let mut fields = vec![];
+        let mut at = 0;
+        while fields.len() < 4 {
+            while at < bytes.len() && bytes[at].is_ascii_whitespace() {
+                at += 1;
+            }
+            if at < bytes.len() && bytes[at] == b'#' {
+                while at < bytes.len() && bytes[at] != b'\n' {
+                    at += 1;
+                }
+                continue;
+            }
+            let start = at;
+            while at < bytes.len() && !bytes[at].is_ascii_whitespace() {
+                at += 1;
+            }
+            if start == at {
+                bail!("PGM header is cut short");
+            }
+            fields.push(std::str::from_utf8(&bytes[start..at])?);
+        }
+        if fields[0] != "P5" {
+            bail!("only binary (P5) PGM images are supported");
+        }
+        let width: usize = fields[1].parse()?;
+        let height: usize = fields[2].parse()?;
+        let max_value: usize = fields[3].parse()?;
+        if max_value == 0 || max_value > 255 {
+            bail!("only 8 bit PGM images are supported");
+        }
+        // a single whitespace character separates the header from the pixels
+        let pixels = &bytes[(at + 1).min(bytes.len())..];
+        match width.checked_mul(height) {
+            Some(size) if size == pixels.len() => {}
+            _ => bail!(
+                "PGM header says {} x {} pixels, but the image has {} bytes of pixel data",
+                width,
+                height,
+                pixels.len()
+            ),
+        }
+
+        let mut grid = OccupancyGrid::new(width, height, resolution, origin);
+        let (l_min, l_max) = grid.clamp;
+        for iy in 0..height {
+            for ix in 0..width {
+                // the top row of the image is the row with the largest y
+                let value = pixels[(height - 1 - iy) * width + ix] as f64;
+                let darkness = (max_value as f64 - value) / max_value as f64;
+                grid.cells[iy * width + ix] = if darkness > 0.65 {
+                    l_max
+                } else if darkness < 0.196 {
+                    l_min
+                } else {
+                    T::zero()
+                };
+            }
+        }
+        Ok(grid)
+    }
+
+    /// reads a grid from a PGM image file
+    pub fn read_pgm(filename: &str, resolution: T, origin: Vector2D<T>) -> anyhow::Result<Self> {
+        OccupancyGrid::from_pgm(&std::fs::read(filename)?, resolution, origin)
+    }
+
+    /// returns the index of the cell containing the point, which may be off
+    /// the grid, or None if the point is not finite or too far away
+    fn world_to_index(&self, p: Vector2D<T>) -> Option<(i64, i64)> {
+        Some((
+            ((p.x - self.origin.x) / self.resolution).floor().to_i64()?,
+            ((p.y - self.origin.y) / self.resolution).floor().to_i64()?,
+        ))
+    }
+
+    fn in_bounds(&self, ix: i64, iy: i64) -> bool {
+        ix >= 0 && iy >= 0 && (ix as usize) < self.width && (iy as usize) < self.height
+    }
+}
+
+/// Returns the cells on the line between two cells, including both ends
+fn bresenham(start: (i64, i64), end: (i64, i64)) -> Vec<(i64, i64)> {
+    let (mut x, mut y) = start;
+    let dx = (end.0 - x).abs();
+    let dy = -(end.1 - y).abs();
+    let sx = if x < end.0 { 1 } else { -1 };
+    let sy = if y < end.1 { 1 } else { -1 };
+    let mut err = dx + dy;
+
+    let mut cells = vec![];
+    loop {
+        cells.push((x, y));
+        if x == end.0 && y == end.1 {
+            break;
+        }
+        let e2 = 2 * err;
+        if e2 >= dy {
+            err += dy;
+            x += sx;
+        }
+        if e2 <= dx {
+            err += dx;
+            y += sy;
+        }
+    }
+    cells
+}
@@ -0,0 +1,527 @@
+//! Export and import of robot data as MCAP files.
+//!
+//! `McapWriter` writes pose, twist, wheel state and path streams as JSON
+//! encoded channels, either with a JSON schema per channel or schemaless,
+//! and `McapLog` reads those channels back into timelines. Times are in
+//! seconds in the API and in nanoseconds in the file.
+//!
+//! Only the parts of the format needed to exchange these streams are
+//! implemented: the writer emits an unchunked file without a summary
+//! section, and the reader understands unchunked files and uncompressed
+//! chunks, which covers the files written here and by most tools set to
+//! not compress.
+use crate::ddrive::WheelState;
+use crate::rigid2d::{Pose2D, Twist2D};
+use crate::utils::{crc32, crc32_update};
+use anyhow::{self, bail};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+
+/// Magic bytes at the start and end of every MCAP file
+pub const MAGIC: [u8; 8] = [0x89, b'M', b'C', b'A', b'P', b'0', b'\r', b'\n'];
+
+const OP_HEADER: u8 = 0x01;
+const OP_FOOTER: u8 = 0x02;
+const OP_SCHEMA: u8 = 0x03;
+const OP_CHANNEL: u8 = 0x04;
+const OP_MESSAGE: u8 = 0x05;
+const OP_CHUNK: u8 = 0x06;
+const OP_DATA_END: u8 = 0x0F;
+
+/// Kind of data carried by a channel
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreamKind {
+    Pose,
+    Twist,
+    Wheels,
+    Path,
+}
+
+impl StreamKind {
+    /// returns the name of the schema of the channel
+    pub fn schema_name(&self) -> &'static str {
+        match self {
+            StreamKind::Pose => "diff_drive.Pose2D",
+            StreamKind::Twist => "diff_drive.Twist2D",
+            StreamKind::Wheels => "diff_drive.WheelState",
+            StreamKind::Path => "diff_drive.Path",
+        }
+    }
+
+    fn json_schema(&self) -> String {
+        let object = |fields: &[&str]| {
+            let properties: Vec<String> = fields
+                .iter()
+                .map(|f| format!(r#""{}":{{"type":"number"}}"#, f))
+                .collect();
+            format!(
+                r#"{{"type":"object","properties":{{{}}},"required":[{}]}}"#,
+                properties.join(","),
+                fields
+                    .iter()
+                    .map(|f| format!(r#""{}""#, f))
+                    .collect::<Vec<_>>()
+                    .join(",")
+            )
+        };
+        let pose = object(&["x", "y", "theta"]);
+        match self {
+            StreamKind::Pose => pose,
+            StreamKind::Twist => object(&["thetadot", "xdot", "ydot"]),
+            StreamKind::Wheels => object(&["left", "right"]),
+            StreamKind::Path => format!(
+                r#"{{"type":"object","properties":{{"poses":{{"type":"array","items":{}}}}},"required":["poses"]}}"#,
+                pose
+            ),
+        }
+    }
+}
+
+/// JSON body of a path message
+#[derive(Serialize, Deserialize)]
+struct PathMessage {
+    poses: Vec<Pose2D<f64>>,
+}
+
+/// converts a time in seconds to nanoseconds
+fn to_nanos(time: f64) -> anyhow::Result<u64> {
+    if !(0.0..=u64::MAX as f64 / 1e9).contains(&time) {
+        bail!("time {} s can not be stored in an MCAP file", time);
+    }
+    Ok((time * 1e9).round() as u64)
+}
+
+fn put_str(out: &mut Vec<u8>, s: &str) {
+    out.extend_from_slice(&(s.len() as u32).to_le_bytes());
+    out.extend_from_slice(s.as_bytes());
+}
+
+/// Writes robot data streams to an MCAP file
+pub struct McapWriter<W: Write> {
+    inner: W,
+
+    /// CRC-32 of everything written so far
+    crc: u32,
+
+    /// channel id and kind of each topic
+    channels: HashMap<String, (u16, StreamKind)>,
+
+    /// schema id of each stream kind written so far
+    schemas: HashMap<&'static str, u16>,
+
+    /// sequence number of the next message on each channel
+    sequences: HashMap<u16, u32>,
+
+    /// whether channels are written with a JSON schema
+    use_schemas: bool,
+}
+
+impl<W: Write> McapWriter<W> {
+    /// writes the start of an MCAP file and returns a writer for it
+    pub fn new(inner: W) -> anyhow::Result<Self> {
+        let mut writer = McapWriter {
+            inner,
+            crc: 0,
+            channels: HashMap::new(),
+            schemas: HashMap::new(),
+            sequences: HashMap::new(),
+            use_schemas: true,
+        };
+        writer.write_bytes(&MAGIC)?;
+        let mut header = vec![];
+        put_str(&mut header, "");
+        put_str(
+            &mut header,
+            concat!("diff-drive ", env!("CARGO_PKG_VERSION")),
+        );
+        writer.write_record(OP_HEADER, &header)?;
+        Ok(writer)
+    }
+
+    /// writes the channels without schemas, leaving it to the reader to
+    /// know what their JSON holds
+    pub fn schemaless(mut self) -> Self {
+        self.use_schemas = false;
+        self
+    }
+
+    /// writes a pose to a pose stream
+    pub fn write_pose(&mut self, topic: &str, time: f64, pose: &Pose2D<f64>) -> anyhow::Result<()> {
+        self.write_message(topic, StreamKind::Pose, time, pose)
+    }
+
+    /// writes a twist to a twist stream
+    pub fn write_twist(
+        &mut self,
+        topic: &str,
+        time: f64,
+        twist: &Twist2D<f64>,
+    ) -> anyhow::Result<()> {
+        self.write_message(topic, StreamKind::Twist, time, twist)
+    }
+
+    /// writes wheel angles or speeds to a wheel state stream
+    pub fn write_wheels(
+        &mut self,
+        topic: &str,
+        time: f64,
+        wheels: &WheelState<f64>,
+    ) -> anyhow::Result<()> {
+        self.write_message(topic, StreamKind::Wheels, time, wheels)
+    }
+
+    /// writes a whole path, e.g. a planned route, to a path stream
+    pub fn write_path(
+        &mut self,
+        topic: &str,
+        time: f64,
+        poses: &[Pose2D<f64>],
+    ) -> anyhow::Result<()> {
+        let path = PathMessage {
+            poses: poses.to_vec(),
+        };
+        self.write_message(topic, StreamKind::Path, time, &path)
+    }
+
+    /// ends the file and returns the underlying writer
+    pub fn finish(mut self) -> anyhow::Result<W> {
+        let crc = self.crc;
+        self.write_record(OP_DATA_END, &crc.to_le_bytes())?;
+        // no summary section, so the offsets and the summary CRC are zero
+        self.write_record(OP_FOOTER, &[0; 20])?;
+        self.write_bytes(&MAGIC)?;
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+
+    fn write_message<S: Serialize>(
+        &mut self,
+        topic: &str,
+        kind: StreamKind,
+        time: f64,
+        message: &S,
+    ) -> anyhow::Result<()> {
+        let log_time = to_nanos(time)?;
+        let channel = self.channel(topic, kind)?;
+        let sequence = self.sequences.entry(channel).or_insert(0);
+        let mut record = vec![];
+        record.extend_from_slice(&channel.to_le_bytes());
+        record.extend_from_slice(&sequence.to_le_bytes());
+        record.extend_from_slice(&log_time.to_le_bytes());
+        record.extend_from_slice(&log_time.to_le_bytes());
+        *sequence = sequence.wrapping_add(1);
+        serde_json::to_writer(&mut record, message)?;
+        self.write_record(OP_MESSAGE, &record)
+    }
+
+    /// returns the id of the topic's channel, writing the channel and its
+    /// schema the first time the topic is used
+    fn channel(&mut self, topic: &str, kind: StreamKind) -> anyhow::Result<u16> {
+        if let Some(&(id, existing)) = self.channels.get(topic) {
+            if existing != kind {
+                bail!(
+                    "topic {} holds {} messages, not {}",
+                    topic,
+                    existing.schema_name(),
+                    kind.schema_name()
+                );
+            }
+            return Ok(id);
+        }
+
+        let schema_id = if self.use_schemas {
+            self.schema(kind)?
+        } else {
+            0
+        };
+        let id = self.channels.len() as u16;
+        let mut record = vec![];
+        record.extend_from_slice(&id.to_le_bytes());
+        record.extend_from_slice(&schema_id.to_le_bytes());
+        put_str(&mut record, topic);
+        put_str(&mut record, "json");
+        // empty metadata map
+        record.extend_from_slice(&0u32.to_le_bytes());
+        self.write_record(OP_CHANNEL, &record)?;
+        self.channels.insert(topic.to_string(), (id, kind));
+        Ok(id)
+    }
+
+    fn schema(&mut self, kind: StreamKind) -> anyhow::Result<u16> {
+        if let Some(&id) = self.schemas.get(kind.schema_name()) {
+            return Ok(id);
+        }
+        // schema id 0 is reserved for schemaless channels
+        let id = self.schemas.len() as u16 + 1;
+        let data = kind.json_schema();
+        let mut record = vec![];
+        record.extend_from_slice(&id.to_le_bytes());
+        put_str(&mut record, kind.schema_name());
+        put_str(&mut record, "jsonschema");
+        put_str(&mut record, &data);
+        self.write_record(OP_SCHEMA, &record)?;
+        self.schemas.insert(kind.schema_name(), id);
+        Ok(id)
+    }
+
+    fn write_record(&mut self, opcode: u8, content: &[u8]) -> anyhow::Result<()> {
+        self.write_bytes(&[opcode])?;
+        self.write_bytes(&(content.len() as u64).to_le_bytes())?;
+        self.write_bytes(content)
+    }
+
+    fn write_bytes(&mut self, bytes: &[u8]) -> anyhow::Result<()> {
+        self.crc = crc32_update(self.crc, bytes);
+        self.inner.write_all(bytes)?;
+        Ok(())
+    }
+}
+
+/// A channel of an MCAP file
+#[derive(Debug, Clone, PartialEq)]
+pub struct McapChannel {
+    pub id: u16,
+    pub topic: String,
+
+    /// name of the channel's schema, None for schemaless channels
+    pub schema_name: Option<String>,
+    pub message_encoding: String,
+}
+
+/// A message of an MCAP file
+#[derive(Debug, Clone, PartialEq)]
+pub struct McapMessage {
+    pub channel_id: u16,
+    pub sequence: u32,
+
+    /// log time in nanoseconds
+    pub log_time: u64,
+    pub data: Vec<u8>,
+}
+
+/// The channels and messages of an MCAP file
+#[derive(Debug, Clone, Default)]
+pub struct McapLog {
+    pub channels: Vec<McapChannel>,
+
+    /// messages in the order they appear in the file
+    pub messages: Vec<McapMessage>,
+}
+
+/// Reads the fields of a record one after the other
+struct Fields<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Fields<'a> {
+    fn take(&mut self, n: usize) -> anyhow::Result<&'a [u8]> {
+        if self.bytes.len() < n {
+            bail!("MCAP record is cut short");
+        }
+        let (head, tail) = self.bytes.split_at(n);
+        self.bytes = tail;
+        Ok(head)
+    }
+
+    fn u16(&mut self) -> anyhow::Result<u16> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into()?))
+    }
+
+    fn u32(&mut self) -> anyhow::Result<u32> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into()?))
+    }
+
+    fn u64(&mut self) -> anyhow::Result<u64> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into()?))
+    }
+
+    fn string(&mut self) -> anyhow::Result<String> {
+        let len = self.u32()? as usize;
+        Ok(String::from_utf8(self.take(len)?.to_vec())?)
+    }
+}
+
+impl McapLog {
+    /// reads an MCAP file
+    pub fn load<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
+        McapLog::from_bytes(&fs::read(path)?)
+    }
+
+    /// parses the contents of an MCAP file
+    pub fn from_bytes(bytes: &[u8]) -> anyhow::Result<Self> {
+        if bytes.len() < 2 * MAGIC.len() || bytes[..8] != MAGIC {
+            bail!("not an MCAP file");
+        }
+        if bytes[bytes.len() - 8..] != MAGIC {
+            bail!("MCAP file is incomplete");
+        }
+
+        let mut log = McapLog::default();
+        let mut schemas = HashMap::new();
+        let mut offset = MAGIC.len();
+        loop {
+            let (opcode, content, next) = McapLog::record(bytes, offset)?;
+            match opcode {
+                OP_FOOTER => break,
+                OP_DATA_END => {
+                    let expected = Fields { bytes: content }.u32()?;
+                    if expected != 0 && crc32(&bytes[..offset]) != expected {
+                        bail!("MCAP data section is corrupt");
+                    }
+                }
+                OP_CHUNK => log.read_chunk(content, &mut schemas)?,
+                _ => log.read_record(opcode, content, &mut schemas)?,
+            }
+            offset = next;
+        }
+        Ok(log)
+    }
+
+    /// returns the opcode and content of the record at the offset, and the
+    /// offset of the next record
+    fn record(bytes: &[u8], offset: usize) -> anyhow::Result<(u8, &[u8], usize)> {
+        if bytes.len() < offset + 9 {
+            bail!("MCAP file ends in the middle of a record");
+        }
+        let len = u64::from_le_bytes(bytes[offset + 1..offset + 9].try_into()?) as usize;
+        let start = offset + 9;
+        if bytes.len() - start < len {
+            bail!("MCAP file ends in the middle of a record");
+        }
+        Ok((bytes[offset], &bytes[start..start + len], start + len))
+    }
+
+    fn read_chunk(
+        &mut self,
+        content: &[u8],
+        schemas: &mut HashMap<u16, String>,
+    ) -> anyhow::Result<()> {
+        let mut fields = Fields { bytes: content };
+        fields.take(16)?; // message start and end time
+        fields.u64()?; // uncompressed size
+        let crc = fields.u32()?;
+        let compression = fields.string()?;
+        if !compression.is_empty() {
+            bail!("{} compressed MCAP chunks are not supported", compression);
+        }
+        let len = fields.u64()? as usize;
+        let records = fields.take(len)?;
+        if crc != 0 && crc32(records) != crc {
+            bail!("MCAP chunk is corrupt");
+        }
+        let mut offset = 0;
+        while offset < records.len() {
+            let (opcode, content, next) = McapLog::record(records, offset)?;
+            self.read_record(opcode, content, schemas)?;
+            offset = next;
+        }
+        Ok(())
+    }
+
+    /// reads a schema, channel or message record and skips the rest
+    fn read_record(
+        &mut self,
+        opcode: u8,
+        content: &[u8],
+        schemas: &mut HashMap<u16, String>,
+    ) -> anyhow::Result<()> {
+        let mut fields = Fields { bytes: content };
+        match opcode {
+            OP_SCHEMA => {
+                let id = fields.u16()?;
+                schemas.insert(id, fields.string()?);
+            }
+            OP_CHANNEL => {
+                let id = fields.u16()?;
+                let schema_id = fields.u16()?;
+                let topic = fields.string()?;
+                let message_encoding = fields.string()?;
+                let schema_name = match schema_id {
+                    0 => None,
+                    id => match schemas.get(&id) {
+                        Some(name) => Some(name.clone()),
+                        None => bail!("channel {} uses the unknown schema {}", topic, id),
+                    },
+                };
+                self.channels.push(McapChannel {
+                    id,
+                    topic,
+                    schema_name,
+                    message_encoding,
+                });
+            }
+            OP_MESSAGE => {
+                let channel_id = fields.u16()?;
+                let sequence = fields.u32()?;
+                let log_time = fields.u64()?;
+                fields.u64()?; // publish time
+                self.messages.push(McapMessage {
+                    channel_id,
+                    sequence,
+                    log_time,
+                    data: fields.bytes.to_vec(),
+                });
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// returns the topics of the file
+    pub fn topics(&self) -> Vec<&str> {
+        self.channels.iter().map(|c| c.topic.as_str()).collect()
+    }
+
+    /// returns the channel of a topic
+    pub fn channel(&self, topic: &str) -> Option<&McapChannel> {
+        self.channels.iter().find(|c| c.topic == topic)
+    }
+
+    /// decodes the JSON messages of a topic into a time-stamped timeline
+    pub fn timeline<M: DeserializeOwned>(&self, topic: &str) -> anyhow::Result<Vec<(f64, M)>> {
+        let channel = match self.channel(topic) {
+            Some(channel) => channel,
+            None => bail!("no topic named {}", topic),
+        };
+        if channel.message_encoding != "json" {
+            bail!(
+                "topic {} is encoded as {}, not json",
+                topic,
+                channel.message_encoding
+            );
+        }
+        self.messages
+            .iter()
+            .filter(|m| m.channel_id == channel.id)
+            .map(|m| Ok((m.log_time as f64 * 1e-9, serde_json::from_slice(&m.data)?)))
+            .collect()
+    }
+
+    /// returns the poses of a pose stream
+    pub fn poses(&self, topic: &str) -> anyhow::Result<Vec<(f64, Pose2D<f64>)>> {
+        self.timeline(topic)
+    }
+
+    /// returns the twists of a twist stream
+    pub fn twists(&self, topic: &str) -> anyhow::Result<Vec<(f64, Twist2D<f64>)>> {
+        self.timeline(topic)
+    }
+
+    /// returns the wheel states of a wheel state stream
+    pub fn wheel_states(&self, topic: &str) -> anyhow::Result<Vec<(f64, WheelState<f64>)>> {
+        self.timeline(topic)
+    }
+
+    /// returns the paths of a path stream
+    pub fn paths(&self, topic: &str) -> anyhow::Result<Vec<(f64, Vec<Pose2D<f64>>)>> {
+        Ok(self
+            .timeline::<PathMessage>(topic)?
+            .into_iter()
+            .map(|(time, path)| (time, path.poses))
+            .collect())
+    }
+}
@@ -0,0 +1,534 @@
+//! Framed binary protocol for talking to a motor controller.
+//!
+//! The host sends wheel velocity commands and heartbeats, and the motor
+//! controller answers with encoder counts. Each frame is
+//!
+//! ```text
+//! u8 kind | u16 sequence | body | u32 CRC-32 of kind, sequence and body
+//! ```
+//!
+//! COBS encoded and terminated by a zero byte, so a receiver can resync at
+//! the next zero after a corrupt or partial frame. All numbers are little
+//! endian. Velocities are sent as f32 in rad/s and encoder counts as i64.
+//!
+//! The protocol runs over any `Read + Write`, e.g. a serial port or the
+//! in-memory `loopback` used with `MockMotorController` in tests. Reads
+//! that fail with `WouldBlock` or `TimedOut` are treated as no data, so
+//! ports should be non-blocking or have a short read timeout.
+use crate::ddrive::{EncoderTicks, WheelState};
+use crate::utils::crc32;
+use anyhow::{self, bail};
+use std::collections::VecDeque;
+use std::io::{ErrorKind, Read, Write};
+use std::sync::{Arc, Mutex};
+
+const KIND_VELOCITY: u8 = 1;
+const KIND_ENCODERS: u8 = 2;
+const KIND_HEARTBEAT: u8 = 3;
+
+/// length of the largest frame, an encoder frame, after COBS encoding
+const MAX_ENCODED_FRAME: usize = 24;
+
+/// Encodes bytes with consistent overhead byte stuffing, so that the
+/// result contains no zero bytes
+pub fn cobs_encode(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len() + data.len() / 254 + 2);
+    let mut code_index = 0;
+    let mut code = 1u8;
+    out.push(0);
+    for &byte in data {
+        if byte != 0 {
+            out.push(byte);
+            code += 1;
+        }
+        if byte == 0 || code == 0xff {
+            out[code_index] = code;
+            code_index = out.len();
+            out.push(0);
+            code = 1;
+        }
+    }
+    out[code_index] = code;
+    out
+}
+
+/// Decodes bytes encoded with `cobs_encode`
+pub fn cobs_decode(data: &[u8]) -> anyhow::Result<Vec<u8>> {
+    let mut out = Vec::with_capacity(data.len());
+    let mut i = 0;
+    while i < data.len() {
+        let code = data[i] as usize;
+        if code == 0 || data[i + 1..(i + code).min(data.len())].contains(&0) {
+            bail!("zero byte inside a COBS block");
+        }
+        if i + code > data.len() {
+            bail!("COBS block runs past the end of the data");
+        }
+        out.extend_from_slice(&data[i + 1..i + code]);
+        i += code;
+        if code < 0xff && i < data.len() {
+            out.push(0);
+        }
+    }
+    Ok(out)
+}
+
+/// A message of the protocol
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Message {
+    /// wheel velocities in rad/s, from the host
+    Velocity(WheelState<f64>),
+
+    /// accumulated encoder counts, from the motor controller
+    Encoders(EncoderTicks),
+
+    /// keeps the link alive when there is nothing else to send
+    Heartbeat,
+}
+
+impl Message {
+    fn encode(&self, sequence: u16) -> Vec<u8> {
+        let mut frame = vec![];
+        match self {
+            Message::Velocity(speeds) => {
+                frame.push(KIND_VELOCITY);
+                frame.extend_from_slice(&sequence.to_le_bytes());
+                frame.extend_from_slice(&(speeds.left as f32).to_le_bytes());
+                frame.extend_from_slice(&(speeds.right as f32).to_le_bytes());
+            }
+            Message::Encoders(ticks) => {
+                frame.push(KIND_ENCODERS);
+                frame.extend_from_slice(&sequence.to_le_bytes());
+                frame.extend_from_slice(&ticks.left.to_le_bytes());
+                frame.extend_from_slice(&ticks.right.to_le_bytes());
+            }
+            Message::Heartbeat => {
+                frame.push(KIND_HEARTBEAT);
+                frame.extend_from_slice(&sequence.to_le_bytes());
+            }
+        }
+        let crc = crc32(&frame);
+        frame.extend_from_slice(&crc.to_le_bytes());
+        frame
+    }
+
+    /// returns the message and sequence number of a decoded frame
+    fn decode(frame: &[u8]) -> anyhow::Result<(Message, u16)> {
+        if frame.len() < 7 {
+            bail!("frame is too short");
+        }
+        let (content, crc) = frame.split_at(frame.len() - 4);
+        if crc32(content) != u32::from_le_bytes(crc.try_into()?) {
+            bail!("frame CRC mismatch");
+        }
+        let sequence = u16::from_le_bytes([content[1], content[2]]);
+        let body = &content[3..];
+        let message = match (content[0], body.len()) {
+            (KIND_VELOCITY, 8) => Message::Velocity(WheelState::new(
+                f32::from_le_bytes(body[..4].try_into()?) as f64,
+                f32::from_le_bytes(body[4..].try_into()?) as f64,
+            )),
+            (KIND_ENCODERS, 16) => Message::Encoders(EncoderTicks::new(
+                i64::from_le_bytes(body[..8].try_into()?),
+                i64::from_le_bytes(body[8..].try_into()?),
+            )),
+            (KIND_HEARTBEAT, 0) => Message::Heartbeat,
+            (kind, len) => bail!("unknown frame kind {} with {} bytes", kind, len),
+        };
+        Ok((message, sequence))
+    }
+}
+
+/// Counters of the frames that went over a port
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LinkStats {
+    pub frames_sent: u64,
+    pub frames_received: u64,
+
+    /// frames that failed COBS decoding or the CRC check
+    pub bad_frames: u64,
+
+    /// frames missing from gaps in the received sequence numbers
+    pub lost_frames: u64,
+}
+
+/// Sends and receives framed messages over a byte stream
+pub struct FramedPort<S: Read + Write> {
+    stream: S,
+
+    /// bytes received since the last frame delimiter
+    buffer: Vec<u8>,
+
+    /// true while skipping an overlong frame up to the next delimiter
+    discarding: bool,
+
+    /// sequence number of the next frame sent
+    next_sequence: u16,
+
+    /// sequence number expected on the next frame received
+    expected_sequence: Option<u16>,
+
+    stats: LinkStats,
+}
+
+impl<S: Read + Write> FramedPort<S> {
+    /// constructs a port over a byte stream
+    pub fn new(stream: S) -> Self {
+        FramedPort {
+            stream,
+            buffer: vec![],
+            discarding: false,
+            next_sequence: 0,
+            expected_sequence: None,
+            stats: LinkStats::default(),
+        }
+    }
+
+    /// returns the frame counters
+    pub fn stats(&self) -> LinkStats {
+        self.stats
+    }
+
+    /// returns the underlying stream
+    pub fn stream_mut(&mut self) -> &mut S {
+        &mut self.stream
+    }
+
+    /// sends a message as a single frame
+    pub fn send(&mut self, message: &Message) -> anyhow::Result<()> {
+        let mut bytes = cobs_encode(&message.encode(self.next_sequence));
+        bytes.push(0);
+        self.stream.write_all(&bytes)?;
+        self.stream.flush()?;
+        self.next_sequence = self.next_sequence.wrapping_add(1);
+        self.stats.frames_sent += 1;
+        Ok(())
+    }
+
+    /// reads what is available and returns the messages of the complete,
+    /// intact frames in it. Bad frames are counted and skipped, including
+    /// runs of bytes too long to be a frame.
+    pub fn receive(&mut self) -> anyhow::Result<Vec<Message>> {
+        let mut chunk = [0u8; 256];
+        let mut messages = vec![];
+        loop {
+            let n = match self.stream.read(&mut chunk) {
+                Ok(0) => break,
+                Ok(n) => n,
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => break,
+                Err(e) => return Err(e.into()),
+            };
+            for &byte in &chunk[..n] {
+                if byte == 0 {
+                    if !self.discarding && !self.buffer.is_empty() {
+                        let encoded = std::mem::take(&mut self.buffer);
+                        messages.extend(self.decode(&encoded));
+                    }
+                    self.discarding = false;
+                } else if self.discarding {
+                    continue;
+                } else if self.buffer.len() == MAX_ENCODED_FRAME {
+                    self.buffer.clear();
+                    self.discarding = true;
+                    self.stats.bad_frames += 1;
+                } else {
+                    self.buffer.push(byte);
+                }
+            }
+        }
+        Ok(messages)
+    }
+
+    /// decodes a frame without its delimiter, counting it as received or bad
+    fn decode(&mut self, encoded: &[u8]) -> Option<Message> {
+        match cobs_decode(encoded).and_then(|frame| Message::decode(&frame)) {
+            Ok((message, sequence)) => {
+                if let Some(expected) = self.expected_sequence {
+                    let gap = sequence.wrapping_sub(expected);
+                    if gap < 0x8000 {
+                        self.stats.lost_frames += gap as u64;
+                    }
+                }
+                self.expected_sequence = Some(sequence.wrapping_add(1));
+                self.stats.frames_received += 1;
+                Some(message)
+            }
+            Err(_) => {
+                self.stats.bad_frames += 1;
+                None
+            }
+        }
+    }
+}
+
+/// Host side of the link to a motor controller. Sends heartbeats when no
+/// command was sent for a while, and commands zero velocity once when the
+/// controller has been silent for longer than the watchdog timeout. Times
+/// are in seconds from any fixed start.
+pub struct MotorLink<S: Read + Write> {
+    port: FramedPort<S>,
+
+    /// time without sending after which a heartbeat is sent
+    heartbeat_interval: f64,
+
+    /// time without receiving after which the link is considered silent
+    watchdog_timeout: f64,
+
+    last_sent: Option<f64>,
+    last_received: Option<f64>,
+
+    /// latest encoder counts from the controller
+    encoders: Option<EncoderTicks>,
+
+    /// whether the watchdog has stopped the wheels
+    tripped: bool,
+}
+
+impl<S: Read + Write> MotorLink<S> {
+    /// constructs a link over a byte stream, with a heartbeat every 0.1 s
+    /// and a watchdog timeout of 0.5 s
+    pub fn new(stream: S) -> Self {
+        MotorLink {
+            port: FramedPort::new(stream),
+            heartbeat_interval: 0.1,
+            watchdog_timeout: 0.5,
+            last_sent: None,
+            last_received: None,
+            encoders: None,
+            tripped: false,
+        }
+    }
+
+    /// sets the time without sending after which a heartbeat is sent
+    pub fn with_heartbeat_interval(mut self, seconds: f64) -> Self {
+        self.heartbeat_interval = seconds;
+        self
+    }
+
+    /// sets the time without hearing from the controller after which the
+    /// wheels are commanded to stop
+    pub fn with_watchdog_timeout(mut self, seconds: f64) -> Self {
+        self.watchdog_timeout = seconds;
+        self
+    }
+
+    /// sends wheel velocities in rad/s. While the watchdog is tripped the
+    /// command is replaced by zero velocity and an error is returned.
+    pub fn send_velocity(&mut self, speeds: WheelState<f64>, now: f64) -> anyhow::Result<()> {
+        self.update(now)?;
+        if self.tripped {
+            self.send(&Message::Velocity(WheelState::new(0.0, 0.0)), now)?;
+            bail!("the motor controller is not responding");
+        }
+        self.send(&Message::Velocity(speeds), now)
+    }
+
+    /// Reads the frames from the controller, sends a heartbeat if one is
+    /// due and runs the watchdog. Call this regularly.
+    pub fn update(&mut self, now: f64) -> anyhow::Result<()> {
+        let messages = self.port.receive()?;
+        if !messages.is_empty() {
+            self.last_received = Some(now);
+            self.tripped = false;
+        }
+        for message in messages {
+            if let Message::Encoders(ticks) = message {
+                self.encoders = Some(ticks);
+            }
+        }
+
+        // the controller gets one timeout to answer after the link starts
+        let since = *self.last_received.get_or_insert(now);
+        if !self.tripped && now - since > self.watchdog_timeout {
+            self.tripped = true;
+            self.send(&Message::Velocity(WheelState::new(0.0, 0.0)), now)?;
+        }
+        if self
+            .last_sent
+            .is_none_or(|t| now - t >= self.heartbeat_interval)
+        {
+            self.send(&Message::Heartbeat, now)?;
+        }
+        Ok(())
+    }
+
+    /// returns the latest encoder counts from the controller
+    pub fn encoders(&self) -> Option<EncoderTicks> {
+        self.encoders
+    }
+
+    /// returns true unless the controller has gone silent
+    pub fn is_alive(&self) -> bool {
+        !self.tripped
+    }
+
+    /// returns the frame counters
+    pub fn stats(&self) -> LinkStats {
+        self.port.stats()
+    }
+
+    /// returns the underlying port
+    pub fn port_mut(&mut self) -> &mut FramedPort<S> {
+        &mut self.port
+    }
+
+    fn send(&mut self, message: &Message, now: f64) -> anyhow::Result<()> {
+        self.port.send(message)?;
+        self.last_sent = Some(now);
+        Ok(())
+    }
+}
+
+/// One end of an in-memory byte pipe, see `loopback`
+#[derive(Debug, Clone)]
+pub struct LoopbackPort {
+    incoming: Arc<Mutex<VecDeque<u8>>>,
+    outgoing: Arc<Mutex<VecDeque<u8>>>,
+
+    /// when false, written bytes are dropped, like an unplugged cable
+    connected: Arc<Mutex<bool>>,
+}
+
+/// Returns the two ends of an in-memory duplex byte pipe. Reads fail with
+/// `WouldBlock` when no bytes are waiting.
+pub fn loopback() -> (LoopbackPort, LoopbackPort) {
+    let a = Arc::new(Mutex::new(VecDeque::new()));
+    let b = Arc::new(Mutex::new(VecDeque::new()));
+    let connected = Arc::new(Mutex::new(true));
+    (
+        LoopbackPort {
+            incoming: a.clone(),
+            outgoing: b.clone(),
+            connected: connected.clone(),
+        },
+        LoopbackPort {
+            incoming: b,
+            outgoing: a,
+            connected,
+        },
+    )
+}
+
+impl LoopbackPort {
+    /// connects or disconnects both ends of the pipe
+    pub fn set_connected(&self, connected: bool) {
+        *self.connected.lock().unwrap() = connected;
+    }
+
+    /// flips bits of a byte waiting to be read by the other end, counted
+    /// from the oldest, to simulate line noise
+    pub fn corrupt_outgoing(&self, index: usize, mask: u8) {
+        if let Some(byte) = self.outgoing.lock().unwrap().get_mut(index) {
+            *byte ^= mask;
+        }
+    }
+}
+
+impl Read for LoopbackPort {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let mut incoming = self.incoming.lock().unwrap();
+        if incoming.is_empty() {
+            return Err(ErrorKind::WouldBlock.into());
+        }
+        let n = buf.len().min(incoming.len());
+        for (slot, byte) in buf.iter_mut().zip(incoming.drain(..n)) {
+            *slot = byte;
+        }
+        Ok(n)
+    }
+}
+
+impl Write for LoopbackPort {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if *self.connected.lock().unwrap() {
+            self.outgoing.lock().unwrap().extend(buf);
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// A simulated motor controller for testing the host side. It drives its
+/// wheels at the last commanded velocity, reports its encoder counts on
+/// every step, and stops the wheels when the host goes silent for longer
+/// than its own watchdog timeout.
+pub struct MockMotorController<S: Read + Write> {
+    port: FramedPort<S>,
+
+    /// encoder counts per wheel radian
+    ticks_per_radian: f64,
+
+    watchdog_timeout: f64,
+    last_received: Option<f64>,
+
+    /// wheel velocities in rad/s
+    velocity: WheelState<f64>,
+
+    /// wheel angles in radians
+    angles: WheelState<f64>,
+}
+
+impl<S: Read + Write> MockMotorController<S> {
+    /// constructs a controller over a byte stream with the given encoder
+    /// resolution and a watchdog timeout of 0.5 s
+    pub fn new(stream: S, ticks_per_radian: f64) -> Self {
+        MockMotorController {
+            port: FramedPort::new(stream),
+            ticks_per_radian,
+            watchdog_timeout: 0.5,
+            last_received: None,
+            velocity: WheelState::new(0.0, 0.0),
+            angles: WheelState::new(0.0, 0.0),
+        }
+    }
+
+    /// sets the time without hearing from the host after which the wheels stop
+    pub fn with_watchdog_timeout(mut self, seconds: f64) -> Self {
+        self.watchdog_timeout = seconds;
+        self
+    }
+
+    /// returns the velocity the wheels are driven at
+    pub fn velocity(&self) -> WheelState<f64> {
+        self.velocity
+    }
+
+    /// returns the encoder counts
+    pub fn encoders(&self) -> EncoderTicks {
+        EncoderTicks::new(
+            (self.angles.left * self.ticks_per_radian).round() as i64,
+            (self.angles.right * self.ticks_per_radian).round() as i64,
+        )
+    }
+
+    /// returns the frame counters
+    pub fn stats(&self) -> LinkStats {
+        self.port.stats()
+    }
+
+    /// handles the frames from the host, runs the watchdog, moves the
+    /// wheels for dt seconds and reports the encoder counts
+    pub fn step(&mut self, now: f64, dt: f64) -> anyhow::Result<()> {
+        for message in self.port.receive()? {
+            self.last_received = Some(now);
+            if let Message::Velocity(speeds) = message {
+                self.velocity = speeds;
+            }
+        }
+        if self
+            .last_received
+            .is_none_or(|t| now - t > self.watchdog_timeout)
+        {
+            self.velocity = WheelState::new(0.0, 0.0);
+        }
+        self.angles = WheelState::new(
+            self.angles.left + self.velocity.left * dt,
+            self.angles.right + self.velocity.right * dt,
+        );
+        self.port.send(&Message::Encoders(self.encoders()))
+    }
+}
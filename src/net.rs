@@ -0,0 +1,722 @@
+//! Teleoperation and telemetry over UDP and TCP.
+//!
+//! `TeleopServer` runs next to the robot. It sends the pose, twist and wheel
+//! state of a `DiffDrive` to its clients at a fixed rate and accepts
+//! `Twist2D` commands from them. A deadman timeout stops the robot when the
+//! commands stop coming, and commands faster than the rate limit are
+//! dropped. `TeleopClient` is the other end, for tools and tests.
+//!
+//! Both transports carry the same packets on the same port number. A UDP
+//! datagram holds one packet, and on TCP every packet is preceded by its
+//! length as a u16. A packet is
+//!
+//! ```text
+//! u8 version | u8 kind | u64 sender | u64 sequence | body | HMAC-SHA256 of everything before
+//! ```
+//!
+//! with all numbers little endian and the bodies
+//!
+//! ```text
+//! command (1):   f64 thetadot | f64 xdot | f64 ydot
+//! telemetry (2): f64 time | f64 x | f64 y | f64 theta
+//!                | f64 thetadot | f64 xdot | f64 ydot
+//!                | f64 left wheel angle | f64 right wheel angle
+//!                | f64 left wheel speed | f64 right wheel speed
+//! subscribe (3): empty
+//! ```
+//!
+//! The HMAC is keyed with a secret shared by the server and its clients,
+//! and packets with a wrong code are dropped. Every sender picks a random
+//! id, and the sequence numbers of each id must increase so that recorded
+//! packets cannot be replayed, from any address. The sequence number of a
+//! packet is at least the system time in microseconds when it was sent, and
+//! the server drops packets sent more than 10 s ago or before it started,
+//! so packets cannot be replayed to a restarted server or after the server
+//! forgot their sender either. The clocks of the server and its clients
+//! must therefore agree to within a few seconds. The server tracks the 64
+//! most recently heard senders.
+//!
+//! UDP clients receive telemetry until 5 s after their last packet, so they
+//! should subscribe again when they have not sent a command for a while.
+//! TCP clients receive telemetry from their first authenticated packet for
+//! as long as they stay connected. Connections that have not authenticated
+//! within 2 s are closed, and at most 8 are kept waiting.
+use crate::ddrive::{DiffDrive, WheelState};
+use crate::rigid2d::{Pose2D, Twist2D};
+use crate::utils::hmac_sha256;
+use anyhow::{self, bail};
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::hash::{BuildHasher, Hasher};
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs, UdpSocket};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+const VERSION: u8 = 1;
+const KIND_COMMAND: u8 = 1;
+const KIND_TELEMETRY: u8 = 2;
+const KIND_SUBSCRIBE: u8 = 3;
+const HEADER_LEN: usize = 18;
+const TAG_LEN: usize = 32;
+
+/// time in seconds a UDP client keeps receiving telemetry after its last
+/// packet
+const SUBSCRIPTION_TIMEOUT: f64 = 5.0;
+
+/// number of senders whose sequence numbers are remembered
+const MAX_SENDERS: usize = 64;
+
+/// age in microseconds after which a packet is dropped as a replay
+const REPLAY_WINDOW: u64 = 10_000_000;
+
+/// time in seconds a TCP client has to send its first authenticated packet
+const AUTHENTICATION_TIMEOUT: f64 = 2.0;
+
+/// number of TCP connections kept while they have not authenticated
+const MAX_UNAUTHENTICATED: usize = 8;
+
+/// State of the robot sent to the clients
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Telemetry {
+    /// server time in seconds
+    pub time: f64,
+    pub pose: Pose2D<f64>,
+    pub twist: Twist2D<f64>,
+    pub wheel_angles: WheelState<f64>,
+
+    /// wheel speeds in rad/s
+    pub wheel_speeds: WheelState<f64>,
+}
+
+/// A packet of the protocol
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Packet {
+    /// twist to drive at, from a client
+    Command(Twist2D<f64>),
+
+    /// state of the robot, from the server
+    Telemetry(Telemetry),
+
+    /// asks the server for telemetry over UDP
+    Subscribe,
+}
+
+impl Packet {
+    /// encodes the packet with the id of its sender and a sequence number,
+    /// authenticated with the key
+    pub fn encode(&self, sender: u64, sequence: u64, key: &[u8]) -> Vec<u8> {
+        let (kind, values) = match self {
+            Packet::Command(twist) => (KIND_COMMAND, vec![twist.thetadot, twist.xdot, twist.ydot]),
+            Packet::Telemetry(t) => (
+                KIND_TELEMETRY,
+                vec![
+                    t.time,
+                    t.pose.x,
+                    t.pose.y,
+                    t.pose.theta,
+                    t.twist.thetadot,
+                    t.twist.xdot,
+                    t.twist.ydot,
+                    t.wheel_angles.left,
+                    t.wheel_angles.right,
+                    t.wheel_speeds.left,
+                    t.wheel_speeds.right,
+                ],
+            ),
+            Packet::Subscribe => (KIND_SUBSCRIBE, vec![]),
+        };
+        let mut bytes = vec![VERSION, kind];
+        bytes.extend_from_slice(&sender.to_le_bytes());
+        bytes.extend_from_slice(&sequence.to_le_bytes());
+        for value in values {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        let tag = hmac_sha256(key, &bytes);
+        bytes.extend_from_slice(&tag);
+        bytes
+    }
+
+    /// authenticates and decodes a packet, returning it with the id of its
+    /// sender and its sequence number
+    pub fn decode(bytes: &[u8], key: &[u8]) -> anyhow::Result<(Packet, u64, u64)> {
+        if bytes.len() < HEADER_LEN + TAG_LEN {
+            bail!("packet is too short");
+        }
+        let (content, tag) = bytes.split_at(bytes.len() - TAG_LEN);
+        // constant time comparison, so the code cannot be guessed byte by byte
+        let difference = hmac_sha256(key, content)
+            .iter()
+            .zip(tag)
+            .fold(0, |acc, (a, b)| acc | (a ^ b));
+        if difference != 0 {
+            bail!("packet failed authentication");
+        }
+        if content[0] != VERSION {
+            bail!("unsupported protocol version {}", content[0]);
+        }
+        let sender = u64::from_le_bytes(content[2..10].try_into()?);
+        let sequence = u64::from_le_bytes(content[10..HEADER_LEN].try_into()?);
+        let body = &content[HEADER_LEN..];
+        if body.len() % 8 != 0 {
+            bail!("packet body is not a whole number of values");
+        }
+        let values: Vec<f64> = body
+            .chunks_exact(8)
+            .map(|chunk| f64::from_le_bytes(chunk.try_into().unwrap()))
+            .collect();
+
+        let packet = match (content[1], values.as_slice()) {
+            (KIND_COMMAND, &[thetadot, xdot, ydot]) => {
+                Packet::Command(Twist2D::new(thetadot, xdot, ydot))
+            }
+            (KIND_TELEMETRY, &[time, x, y, theta, thetadot, xdot, ydot, la, ra, ls, rs]) => {
+                Packet::Telemetry(Telemetry {
+                    time,
+                    pose: Pose2D::new(x, y, theta),
+                    twist: Twist2D::new(thetadot, xdot, ydot),
+                    wheel_angles: WheelState::new(la, ra),
+                    wheel_speeds: WheelState::new(ls, rs),
+                })
+            }
+            (KIND_SUBSCRIBE, &[]) => Packet::Subscribe,
+            (kind, values) => bail!("unknown packet kind {} with {} values", kind, values.len()),
+        };
+        Ok((packet, sender, sequence))
+    }
+}
+
+/// Counters of what the server received and sent
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ServerStats {
+    pub commands_accepted: u64,
+
+    /// commands dropped for coming faster than the rate limit
+    pub commands_rate_limited: u64,
+
+    /// packets that failed authentication or decoding, or were replayed
+    pub packets_rejected: u64,
+
+    /// TCP connections closed for not authenticating in time, or refused
+    /// because too many were waiting to
+    pub connections_dropped: u64,
+
+    /// telemetry packets sent, counting every client
+    pub telemetry_sent: u64,
+
+    /// times the deadman timeout stopped the robot
+    pub deadman_stops: u64,
+}
+
+/// Transport a packet arrived on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Peer {
+    Udp(SocketAddr),
+    Tcp,
+}
+
+/// A TCP client of the server
+struct Connection {
+    stream: TcpStream,
+
+    /// bytes received that do not make a whole packet yet
+    buffer: Vec<u8>,
+
+    /// when the connection was accepted
+    accepted: f64,
+
+    /// true once the client sent an authenticated packet
+    authenticated: bool,
+}
+
+impl Connection {
+    /// reads what is available and appends the packets in it, returns
+    /// false once the client has disconnected
+    fn receive(&mut self, packets: &mut Vec<Vec<u8>>) -> io::Result<bool> {
+        let mut open = true;
+        let mut chunk = [0u8; 512];
+        loop {
+            match self.stream.read(&mut chunk) {
+                Ok(0) => {
+                    open = false;
+                    break;
+                }
+                Ok(n) => self.buffer.extend_from_slice(&chunk[..n]),
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) if no_data(&e) => break,
+                Err(e) => return Err(e),
+            }
+        }
+        while let Some(packet) = take_frame(&mut self.buffer) {
+            packets.push(packet);
+        }
+        Ok(open)
+    }
+}
+
+/// Accepts teleop commands and sends telemetry over UDP and TCP. Times are
+/// in seconds from any fixed start.
+pub struct TeleopServer {
+    udp: UdpSocket,
+    tcp: TcpListener,
+    connections: Vec<Connection>,
+    key: Vec<u8>,
+
+    /// time between telemetry packets
+    telemetry_period: f64,
+
+    /// time without commands after which the robot is stopped
+    deadman_timeout: f64,
+
+    /// shortest time between accepted commands
+    min_command_interval: f64,
+
+    /// address of every UDP client and when it was last heard from, by
+    /// sender id
+    subscribers: HashMap<u64, (SocketAddr, f64)>,
+
+    /// latest sequence number of every sender and when it was received
+    sequences: HashMap<u64, (u64, f64)>,
+
+    /// system time in microseconds when the server started
+    started: u64,
+
+    /// latest command, until the deadman timeout passes
+    command: Option<Twist2D<f64>>,
+
+    last_command: Option<f64>,
+    last_telemetry: Option<f64>,
+
+    /// sender id of the telemetry packets
+    id: u64,
+
+    /// sequence number of the next telemetry packet
+    sequence: u64,
+
+    stats: ServerStats,
+}
+
+impl TeleopServer {
+    /// Listens for UDP and TCP clients on the address, authenticating them
+    /// with the shared key. Sends telemetry at 10 Hz, accepts commands at up
+    /// to 50 Hz and stops the robot after 0.5 s without commands.
+    pub fn bind<A: ToSocketAddrs>(address: A, key: &[u8]) -> anyhow::Result<Self> {
+        if key.is_empty() {
+            bail!("the shared key must not be empty");
+        }
+        let udp = UdpSocket::bind(address)?;
+        let tcp = TcpListener::bind(udp.local_addr()?)?;
+        udp.set_nonblocking(true)?;
+        tcp.set_nonblocking(true)?;
+        Ok(TeleopServer {
+            udp,
+            tcp,
+            connections: vec![],
+            key: key.to_vec(),
+            telemetry_period: 0.1,
+            deadman_timeout: 0.5,
+            min_command_interval: 0.02,
+            subscribers: HashMap::new(),
+            sequences: HashMap::new(),
+            started: clock_micros(),
+            command: None,
+            last_command: None,
+            last_telemetry: None,
+            id: random_id(),
+            sequence: clock_micros(),
+            stats: ServerStats::default(),
+        })
+    }
+
+    /// sets how many telemetry packets are sent per second
+    pub fn with_telemetry_rate(mut self, hz: f64) -> Self {
+        self.telemetry_period = 1.0 / hz;
+        self
+    }
+
+    /// sets the time without commands after which the robot is stopped
+    pub fn with_deadman_timeout(mut self, seconds: f64) -> Self {
+        self.deadman_timeout = seconds;
+        self
+    }
+
+    /// sets how many commands are accepted per second, the rest are dropped
+    pub fn with_command_rate_limit(mut self, hz: f64) -> Self {
+        self.min_command_interval = 1.0 / hz;
+        self
+    }
+
+    /// returns the address the server listens on, for both transports
+    pub fn local_addr(&self) -> anyhow::Result<SocketAddr> {
+        Ok(self.udp.local_addr()?)
+    }
+
+    /// returns the counters of what was received and sent
+    pub fn stats(&self) -> ServerStats {
+        self.stats
+    }
+
+    /// returns the number of connected TCP clients that have authenticated
+    pub fn tcp_clients(&self) -> usize {
+        self.connections.iter().filter(|c| c.authenticated).count()
+    }
+
+    /// Reads the packets from the clients and returns the twist the robot
+    /// should drive at: the latest command, or zero before the first command
+    /// and once the deadman timeout has passed. Call this regularly.
+    pub fn poll(&mut self, now: f64) -> anyhow::Result<Twist2D<f64>> {
+        let mut datagram = [0u8; 512];
+        loop {
+            match self.udp.recv_from(&mut datagram) {
+                Ok((n, address)) => {
+                    self.handle(Peer::Udp(address), &datagram[..n], now);
+                }
+                Err(e) if no_data(&e) => break,
+                // ICMP errors from clients that went away
+                Err(e) if e.kind() == ErrorKind::ConnectionReset => continue,
+                Err(e) => return Err(e.into()),
+            }
+        }
+        loop {
+            match self.tcp.accept() {
+                Ok((stream, _)) => {
+                    let waiting = self.connections.iter().filter(|c| !c.authenticated);
+                    if waiting.count() >= MAX_UNAUTHENTICATED {
+                        self.stats.connections_dropped += 1;
+                        continue;
+                    }
+                    stream.set_nonblocking(true)?;
+                    stream.set_nodelay(true)?;
+                    self.connections.push(Connection {
+                        stream,
+                        buffer: vec![],
+                        accepted: now,
+                        authenticated: false,
+                    });
+                }
+                Err(e) if no_data(&e) => break,
+                Err(e) => return Err(e.into()),
+            }
+        }
+        let mut connections = std::mem::take(&mut self.connections);
+        connections.retain_mut(|connection| {
+            let mut packets = vec![];
+            let open = connection.receive(&mut packets).unwrap_or(false);
+            for bytes in packets {
+                if self.handle(Peer::Tcp, &bytes, now) {
+                    connection.authenticated = true;
+                }
+            }
+            if open
+                && !connection.authenticated
+                && now - connection.accepted > AUTHENTICATION_TIMEOUT
+            {
+                self.stats.connections_dropped += 1;
+                return false;
+            }
+            open
+        });
+        self.connections = connections;
+
+        if self.command.is_some()
+            && self
+                .last_command
+                .is_some_and(|t| now - t > self.deadman_timeout)
+        {
+            self.command = None;
+            self.stats.deadman_stops += 1;
+        }
+        Ok(self.command.unwrap_or(Twist2D::new(0.0, 0.0, 0.0)))
+    }
+
+    /// handles a packet, returning true if it was authentic and not a replay
+    fn handle(&mut self, peer: Peer, bytes: &[u8], now: f64) -> bool {
+        let (packet, sender, sequence) = match Packet::decode(bytes, &self.key) {
+            Ok(decoded) => decoded,
+            Err(_) => {
+                self.stats.packets_rejected += 1;
+                return false;
+            }
+        };
+        let oldest = clock_micros()
+            .saturating_sub(REPLAY_WINDOW)
+            .max(self.started);
+        match self.sequences.get(&sender) {
+            _ if sequence < oldest => {
+                self.stats.packets_rejected += 1;
+                return false;
+            }
+            Some(&(last, _)) if sequence <= last => {
+                self.stats.packets_rejected += 1;
+                return false;
+            }
+            Some(_) => {}
+            None => {
+                // forget the sender heard from longest ago to make room
+                if self.sequences.len() >= MAX_SENDERS {
+                    let oldest = self
+                        .sequences
+                        .iter()
+                        .min_by(|a, b| a.1 .1.total_cmp(&b.1 .1))
+                        .map(|(id, _)| *id);
+                    if let Some(id) = oldest {
+                        self.sequences.remove(&id);
+                        self.subscribers.remove(&id);
+                    }
+                }
+            }
+        }
+        self.sequences.insert(sender, (sequence, now));
+        if let Peer::Udp(address) = peer {
+            self.subscribers.insert(sender, (address, now));
+        }
+
+        match packet {
+            Packet::Command(twist) => {
+                let finite = [twist.thetadot, twist.xdot, twist.ydot]
+                    .iter()
+                    .all(|v| v.is_finite());
+                if !finite {
+                    self.stats.packets_rejected += 1;
+                } else if self
+                    .last_command
+                    .is_some_and(|t| now - t < self.min_command_interval)
+                {
+                    self.stats.commands_rate_limited += 1;
+                } else {
+                    self.command = Some(twist);
+                    self.last_command = Some(now);
+                    self.stats.commands_accepted += 1;
+                }
+            }
+            Packet::Subscribe => {}
+            Packet::Telemetry(_) => self.stats.packets_rejected += 1,
+        }
+        true
+    }
+
+    /// Sends the state of the robot, driving at the given twist, to the
+    /// clients when telemetry is due. Returns whether it was sent.
+    pub fn publish(
+        &mut self,
+        now: f64,
+        robot: &DiffDrive<f64>,
+        twist: Twist2D<f64>,
+    ) -> anyhow::Result<bool> {
+        if self
+            .last_telemetry
+            .is_some_and(|t| now - t < self.telemetry_period)
+        {
+            return Ok(false);
+        }
+        self.last_telemetry = Some(now);
+
+        let telemetry = Telemetry {
+            time: now,
+            pose: robot.pose(),
+            twist,
+            wheel_angles: robot.wheel_angles(),
+            wheel_speeds: robot.speeds_from_twist(Twist2D::new(twist.thetadot, twist.xdot, 0.0)),
+        };
+        let bytes = Packet::Telemetry(telemetry).encode(self.id, self.sequence, &self.key);
+        self.sequence += 1;
+
+        self.subscribers
+            .retain(|_, (_, heard)| now - *heard <= SUBSCRIPTION_TIMEOUT);
+        for (address, _) in self.subscribers.values() {
+            match self.udp.send_to(&bytes, address) {
+                Ok(_) => self.stats.telemetry_sent += 1,
+                Err(e) if no_data(&e) => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        // a client that cannot keep up is disconnected rather than sent a
+        // partial packet
+        let framed = frame(&bytes);
+        let mut sent = 0;
+        self.connections.retain_mut(|connection| {
+            if !connection.authenticated {
+                return true;
+            }
+            let written = connection.stream.write_all(&framed).is_ok();
+            sent += written as u64;
+            written
+        });
+        self.stats.telemetry_sent += sent;
+        Ok(true)
+    }
+}
+
+enum Transport {
+    Udp(UdpSocket),
+    Tcp(TcpStream),
+}
+
+/// Client of a `TeleopServer`
+pub struct TeleopClient {
+    transport: Transport,
+    key: Vec<u8>,
+
+    /// sender id of the packets sent
+    id: u64,
+
+    /// sequence number of the next packet sent, unless the clock is ahead
+    sequence: u64,
+
+    /// sequence number of the latest telemetry received
+    last_received: Option<u64>,
+
+    /// bytes received over TCP that do not make a whole packet yet
+    buffer: Vec<u8>,
+}
+
+impl TeleopClient {
+    /// connects to a server over UDP and subscribes to its telemetry
+    pub fn udp<A: ToSocketAddrs>(server: A, key: &[u8]) -> anyhow::Result<Self> {
+        let server = match server.to_socket_addrs()?.next() {
+            Some(address) => address,
+            None => bail!("the server address did not resolve"),
+        };
+        let local = if server.is_ipv4() {
+            "0.0.0.0:0"
+        } else {
+            "[::]:0"
+        };
+        let socket = UdpSocket::bind(local)?;
+        socket.connect(server)?;
+        let mut client = TeleopClient::new(Transport::Udp(socket), key);
+        client.subscribe()?;
+        Ok(client)
+    }
+
+    /// connects to a server over TCP and subscribes to its telemetry, which
+    /// the server sends for as long as the connection is open
+    pub fn tcp<A: ToSocketAddrs>(server: A, key: &[u8]) -> anyhow::Result<Self> {
+        let stream = TcpStream::connect(server)?;
+        stream.set_nodelay(true)?;
+        let mut client = TeleopClient::new(Transport::Tcp(stream), key);
+        client.subscribe()?;
+        Ok(client)
+    }
+
+    fn new(transport: Transport, key: &[u8]) -> Self {
+        TeleopClient {
+            transport,
+            key: key.to_vec(),
+            id: random_id(),
+            sequence: 0,
+            last_received: None,
+            buffer: vec![],
+        }
+    }
+
+    /// asks the server for telemetry, which UDP clients must repeat when
+    /// they have not sent anything for a few seconds
+    pub fn subscribe(&mut self) -> anyhow::Result<()> {
+        self.send(&Packet::Subscribe)
+    }
+
+    /// sends a twist for the robot to drive at
+    pub fn send_command(&mut self, twist: Twist2D<f64>) -> anyhow::Result<()> {
+        self.send(&Packet::Command(twist))
+    }
+
+    fn send(&mut self, packet: &Packet) -> anyhow::Result<()> {
+        self.sequence = self.sequence.max(clock_micros());
+        let bytes = packet.encode(self.id, self.sequence, &self.key);
+        self.sequence += 1;
+        match &mut self.transport {
+            Transport::Udp(socket) => {
+                socket.send(&bytes)?;
+            }
+            Transport::Tcp(stream) => stream.write_all(&frame(&bytes))?,
+        }
+        Ok(())
+    }
+
+    /// waits up to the timeout for the next telemetry from the server,
+    /// skipping packets that fail authentication or are out of order
+    pub fn receive_telemetry(&mut self, timeout: Duration) -> anyhow::Result<Option<Telemetry>> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let bytes = match take_frame(&mut self.buffer) {
+                Some(bytes) => bytes,
+                None => {
+                    let remaining = deadline.saturating_duration_since(Instant::now());
+                    if remaining.is_zero() {
+                        return Ok(None);
+                    }
+                    let mut chunk = [0u8; 512];
+                    let received = match &mut self.transport {
+                        Transport::Udp(socket) => {
+                            socket.set_read_timeout(Some(remaining))?;
+                            socket.recv(&mut chunk)
+                        }
+                        Transport::Tcp(stream) => {
+                            stream.set_read_timeout(Some(remaining))?;
+                            stream.read(&mut chunk)
+                        }
+                    };
+                    let n = match received {
+                        Ok(n) => n,
+                        Err(e) if no_data(&e) || e.kind() == ErrorKind::Interrupted => continue,
+                        Err(e) => return Err(e.into()),
+                    };
+                    match self.transport {
+                        Transport::Udp(_) => chunk[..n].to_vec(),
+                        Transport::Tcp(_) if n == 0 => bail!("the server closed the connection"),
+                        Transport::Tcp(_) => {
+                            self.buffer.extend_from_slice(&chunk[..n]);
+                            continue;
+                        }
+                    }
+                }
+            };
+            if let Ok((Packet::Telemetry(telemetry), _, sequence)) =
+                Packet::decode(&bytes, &self.key)
+            {
+                if self.last_received.is_none_or(|last| sequence > last) {
+                    self.last_received = Some(sequence);
+                    return Ok(Some(telemetry));
+                }
+            }
+        }
+    }
+}
+
+/// true for the errors of reads and writes that had nothing to do
+fn no_data(e: &io::Error) -> bool {
+    matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut)
+}
+
+/// prefixes a packet with its length, for TCP
+fn frame(packet: &[u8]) -> Vec<u8> {
+    let mut bytes = (packet.len() as u16).to_le_bytes().to_vec();
+    bytes.extend_from_slice(packet);
+    bytes
+}
+
+/// removes the first whole length-prefixed packet from the buffer
+fn take_frame(buffer: &mut Vec<u8>) -> Option<Vec<u8>> {
+    if buffer.len() < 2 {
+        return None;
+    }
+    let len = u16::from_le_bytes([buffer[0], buffer[1]]) as usize;
+    if buffer.len() < 2 + len {
+        return None;
+    }
+    Some(buffer.drain(..2 + len).skip(2).collect())
+}
+
+/// random sender id, from the randomly seeded hasher of the standard library
+fn random_id() -> u64 {
+    RandomState::new().build_hasher().finish()
+}
+
+/// system time in microseconds, the lowest sequence number of a packet
+fn clock_micros() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_micros() as u64)
+        .unwrap_or(0)
+}
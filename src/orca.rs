@@ -0,0 +1,576 @@
+//! Reciprocal collision avoidance between robots sharing a space.
+//!
+//! `Orca` implements optimal reciprocal collision avoidance: every robot is
+//! a disc, and for every pair of robots the velocities that would collide
+//! within the time horizon are cut off by a half-plane, each robot taking
+//! half of the avoidance. A linear program then picks the velocity closest
+//! to the preferred one that satisfies all half-planes, or the one that
+//! violates them least when the robots are boxed in. Every robot runs the
+//! same computation with the same inputs, so no communication beyond poses
+//! and velocities is needed.
+//!
+//! Differential drive robots cannot follow an arbitrary velocity. With
+//! `with_differential_drive` the velocities are restricted to those a robot
+//! can track within a given error by turning towards them over the heading
+//! time, the discs grow by that error, and the result is turned into a
+//! forward speed and turn rate within the wheel speed limits.
+//!
+//! Collisions are avoided, but robots converging in a symmetric crowd can
+//! stall against each other. Preferred velocities that follow a traffic
+//! rule, such as keeping to the right, avoid that.
+use crate::ddrive::DiffDrive;
+use crate::rigid2d::{Pose2D, Twist2D, Vector2D};
+use crate::utils::normalize_angle;
+use num_traits::Float;
+
+/// A robot as seen by the collision avoidance
+#[derive(Debug, Clone, Copy)]
+pub struct OrcaAgent<T: Float> {
+    pub pose: Pose2D<T>,
+
+    /// current velocity in the world frame
+    pub velocity: Vector2D<T>,
+
+    /// twist the robot would drive without other robots, in the robot frame
+    pub preferred: Twist2D<T>,
+
+    /// radius of a disc around the robot's center that covers it
+    pub radius: T,
+}
+
+impl<T: Float> OrcaAgent<T> {
+    /// constructs a new OrcaAgent
+    pub fn new(pose: Pose2D<T>, velocity: Vector2D<T>, preferred: Twist2D<T>, radius: T) -> Self {
+        OrcaAgent {
+            pose,
+            velocity,
+            preferred,
+            radius,
+        }
+    }
+}
+
+/// A half-plane of allowed velocities, to the left of a directed line
+#[derive(Debug, Clone, Copy)]
+struct Line<T: Float> {
+    point: Vector2D<T>,
+    direction: Vector2D<T>,
+}
+
+/// Limits of a differential drive robot tracking a velocity
+#[derive(Debug, Clone, Copy)]
+struct NonHolonomic<T: Float> {
+    /// distance between the wheels
+    separation: T,
+
+    /// fastest a wheel rim moves in m/s
+    wheel_speed: T,
+
+    /// largest distance from the tracked velocity's straight line
+    tracking_error: T,
+
+    /// time the robot takes to turn towards a new velocity
+    heading_time: T,
+}
+
+impl<T: Float> NonHolonomic<T> {
+    /// time to turn by an angle without exceeding the wheel speed
+    fn turn_time(&self, angle: T) -> T {
+        let two = T::from(2.0).unwrap();
+        self.heading_time
+            .max(angle.abs() * self.separation / (two * self.wheel_speed))
+    }
+
+    /// ratio of the forward speed to the tracked speed that keeps the
+    /// robot closest to the tracked line while turning by the angle
+    fn speed_ratio(angle: T) -> T {
+        if angle.abs() < T::from(1e-6).unwrap() {
+            return T::one();
+        }
+        let two = T::from(2.0).unwrap();
+        angle * angle.sin() / (two * (T::one() - angle.cos()))
+    }
+
+    /// Returns the fastest velocity at the angle from the heading that can
+    /// be tracked within the error. Turning by the angle over time t at
+    /// the best forward speed leaves the robot u t |sin(angle / 2)| from
+    /// the tracked line, and turning on the spot leaves it u t away.
+    fn max_speed(&self, angle: T) -> T {
+        let two = T::from(2.0).unwrap();
+        let time = self.turn_time(angle);
+        let half_pi = T::from(core::f64::consts::FRAC_PI_2).unwrap();
+        if angle.abs() >= half_pi {
+            return self.tracking_error / time;
+        }
+        let wheels = (self.wheel_speed - angle.abs() * self.separation / (two * time))
+            .max(T::zero())
+            / Self::speed_ratio(angle);
+        let half = (angle / two).sin().abs();
+        if half > T::epsilon() {
+            wheels.min(self.tracking_error / (time * half))
+        } else {
+            wheels
+        }
+    }
+
+    /// Returns a convex polygon, counterclockwise, of velocities in the
+    /// world frame that can be tracked from the heading. The fastest
+    /// velocity in a direction is sampled every 11.25 degrees, and samples
+    /// that would make the polygon concave are pulled in until it is convex.
+    fn trackable(&self, heading: T, max_speed: T) -> Vec<Vector2D<T>> {
+        let count = 32;
+        let mut vertices: Vec<Vector2D<T>> = (0..count)
+            .map(|k| {
+                let angle = T::from(2.0 * core::f64::consts::PI * k as f64 / count as f64).unwrap()
+                    - T::from(core::f64::consts::PI).unwrap();
+                let speed = self.max_speed(angle).min(max_speed);
+                let (s, c) = (heading + angle).sin_cos();
+                Vector2D::new(speed * c, speed * s)
+            })
+            .collect();
+        let directions: Vec<Vector2D<T>> = vertices
+            .iter()
+            .enumerate()
+            .map(|(k, _)| {
+                let angle = T::from(2.0 * core::f64::consts::PI * k as f64 / count as f64).unwrap()
+                    - T::from(core::f64::consts::PI).unwrap();
+                let (s, c) = (heading + angle).sin_cos();
+                Vector2D::new(c, s)
+            })
+            .collect();
+        // moves a vertex along its direction onto the line through a and b
+        let onto = |k: usize, a: Vector2D<T>, b: Vector2D<T>| {
+            let edge = sub(b, a);
+            let denominator = det(directions[k], edge);
+            let t = if denominator.abs() > T::epsilon() {
+                (det(a, edge) / denominator).max(T::zero())
+            } else {
+                T::zero()
+            };
+            scale(directions[k], t)
+        };
+        let tolerance = T::from(1e-12).unwrap();
+        for _ in 0..4 * count {
+            let mut changed = false;
+            for k in 0..count {
+                let (i, j) = ((k + count - 1) % count, (k + 1) % count);
+                let (prev, next) = (vertices[i], vertices[j]);
+                if det(sub(vertices[k], prev), sub(next, vertices[k])) >= -tolerance {
+                    continue;
+                }
+                // a reflex vertex, pull in the farther neighbor
+                if dot(prev, prev) > dot(next, next) {
+                    vertices[i] = onto(i, vertices[k], next);
+                } else {
+                    vertices[j] = onto(j, prev, vertices[k]);
+                }
+                changed = true;
+            }
+            if !changed {
+                break;
+            }
+        }
+        vertices
+    }
+
+    /// returns the twist that tracks a velocity in the world frame
+    fn twist(&self, heading: T, velocity: Vector2D<T>) -> Twist2D<T> {
+        let two = T::from(2.0).unwrap();
+        let speed = velocity.x.hypot(velocity.y);
+        if speed <= T::epsilon() {
+            return Twist2D::new(T::zero(), T::zero(), T::zero());
+        }
+        let angle = normalize_angle(velocity.y.atan2(velocity.x) - heading);
+        let thetadot = angle / self.turn_time(angle);
+        let forward = if angle.abs() >= T::from(core::f64::consts::FRAC_PI_2).unwrap() {
+            T::zero()
+        } else {
+            speed * Self::speed_ratio(angle)
+        };
+        let limit = (self.wheel_speed - thetadot.abs() * self.separation / two).max(T::zero());
+        Twist2D::new(thetadot, forward.min(limit), T::zero())
+    }
+}
+
+/// Computes collision-free velocities for a group of robots
+#[derive(Debug, Clone, Copy)]
+pub struct Orca<T: Float> {
+    /// fastest a robot moves in m/s
+    max_speed: T,
+
+    /// how far ahead in seconds collisions are avoided
+    time_horizon: T,
+
+    /// control period in seconds, used to separate robots that overlap
+    time_step: T,
+
+    /// only robots this close are considered
+    neighbor_distance: T,
+
+    nonholonomic: Option<NonHolonomic<T>>,
+}
+
+impl<T: Float> Orca<T> {
+    /// Constructs an Orca for holonomic robots with the given top speed,
+    /// avoiding collisions 2 s ahead with a 0.1 s control period
+    pub fn new(max_speed: T) -> Self {
+        Orca {
+            max_speed,
+            time_horizon: T::from(2.0).unwrap(),
+            time_step: T::from(0.1).unwrap(),
+            neighbor_distance: T::infinity(),
+            nonholonomic: None,
+        }
+    }
+
+    /// sets how far ahead in seconds collisions are avoided
+    pub fn with_time_horizon(mut self, seconds: T) -> Self {
+        self.time_horizon = seconds;
+        self
+    }
+
+    /// sets the control period in seconds
+    pub fn with_time_step(mut self, seconds: T) -> Self {
+        self.time_step = seconds;
+        self
+    }
+
+    /// ignores robots farther away than the distance in meters
+    pub fn with_neighbor_distance(mut self, distance: T) -> Self {
+        self.neighbor_distance = distance;
+        self
+    }
+
+    /// Restricts the velocities to those a differential drive robot with
+    /// the given wheel speed limit in rad/s tracks within the error in
+    /// meters, turning towards a new velocity over the heading time in
+    /// seconds or more when the wheels need longer
+    pub fn with_differential_drive(
+        mut self,
+        robot: &DiffDrive<T>,
+        max_wheel_speed: T,
+        tracking_error: T,
+        heading_time: T,
+    ) -> Self
+    where
+        T: Default,
+    {
+        let radius = robot.left_wheel_radius().min(robot.right_wheel_radius());
+        self.nonholonomic = Some(NonHolonomic {
+            separation: robot.wheel_separation(),
+            wheel_speed: max_wheel_speed * radius,
+            tracking_error,
+            heading_time,
+        });
+        self
+    }
+
+    /// Returns the velocity in the world frame a robot would like to move
+    /// with. Differential drive robots aim along their heading halfway
+    /// through the turn.
+    pub fn preferred_velocity(&self, agent: &OrcaAgent<T>) -> Vector2D<T> {
+        let two = T::from(2.0).unwrap();
+        let time = self.nonholonomic.map_or(T::zero(), |nh| nh.heading_time);
+        let heading = agent.pose.theta + agent.preferred.thetadot * time / two;
+        let (s, c) = heading.sin_cos();
+        Vector2D::new(
+            agent.preferred.xdot * c - agent.preferred.ydot * s,
+            agent.preferred.xdot * s + agent.preferred.ydot * c,
+        )
+    }
+
+    /// returns a collision-free velocity in the world frame for every agent
+    pub fn velocities(&self, agents: &[OrcaAgent<T>]) -> Vec<Vector2D<T>> {
+        (0..agents.len())
+            .map(|i| self.velocity(agents, i))
+            .collect()
+    }
+
+    /// Returns a collision-free twist in the robot frame for every agent.
+    /// Holonomic robots keep their preferred turn rate, differential drive
+    /// robots turn to track the velocity.
+    pub fn twists(&self, agents: &[OrcaAgent<T>]) -> Vec<Twist2D<T>> {
+        agents
+            .iter()
+            .zip(self.velocities(agents))
+            .map(|(agent, v)| match self.nonholonomic {
+                Some(nh) => nh.twist(agent.pose.theta, v),
+                None => {
+                    let (s, c) = agent.pose.theta.sin_cos();
+                    Twist2D::new(
+                        agent.preferred.thetadot,
+                        v.x * c + v.y * s,
+                        v.y * c - v.x * s,
+                    )
+                }
+            })
+            .collect()
+    }
+
+    /// computes the new velocity of one agent
+    fn velocity(&self, agents: &[OrcaAgent<T>], index: usize) -> Vector2D<T> {
+        let zero = T::zero();
+        let half = T::from(0.5).unwrap();
+        let agent = &agents[index];
+        let position = Vector2D::new(agent.pose.x, agent.pose.y);
+        let margin = self.nonholonomic.map_or(zero, |nh| nh.tracking_error);
+
+        // hard limits of the velocities the robot can track
+        let mut lines: Vec<Line<T>> = vec![];
+        if let Some(nh) = self.nonholonomic {
+            let vertices = nh.trackable(agent.pose.theta, self.max_speed);
+            let count = vertices.len();
+            for k in 0..count {
+                let (a, b) = (vertices[k], vertices[(k + 1) % count]);
+                let edge = sub(b, a);
+                let length = edge.x.hypot(edge.y);
+                if length <= T::from(1e-9).unwrap() {
+                    continue;
+                }
+                let direction = scale(edge, T::one() / length);
+                // merge edges along the same line
+                if let Some(last) = lines.last() {
+                    if det(last.direction, direction).abs() <= T::from(1e-9).unwrap()
+                        && dot(last.direction, direction) > T::zero()
+                    {
+                        continue;
+                    }
+                }
+                lines.push(Line {
+                    point: a,
+                    direction,
+                });
+            }
+        }
+        let hard = lines.len();
+
+        let inv_horizon = T::one() / self.time_horizon;
+        for (j, other) in agents.iter().enumerate() {
+            let relative_position = sub(Vector2D::new(other.pose.x, other.pose.y), position);
+            let distance2 = dot(relative_position, relative_position);
+            if j == index || distance2.sqrt() > self.neighbor_distance {
+                continue;
+            }
+            let relative_velocity = sub(agent.velocity, other.velocity);
+            let radius = agent.radius + other.radius + margin + margin;
+            let radius2 = radius * radius;
+
+            let (direction, u) = if distance2 > radius2 {
+                // vector from the cutoff center to the relative velocity
+                let w = sub(relative_velocity, scale(relative_position, inv_horizon));
+                let w_length2 = dot(w, w);
+                let dot1 = dot(w, relative_position);
+                if dot1 < zero && dot1 * dot1 > radius2 * w_length2 {
+                    // project on the cutoff circle
+                    let w_length = w_length2.sqrt();
+                    let unit = scale(w, T::one() / w_length);
+                    (
+                        Vector2D::new(unit.y, -unit.x),
+                        scale(unit, radius * inv_horizon - w_length),
+                    )
+                } else {
+                    // project on the nearer leg of the cone
+                    let leg = (distance2 - radius2).sqrt();
+                    let p = relative_position;
+                    let direction = if det(p, w) > zero {
+                        scale(
+                            Vector2D::new(p.x * leg - p.y * radius, p.x * radius + p.y * leg),
+                            T::one() / distance2,
+                        )
+                    } else {
+                        scale(
+                            Vector2D::new(p.x * leg + p.y * radius, -p.x * radius + p.y * leg),
+                            -T::one() / distance2,
+                        )
+                    };
+                    let projection = dot(relative_velocity, direction);
+                    (
+                        direction,
+                        sub(scale(direction, projection), relative_velocity),
+                    )
+                }
+            } else {
+                // already overlapping, separate within one time step
+                let inv_step = T::one() / self.time_step;
+                let w = sub(relative_velocity, scale(relative_position, inv_step));
+                let w_length = dot(w, w).sqrt();
+                let unit = if w_length > T::epsilon() {
+                    scale(w, T::one() / w_length)
+                } else {
+                    // on top of each other, part along x
+                    Vector2D::new(T::one(), zero)
+                };
+                (
+                    Vector2D::new(unit.y, -unit.x),
+                    scale(unit, radius * inv_step - w_length),
+                )
+            };
+            lines.push(Line {
+                point: agent.velocity + scale(u, half),
+                direction,
+            });
+        }
+
+        let preferred = self.preferred_velocity(agent);
+        let mut result = Vector2D::new(zero, zero);
+        let failed = linear_program2(&lines, self.max_speed, preferred, false, &mut result);
+        // the tracking limits always admit standing still, so only the
+        // avoidance lines can fail
+        if failed >= hard && failed < lines.len() {
+            linear_program3(&lines, hard, failed, self.max_speed, &mut result);
+        }
+        result
+    }
+}
+
+fn sub<T: Float>(a: Vector2D<T>, b: Vector2D<T>) -> Vector2D<T> {
+    Vector2D::new(a.x - b.x, a.y - b.y)
+}
+
+fn scale<T: Float>(a: Vector2D<T>, s: T) -> Vector2D<T> {
+    Vector2D::new(a.x * s, a.y * s)
+}
+
+fn dot<T: Float>(a: Vector2D<T>, b: Vector2D<T>) -> T {
+    a.x * b.x + a.y * b.y
+}
+
+fn det<T: Float>(a: Vector2D<T>, b: Vector2D<T>) -> T {
+    a.x * b.y - a.y * b.x
+}
+
+/// Finds the velocity on one line closest to the optimum, or furthest in
+/// its direction, that satisfies the lines before it and the speed limit
+fn linear_program1<T: Float>(
+    lines: &[Line<T>],
+    index: usize,
+    radius: T,
+    optimum: Vector2D<T>,
+    direction_optimum: bool,
+    result: &mut Vector2D<T>,
+) -> bool {
+    let line = lines[index];
+    let projection = dot(line.point, line.direction);
+    let discriminant = projection * projection + radius * radius - dot(line.point, line.point);
+    if discriminant < T::zero() {
+        // the speed limit excludes the whole line
+        return false;
+    }
+    let root = discriminant.sqrt();
+    let mut t_left = -projection - root;
+    let mut t_right = -projection + root;
+
+    for other in &lines[..index] {
+        let denominator = det(line.direction, other.direction);
+        let numerator = det(other.direction, sub(line.point, other.point));
+        if denominator.abs() <= T::epsilon() {
+            // parallel lines
+            if numerator < T::zero() {
+                return false;
+            }
+            continue;
+        }
+        let t = numerator / denominator;
+        if denominator >= T::zero() {
+            t_right = t_right.min(t);
+        } else {
+            t_left = t_left.max(t);
+        }
+        if t_left > t_right {
+            return false;
+        }
+    }
+
+    let t = if direction_optimum {
+        if dot(optimum, line.direction) > T::zero() {
+            t_right
+        } else {
+            t_left
+        }
+    } else {
+        dot(line.direction, sub(optimum, line.point))
+            .max(t_left)
+            .min(t_right)
+    };
+    *result = line.point + scale(line.direction, t);
+    true
+}
+
+/// Finds the velocity closest to the optimum, or furthest in its direction,
+/// that satisfies all lines and the speed limit. Returns the number of
+/// lines, or the index of the first line that could not be satisfied.
+fn linear_program2<T: Float>(
+    lines: &[Line<T>],
+    radius: T,
+    optimum: Vector2D<T>,
+    direction_optimum: bool,
+    result: &mut Vector2D<T>,
+) -> usize {
+    let length = optimum.x.hypot(optimum.y);
+    *result = if direction_optimum {
+        scale(optimum, radius)
+    } else if length > radius {
+        scale(optimum, radius / length)
+    } else {
+        optimum
+    };
+    for (i, line) in lines.iter().enumerate() {
+        if det(line.direction, sub(line.point, *result)) > T::zero() {
+            let previous = *result;
+            if !linear_program1(lines, i, radius, optimum, direction_optimum, result) {
+                *result = previous;
+                return i;
+            }
+        }
+    }
+    lines.len()
+}
+
+/// Finds the velocity that violates the soft lines from the failed one on
+/// the least, keeping the first hard lines satisfied
+fn linear_program3<T: Float>(
+    lines: &[Line<T>],
+    hard: usize,
+    failed: usize,
+    radius: T,
+    result: &mut Vector2D<T>,
+) {
+    let half = T::from(0.5).unwrap();
+    let mut distance = T::zero();
+    for i in failed..lines.len() {
+        let line = lines[i];
+        if det(line.direction, sub(line.point, *result)) <= distance {
+            continue;
+        }
+        let mut projected: Vec<Line<T>> = lines[..hard].to_vec();
+        for other in &lines[hard..i] {
+            let determinant = det(line.direction, other.direction);
+            let point = if determinant.abs() <= T::epsilon() {
+                if dot(line.direction, other.direction) > T::zero() {
+                    // same direction
+                    continue;
+                }
+                scale(line.point + other.point, half)
+            } else {
+                line.point
+                    + scale(
+                        line.direction,
+                        det(other.direction, sub(line.point, other.point)) / determinant,
+                    )
+            };
+            let direction = sub(other.direction, line.direction);
+            let length = direction.x.hypot(direction.y);
+            projected.push(Line {
+                point,
+                direction: scale(direction, T::one() / length),
+            });
+        }
+        let previous = *result;
+        let optimum = Vector2D::new(-line.direction.y, line.direction.x);
+        if linear_program2(&projected, radius, optimum, true, result) < projected.len() {
+            // can only fail on rounding, keep the previous result
+            *result = previous;
+        }
+        distance = det(line.direction, sub(line.point, *result));
+    }
+}
@@ -0,0 +1,399 @@
+//! PID control of wheel velocities.
+//!
+//! `Pid` is a single loop with a filtered derivative acting on the
+//! measurement, anti-windup, output limits, a velocity feed-forward term and
+//! bumpless transfer between manual and automatic mode. `WheelPid` runs one
+//! loop per wheel between `speeds_from_twist` and the motor driver, and
+//! `RelayAutotuner` finds gains from a relay feedback experiment
+//! (Astrom and Hagglund, 1984).
+use crate::ddrive::WheelState;
+use anyhow::{self, bail};
+use num_traits::Float;
+
+/// How the integrator is kept from winding up while the output is saturated
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AntiWindup<T: Float> {
+    /// always integrate the error
+    None,
+
+    /// stop integrating while the output is saturated and the error would
+    /// push it further into saturation
+    ConditionalIntegration,
+
+    /// bleed the integrator by the saturation excess times the tracking gain
+    BackCalculation(T),
+}
+
+/// A PID controller
+#[derive(Debug, Clone, Copy)]
+pub struct Pid<T: Float> {
+    kp: T,
+    ki: T,
+    kd: T,
+
+    /// output per unit of setpoint
+    kff: T,
+
+    /// time constant of the first-order derivative filter, zero for none
+    derivative_filter: T,
+
+    output_min: T,
+    output_max: T,
+    anti_windup: AntiWindup<T>,
+
+    /// integral term, stored as its contribution to the output so that
+    /// changing ki does not bump the output
+    integral: T,
+
+    /// filtered derivative term
+    derivative: T,
+
+    /// error of the last update
+    error: T,
+
+    previous_measurement: Option<T>,
+    output: T,
+    manual: bool,
+}
+
+impl<T: Float> Pid<T> {
+    /// constructs a new Pid with unlimited output and no anti-windup
+    pub fn new(kp: T, ki: T, kd: T) -> Self {
+        Pid {
+            kp,
+            ki,
+            kd,
+            kff: T::zero(),
+            derivative_filter: T::zero(),
+            output_min: T::neg_infinity(),
+            output_max: T::infinity(),
+            anti_windup: AntiWindup::None,
+            integral: T::zero(),
+            derivative: T::zero(),
+            error: T::zero(),
+            previous_measurement: None,
+            output: T::zero(),
+            manual: false,
+        }
+    }
+
+    /// sets the velocity feed-forward gain
+    pub fn with_feedforward(mut self, kff: T) -> Self {
+        self.kff = kff;
+        self
+    }
+
+    /// sets the time constant of the derivative filter
+    pub fn with_derivative_filter(mut self, time_constant: T) -> Self {
+        self.derivative_filter = time_constant;
+        self
+    }
+
+    /// sets the limits of the output
+    pub fn with_output_limits(mut self, min: T, max: T) -> Self {
+        self.output_min = min;
+        self.output_max = max;
+        self
+    }
+
+    /// sets the anti-windup strategy
+    pub fn with_anti_windup(mut self, anti_windup: AntiWindup<T>) -> Self {
+        self.anti_windup = anti_windup;
+        self
+    }
+
+    /// returns the (kp, ki, kd) gains
+    pub fn gains(&self) -> (T, T, T) {
+        (self.kp, self.ki, self.kd)
+    }
+
+    /// Changes the gains without a jump in the output. The integral term
+    /// absorbs the change of the proportional and derivative terms.
+    pub fn set_gains(&mut self, kp: T, ki: T, kd: T) {
+        let derivative = if self.kd == T::zero() {
+            T::zero()
+        } else {
+            self.derivative * kd / self.kd
+        };
+        self.integral = self.integral + (self.kp - kp) * self.error + self.derivative - derivative;
+        self.derivative = derivative;
+        self.kp = kp;
+        self.ki = ki;
+        self.kd = kd;
+    }
+
+    /// returns the integral term
+    pub fn integral(&self) -> T {
+        self.integral
+    }
+
+    /// returns the last output
+    pub fn output(&self) -> T {
+        self.output
+    }
+
+    /// returns true while the controller is in manual mode
+    pub fn is_manual(&self) -> bool {
+        self.manual
+    }
+
+    /// switches to manual mode, where `update` returns the given output
+    pub fn set_manual(&mut self, output: T) {
+        self.manual = true;
+        self.output = self.clamp(output);
+    }
+
+    /// Switches back to automatic mode. The integral term is initialized so
+    /// that the first automatic output equals the last manual output.
+    pub fn set_auto(&mut self, setpoint: T, measurement: T) {
+        if !self.manual {
+            return;
+        }
+        self.manual = false;
+        self.derivative = T::zero();
+        self.error = setpoint - measurement;
+        self.previous_measurement = Some(measurement);
+        self.integral = self.output - self.kp * (setpoint - measurement) - self.kff * setpoint;
+    }
+
+    /// clears the integral, derivative and output
+    pub fn reset(&mut self) {
+        self.integral = T::zero();
+        self.derivative = T::zero();
+        self.error = T::zero();
+        self.previous_measurement = None;
+        self.output = T::zero();
+    }
+
+    /// computes the output for the next dt seconds. Without time passing
+    /// the previous output is returned unchanged.
+    pub fn update(&mut self, setpoint: T, measurement: T, dt: T) -> T {
+        if self.manual {
+            self.previous_measurement = Some(measurement);
+            return self.output;
+        }
+        if dt <= T::zero() {
+            return self.output;
+        }
+        let error = setpoint - measurement;
+        self.error = error;
+
+        // derivative on the measurement avoids a kick when the setpoint steps
+        let change = match self.previous_measurement {
+            Some(previous) => measurement - previous,
+            None => T::zero(),
+        };
+        self.previous_measurement = Some(measurement);
+        self.derivative = (self.derivative_filter * self.derivative - self.kd * change)
+            / (self.derivative_filter + dt);
+
+        let unsaturated = self.kp * error + self.integral + self.derivative + self.kff * setpoint;
+        self.output = self.clamp(unsaturated);
+
+        let increment = self.ki * error * dt;
+        match self.anti_windup {
+            AntiWindup::None => self.integral = self.integral + increment,
+            AntiWindup::ConditionalIntegration => {
+                let pushes_high = unsaturated > self.output_max && increment > T::zero();
+                let pushes_low = unsaturated < self.output_min && increment < T::zero();
+                if !(pushes_high || pushes_low) {
+                    self.integral = self.integral + increment;
+                }
+            }
+            AntiWindup::BackCalculation(gain) => {
+                self.integral = self.integral + increment + gain * (self.output - unsaturated) * dt;
+            }
+        }
+        self.output
+    }
+
+    fn clamp(&self, value: T) -> T {
+        value.max(self.output_min).min(self.output_max)
+    }
+}
+
+/// A pair of velocity loops, one per wheel
+#[derive(Debug, Clone, Copy)]
+pub struct WheelPid<T: Float> {
+    /// loop of the left wheel
+    pub left: Pid<T>,
+
+    /// loop of the right wheel
+    pub right: Pid<T>,
+}
+
+impl<T: Float + Default> WheelPid<T> {
+    /// constructs a WheelPid with the same controller on both wheels
+    pub fn new(pid: Pid<T>) -> Self {
+        WheelPid {
+            left: pid,
+            right: pid,
+        }
+    }
+
+    /// computes the motor commands from the desired and measured wheel speeds
+    pub fn update(
+        &mut self,
+        setpoint: WheelState<T>,
+        measured: WheelState<T>,
+        dt: T,
+    ) -> WheelState<T> {
+        WheelState::new(
+            self.left.update(setpoint.left, measured.left, dt),
+            self.right.update(setpoint.right, measured.right, dt),
+        )
+    }
+
+    /// resets both loops
+    pub fn reset(&mut self) {
+        self.left.reset();
+        self.right.reset();
+    }
+}
+
+/// Rule that turns the ultimate gain and period into PID gains
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TuningRule {
+    ZieglerNicholsPi,
+    ZieglerNicholsPid,
+    TyreusLuyben,
+}
+
+/// Result of a relay feedback experiment
+#[derive(Debug, Clone, Copy)]
+pub struct RelayResult<T: Float> {
+    /// gain at which the closed loop oscillates
+    pub ultimate_gain: T,
+
+    /// period of the oscillation in seconds
+    pub ultimate_period: T,
+
+    /// peak to peak amplitude of the measured oscillation divided by two
+    pub amplitude: T,
+}
+
+impl<T: Float> RelayResult<T> {
+    /// returns the (kp, ki, kd) gains given by the rule
+    pub fn gains(&self, rule: TuningRule) -> (T, T, T) {
+        let f = |x: f64| T::from(x).unwrap();
+        let (ku, tu) = (self.ultimate_gain, self.ultimate_period);
+        let (kp, ti, td) = match rule {
+            TuningRule::ZieglerNicholsPi => (f(0.45) * ku, tu / f(1.2), T::zero()),
+            TuningRule::ZieglerNicholsPid => (f(0.6) * ku, tu / f(2.0), tu / f(8.0)),
+            TuningRule::TyreusLuyben => (ku / f(2.2), f(2.2) * tu, tu / f(6.3)),
+        };
+        (kp, kp / ti, kp * td)
+    }
+
+    /// constructs a Pid tuned by the rule
+    pub fn pid(&self, rule: TuningRule) -> Pid<T> {
+        let (kp, ki, kd) = self.gains(rule);
+        Pid::new(kp, ki, kd)
+    }
+}
+
+/// Finds the ultimate gain and period of a plant by replacing the controller
+/// with a relay, which makes the loop oscillate at its critical frequency
+#[derive(Debug, Clone, Copy)]
+pub struct RelayAutotuner<T: Float> {
+    /// output around which the relay switches, e.g. the feed-forward for the setpoint
+    pub bias: T,
+
+    /// distance of the relay output from the bias
+    pub amplitude: T,
+
+    /// error band inside which the relay does not switch, to reject noise
+    pub hysteresis: T,
+
+    /// number of oscillation cycles to discard while the loop settles
+    pub settle_cycles: usize,
+
+    /// number of oscillation cycles to measure
+    pub measure_cycles: usize,
+}
+
+impl<T: Float> RelayAutotuner<T> {
+    /// constructs a new RelayAutotuner
+    pub fn new(bias: T, amplitude: T, hysteresis: T) -> Self {
+        RelayAutotuner {
+            bias,
+            amplitude,
+            hysteresis,
+            settle_cycles: 3,
+            measure_cycles: 4,
+        }
+    }
+
+    /// Runs the experiment. `plant` applies an output for dt seconds and
+    /// returns the measurement at the end of the step. Returns an error if
+    /// no cycles are to be measured or the loop does not oscillate within
+    /// the given time.
+    pub fn tune<F>(
+        &self,
+        setpoint: T,
+        dt: T,
+        timeout: T,
+        mut plant: F,
+    ) -> anyhow::Result<RelayResult<T>>
+    where
+        F: FnMut(T) -> T,
+    {
+        if self.measure_cycles == 0 {
+            bail!("the autotuner must measure at least one cycle");
+        }
+        let steps = (timeout / dt).to_usize().unwrap_or(0);
+        let mut high = true;
+        let mut switches: Vec<T> = vec![];
+        let (mut peak, mut trough) = (T::neg_infinity(), T::infinity());
+        let mut amplitudes: Vec<T> = vec![];
+        let cycles = self.settle_cycles + self.measure_cycles;
+
+        for step in 0..steps {
+            let output = if high {
+                self.bias + self.amplitude
+            } else {
+                self.bias - self.amplitude
+            };
+            let measurement = plant(output);
+            peak = peak.max(measurement);
+            trough = trough.min(measurement);
+
+            let error = setpoint - measurement;
+            if high && error < -self.hysteresis {
+                high = false;
+            } else if !high && error > self.hysteresis {
+                // a rising switch completes a cycle
+                high = true;
+                switches.push(T::from(step + 1).unwrap() * dt);
+                amplitudes.push((peak - trough) / T::from(2.0).unwrap());
+                peak = T::neg_infinity();
+                trough = T::infinity();
+                if switches.len() > cycles {
+                    break;
+                }
+            }
+        }
+        if switches.len() <= cycles {
+            bail!("the loop did not reach a sustained oscillation");
+        }
+
+        let n = T::from(self.measure_cycles).unwrap();
+        let first = switches.len() - 1 - self.measure_cycles;
+        let period = (switches[switches.len() - 1] - switches[first]) / n;
+        let amplitude = amplitudes[first + 1..]
+            .iter()
+            .fold(T::zero(), |acc, a| acc + *a)
+            / n;
+        if amplitude <= self.hysteresis {
+            bail!("the oscillation is smaller than the relay hysteresis");
+        }
+
+        let pi = T::from(std::f64::consts::PI).unwrap();
+        let effective = (amplitude * amplitude - self.hysteresis * self.hysteresis).sqrt();
+        Ok(RelayResult {
+            ultimate_gain: T::from(4.0).unwrap() * self.amplitude / (pi * effective),
+            ultimate_period: period,
+            amplitude,
+        })
+    }
+}
@@ -0,0 +1,224 @@
+//! Path planning on occupancy grids.
+//!
+//! `GridPlanner` runs A* over the 8-connected cells of an `OccupancyGrid`.
+//! Obstacles are inflated by the robot radius so that the robot can be
+//! treated as a point, and diagonal moves may not cut the corners of
+//! blocked cells.
+use crate::mapping::{CellState, OccupancyGrid};
+use crate::rigid2d::Vector2D;
+use anyhow::{self, bail};
+use num_traits::Float;
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+/// A* planner over an occupancy grid
+#[derive(Debug, Clone, Copy)]
+pub struct GridPlanner<T: Float> {
+    /// radius of a circle containing the robot in meters
+    robot_radius: T,
+
+    /// whether unknown cells may be driven through
+    allow_unknown: bool,
+}
+
+/// Entry of the open set, ordered so that the smallest cost pops first
+struct Open<T: Float> {
+    cost: T,
+    cell: usize,
+}
+
+impl<T: Float> PartialEq for Open<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cost == other.cost
+    }
+}
+
+impl<T: Float> Eq for Open<T> {}
+
+impl<T: Float> PartialOrd for Open<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T: Float> Ord for Open<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .cost
+            .partial_cmp(&self.cost)
+            .unwrap_or(Ordering::Equal)
+    }
+}
+
+impl<T: Float> GridPlanner<T> {
+    /// constructs a planner for a robot of the given radius
+    pub fn new(robot_radius: T) -> Self {
+        GridPlanner {
+            robot_radius,
+            allow_unknown: false,
+        }
+    }
+
+    /// lets the planner drive through unknown cells, which it avoids by default
+    pub fn with_unknown_allowed(mut self, allow: bool) -> Self {
+        self.allow_unknown = allow;
+        self
+    }
+
+    /// Returns which cells the robot's center may be in, row major starting
+    /// at the origin: cells farther than the robot radius from every
+    /// blocked cell.
+    pub fn traversable(&self, grid: &OccupancyGrid<T>) -> Vec<bool> {
+        let (width, height) = (grid.width(), grid.height());
+        let blocked: Vec<bool> = grid
+            .states()
+            .iter()
+            .map(|state| match state {
+                CellState::Free => false,
+                CellState::Occupied => true,
+                CellState::Unknown => !self.allow_unknown,
+            })
+            .collect();
+
+        let reach = (self.robot_radius / grid.resolution())
+            .ceil()
+            .to_i64()
+            .unwrap_or(0)
+            .max(0);
+        let radius_cells = self.robot_radius / grid.resolution();
+        let mut traversable: Vec<bool> = blocked.iter().map(|b| !b).collect();
+        for iy in 0..height as i64 {
+            for ix in 0..width as i64 {
+                if !blocked[iy as usize * width + ix as usize] {
+                    continue;
+                }
+                for dy in -reach..=reach {
+                    for dx in -reach..=reach {
+                        let (x, y) = (ix + dx, iy + dy);
+                        if x < 0 || y < 0 || x >= width as i64 || y >= height as i64 {
+                            continue;
+                        }
+                        let distance = T::from(((dx * dx + dy * dy) as f64).sqrt()).unwrap();
+                        if distance <= radius_cells {
+                            traversable[y as usize * width + x as usize] = false;
+                        }
+                    }
+                }
+            }
+        }
+        traversable
+    }
+
+    /// Plans a path from start to goal in world coordinates. The path runs
+    /// through the centers of the cells in between and ends exactly at the
+    /// start and goal.
+    pub fn plan(
+        &self,
+        grid: &OccupancyGrid<T>,
+        start: Vector2D<T>,
+        goal: Vector2D<T>,
+    ) -> anyhow::Result<Vec<Vector2D<T>>> {
+        let width = grid.width();
+        let traversable = self.traversable(grid);
+        let index = |p: Vector2D<T>, name: &str| -> anyhow::Result<usize> {
+            let (ix, iy) = match grid.world_to_cell(p) {
+                Some(cell) => cell,
+                None => bail!("the {} is off the map", name),
+            };
+            if !traversable[iy * width + ix] {
+                bail!("the {} is blocked or too close to an obstacle", name);
+            }
+            Ok(iy * width + ix)
+        };
+        let start_cell = index(start, "start")?;
+        let goal_cell = index(goal, "goal")?;
+
+        let (gx, gy) = ((goal_cell % width) as i64, (goal_cell / width) as i64);
+        let sqrt2 = T::from(2.0).unwrap().sqrt();
+        // octile distance, exact on an empty 8-connected grid
+        let heuristic = |cell: usize| {
+            let dx = T::from(((cell % width) as i64 - gx).abs()).unwrap();
+            let dy = T::from(((cell / width) as i64 - gy).abs()).unwrap();
+            dx.max(dy) + (sqrt2 - T::one()) * dx.min(dy)
+        };
+
+        let mut cost = vec![T::infinity(); traversable.len()];
+        let mut parent = vec![usize::MAX; traversable.len()];
+        let mut open = BinaryHeap::new();
+        cost[start_cell] = T::zero();
+        open.push(Open {
+            cost: heuristic(start_cell),
+            cell: start_cell,
+        });
+        while let Some(Open { cell, .. }) = open.pop() {
+            if cell == goal_cell {
+                break;
+            }
+            let (x, y) = ((cell % width) as i64, (cell / width) as i64);
+            for (dx, dy) in [
+                (1, 0),
+                (-1, 0),
+                (0, 1),
+                (0, -1),
+                (1, 1),
+                (1, -1),
+                (-1, 1),
+                (-1, -1),
+            ] {
+                let (nx, ny) = (x + dx, y + dy);
+                if nx < 0 || ny < 0 || nx >= width as i64 || ny >= grid.height() as i64 {
+                    continue;
+                }
+                let next = ny as usize * width + nx as usize;
+                let diagonal = dx != 0 && dy != 0;
+                let cuts_corner = diagonal
+                    && !(traversable[y as usize * width + nx as usize]
+                        && traversable[ny as usize * width + x as usize]);
+                if !traversable[next] || cuts_corner {
+                    continue;
+                }
+                let step = if diagonal { sqrt2 } else { T::one() };
+                let new_cost = cost[cell] + step;
+                if new_cost < cost[next] {
+                    cost[next] = new_cost;
+                    parent[next] = cell;
+                    open.push(Open {
+                        cost: new_cost + heuristic(next),
+                        cell: next,
+                    });
+                }
+            }
+        }
+        if cost[goal_cell].is_infinite() {
+            bail!("there is no path from the start to the goal");
+        }
+
+        let mut cells = vec![goal_cell];
+        while let Some(&cell) = cells.last() {
+            if cell == start_cell {
+                break;
+            }
+            cells.push(parent[cell]);
+        }
+        cells.reverse();
+        let mut path: Vec<Vector2D<T>> = cells
+            .iter()
+            .map(|&cell| grid.cell_to_world(cell % width, cell / width))
+            .collect();
+        path[0] = start;
+        let last = path.len() - 1;
+        if last == 0 {
+            path.push(goal);
+        } else {
+            path[last] = goal;
+        }
+        Ok(path)
+    }
+}
+
+/// returns the length of a polyline
+pub fn path_length<T: Float>(path: &[Vector2D<T>]) -> T {
+    path.windows(2)
+        .map(|w| ((w[1].x - w[0].x).powi(2) + (w[1].y - w[0].y).powi(2)).sqrt())
+        .fold(T::zero(), |a, b| a + b)
+}
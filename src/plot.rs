@@ -0,0 +1,1144 @@
+//! Plots of paths, odometry traces and maps as SVG or PNG images.
+//!
+//! A `Plot` collects items in world coordinates (meters), fits them into
+//! the image with equal scaling on both axes and draws them over axes with
+//! tick labels, a scale bar and a legend of the labelled items.
+//!
+//! ```no_run
+//! use diff_drive::plot::Plot;
+//! use diff_drive::trajectory::Path;
+//!
+//! let mut plot = Plot::new(640, 480).with_title("semi circle");
+//! plot.add_path(&Path::semi_circle(1.0, 50), "reference");
+//! plot.save("path.svg").unwrap();
+//! ```
+//!
+//! PNG images are rasterized in-crate, with a small built-in bitmap font
+//! for the text, and written uncompressed.
+use crate::mapping::{CellState, OccupancyGrid};
+use crate::rigid2d::{Pose2D, Vector2D};
+use crate::trajectory::Path as TrajectoryPath;
+use crate::utils::{crc32, crc32_update};
+use anyhow::{self, bail};
+use std::fmt::Write as _;
+use std::fs;
+use std::path::Path;
+
+/// An RGBA color
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+
+    /// opacity, 255 is opaque
+    pub a: u8,
+}
+
+impl Color {
+    pub const BLACK: Color = Color::rgb(0, 0, 0);
+    pub const WHITE: Color = Color::rgb(255, 255, 255);
+    pub const GRAY: Color = Color::rgb(128, 128, 128);
+
+    /// colors given to items in the order they are added
+    pub const PALETTE: [Color; 6] = [
+        Color::rgb(31, 119, 180),
+        Color::rgb(255, 127, 14),
+        Color::rgb(44, 160, 44),
+        Color::rgb(214, 39, 40),
+        Color::rgb(148, 103, 189),
+        Color::rgb(140, 86, 75),
+    ];
+
+    /// constructs an opaque color
+    pub const fn rgb(r: u8, g: u8, b: u8) -> Self {
+        Color { r, g, b, a: 255 }
+    }
+
+    /// returns the color with a different opacity
+    pub const fn with_alpha(self, a: u8) -> Self {
+        Color { a, ..self }
+    }
+
+    fn hex(&self) -> String {
+        format!("#{:02x}{:02x}{:02x}", self.r, self.g, self.b)
+    }
+
+    fn opacity(&self) -> f64 {
+        self.a as f64 / 255.0
+    }
+}
+
+/// Something drawn in world coordinates
+#[derive(Debug, Clone)]
+enum Item {
+    Line {
+        points: Vec<Vector2D<f64>>,
+        color: Color,
+        width: f64,
+        dashed: bool,
+    },
+    Arrows {
+        poses: Vec<Pose2D<f64>>,
+        color: Color,
+    },
+    Polygon {
+        points: Vec<Vector2D<f64>>,
+        color: Color,
+    },
+    Grid {
+        origin: Vector2D<f64>,
+        resolution: f64,
+        columns: usize,
+        rows: usize,
+
+        /// gray level of every cell, row major starting at the origin
+        cells: Vec<u8>,
+    },
+}
+
+/// How a legend entry is drawn
+#[derive(Debug, Clone, Copy)]
+enum Swatch {
+    Line,
+    Fill,
+}
+
+/// Anchor of a text label
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Anchor {
+    Start,
+    Middle,
+    End,
+}
+
+/// Something drawn in pixel coordinates, with y pointing down
+#[derive(Debug, Clone)]
+enum Shape {
+    Line {
+        points: Vec<(f64, f64)>,
+        color: Color,
+        width: f64,
+        dashed: bool,
+    },
+    Polygon {
+        points: Vec<(f64, f64)>,
+        fill: Color,
+    },
+    Rect {
+        x: f64,
+        y: f64,
+        w: f64,
+        h: f64,
+        fill: Color,
+    },
+    Text {
+        x: f64,
+        y: f64,
+        text: String,
+        color: Color,
+        anchor: Anchor,
+    },
+}
+
+/// width of a character in pixels, shared by the SVG and PNG text
+const CHAR_WIDTH: f64 = 8.0;
+
+/// scale of the 3x5 bitmap font in PNG images
+const FONT_SCALE: i64 = 2;
+
+const MARGIN_LEFT: f64 = 64.0;
+const MARGIN_RIGHT: f64 = 16.0;
+const MARGIN_TOP: f64 = 32.0;
+const MARGIN_BOTTOM: f64 = 44.0;
+
+/// length of heading arrows in pixels
+const ARROW_LENGTH: f64 = 14.0;
+
+/// A plot of robot data in world coordinates
+#[derive(Debug, Clone)]
+pub struct Plot {
+    width: u32,
+    height: u32,
+    title: Option<String>,
+    items: Vec<Item>,
+    legend: Vec<(String, Color, Swatch)>,
+
+    /// number of items that took a color from the palette
+    colors_used: usize,
+}
+
+impl Plot {
+    /// constructs an empty plot of the given size in pixels
+    pub fn new(width: u32, height: u32) -> Self {
+        Plot {
+            width,
+            height,
+            title: None,
+            items: vec![],
+            legend: vec![],
+            colors_used: 0,
+        }
+    }
+
+    /// sets the title shown above the plot
+    pub fn with_title(mut self, title: &str) -> Self {
+        self.title = Some(title.to_string());
+        self
+    }
+
+    /// adds a polyline through the points. Items with an empty label are
+    /// left out of the legend.
+    pub fn add_line(&mut self, points: &[Vector2D<f64>], color: Color, label: &str) -> &mut Self {
+        self.items.push(Item::Line {
+            points: points.to_vec(),
+            color,
+            width: 2.0,
+            dashed: false,
+        });
+        self.add_legend(label, color, Swatch::Line);
+        self
+    }
+
+    /// adds a reference path as a dashed line
+    pub fn add_path(&mut self, path: &TrajectoryPath, label: &str) -> &mut Self {
+        let color = self.next_color();
+        let points = path
+            .to_vec()
+            .iter()
+            .map(|p| Vector2D::new(p.x as f64, p.y as f64))
+            .collect();
+        self.items.push(Item::Line {
+            points,
+            color,
+            width: 2.0,
+            dashed: true,
+        });
+        self.add_legend(label, color, Swatch::Line);
+        self
+    }
+
+    /// adds a trace of poses, e.g. from odometry, with heading arrows at
+    /// about `arrows` evenly spaced poses
+    pub fn add_pose_trace(
+        &mut self,
+        poses: &[Pose2D<f64>],
+        arrows: usize,
+        label: &str,
+    ) -> &mut Self {
+        let color = self.next_color();
+        self.items.push(Item::Line {
+            points: poses.iter().map(|p| Vector2D::new(p.x, p.y)).collect(),
+            color,
+            width: 2.0,
+            dashed: false,
+        });
+        if arrows > 0 && !poses.is_empty() {
+            let step = poses.len().div_ceil(arrows).max(1);
+            self.items.push(Item::Arrows {
+                poses: poses.iter().step_by(step).copied().collect(),
+                color,
+            });
+        }
+        self.add_legend(label, color, Swatch::Line);
+        self
+    }
+
+    /// adds the rectangular footprint of a robot centered at a pose, with
+    /// its length along the robot's heading
+    pub fn add_footprint(
+        &mut self,
+        pose: Pose2D<f64>,
+        length: f64,
+        width: f64,
+        label: &str,
+    ) -> &mut Self {
+        let (l, w) = (length / 2.0, width / 2.0);
+        let corners = [
+            Vector2D::new(l, w),
+            Vector2D::new(-l, w),
+            Vector2D::new(-l, -w),
+            Vector2D::new(l, -w),
+        ];
+        self.add_footprint_polygon(pose, &corners, label)
+    }
+
+    /// adds a footprint given as a polygon in the robot frame
+    pub fn add_footprint_polygon(
+        &mut self,
+        pose: Pose2D<f64>,
+        corners: &[Vector2D<f64>],
+        label: &str,
+    ) -> &mut Self {
+        let color = self.next_color();
+        let (sin, cos) = pose.theta.sin_cos();
+        let points: Vec<Vector2D<f64>> = corners
+            .iter()
+            .map(|c| {
+                Vector2D::new(
+                    pose.x + cos * c.x - sin * c.y,
+                    pose.y + sin * c.x + cos * c.y,
+                )
+            })
+            .collect();
+        self.items.push(Item::Polygon {
+            points: points.clone(),
+            color: color.with_alpha(64),
+        });
+        let mut outline = points;
+        outline.extend(outline.first().copied());
+        self.items.push(Item::Line {
+            points: outline,
+            color,
+            width: 1.5,
+            dashed: false,
+        });
+        self.items.push(Item::Arrows {
+            poses: vec![pose],
+            color,
+        });
+        self.add_legend(label, color.with_alpha(64), Swatch::Fill);
+        self
+    }
+
+    /// adds an occupancy grid, drawn below every other item with free cells
+    /// white, occupied cells black and unknown cells gray as in `to_pgm`
+    pub fn add_grid(&mut self, grid: &OccupancyGrid<f64>) -> &mut Self {
+        let cells = grid
+            .states()
+            .iter()
+            .map(|state| match state {
+                CellState::Free => 254,
+                CellState::Occupied => 0,
+                CellState::Unknown => 205,
+            })
+            .collect();
+        self.items.push(Item::Grid {
+            origin: grid.origin(),
+            resolution: grid.resolution(),
+            columns: grid.width(),
+            rows: grid.height(),
+            cells,
+        });
+        self
+    }
+
+    /// adds the rollouts a controller considered as thin translucent lines,
+    /// highlighting the chosen one
+    pub fn add_rollouts(
+        &mut self,
+        rollouts: &[Vec<Pose2D<f64>>],
+        chosen: Option<usize>,
+        label: &str,
+    ) -> &mut Self {
+        let color = self.next_color();
+        for rollout in rollouts {
+            self.items.push(Item::Line {
+                points: rollout.iter().map(|p| Vector2D::new(p.x, p.y)).collect(),
+                color: color.with_alpha(90),
+                width: 1.0,
+                dashed: false,
+            });
+        }
+        self.add_legend(label, color.with_alpha(90), Swatch::Line);
+        if let Some(rollout) = chosen.and_then(|i| rollouts.get(i)) {
+            let chosen_color = self.next_color();
+            self.items.push(Item::Line {
+                points: rollout.iter().map(|p| Vector2D::new(p.x, p.y)).collect(),
+                color: chosen_color,
+                width: 3.0,
+                dashed: false,
+            });
+            if !label.is_empty() {
+                self.add_legend(&format!("{} (chosen)", label), chosen_color, Swatch::Line);
+            }
+        }
+        self
+    }
+
+    /// renders the plot as an SVG document
+    pub fn to_svg(&self) -> String {
+        let mut svg = String::new();
+        let _ = writeln!(
+            svg,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="0 0 {w} {h}">"#,
+            w = self.width,
+            h = self.height
+        );
+        for shape in self.shapes() {
+            shape.write_svg(&mut svg);
+        }
+        svg.push_str("</svg>\n");
+        svg
+    }
+
+    /// renders the plot into an image
+    pub fn render(&self) -> Image {
+        let mut image = Image::new(self.width, self.height, Color::WHITE);
+        for shape in self.shapes() {
+            shape.rasterize(&mut image);
+        }
+        image
+    }
+
+    /// renders the plot as a PNG file
+    pub fn to_png(&self) -> Vec<u8> {
+        self.render().to_png()
+    }
+
+    /// writes the plot to an .svg or .png file, picked by the extension
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> anyhow::Result<()> {
+        let path = path.as_ref();
+        let extension = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_ascii_lowercase());
+        match extension.as_deref() {
+            Some("svg") => fs::write(path, self.to_svg())?,
+            Some("png") => fs::write(path, self.to_png())?,
+            _ => bail!("can not tell the image format of {}", path.display()),
+        }
+        Ok(())
+    }
+
+    fn next_color(&mut self) -> Color {
+        let color = Color::PALETTE[self.colors_used % Color::PALETTE.len()];
+        self.colors_used += 1;
+        color
+    }
+
+    fn add_legend(&mut self, label: &str, color: Color, swatch: Swatch) {
+        if !label.is_empty() {
+            self.legend.push((label.to_string(), color, swatch));
+        }
+    }
+
+    /// returns the smallest and largest world coordinates of the items
+    fn bounds(&self) -> (Vector2D<f64>, Vector2D<f64>) {
+        let mut min = Vector2D::new(f64::INFINITY, f64::INFINITY);
+        let mut max = Vector2D::new(f64::NEG_INFINITY, f64::NEG_INFINITY);
+        let mut add = |p: Vector2D<f64>| {
+            if p.x.is_finite() && p.y.is_finite() {
+                min = Vector2D::new(min.x.min(p.x), min.y.min(p.y));
+                max = Vector2D::new(max.x.max(p.x), max.y.max(p.y));
+            }
+        };
+        for item in &self.items {
+            match item {
+                Item::Line { points, .. } | Item::Polygon { points, .. } => {
+                    points.iter().for_each(|p| add(*p))
+                }
+                Item::Arrows { poses, .. } => {
+                    poses.iter().for_each(|p| add(Vector2D::new(p.x, p.y)))
+                }
+                Item::Grid {
+                    origin,
+                    resolution,
+                    columns,
+                    rows,
+                    ..
+                } => {
+                    add(*origin);
+                    add(Vector2D::new(
+                        origin.x + *columns as f64 * resolution,
+                        origin.y + *rows as f64 * resolution,
+                    ));
+                }
+            }
+        }
+        if min.x > max.x {
+            return (Vector2D::new(-1.0, -1.0), Vector2D::new(1.0, 1.0));
+        }
+        // pad the data and keep a minimum extent for single points
+        let pad_x = ((max.x - min.x) * 0.05).max(0.05);
+        let pad_y = ((max.y - min.y) * 0.05).max(0.05);
+        (
+            Vector2D::new(min.x - pad_x, min.y - pad_y),
+            Vector2D::new(max.x + pad_x, max.y + pad_y),
+        )
+    }
+
+    /// lays out the plot into shapes in pixel coordinates
+    fn shapes(&self) -> Vec<Shape> {
+        let area_w = (self.width as f64 - MARGIN_LEFT - MARGIN_RIGHT).max(1.0);
+        let area_h = (self.height as f64 - MARGIN_TOP - MARGIN_BOTTOM).max(1.0);
+        let (min, max) = self.bounds();
+        let scale = (area_w / (max.x - min.x)).min(area_h / (max.y - min.y));
+
+        // center the data in the plot area and grow the world window to fill it
+        let view_w = area_w / scale;
+        let view_h = area_h / scale;
+        let x0 = (min.x + max.x - view_w) / 2.0;
+        let y1 = (min.y + max.y + view_h) / 2.0;
+        let to_pixel = |p: Vector2D<f64>| {
+            (
+                MARGIN_LEFT + (p.x - x0) * scale,
+                MARGIN_TOP + (y1 - p.y) * scale,
+            )
+        };
+
+        let mut shapes = vec![];
+        let grids = self.items.iter().filter(|i| matches!(i, Item::Grid { .. }));
+        let others = self
+            .items
+            .iter()
+            .filter(|i| !matches!(i, Item::Grid { .. }));
+        for item in grids.chain(others) {
+            item.shapes(&to_pixel, &mut shapes);
+        }
+
+        self.axes(x0, y1, view_w, view_h, &to_pixel, &mut shapes);
+        self.scale_bar(view_w, scale, &mut shapes);
+        self.legend_shapes(&mut shapes);
+        if let Some(title) = &self.title {
+            shapes.push(Shape::Text {
+                x: self.width as f64 / 2.0,
+                y: MARGIN_TOP / 2.0,
+                text: title.clone(),
+                color: Color::BLACK,
+                anchor: Anchor::Middle,
+            });
+        }
+        shapes
+    }
+
+    fn axes(
+        &self,
+        x0: f64,
+        y1: f64,
+        view_w: f64,
+        view_h: f64,
+        to_pixel: &impl Fn(Vector2D<f64>) -> (f64, f64),
+        shapes: &mut Vec<Shape>,
+    ) {
+        let left = MARGIN_LEFT;
+        let top = MARGIN_TOP;
+        let right = self.width as f64 - MARGIN_RIGHT;
+        let bottom = self.height as f64 - MARGIN_BOTTOM;
+        shapes.push(Shape::Line {
+            points: vec![
+                (left, top),
+                (right, top),
+                (right, bottom),
+                (left, bottom),
+                (left, top),
+            ],
+            color: Color::BLACK,
+            width: 1.0,
+            dashed: false,
+        });
+
+        let step = nice_step(view_w.max(view_h) / 6.0);
+        let mut x = (x0 / step).ceil() * step;
+        while x <= x0 + view_w {
+            let (px, _) = to_pixel(Vector2D::new(x, 0.0));
+            shapes.push(tick((px, bottom), (px, bottom + 5.0)));
+            shapes.push(Shape::Text {
+                x: px,
+                y: bottom + 14.0,
+                text: format_number(x, step),
+                color: Color::BLACK,
+                anchor: Anchor::Middle,
+            });
+            x += step;
+        }
+        let mut y = ((y1 - view_h) / step).ceil() * step;
+        while y <= y1 {
+            let (_, py) = to_pixel(Vector2D::new(0.0, y));
+            shapes.push(tick((left - 5.0, py), (left, py)));
+            shapes.push(Shape::Text {
+                x: left - 8.0,
+                y: py,
+                text: format_number(y, step),
+                color: Color::BLACK,
+                anchor: Anchor::End,
+            });
+            y += step;
+        }
+        shapes.push(Shape::Text {
+            x: (left + right) / 2.0,
+            y: bottom + 32.0,
+            text: "x [m]".to_string(),
+            color: Color::BLACK,
+            anchor: Anchor::Middle,
+        });
+        shapes.push(Shape::Text {
+            x: 4.0,
+            y: top - 12.0,
+            text: "y [m]".to_string(),
+            color: Color::BLACK,
+            anchor: Anchor::Start,
+        });
+    }
+
+    /// draws a bar of a round length in the lower left corner
+    fn scale_bar(&self, view_w: f64, scale: f64, shapes: &mut Vec<Shape>) {
+        let length = nice_step(view_w / 5.0);
+        let x = MARGIN_LEFT + 12.0;
+        let y = self.height as f64 - MARGIN_BOTTOM - 12.0;
+        let end = x + length * scale;
+        shapes.push(Shape::Line {
+            points: vec![(x, y - 4.0), (x, y), (end, y), (end, y - 4.0)],
+            color: Color::BLACK,
+            width: 2.0,
+            dashed: false,
+        });
+        shapes.push(Shape::Text {
+            x: (x + end) / 2.0,
+            y: y - 12.0,
+            text: format!("{} m", format_number(length, length)),
+            color: Color::BLACK,
+            anchor: Anchor::Middle,
+        });
+    }
+
+    /// draws the labelled items in a box in the upper right corner
+    fn legend_shapes(&self, shapes: &mut Vec<Shape>) {
+        if self.legend.is_empty() {
+            return;
+        }
+        let longest = self.legend.iter().map(|e| e.0.chars().count()).max();
+        let w = 44.0 + longest.unwrap_or(0) as f64 * CHAR_WIDTH;
+        let row = 18.0;
+        let h = 8.0 + row * self.legend.len() as f64;
+        let x = self.width as f64 - MARGIN_RIGHT - 8.0 - w;
+        let y = MARGIN_TOP + 8.0;
+        shapes.push(Shape::Rect {
+            x,
+            y,
+            w,
+            h,
+            fill: Color::WHITE.with_alpha(220),
+        });
+        shapes.push(Shape::Line {
+            points: vec![(x, y), (x + w, y), (x + w, y + h), (x, y + h), (x, y)],
+            color: Color::GRAY,
+            width: 1.0,
+            dashed: false,
+        });
+        for (i, (label, color, swatch)) in self.legend.iter().enumerate() {
+            let cy = y + 4.0 + row * (i as f64 + 0.5);
+            shapes.push(match swatch {
+                Swatch::Line => Shape::Line {
+                    points: vec![(x + 8.0, cy), (x + 32.0, cy)],
+                    color: *color,
+                    width: 3.0,
+                    dashed: false,
+                },
+                Swatch::Fill => Shape::Rect {
+                    x: x + 8.0,
+                    y: cy - 5.0,
+                    w: 24.0,
+                    h: 10.0,
+                    fill: *color,
+                },
+            });
+            shapes.push(Shape::Text {
+                x: x + 38.0,
+                y: cy,
+                text: label.clone(),
+                color: Color::BLACK,
+                anchor: Anchor::Start,
+            });
+        }
+    }
+}
+
+impl Item {
+    fn shapes(&self, to_pixel: &impl Fn(Vector2D<f64>) -> (f64, f64), shapes: &mut Vec<Shape>) {
+        match self {
+            Item::Line {
+                points,
+                color,
+                width,
+                dashed,
+            } => shapes.push(Shape::Line {
+                points: points.iter().map(|p| to_pixel(*p)).collect(),
+                color: *color,
+                width: *width,
+                dashed: *dashed,
+            }),
+            Item::Polygon { points, color } => shapes.push(Shape::Polygon {
+                points: points.iter().map(|p| to_pixel(*p)).collect(),
+                fill: *color,
+            }),
+            Item::Arrows { poses, color } => {
+                for pose in poses {
+                    let (x, y) = to_pixel(Vector2D::new(pose.x, pose.y));
+                    // y points down in pixel coordinates
+                    let (sin, cos) = (-pose.theta).sin_cos();
+                    let tip = (x + cos * ARROW_LENGTH, y + sin * ARROW_LENGTH);
+                    let barb = |angle: f64| {
+                        let (s, c) = (-pose.theta + angle).sin_cos();
+                        (tip.0 - c * 5.0, tip.1 - s * 5.0)
+                    };
+                    shapes.push(Shape::Line {
+                        points: vec![(x, y), tip],
+                        color: *color,
+                        width: 1.5,
+                        dashed: false,
+                    });
+                    shapes.push(Shape::Polygon {
+                        points: vec![tip, barb(0.5), barb(-0.5)],
+                        fill: *color,
+                    });
+                }
+            }
+            Item::Grid {
+                origin,
+                resolution,
+                columns,
+                rows,
+                cells,
+            } => {
+                // one rectangle per run of equal cells along a row
+                for iy in 0..*rows {
+                    let mut ix = 0;
+                    while ix < *columns {
+                        let value = cells[iy * columns + ix];
+                        let start = ix;
+                        while ix < *columns && cells[iy * columns + ix] == value {
+                            ix += 1;
+                        }
+                        let (x0, y0) = to_pixel(Vector2D::new(
+                            origin.x + start as f64 * resolution,
+                            origin.y + (iy + 1) as f64 * resolution,
+                        ));
+                        let (x1, y1) = to_pixel(Vector2D::new(
+                            origin.x + ix as f64 * resolution,
+                            origin.y + iy as f64 * resolution,
+                        ));
+                        shapes.push(Shape::Rect {
+                            x: x0,
+                            y: y0,
+                            w: x1 - x0,
+                            h: y1 - y0,
+                            fill: Color::rgb(value, value, value),
+                        });
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn tick(a: (f64, f64), b: (f64, f64)) -> Shape {
+    Shape::Line {
+        points: vec![a, b],
+        color: Color::BLACK,
+        width: 1.0,
+        dashed: false,
+    }
+}
+
+/// rounds a step up to 1, 2 or 5 times a power of ten
+fn nice_step(raw: f64) -> f64 {
+    if raw <= 0.0 || !raw.is_finite() {
+        return 1.0;
+    }
+    let power = 10f64.powf(raw.log10().floor());
+    let fraction = raw / power;
+    let nice = if fraction <= 1.0 {
+        1.0
+    } else if fraction <= 2.0 {
+        2.0
+    } else if fraction <= 5.0 {
+        5.0
+    } else {
+        10.0
+    };
+    nice * power
+}
+
+/// formats a value with as many decimals as the step needs
+fn format_number(value: f64, step: f64) -> String {
+    let decimals = (-step.log10().floor()).max(0.0) as usize;
+    let text = format!("{:.*}", decimals, value);
+    // avoid printing -0
+    if text
+        .trim_start_matches('-')
+        .chars()
+        .all(|c| c == '0' || c == '.')
+    {
+        text.trim_start_matches('-').to_string()
+    } else {
+        text
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+impl Shape {
+    fn write_svg(&self, svg: &mut String) {
+        let points = |points: &[(f64, f64)]| {
+            points
+                .iter()
+                .map(|(x, y)| format!("{:.2},{:.2}", x, y))
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+        let _ = match self {
+            Shape::Line {
+                points: p,
+                color,
+                width,
+                dashed,
+            } => writeln!(
+                svg,
+                r#"<polyline points="{}" fill="none" stroke="{}" stroke-opacity="{:.3}" stroke-width="{}" stroke-linejoin="round"{}/>"#,
+                points(p),
+                color.hex(),
+                color.opacity(),
+                width,
+                if *dashed {
+                    r#" stroke-dasharray="6 4""#
+                } else {
+                    ""
+                }
+            ),
+            Shape::Polygon { points: p, fill } => writeln!(
+                svg,
+                r#"<polygon points="{}" fill="{}" fill-opacity="{:.3}"/>"#,
+                points(p),
+                fill.hex(),
+                fill.opacity()
+            ),
+            Shape::Rect { x, y, w, h, fill } => writeln!(
+                svg,
+                r#"<rect x="{:.2}" y="{:.2}" width="{:.2}" height="{:.2}" fill="{}" fill-opacity="{:.3}"/>"#,
+                x,
+                y,
+                w,
+                h,
+                fill.hex(),
+                fill.opacity()
+            ),
+            Shape::Text {
+                x,
+                y,
+                text,
+                color,
+                anchor,
+            } => writeln!(
+                svg,
+                r#"<text x="{:.2}" y="{:.2}" fill="{}" font-family="monospace" font-size="13" dominant-baseline="middle" text-anchor="{}">{}</text>"#,
+                x,
+                y,
+                color.hex(),
+                match anchor {
+                    Anchor::Start => "start",
+                    Anchor::Middle => "middle",
+                    Anchor::End => "end",
+                },
+                escape(text)
+            ),
+        };
+    }
+
+    fn rasterize(&self, image: &mut Image) {
+        match self {
+            Shape::Line {
+                points,
+                color,
+                width,
+                dashed,
+            } => {
+                let mut travelled = 0.0;
+                for segment in points.windows(2) {
+                    image.segment(
+                        segment[0],
+                        segment[1],
+                        *width,
+                        *color,
+                        dashed.then_some(travelled),
+                    );
+                    let (dx, dy) = (segment[1].0 - segment[0].0, segment[1].1 - segment[0].1);
+                    travelled += (dx * dx + dy * dy).sqrt();
+                }
+            }
+            Shape::Polygon { points, fill } => image.polygon(points, *fill),
+            Shape::Rect { x, y, w, h, fill } => {
+                image.polygon(&[(*x, *y), (x + w, *y), (x + w, y + h), (*x, y + h)], *fill)
+            }
+            Shape::Text {
+                x,
+                y,
+                text,
+                color,
+                anchor,
+            } => image.text(*x, *y, text, *color, *anchor),
+        }
+    }
+}
+
+/// An RGB image
+#[derive(Debug, Clone, PartialEq)]
+pub struct Image {
+    width: u32,
+    height: u32,
+
+    /// pixels row major from the top left
+    pixels: Vec<[u8; 3]>,
+}
+
+impl Image {
+    /// constructs an image filled with a color. Panics if the number of
+    /// pixels overflows usize.
+    pub fn new(width: u32, height: u32, background: Color) -> Self {
+        let size = (width as usize)
+            .checked_mul(height as usize)
+            .expect("the image has too many pixels");
+        Image {
+            width,
+            height,
+            pixels: vec![[background.r, background.g, background.b]; size],
+        }
+    }
+
+    /// returns the width in pixels
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// returns the height in pixels
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// returns the color of a pixel, counted from the top left
+    pub fn pixel(&self, x: u32, y: u32) -> Color {
+        let [r, g, b] = self.pixels[y as usize * self.width as usize + x as usize];
+        Color::rgb(r, g, b)
+    }
+
+    /// encodes the image as an 8 bit RGB PNG file with stored (uncompressed)
+    /// deflate blocks
+    pub fn to_png(&self) -> Vec<u8> {
+        let mut raw = Vec::with_capacity(self.pixels.len() * 3 + self.height as usize);
+        for row in self.pixels.chunks(self.width.max(1) as usize) {
+            // filter type none
+            raw.push(0);
+            raw.extend(row.iter().flatten());
+        }
+
+        let mut zlib = vec![0x78, 0x01];
+        let blocks: Vec<&[u8]> = raw.chunks(65535).collect();
+        for (i, block) in blocks.iter().enumerate() {
+            zlib.push((i + 1 == blocks.len()) as u8);
+            zlib.extend_from_slice(&(block.len() as u16).to_le_bytes());
+            zlib.extend_from_slice(&(!(block.len() as u16)).to_le_bytes());
+            zlib.extend_from_slice(block);
+        }
+        if blocks.is_empty() {
+            zlib.extend_from_slice(&[1, 0, 0, 0xff, 0xff]);
+        }
+        zlib.extend_from_slice(&adler32(&raw).to_be_bytes());
+
+        let mut header = vec![];
+        header.extend_from_slice(&self.width.to_be_bytes());
+        header.extend_from_slice(&self.height.to_be_bytes());
+        // 8 bit RGB, deflate, adaptive filtering, no interlacing
+        header.extend_from_slice(&[8, 2, 0, 0, 0]);
+
+        let mut png = vec![0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+        png_chunk(&mut png, b"IHDR", &header);
+        png_chunk(&mut png, b"IDAT", &zlib);
+        png_chunk(&mut png, b"IEND", &[]);
+        png
+    }
+
+    fn blend(&mut self, x: i64, y: i64, color: Color) {
+        if x < 0 || y < 0 || x >= self.width as i64 || y >= self.height as i64 {
+            return;
+        }
+        let pixel = &mut self.pixels[(y * self.width as i64 + x) as usize];
+        let a = color.a as u32;
+        for (channel, value) in pixel.iter_mut().zip([color.r, color.g, color.b]) {
+            *channel = ((value as u32 * a + *channel as u32 * (255 - a) + 127) / 255) as u8;
+        }
+    }
+
+    /// draws a thick segment, dashed if the length of the line before the
+    /// segment is given
+    fn segment(
+        &mut self,
+        a: (f64, f64),
+        b: (f64, f64),
+        width: f64,
+        color: Color,
+        dash_offset: Option<f64>,
+    ) {
+        let half = (width / 2.0).max(0.5);
+        let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+        let length2 = dx * dx + dy * dy;
+        let x_range = (a.0.min(b.0) - half).floor() as i64..=(a.0.max(b.0) + half).ceil() as i64;
+        let y_range = (a.1.min(b.1) - half).floor() as i64..=(a.1.max(b.1) + half).ceil() as i64;
+        for py in y_range {
+            for px in x_range.clone() {
+                let (cx, cy) = (px as f64 + 0.5, py as f64 + 0.5);
+                let t = if length2 > 0.0 {
+                    (((cx - a.0) * dx + (cy - a.1) * dy) / length2).clamp(0.0, 1.0)
+                } else {
+                    0.0
+                };
+                let (ex, ey) = (a.0 + t * dx - cx, a.1 + t * dy - cy);
+                if ex * ex + ey * ey > half * half {
+                    continue;
+                }
+                if let Some(offset) = dash_offset {
+                    // 6 pixels on, 4 off
+                    if (offset + t * length2.sqrt()) % 10.0 >= 6.0 {
+                        continue;
+                    }
+                }
+                self.blend(px, py, color);
+            }
+        }
+    }
+
+    /// fills a polygon with the even-odd rule, sampling at pixel centers
+    fn polygon(&mut self, points: &[(f64, f64)], color: Color) {
+        if points.len() < 3 {
+            return;
+        }
+        let top = points.iter().map(|p| p.1).fold(f64::INFINITY, f64::min);
+        let bottom = points.iter().map(|p| p.1).fold(f64::NEG_INFINITY, f64::max);
+        let first_row = top.floor().max(0.0) as i64;
+        let last_row = bottom.ceil().min(self.height as f64) as i64;
+        for py in first_row..last_row {
+            let cy = py as f64 + 0.5;
+            let mut crossings = vec![];
+            for i in 0..points.len() {
+                let (a, b) = (points[i], points[(i + 1) % points.len()]);
+                if (a.1 <= cy) != (b.1 <= cy) {
+                    crossings.push(a.0 + (cy - a.1) / (b.1 - a.1) * (b.0 - a.0));
+                }
+            }
+            crossings.sort_by(|a, b| a.total_cmp(b));
+            for pair in crossings.chunks_exact(2) {
+                let start = (pair[0] - 0.5).ceil().max(0.0) as i64;
+                let end = (pair[1] - 0.5).ceil().min(self.width as f64) as i64;
+                for px in start..end {
+                    self.blend(px, py, color);
+                }
+            }
+        }
+    }
+
+    /// draws text with the built-in bitmap font, vertically centered on y
+    fn text(&mut self, x: f64, y: f64, text: &str, color: Color, anchor: Anchor) {
+        let width = text.chars().count() as f64 * CHAR_WIDTH;
+        let left = match anchor {
+            Anchor::Start => x,
+            Anchor::Middle => x - width / 2.0,
+            Anchor::End => x - width,
+        };
+        let top = (y - 2.5 * FONT_SCALE as f64).round() as i64;
+        for (i, c) in text.chars().enumerate() {
+            let origin = (left + i as f64 * CHAR_WIDTH + 1.0).round() as i64;
+            let glyph = match glyph(c.to_ascii_uppercase()) {
+                Some(glyph) => glyph,
+                None => continue,
+            };
+            for (row, bits) in glyph.iter().enumerate() {
+                for (column, bit) in bits.bytes().enumerate() {
+                    if bit != b'#' {
+                        continue;
+                    }
+                    for sy in 0..FONT_SCALE {
+                        for sx in 0..FONT_SCALE {
+                            self.blend(
+                                origin + column as i64 * FONT_SCALE + sx,
+                                top + row as i64 * FONT_SCALE + sy,
+                                color,
+                            );
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn adler32(bytes: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for chunk in bytes.chunks(5552) {
+        for byte in chunk {
+            a += *byte as u32;
+            b += a;
+        }
+        a %= 65521;
+        b %= 65521;
+    }
+    (b << 16) | a
+}
+
+fn png_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+    png.extend_from_slice(&crc32_update(crc32(kind), data).to_be_bytes());
+}
+
+/// returns the rows of a character in the 3x5 font, None for characters
+/// it does not have
+fn glyph(c: char) -> Option<[&'static str; 5]> {
+    Some(match c {
+        '0' => ["###", "#.#", "#.#", "#.#", "###"],
+        '1' => [".#.", "##.", ".#.", ".#.", "###"],
+        '2' => ["###", "..#", "###", "#..", "###"],
+        '3' => ["###", "..#", "###", "..#", "###"],
+        '4' => ["#.#", "#.#", "###", "..#", "..#"],
+        '5' => ["###", "#..", "###", "..#", "###"],
+        '6' => ["###", "#..", "###", "#.#", "###"],
+        '7' => ["###", "..#", "..#", "..#", "..#"],
+        '8' => ["###", "#.#", "###", "#.#", "###"],
+        '9' => ["###", "#.#", "###", "..#", "###"],
+        'A' => [".#.", "#.#", "###", "#.#", "#.#"],
+        'B' => ["##.", "#.#", "##.", "#.#", "##."],
+        'C' => [".##", "#..", "#..", "#..", ".##"],
+        'D' => ["##.", "#.#", "#.#", "#.#", "##."],
+        'E' => ["###", "#..", "##.", "#..", "###"],
+        'F' => ["###", "#..", "##.", "#..", "#.."],
+        'G' => [".##", "#..", "#.#", "#.#", ".##"],
+        'H' => ["#.#", "#.#", "###", "#.#", "#.#"],
+        'I' => ["###", ".#.", ".#.", ".#.", "###"],
+        'J' => ["..#", "..#", "..#", "#.#", ".#."],
+        'K' => ["#.#", "#.#", "##.", "#.#", "#.#"],
+        'L' => ["#..", "#..", "#..", "#..", "###"],
+        'M' => ["#.#", "###", "###", "#.#", "#.#"],
+        'N' => ["##.", "#.#", "#.#", "#.#", "#.#"],
+        'O' => [".#.", "#.#", "#.#", "#.#", ".#."],
+        'P' => ["##.", "#.#", "##.", "#..", "#.."],
+        'Q' => [".#.", "#.#", "#.#", "##.", ".##"],
+        'R' => ["##.", "#.#", "##.", "#.#", "#.#"],
+        'S' => [".##", "#..", ".#.", "..#", "##."],
+        'T' => ["###", ".#.", ".#.", ".#.", ".#."],
+        'U' => ["#.#", "#.#", "#.#", "#.#", "###"],
+        'V' => ["#.#", "#.#", "#.#", "#.#", ".#."],
+        'W' => ["#.#", "#.#", "###", "###", "#.#"],
+        'X' => ["#.#", "#.#", ".#.", "#.#", "#.#"],
+        'Y' => ["#.#", "#.#", ".#.", ".#.", ".#."],
+        'Z' => ["###", "..#", ".#.", "#..", "###"],
+        '.' => ["...", "...", "...", "...", ".#."],
+        ',' => ["...", "...", "...", ".#.", "#.."],
+        ':' => ["...", ".#.", "...", ".#.", "..."],
+        '-' => ["...", "...", "###", "...", "..."],
+        '+' => ["...", ".#.", "###", ".#.", "..."],
+        '=' => ["...", "###", "...", "###", "..."],
+        '_' => ["...", "...", "...", "...", "###"],
+        '/' => ["..#", "..#", ".#.", "#..", "#.."],
+        '%' => ["#.#", "..#", ".#.", "#..", "#.#"],
+        '&' => [".#.", "#.#", ".#.", "#.#", ".##"],
+        '(' => ["..#", ".#.", ".#.", ".#.", "..#"],
+        ')' => ["#..", ".#.", ".#.", ".#.", "#.."],
+        '[' => [".##", ".#.", ".#.", ".#.", ".##"],
+        ']' => ["##.", ".#.", ".#.", ".#.", "##."],
+        _ => return None,
+    })
+}
@@ -0,0 +1,547 @@
+//! 2D pose-graph SLAM back-end.
+//!
+//! Nodes are robot poses and edges are relative transforms between two poses,
+//! measured by odometry or scan matching, weighted by an information matrix
+//! ordered (x, y, theta). The graph is optimized with Gauss-Newton or
+//! Levenberg-Marquardt over a sparse linear system, and robust kernels limit
+//! the influence of bad loop closures.
+//!
+//! Graphs can be read from and written to the g2o 2D text format
+//! (`VERTEX_SE2`, `EDGE_SE2` and `FIX` lines).
+use crate::linalg::{self, BlockSparseMatrix, Matrix3};
+use crate::rigid2d::{Pose2D, Transform2D, Vector2D};
+use crate::scan_matching::ScanMatch;
+use crate::utils::normalize_angle;
+use anyhow::{anyhow, bail};
+use num_traits::Float;
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+
+/// A relative transform measured between two nodes
+#[derive(Debug, Clone, Copy)]
+pub struct Edge<T: Float> {
+    /// index of the node the measurement is taken from
+    pub from: usize,
+
+    /// index of the node the measurement is taken to
+    pub to: usize,
+
+    /// pose of the `to` node in the frame of the `from` node
+    pub measurement: Transform2D<T>,
+
+    /// information (inverse covariance) matrix of the measurement
+    pub information: Matrix3<T>,
+}
+
+/// Robust kernel applied to the squared error of each edge
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RobustKernel<T: Float> {
+    /// plain least squares
+    None,
+
+    /// quadratic below delta and linear above
+    Huber(T),
+
+    /// logarithmic growth with scale c
+    Cauchy(T),
+}
+
+impl<T: Float> RobustKernel<T> {
+    /// returns the weight applied to an edge with the given squared error
+    pub fn weight(&self, chi2: T) -> T {
+        match *self {
+            RobustKernel::None => T::one(),
+            RobustKernel::Huber(delta) => {
+                let e = chi2.sqrt();
+                if e <= delta {
+                    T::one()
+                } else {
+                    delta / e
+                }
+            }
+            RobustKernel::Cauchy(c) => T::one() / (T::one() + chi2 / (c * c)),
+        }
+    }
+
+    /// returns the robust cost of an edge with the given squared error
+    pub fn cost(&self, chi2: T) -> T {
+        let two = T::from(2.0).unwrap();
+        match *self {
+            RobustKernel::None => chi2,
+            RobustKernel::Huber(delta) => {
+                let e = chi2.sqrt();
+                if e <= delta {
+                    chi2
+                } else {
+                    two * delta * e - delta * delta
+                }
+            }
+            RobustKernel::Cauchy(c) => c * c * (T::one() + chi2 / (c * c)).ln(),
+        }
+    }
+}
+
+/// Nonlinear least squares algorithm
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Solver {
+    GaussNewton,
+    LevenbergMarquardt,
+}
+
+/// Parameters of the pose-graph optimizer
+#[derive(Debug, Clone, Copy)]
+pub struct OptimizerConfig<T: Float> {
+    /// nonlinear least squares algorithm
+    pub solver: Solver,
+
+    /// robust kernel applied to every edge
+    pub kernel: RobustKernel<T>,
+
+    /// maximum number of outer iterations
+    pub max_iterations: usize,
+
+    /// optimization stops once the relative decrease of the cost is below this
+    pub tolerance: T,
+}
+
+impl<T: Float> Default for OptimizerConfig<T> {
+    fn default() -> Self {
+        OptimizerConfig {
+            solver: Solver::LevenbergMarquardt,
+            kernel: RobustKernel::None,
+            max_iterations: 50,
+            tolerance: T::from(1e-9).unwrap(),
+        }
+    }
+}
+
+/// Summary of an optimization run
+#[derive(Debug, Clone, Copy)]
+pub struct OptimizationSummary<T: Float> {
+    /// robust cost before optimizing
+    pub initial_cost: T,
+
+    /// robust cost after optimizing
+    pub final_cost: T,
+
+    /// number of iterations used
+    pub iterations: usize,
+
+    /// true if the relative cost decrease fell below the tolerance
+    pub converged: bool,
+
+    /// true if no step could lower the cost before the tolerance was reached
+    pub stalled: bool,
+}
+
+/// A graph of 2D poses connected by relative measurements
+#[derive(Debug, Clone, Default)]
+pub struct PoseGraph<T: Float> {
+    nodes: Vec<Pose2D<T>>,
+
+    /// id of every node, used when reading and writing g2o files
+    ids: Vec<usize>,
+
+    /// nodes held constant during optimization
+    fixed: Vec<bool>,
+
+    edges: Vec<Edge<T>>,
+}
+
+impl<T: Float> PoseGraph<T> {
+    /// constructs an empty pose graph
+    pub fn new() -> Self {
+        PoseGraph {
+            nodes: vec![],
+            ids: vec![],
+            fixed: vec![],
+            edges: vec![],
+        }
+    }
+
+    /// adds a node at the given initial pose and returns its index. The first
+    /// node is fixed so that the solution is anchored in the world frame.
+    pub fn add_node(&mut self, pose: Pose2D<T>) -> usize {
+        let index = self.nodes.len();
+        self.nodes.push(pose);
+        self.ids.push(index);
+        self.fixed.push(index == 0);
+        index
+    }
+
+    /// holds a node constant (or releases it) during optimization
+    pub fn set_fixed(&mut self, node: usize, fixed: bool) {
+        self.fixed[node] = fixed;
+    }
+
+    /// adds a relative measurement of node `to` in the frame of node `from`
+    pub fn add_edge(
+        &mut self,
+        from: usize,
+        to: usize,
+        measurement: Transform2D<T>,
+        information: Matrix3<T>,
+    ) -> anyhow::Result<()> {
+        if from >= self.nodes.len() || to >= self.nodes.len() {
+            bail!(
+                "edge {} -> {} refers to a node that does not exist",
+                from,
+                to
+            );
+        }
+        self.edges.push(Edge {
+            from,
+            to,
+            measurement,
+            information,
+        });
+        Ok(())
+    }
+
+    /// adds the result of matching the scan taken at node `to` against the
+    /// scan taken at node `from`, weighted by the inverse of its covariance
+    pub fn add_scan_match(
+        &mut self,
+        from: usize,
+        to: usize,
+        scan_match: &ScanMatch<T>,
+    ) -> anyhow::Result<()> {
+        let information = linalg::inverse3(&scan_match.covariance)
+            .ok_or_else(|| anyhow!("scan match covariance is singular"))?;
+        self.add_edge(from, to, scan_match.transform, information)
+    }
+
+    /// returns the poses of all nodes
+    pub fn nodes(&self) -> &[Pose2D<T>] {
+        &self.nodes
+    }
+
+    /// returns all edges
+    pub fn edges(&self) -> &[Edge<T>] {
+        &self.edges
+    }
+
+    /// computes the error of an edge at the current node poses
+    pub fn edge_error(&self, edge: &Edge<T>) -> [T; 3] {
+        let xi = Transform2D::from_pose(self.nodes[edge.from]);
+        let xj = Transform2D::from_pose(self.nodes[edge.to]);
+        let e = edge.measurement.inv() * (xi.inv() * xj);
+        [
+            e.translation().x,
+            e.translation().y,
+            normalize_angle(e.rotation()),
+        ]
+    }
+
+    /// computes the total robust cost of the graph
+    pub fn cost(&self, kernel: RobustKernel<T>) -> T {
+        self.edges.iter().fold(T::zero(), |acc, edge| {
+            acc + kernel.cost(quadratic_form(&self.edge_error(edge), &edge.information))
+        })
+    }
+
+    /// optimizes the node poses to best agree with the edges
+    pub fn optimize(
+        &mut self,
+        config: &OptimizerConfig<T>,
+    ) -> anyhow::Result<OptimizationSummary<T>> {
+        if self.nodes.is_empty() {
+            bail!("can not optimize an empty pose graph");
+        }
+
+        let initial_cost = self.cost(config.kernel);
+        let mut cost = initial_cost;
+        let mut lambda = T::from(1e-4).unwrap();
+        let mut iterations = 0;
+        let mut converged = false;
+        let mut stalled = false;
+
+        while iterations < config.max_iterations {
+            iterations += 1;
+            let (h, b) = self.linearize(config.kernel);
+
+            let (candidate, candidate_cost) = loop {
+                let mut damped = h.clone();
+                if config.solver == Solver::LevenbergMarquardt {
+                    for i in 0..damped.block_rows() {
+                        let mut diag = linalg::zeros3();
+                        if let Some(block) = damped.block(i, i) {
+                            for a in 0..3 {
+                                diag[a][a] = lambda * block[a][a].max(T::from(1e-9).unwrap());
+                            }
+                        }
+                        damped.add_block(i, i, &diag);
+                    }
+                }
+                let dx =
+                    damped.solve_cg(&b, &self.fixed, T::from(1e-12).unwrap(), 10 * b.len() + 100);
+
+                let mut candidate = self.clone();
+                candidate.apply_update(&dx);
+                let candidate_cost = candidate.cost(config.kernel);
+                if config.solver == Solver::GaussNewton
+                    || candidate_cost <= cost
+                    || lambda > T::from(1e10).unwrap()
+                {
+                    break (candidate, candidate_cost);
+                }
+                lambda = lambda * T::from(10.0).unwrap();
+            };
+
+            if config.solver == Solver::LevenbergMarquardt {
+                if candidate_cost > cost {
+                    stalled = true;
+                    break;
+                }
+                lambda = (lambda / T::from(10.0).unwrap()).max(T::from(1e-12).unwrap());
+            }
+
+            let decrease = cost - candidate_cost;
+            *self = candidate;
+            let previous = cost;
+            cost = candidate_cost;
+            if decrease.abs() <= config.tolerance * previous.max(T::epsilon()) {
+                converged = true;
+                break;
+            }
+        }
+
+        Ok(OptimizationSummary {
+            initial_cost,
+            final_cost: cost,
+            iterations,
+            converged,
+            stalled,
+        })
+    }
+
+    /// builds the normal equations H dx = b of the weighted least squares problem
+    fn linearize(&self, kernel: RobustKernel<T>) -> (BlockSparseMatrix<T>, Vec<T>) {
+        let n = self.nodes.len();
+        let mut h = BlockSparseMatrix::new(n);
+        let mut b = vec![T::zero(); 3 * n];
+
+        for edge in &self.edges {
+            let e = self.edge_error(edge);
+            let w = kernel.weight(quadratic_form(&e, &edge.information));
+            let omega = edge.information.map(|row| row.map(|v| v * w));
+            let (a, bj) = self.jacobians(edge);
+
+            let at = linalg::transpose3(&a);
+            let bt = linalg::transpose3(&bj);
+            let at_omega = linalg::mul3(&at, &omega);
+            let bt_omega = linalg::mul3(&bt, &omega);
+
+            h.add_block(edge.from, edge.from, &linalg::mul3(&at_omega, &a));
+            h.add_block(edge.from, edge.to, &linalg::mul3(&at_omega, &bj));
+            h.add_block(edge.to, edge.from, &linalg::mul3(&bt_omega, &a));
+            h.add_block(edge.to, edge.to, &linalg::mul3(&bt_omega, &bj));
+
+            for r in 0..3 {
+                for c in 0..3 {
+                    b[3 * edge.from + r] = b[3 * edge.from + r] - at_omega[r][c] * e[c];
+                    b[3 * edge.to + r] = b[3 * edge.to + r] - bt_omega[r][c] * e[c];
+                }
+            }
+        }
+        (h, b)
+    }
+
+    /// Jacobians of the edge error with respect to the from and to poses
+    fn jacobians(&self, edge: &Edge<T>) -> (Matrix3<T>, Matrix3<T>) {
+        let xi = self.nodes[edge.from];
+        let xj = self.nodes[edge.to];
+        let (si, ci) = (xi.theta.sin(), xi.theta.cos());
+        let tz = edge.measurement.rotation();
+        let (sz, cz) = (tz.sin(), tz.cos());
+        let (dx, dy) = (xj.x - xi.x, xj.y - xi.y);
+
+        // R_z^T R_i^T
+        let rzt_rit = [
+            [cz * ci - sz * si, cz * si + sz * ci],
+            [-sz * ci - cz * si, -sz * si + cz * ci],
+        ];
+        // R_z^T dR_i^T/dtheta (t_j - t_i)
+        let d = [-si * dx + ci * dy, -ci * dx - si * dy];
+        let dtheta = [cz * d[0] + sz * d[1], -sz * d[0] + cz * d[1]];
+
+        let zero = T::zero();
+        let one = T::one();
+        let a = [
+            [-rzt_rit[0][0], -rzt_rit[0][1], dtheta[0]],
+            [-rzt_rit[1][0], -rzt_rit[1][1], dtheta[1]],
+            [zero, zero, -one],
+        ];
+        let b = [
+            [rzt_rit[0][0], rzt_rit[0][1], zero],
+            [rzt_rit[1][0], rzt_rit[1][1], zero],
+            [zero, zero, one],
+        ];
+        (a, b)
+    }
+
+    fn apply_update(&mut self, dx: &[T]) {
+        for (i, pose) in self.nodes.iter_mut().enumerate() {
+            if self.fixed[i] {
+                continue;
+            }
+            pose.x = pose.x + dx[3 * i];
+            pose.y = pose.y + dx[3 * i + 1];
+            pose.theta = normalize_angle(pose.theta + dx[3 * i + 2]);
+        }
+    }
+
+    /// reads a graph in the g2o 2D text format
+    pub fn from_g2o<R: BufRead>(reader: R) -> anyhow::Result<Self> {
+        let mut graph = PoseGraph::new();
+        let mut index: HashMap<usize, usize> = HashMap::new();
+        let mut fixed = vec![];
+        let mut edges = vec![];
+
+        for (line_number, line) in reader.lines().enumerate() {
+            let line = line?;
+            let mut tokens = line.split_whitespace();
+            let tag = match tokens.next() {
+                Some(tag) if !tag.starts_with('#') => tag,
+                _ => continue,
+            };
+            let tokens: Vec<&str> = tokens.collect();
+            let expect = |n: usize| -> anyhow::Result<()> {
+                if tokens.len() != n {
+                    bail!("line {}: {} expects {} values", line_number + 1, tag, n);
+                }
+                Ok(())
+            };
+            let id = |token: &str| {
+                token
+                    .parse::<usize>()
+                    .map_err(|_| anyhow!("line {}: invalid vertex id {}", line_number + 1, token))
+            };
+            let values = |tokens: &[&str]| -> anyhow::Result<Vec<T>> {
+                tokens
+                    .iter()
+                    .map(|token| {
+                        token
+                            .parse::<f64>()
+                            .map(|v| T::from(v).unwrap())
+                            .map_err(|e| anyhow!("line {}: {}", line_number + 1, e))
+                    })
+                    .collect()
+            };
+
+            match tag {
+                "VERTEX_SE2" => {
+                    expect(4)?;
+                    let id = id(tokens[0])?;
+                    if index.contains_key(&id) {
+                        bail!("line {}: duplicate vertex {}", line_number + 1, id);
+                    }
+                    let v = values(&tokens[1..])?;
+                    let i = graph.add_node(Pose2D::new(v[0], v[1], v[2]));
+                    graph.ids[i] = id;
+                    graph.fixed[i] = false;
+                    index.insert(id, i);
+                }
+                "EDGE_SE2" => {
+                    expect(11)?;
+                    let (from, to) = (id(tokens[0])?, id(tokens[1])?);
+                    let v = values(&tokens[2..])?;
+                    let information = [[v[3], v[4], v[5]], [v[4], v[6], v[7]], [v[5], v[7], v[8]]];
+                    let measurement = Transform2D::new(Vector2D::new(v[0], v[1]), v[2]);
+                    edges.push((from, to, measurement, information));
+                }
+                "FIX" => {
+                    for token in &tokens {
+                        fixed.push(id(token)?);
+                    }
+                }
+                _ => bail!("line {}: unsupported g2o element {}", line_number + 1, tag),
+            }
+        }
+
+        let lookup = |id: usize| {
+            index
+                .get(&id)
+                .copied()
+                .ok_or_else(|| anyhow!("edge refers to unknown vertex {}", id))
+        };
+        for (from, to, measurement, information) in edges {
+            graph.add_edge(lookup(from)?, lookup(to)?, measurement, information)?;
+        }
+        if fixed.is_empty() && !graph.nodes.is_empty() {
+            graph.fixed[0] = true;
+        }
+        for id in fixed {
+            let i = lookup(id)?;
+            graph.fixed[i] = true;
+        }
+        Ok(graph)
+    }
+
+    /// reads a graph from a g2o file
+    pub fn read_g2o(filename: &str) -> anyhow::Result<Self> {
+        PoseGraph::from_g2o(BufReader::new(File::open(filename)?))
+    }
+
+    /// writes the graph in the g2o 2D text format
+    pub fn to_g2o<W: Write>(&self, mut writer: W) -> anyhow::Result<()>
+    where
+        T: Display,
+    {
+        for (i, pose) in self.nodes.iter().enumerate() {
+            writeln!(
+                writer,
+                "VERTEX_SE2 {} {} {} {}",
+                self.ids[i], pose.x, pose.y, pose.theta
+            )?;
+        }
+        for edge in &self.edges {
+            let (m, info) = (edge.measurement, edge.information);
+            writeln!(
+                writer,
+                "EDGE_SE2 {} {} {} {} {} {} {} {} {} {} {}",
+                self.ids[edge.from],
+                self.ids[edge.to],
+                m.translation().x,
+                m.translation().y,
+                m.rotation(),
+                info[0][0],
+                info[0][1],
+                info[0][2],
+                info[1][1],
+                info[1][2],
+                info[2][2]
+            )?;
+        }
+        for (i, fixed) in self.fixed.iter().enumerate() {
+            if *fixed {
+                writeln!(writer, "FIX {}", self.ids[i])?;
+            }
+        }
+        Ok(())
+    }
+
+    /// writes the graph to a g2o file
+    pub fn write_g2o(&self, filename: &str) -> anyhow::Result<()>
+    where
+        T: Display,
+    {
+        let mut writer = BufWriter::new(File::create(filename)?);
+        self.to_g2o(&mut writer)?;
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+/// computes e^T omega e
+fn quadratic_form<T: Float>(e: &[T; 3], omega: &Matrix3<T>) -> T {
+    let mut sum = T::zero();
+    for a in 0..3 {
+        for b in 0..3 {
+            sum = sum + e[a] * omega[a][b] * e[b];
+        }
+    }
+    sum
+}
@@ -1,12 +1,16 @@
+use crate::scalar::Scalar;
 /// rigid2D: 2D rigid body motion library
 use crate::utils::{almost_equal, rad2deg};
-use num_traits::Float;
-use std::fmt::Display;
-use std::ops;
+use core::fmt::Display;
+use core::ops;
+
+#[cfg(feature = "alloc")]
+use alloc::{vec, vec::Vec};
 
 /// a 2-dimensional vector
 #[derive(Debug, Clone, Copy)]
-pub struct Vector2D<T: Float> {
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Vector2D<T: Scalar> {
     /// x coordinate
     pub x: T,
 
@@ -15,7 +19,7 @@ pub struct Vector2D<T: Float> {
 }
 
 /// Implements the operation: Vector2D + Vector2D
-impl<T: Float> ops::Add<Vector2D<T>> for Vector2D<T> {
+impl<T: Scalar> ops::Add<Vector2D<T>> for Vector2D<T> {
     type Output = Vector2D<T>;
     fn add(self, _rhs: Vector2D<T>) -> Vector2D<T> {
         Vector2D::new(self.x + _rhs.x, self.y + _rhs.y)
@@ -23,13 +27,13 @@ impl<T: Float> ops::Add<Vector2D<T>> for Vector2D<T> {
 }
 
 /// Implements the Display trait for Vector2D
-impl<T: Float + Display> Display for Vector2D<T> {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl<T: Scalar + Display> Display for Vector2D<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         write!(f, "[{}, {}]", self.x, self.y)
     }
 }
 
-impl<T: Float> Vector2D<T> {
+impl<T: Scalar> Vector2D<T> {
     /// constructs a Vector2D from floats (x,y)
     pub fn new(x: T, y: T) -> Self {
         Vector2D { x, y }
@@ -45,9 +49,7 @@ impl<T: Float> Vector2D<T> {
 
     /// computes the L2 norm of the 2D vector
     pub fn magnitude(&self) -> T {
-        let x_sqr = self.x.powf(T::from(2.0).unwrap());
-        let y_sqr = self.y.powf(T::from(2.0).unwrap());
-        (x_sqr + y_sqr).sqrt()
+        (self.x * self.x + self.y * self.y).sqrt()
     }
 
     /// computes the dot product of the vector with another vector
@@ -59,37 +61,35 @@ impl<T: Float> Vector2D<T> {
     pub fn angle(&self, rhs: &Vector2D<T>) -> T {
         let dot_prod = self.dot(rhs);
         let prod_of_msg = self.magnitude() * rhs.magnitude();
-        return (dot_prod / prod_of_msg).acos();
+        (dot_prod / prod_of_msg).acos()
     }
 
     /// normalizes the vector
     pub fn normalize(&self) -> Self {
-        let v_norm: Self;
         if self.x.is_zero() || self.y.is_zero() {
-            v_norm = Vector2D::new(T::zero(), T::zero());
+            Vector2D::new(T::zero(), T::zero())
         } else {
             let mag = self.magnitude();
-            v_norm = Vector2D::new(self.x / mag, self.y / mag);
+            Vector2D::new(self.x / mag, self.y / mag)
         }
-        return v_norm;
     }
 
     pub fn distance(&self, p2: Vector2D<T>) -> T {
-        T::sqrt(
-            T::from(p2.x - self.x).unwrap().powf(T::from(2.0).unwrap())
-                + T::from(p2.y - self.y).unwrap().powf(T::from(2.0).unwrap()),
-        )
+        let (dx, dy) = (p2.x - self.x, p2.y - self.y);
+        T::sqrt(dx * dx + dy * dy)
     }
 
     /// returns the Vector2D<T> as a Vec<T>
+    #[cfg(feature = "alloc")]
     pub fn to_vec(&self) -> Vec<T> {
         vec![self.x, self.y]
     }
 }
 
 /// A 2-dimensional pose
-#[derive(Debug, Clone, Copy, Default)]
-pub struct Pose2D<T: Float> {
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Pose2D<T: Scalar> {
     /// x position
     pub x: T,
 
@@ -100,7 +100,7 @@ pub struct Pose2D<T: Float> {
     pub theta: T,
 }
 
-impl<T: Float> Pose2D<T> {
+impl<T: Scalar> Pose2D<T> {
     /// constructs a new Pose2D object from (x,y,theta) which are floats
     pub fn new(x: T, y: T, theta: T) -> Self {
         Pose2D { x, y, theta }
@@ -108,15 +108,16 @@ impl<T: Float> Pose2D<T> {
 }
 
 /// Implments the Display trait
-impl<T: Float + Display> Display for Pose2D<T> {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl<T: Scalar + Display> Display for Pose2D<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         write!(f, "deg:{} x:{} y:{}", rad2deg(self.theta), self.x, self.y)
     }
 }
 
 /// A 2-dimensional Twist
-#[derive(Debug)]
-pub struct Twist2D<T: Float> {
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Twist2D<T: Scalar> {
     /// angular velocity
     pub thetadot: T,
 
@@ -127,7 +128,7 @@ pub struct Twist2D<T: Float> {
     pub ydot: T,
 }
 
-impl<T: Float> Twist2D<T> {
+impl<T: Scalar> Twist2D<T> {
     /// constructs as new Twist2D object from theta, x, and y velocities
     pub fn new(thetadot: T, xdot: T, ydot: T) -> Self {
         Twist2D {
@@ -139,23 +140,26 @@ impl<T: Float> Twist2D<T> {
 }
 
 /// Implements the Display trait
-impl<T: Float + Display> Display for Twist2D<T> {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl<T: Scalar + Display> Display for Twist2D<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         write!(f, "[{}, {}, {}]", self.thetadot, self.xdot, self.ydot)
     }
 }
 
 /// A 2-dimensional rigid body transformation
 #[derive(Debug, Clone, Copy)]
-pub struct Transform2D<T: Float> {
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Transform2D<T: Scalar> {
     /// translational component of the transform
+    #[cfg_attr(feature = "serde", serde(rename = "translation"))]
     p_vec: Vector2D<T>,
 
     /// rotational component of the transform in radians
+    #[cfg_attr(feature = "serde", serde(rename = "rotation"))]
     angle: T,
 }
 
-impl<T: Float> Transform2D<T> {
+impl<T: Scalar> Transform2D<T> {
     /// contructs a new Transform2D from a translation and rotation
     pub fn new(p_vec: Vector2D<T>, angle: T) -> Self {
         Transform2D { p_vec, angle }
@@ -170,33 +174,87 @@ impl<T: Float> Transform2D<T> {
     pub fn translation(&self) -> Vector2D<T> {
         self.p_vec
     }
+
+    /// constructs the transform from the world frame to a body at the given pose
+    pub fn from_pose(pose: Pose2D<T>) -> Self {
+        Transform2D::new(Vector2D::new(pose.x, pose.y), pose.theta)
+    }
+
+    /// returns the pose of a body whose frame is given by this transform
+    pub fn to_pose(&self) -> Pose2D<T> {
+        Pose2D::new(self.p_vec.x, self.p_vec.y, self.angle)
+    }
+
+    /// computes the inverse of the transform
+    pub fn inv(&self) -> Self {
+        let (s, c) = (self.angle.sin(), self.angle.cos());
+        Transform2D {
+            p_vec: Vector2D::new(
+                -(self.p_vec.x * c + self.p_vec.y * s),
+                self.p_vec.x * s - self.p_vec.y * c,
+            ),
+            angle: -self.angle,
+        }
+    }
+
+    /// applies the transform to a point
+    pub fn apply(&self, v: Vector2D<T>) -> Vector2D<T> {
+        let (s, c) = (self.angle.sin(), self.angle.cos());
+        Vector2D::new(
+            self.p_vec.x + v.x * c - v.y * s,
+            self.p_vec.y + v.x * s + v.y * c,
+        )
+    }
     /// computes the transform cooresponding to a rigid body
     /// following a constant twist in its original body frame for
     /// one time unit
     pub fn integrate_twist(&self, v: Twist2D<T>) -> Self {
-        if almost_equal(v.thetadot, T::from(0.0).unwrap(), T::from(0.0001).unwrap()) {
+        if almost_equal(v.thetadot, T::zero(), T::from_ratio(1, 10_000)) {
             let p = Vector2D::new(v.xdot, v.ydot);
             Transform2D {
                 p_vec: p,
-                angle: T::from(0.0).unwrap(),
+                angle: T::zero(),
             }
         } else {
+            // x = (xdot sin(a) + ydot (cos(a) - 1)) / a
+            // y = (xdot (1 - cos(a)) + ydot sin(a)) / a
             let angle = v.thetadot;
-            let mut vec = Vector2D::new(T::from(0.0).unwrap(), T::from(0.0).unwrap());
-            vec.x = (T::from(1.0).unwrap() / angle)
-                * (-v.ydot + v.xdot * T::sin(angle) + v.ydot * T::cos(angle));
-            vec.y = (T::from(1.0).unwrap() / angle)
-                * (v.xdot - v.xdot * T::cos(angle) + v.ydot * T::sin(angle));
+            let (s, c) = (sin_over(angle), one_minus_cos_over(angle));
+            let vec = Vector2D::new(v.xdot * s - v.ydot * c, v.xdot * c + v.ydot * s);
             Transform2D { p_vec: vec, angle }
         }
     }
 }
 
-impl<T: Float> ops::Mul<Transform2D<T>> for Transform2D<T> {
+/// computes sin(x) / x, from its series for small x where dividing would
+/// lose the precision of a fixed-point scalar
+fn sin_over<T: Scalar>(x: T) -> T {
+    if x.abs() > T::from_ratio(1, 2) {
+        return x.sin() / x;
+    }
+    let x2 = x * x;
+    [13 * 12, 11 * 10, 9 * 8, 7 * 6, 5 * 4, 3 * 2]
+        .iter()
+        .fold(T::one(), |acc, d| T::one() - x2 * acc / T::from_int(*d))
+}
+
+/// computes (1 - cos(x)) / x, from its series for small x
+fn one_minus_cos_over<T: Scalar>(x: T) -> T {
+    if x.abs() > T::from_ratio(1, 2) {
+        return (T::one() - x.cos()) / x;
+    }
+    let x2 = x * x;
+    let series = [14 * 13, 12 * 11, 10 * 9, 8 * 7, 6 * 5, 4 * 3]
+        .iter()
+        .fold(T::one(), |acc, d| T::one() - x2 * acc / T::from_int(*d));
+    x * series / T::from_int(2)
+}
+
+impl<T: Scalar> ops::Mul<Transform2D<T>> for Transform2D<T> {
     type Output = Transform2D<T>;
 
     fn mul(self, rhs: Transform2D<T>) -> Transform2D<T> {
-        let mut p_new = Vector2D::new(T::from(0.0).unwrap(), T::from(0.0).unwrap());
+        let mut p_new = Vector2D::new(T::zero(), T::zero());
         p_new.x =
             self.p_vec.x + rhs.p_vec.x * T::cos(self.angle) - rhs.p_vec.y * T::sin(self.angle);
 
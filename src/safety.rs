@@ -0,0 +1,311 @@
+//! Safety supervision of velocity commands.
+//!
+//! `SafetySupervisor` keeps the latest obstacle points around the robot,
+//! from a range scan or any other sensor, and checks every command against
+//! them before it is sent. The robot footprint is projected along the
+//! commanded `Twist2D` with `Transform2D::integrate_twist`, and the first
+//! time it comes within a margin of an obstacle is the time to collision.
+//! A command that would collide within the protective horizon is replaced
+//! by a stop, and one that would collide within the warning horizon is
+//! slowed down. The zones are swept by the footprint at the commanded
+//! speed, so they grow with speed, and both horizons also grow by the time
+//! the robot needs to brake.
+//!
+//! Obstacles that the footprint moves away from are ignored, so that a
+//! robot stopped next to an obstacle can still back away from it. Inside
+//! the footprint the distance to its outline counts as negative, so an
+//! obstacle on the bumper stops every command that pushes into it.
+//! Commands that are not finite are replaced by a stop.
+use crate::geometry::Polygon;
+use crate::rigid2d::{Transform2D, Twist2D, Vector2D};
+use crate::scan::LaserScan;
+use num_traits::Float;
+use std::fmt;
+
+/// Zone an obstacle was found in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Zone {
+    /// no obstacle within the warning horizon
+    Clear,
+
+    /// an obstacle within the warning horizon, the command is slowed down
+    Warning,
+
+    /// an obstacle within the protective horizon, the robot is stopped
+    Protective,
+}
+
+/// Outcome of checking a command, with the reason it was limited
+#[derive(Debug, Clone, Copy)]
+pub struct SafetyReport<T: Float> {
+    /// command as requested
+    pub requested: Twist2D<T>,
+
+    /// command that may be sent
+    pub command: Twist2D<T>,
+
+    /// factor the requested command was scaled by
+    pub scale: T,
+    pub zone: Zone,
+
+    /// the obstacle point that limited the command, in the robot frame
+    pub obstacle: Option<Vector2D<T>>,
+
+    /// time in seconds until the footprint would reach the obstacle at the
+    /// requested speed
+    pub time_to_collision: Option<T>,
+}
+
+impl<T: Float> SafetyReport<T> {
+    /// returns true if the command was slowed down or stopped
+    pub fn is_limited(&self) -> bool {
+        self.zone != Zone::Clear
+    }
+}
+
+impl<T: Float + fmt::Display> fmt::Display for SafetyReport<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (obstacle, time) = match (self.obstacle, self.time_to_collision) {
+            (Some(obstacle), Some(time)) => (obstacle, time),
+            _ if self.zone == Zone::Protective => {
+                return write!(f, "stopped, the command is invalid")
+            }
+            _ => return write!(f, "clear"),
+        };
+        let hundred = T::from(100.0).unwrap();
+        match self.zone {
+            Zone::Clear => write!(f, "clear"),
+            Zone::Warning => write!(
+                f,
+                "slowed to {:.0}% in the warning zone, obstacle at ({:.2}, {:.2}) {:.2} s ahead",
+                self.scale * hundred,
+                obstacle.x,
+                obstacle.y,
+                time
+            ),
+            Zone::Protective => write!(
+                f,
+                "stopped in the protective zone, obstacle at ({:.2}, {:.2}) {:.2} s ahead",
+                obstacle.x, obstacle.y, time
+            ),
+        }
+    }
+}
+
+/// Checks commands against the latest obstacles
+#[derive(Debug, Clone)]
+pub struct SafetySupervisor<T: Float> {
+    /// robot outline in the robot frame
+    footprint: Polygon<T>,
+
+    /// latest obstacle points in the robot frame
+    obstacles: Vec<Vector2D<T>>,
+
+    /// distance from the footprint that counts as a collision
+    margin: T,
+
+    /// time to collision at which the robot is stopped, before braking time
+    protective_time: T,
+
+    /// time to collision at which the robot is slowed down, before braking
+    /// time
+    warning_time: T,
+
+    /// scale at the inner edge of the warning zone
+    min_warning_scale: T,
+
+    /// deceleration the robot can brake with
+    deceleration: T,
+
+    /// largest distance a footprint point moves between checks
+    resolution: T,
+}
+
+impl<T: Float> SafetySupervisor<T> {
+    /// Constructs a supervisor for a robot with the given footprint, with a
+    /// 5 cm margin, a protective horizon of 0.5 s and a warning horizon of
+    /// 1.5 s in which speed is scaled down to 20 %
+    pub fn new(footprint: Polygon<T>) -> Self {
+        SafetySupervisor {
+            footprint,
+            obstacles: vec![],
+            margin: T::from(0.05).unwrap(),
+            protective_time: T::from(0.5).unwrap(),
+            warning_time: T::from(1.5).unwrap(),
+            min_warning_scale: T::from(0.2).unwrap(),
+            deceleration: T::infinity(),
+            resolution: T::from(0.02).unwrap(),
+        }
+    }
+
+    /// sets the distance from the footprint that counts as a collision
+    pub fn with_margin(mut self, margin: T) -> Self {
+        self.margin = margin;
+        self
+    }
+
+    /// sets the time to collision in seconds below which the robot stops
+    pub fn with_protective_time(mut self, seconds: T) -> Self {
+        self.protective_time = seconds;
+        self
+    }
+
+    /// sets the time to collision in seconds below which the robot slows
+    /// down, and the scale at the inner edge of the warning zone. The scale
+    /// rises linearly to one at the outer edge.
+    pub fn with_warning_zone(mut self, seconds: T, min_scale: T) -> Self {
+        self.warning_time = seconds;
+        self.min_warning_scale = min_scale;
+        self
+    }
+
+    /// sets the deceleration in m/s^2 the robot brakes with, which extends
+    /// both horizons by the time needed to stop
+    pub fn with_deceleration(mut self, deceleration: T) -> Self {
+        self.deceleration = deceleration;
+        self
+    }
+
+    /// sets the largest distance in meters a footprint point moves between
+    /// collision checks, 2 cm by default
+    pub fn with_resolution(mut self, resolution: T) -> Self {
+        self.resolution = resolution;
+        self
+    }
+
+    /// returns the footprint in the robot frame
+    pub fn footprint(&self) -> &Polygon<T> {
+        &self.footprint
+    }
+
+    /// returns the latest obstacle points in the robot frame
+    pub fn obstacles(&self) -> &[Vector2D<T>] {
+        &self.obstacles
+    }
+
+    /// replaces the obstacles with points in the robot frame
+    pub fn set_obstacles(&mut self, points: Vec<Vector2D<T>>) {
+        self.obstacles = points;
+    }
+
+    /// replaces the obstacles with the returns of a scan from a sensor
+    /// mounted at the given transform from the robot frame
+    pub fn set_scan(&mut self, scan: &LaserScan<T>, sensor: Transform2D<T>) {
+        self.obstacles = scan
+            .to_points()
+            .into_iter()
+            .map(|p| sensor.apply(p))
+            .collect();
+    }
+
+    /// Returns the first time within the horizon at which the footprint,
+    /// moving with the twist, comes within the margin of an obstacle it is
+    /// approaching, together with that obstacle
+    pub fn time_to_collision(&self, twist: Twist2D<T>, horizon: T) -> Option<(T, Vector2D<T>)> {
+        let speed = twist.xdot.hypot(twist.ydot);
+        let radius = self.footprint.bounding_radius();
+        let reach = radius + self.margin + speed * horizon;
+        let nearby: Vec<Vector2D<T>> = self
+            .obstacles
+            .iter()
+            .copied()
+            .filter(|p| p.x.hypot(p.y) <= reach)
+            .collect();
+        if nearby.is_empty() {
+            return None;
+        }
+
+        let travel = (speed * horizon).max(twist.thetadot.abs() * horizon * radius);
+        let steps = (travel / self.resolution)
+            .ceil()
+            .to_usize()
+            .unwrap_or(1)
+            .clamp(1, 10_000);
+        let mut previous: Vec<T> = nearby.iter().map(|p| self.clearance(*p)).collect();
+        let origin = Transform2D::new(Vector2D::new(T::zero(), T::zero()), T::zero());
+        let mut before = T::zero();
+        for k in 1..=steps {
+            let t = horizon * T::from(k).unwrap() / T::from(steps).unwrap();
+            let moved = Twist2D::new(twist.thetadot * t, twist.xdot * t, twist.ydot * t);
+            let to_body = origin.integrate_twist(moved).inv();
+            let mut hit: Option<(T, Vector2D<T>, T)> = None;
+            for (p, last) in nearby.iter().zip(previous.iter_mut()) {
+                let d = self.clearance(to_body.apply(*p));
+                if d <= self.margin && d < *last && hit.is_none_or(|(best, _, _)| d < best) {
+                    hit = Some((d, *p, *last));
+                }
+                *last = d;
+            }
+            if let Some((_, p, last)) = hit {
+                // an obstacle already within the margin is hit right away
+                return Some((if last <= self.margin { before } else { t }, p));
+            }
+            before = t;
+        }
+        None
+    }
+
+    /// distance from the footprint to a point, negative inside
+    fn clearance(&self, p: Vector2D<T>) -> T {
+        if self.footprint.contains(p) {
+            -self.footprint.boundary_distance(p)
+        } else {
+            self.footprint.boundary_distance(p)
+        }
+    }
+
+    /// checks a command against the obstacles and returns the command that
+    /// may be sent, with the reason it was limited
+    pub fn limit(&self, twist: Twist2D<T>) -> SafetyReport<T> {
+        let zero = Twist2D::new(T::zero(), T::zero(), T::zero());
+        if [twist.thetadot, twist.xdot, twist.ydot]
+            .iter()
+            .any(|v| !v.is_finite())
+        {
+            return SafetyReport {
+                requested: twist,
+                command: zero,
+                scale: T::zero(),
+                zone: Zone::Protective,
+                obstacle: None,
+                time_to_collision: None,
+            };
+        }
+        let braking = if self.deceleration.is_finite() && self.deceleration > T::zero() {
+            twist.xdot.hypot(twist.ydot) / self.deceleration
+        } else {
+            T::zero()
+        };
+        let protective = self.protective_time + braking;
+        let warning = self.warning_time.max(self.protective_time) + braking;
+
+        let mut report = SafetyReport {
+            requested: twist,
+            command: twist,
+            scale: T::one(),
+            zone: Zone::Clear,
+            obstacle: None,
+            time_to_collision: None,
+        };
+        let (time, obstacle) = match self.time_to_collision(twist, warning) {
+            Some(hit) => hit,
+            None => return report,
+        };
+        report.obstacle = Some(obstacle);
+        report.time_to_collision = Some(time);
+        if time <= protective {
+            report.zone = Zone::Protective;
+            report.scale = T::zero();
+        } else {
+            report.zone = Zone::Warning;
+            let fraction = (time - protective) / (warning - protective);
+            report.scale = self.min_warning_scale + (T::one() - self.min_warning_scale) * fraction;
+        }
+        report.command = Twist2D::new(
+            twist.thetadot * report.scale,
+            twist.xdot * report.scale,
+            twist.ydot * report.scale,
+        );
+        report
+    }
+}
@@ -0,0 +1,464 @@
+//! Scalar types for the core kinematics.
+//!
+//! `rigid2d`, `ddrive` and `utils` are generic over `Scalar` rather than
+//! `num_traits::Float`, so they also run on microcontrollers without an FPU.
+//! Every `Float` is a `Scalar`, and `Q16` is a Q16.16 fixed-point number
+//! whose sin, cos, atan2 and sqrt are computed with integer arithmetic.
+use core::cmp::Ordering;
+use core::fmt::{self, Display};
+use core::ops::{Add, Div, Mul, Neg, Rem, Sub};
+use num_traits::{Float, Num, NumCast, One, ToPrimitive, Zero};
+
+/// Number type the core kinematics are generic over
+pub trait Scalar: Num + NumCast + Copy + PartialOrd + Neg<Output = Self> {
+    /// computes the absolute value
+    fn abs(self) -> Self;
+
+    /// computes the square root
+    fn sqrt(self) -> Self;
+
+    /// computes the sine of an angle in radians
+    fn sin(self) -> Self;
+
+    /// computes the cosine of an angle in radians
+    fn cos(self) -> Self;
+
+    /// computes the four quadrant arctangent of self (y) and x in radians
+    fn atan2(self, x: Self) -> Self;
+
+    /// computes the arccosine in radians
+    fn acos(self) -> Self;
+
+    /// converts a small integer, such as a constant in a formula
+    fn from_int(value: i32) -> Self {
+        <Self as NumCast>::from(value).unwrap()
+    }
+
+    /// computes numerator / denominator, e.g. a tolerance, without going
+    /// through a float
+    fn from_ratio(numerator: i32, denominator: i32) -> Self {
+        Self::from_int(numerator) / Self::from_int(denominator)
+    }
+
+    /// returns pi
+    fn pi() -> Self {
+        <Self as NumCast>::from(core::f64::consts::PI).unwrap()
+    }
+
+    /// returns 2 pi
+    fn tau() -> Self {
+        <Self as NumCast>::from(core::f64::consts::TAU).unwrap()
+    }
+
+    /// adds, wrapping around at the ends of the range instead of saturating
+    fn wrapping_add(self, rhs: Self) -> Self {
+        self + rhs
+    }
+
+    /// subtracts, wrapping around at the ends of the range instead of saturating
+    fn wrapping_sub(self, rhs: Self) -> Self {
+        self - rhs
+    }
+
+    /// multiplies by an integer, wrapping around at the ends of the range
+    /// instead of saturating
+    fn wrapping_mul_int(self, n: i64) -> Self {
+        self * <Self as NumCast>::from(n).unwrap()
+    }
+}
+
+impl<T: Float> Scalar for T {
+    fn abs(self) -> Self {
+        Float::abs(self)
+    }
+
+    fn sqrt(self) -> Self {
+        Float::sqrt(self)
+    }
+
+    fn sin(self) -> Self {
+        Float::sin(self)
+    }
+
+    fn cos(self) -> Self {
+        Float::cos(self)
+    }
+
+    fn atan2(self, x: Self) -> Self {
+        Float::atan2(self, x)
+    }
+
+    fn acos(self) -> Self {
+        Float::acos(self)
+    }
+}
+
+/// A Q16.16 fixed-point number, i.e. a signed 32 bit integer counting units
+/// of 2^-16. The range is [-32768, 32768) with a resolution of about 1.5e-5.
+/// Arithmetic rounds to the nearest value and saturates instead of overflowing.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Q16(i32);
+
+const FRAC_BITS: u32 = 16;
+const ONE: i64 = 1 << FRAC_BITS;
+
+/// trigonometry is evaluated with 30 fractional bits in an i64
+const TRIG_BITS: u32 = 30;
+const TRIG_ONE: i64 = 1 << TRIG_BITS;
+const TRIG_PI: i64 = 3_373_259_426;
+const TRIG_HALF_PI: i64 = TRIG_PI / 2;
+
+/// pi and 2 pi with FRAC_BITS fractional bits
+const PI: i32 = 205_887;
+const TAU: i32 = 411_775;
+
+fn saturate(value: i64) -> Q16 {
+    Q16(value.clamp(i32::MIN as i64, i32::MAX as i64) as i32)
+}
+
+/// divides rounding to the nearest integer
+fn div_round(n: i64, d: i64) -> i64 {
+    if (n < 0) == (d < 0) {
+        (n + d / 2) / d
+    } else {
+        (n - d / 2) / d
+    }
+}
+
+fn trig_mul(a: i64, b: i64) -> i64 {
+    (a * b + (1 << (TRIG_BITS - 1))) >> TRIG_BITS
+}
+
+fn isqrt(n: u64) -> u64 {
+    if n < 2 {
+        return n;
+    }
+    let mut x = n;
+    let mut y = x.div_ceil(2);
+    while y < x {
+        x = y;
+        y = (x + n / x) / 2;
+    }
+    x
+}
+
+/// sine of an angle in [-pi/2, pi/2], both in TRIG_BITS fixed point
+fn trig_sin_reduced(x: i64) -> i64 {
+    // x (1 - x^2/6 (1 - x^2/20 (1 - x^2/42 (1 - x^2/72 (1 - x^2/110)))))
+    let x2 = trig_mul(x, x);
+    let mut term = TRIG_ONE;
+    for d in [110, 72, 42, 20, 6] {
+        term = TRIG_ONE - trig_mul(x2, term) / d;
+    }
+    trig_mul(x, term)
+}
+
+/// sine of any angle in TRIG_BITS fixed point
+fn trig_sin(x: i64) -> i64 {
+    let two_pi = 2 * TRIG_PI;
+    let mut x = x % two_pi;
+    if x > TRIG_PI {
+        x -= two_pi;
+    } else if x < -TRIG_PI {
+        x += two_pi;
+    }
+    if x > TRIG_HALF_PI {
+        x = TRIG_PI - x;
+    } else if x < -TRIG_HALF_PI {
+        x = -TRIG_PI - x;
+    }
+    trig_sin_reduced(x)
+}
+
+/// arctangent of t in [0, 1], both in TRIG_BITS fixed point
+fn trig_atan_unit(t: i64) -> i64 {
+    // atan(t) = 2 atan(t / (1 + sqrt(1 + t^2))) brings t below tan(pi/8)
+    let root = isqrt(((TRIG_ONE + trig_mul(t, t)) as u64) << TRIG_BITS) as i64;
+    let u = (t << TRIG_BITS) / (TRIG_ONE + root);
+    let u2 = trig_mul(u, u);
+    let mut sum = 0;
+    let mut power = u;
+    for k in 0..8 {
+        let term = power / (2 * k + 1);
+        sum += if k % 2 == 0 { term } else { -term };
+        power = trig_mul(power, u2);
+    }
+    2 * sum
+}
+
+impl Q16 {
+    /// the smallest positive value, 2^-16
+    pub const EPSILON: Q16 = Q16(1);
+
+    /// the largest value
+    pub const MAX: Q16 = Q16(i32::MAX);
+
+    /// the smallest value
+    pub const MIN: Q16 = Q16(i32::MIN);
+
+    /// constructs a Q16 from its raw representation in units of 2^-16
+    pub const fn from_bits(bits: i32) -> Self {
+        Q16(bits)
+    }
+
+    /// returns the raw representation in units of 2^-16
+    pub const fn to_bits(self) -> i32 {
+        self.0
+    }
+
+    /// constructs a Q16 from an integer, saturating if it is out of range
+    pub const fn from_int(value: i32) -> Self {
+        if value > i16::MAX as i32 {
+            Q16::MAX
+        } else if value < i16::MIN as i32 {
+            Q16::MIN
+        } else {
+            Q16(value << FRAC_BITS)
+        }
+    }
+
+    /// constructs a Q16 from a float, rounding to the nearest value and
+    /// saturating if it is out of range
+    pub fn from_f64(value: f64) -> Self {
+        if value.is_nan() {
+            return Q16(0);
+        }
+        let scaled = value * ONE as f64;
+        if scaled >= i32::MAX as f64 {
+            Q16::MAX
+        } else if scaled <= i32::MIN as f64 {
+            Q16::MIN
+        } else if scaled >= 0.0 {
+            Q16((scaled + 0.5) as i32)
+        } else {
+            Q16((scaled - 0.5) as i32)
+        }
+    }
+
+    /// converts to a float
+    pub fn to_f64(self) -> f64 {
+        self.0 as f64 / ONE as f64
+    }
+
+    fn from_trig(value: i64) -> Self {
+        let shift = TRIG_BITS - FRAC_BITS;
+        saturate((value + (1 << (shift - 1))) >> shift)
+    }
+
+    fn to_trig(self) -> i64 {
+        (self.0 as i64) << (TRIG_BITS - FRAC_BITS)
+    }
+}
+
+impl Display for Q16 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        Display::fmt(&Q16::to_f64(*self), f)
+    }
+}
+
+impl Add for Q16 {
+    type Output = Q16;
+    fn add(self, rhs: Q16) -> Q16 {
+        Q16(self.0.saturating_add(rhs.0))
+    }
+}
+
+impl Sub for Q16 {
+    type Output = Q16;
+    fn sub(self, rhs: Q16) -> Q16 {
+        Q16(self.0.saturating_sub(rhs.0))
+    }
+}
+
+impl Mul for Q16 {
+    type Output = Q16;
+    fn mul(self, rhs: Q16) -> Q16 {
+        let product = self.0 as i64 * rhs.0 as i64;
+        saturate((product + (1 << (FRAC_BITS - 1))) >> FRAC_BITS)
+    }
+}
+
+/// Division by zero saturates towards the sign of the dividend
+impl Div for Q16 {
+    type Output = Q16;
+    fn div(self, rhs: Q16) -> Q16 {
+        if rhs.0 == 0 {
+            return match self.0.cmp(&0) {
+                Ordering::Less => Q16::MIN,
+                Ordering::Equal => Q16(0),
+                Ordering::Greater => Q16::MAX,
+            };
+        }
+        saturate(div_round((self.0 as i64) << FRAC_BITS, rhs.0 as i64))
+    }
+}
+
+/// The remainder has the sign of the dividend, and is zero for a zero divisor
+impl Rem for Q16 {
+    type Output = Q16;
+    fn rem(self, rhs: Q16) -> Q16 {
+        if rhs.0 == 0 {
+            return Q16(0);
+        }
+        Q16(self.0.wrapping_rem(rhs.0))
+    }
+}
+
+impl Neg for Q16 {
+    type Output = Q16;
+    fn neg(self) -> Q16 {
+        Q16(self.0.saturating_neg())
+    }
+}
+
+impl Zero for Q16 {
+    fn zero() -> Self {
+        Q16(0)
+    }
+
+    fn is_zero(&self) -> bool {
+        self.0 == 0
+    }
+}
+
+impl One for Q16 {
+    fn one() -> Self {
+        Q16(ONE as i32)
+    }
+}
+
+impl Num for Q16 {
+    type FromStrRadixErr = num_traits::ParseFloatError;
+
+    fn from_str_radix(s: &str, radix: u32) -> Result<Self, Self::FromStrRadixErr> {
+        <f64 as Num>::from_str_radix(s, radix).map(Q16::from_f64)
+    }
+}
+
+impl ToPrimitive for Q16 {
+    fn to_i64(&self) -> Option<i64> {
+        Some(self.0 as i64 / ONE)
+    }
+
+    fn to_u64(&self) -> Option<u64> {
+        if self.0 < 0 {
+            None
+        } else {
+            Some((self.0 as i64 / ONE) as u64)
+        }
+    }
+
+    fn to_f64(&self) -> Option<f64> {
+        Some(Q16::to_f64(*self))
+    }
+}
+
+impl NumCast for Q16 {
+    /// returns None if the value is out of range or NaN
+    fn from<N: ToPrimitive>(n: N) -> Option<Self> {
+        let value = n.to_f64()?;
+        let scaled = value * ONE as f64;
+        if value.is_nan() || scaled > i32::MAX as f64 || scaled < i32::MIN as f64 {
+            None
+        } else {
+            Some(Q16::from_f64(value))
+        }
+    }
+}
+
+impl Scalar for Q16 {
+    fn abs(self) -> Self {
+        Q16(self.0.saturating_abs())
+    }
+
+    /// returns zero for negative values
+    fn sqrt(self) -> Self {
+        if self.0 <= 0 {
+            return Q16(0);
+        }
+        saturate(isqrt((self.0 as u64) << FRAC_BITS) as i64)
+    }
+
+    fn sin(self) -> Self {
+        Q16::from_trig(trig_sin(self.to_trig()))
+    }
+
+    fn cos(self) -> Self {
+        Q16::from_trig(trig_sin(self.to_trig() + TRIG_HALF_PI))
+    }
+
+    fn atan2(self, x: Self) -> Self {
+        let (y, x) = (self.0 as i64, x.0 as i64);
+        if x == 0 && y == 0 {
+            return Q16(0);
+        }
+        let (ay, ax) = (y.abs(), x.abs());
+        let mut angle = if ay <= ax {
+            trig_atan_unit((ay << TRIG_BITS) / ax)
+        } else {
+            TRIG_HALF_PI - trig_atan_unit((ax << TRIG_BITS) / ay)
+        };
+        if x < 0 {
+            angle = TRIG_PI - angle;
+        }
+        if y < 0 {
+            angle = -angle;
+        }
+        Q16::from_trig(angle)
+    }
+
+    fn acos(self) -> Self {
+        let x = self.max(-Q16::one()).min(Q16::one());
+        (Q16::one() - x * x).sqrt().atan2(x)
+    }
+
+    fn from_int(value: i32) -> Self {
+        Q16::from_int(value)
+    }
+
+    /// saturates towards the sign of the numerator for a zero denominator
+    fn from_ratio(numerator: i32, denominator: i32) -> Self {
+        if denominator == 0 {
+            return Q16::from_int(numerator) / Q16(0);
+        }
+        saturate(div_round(
+            (numerator as i64) << FRAC_BITS,
+            denominator as i64,
+        ))
+    }
+
+    fn pi() -> Self {
+        Q16(PI)
+    }
+
+    fn tau() -> Self {
+        Q16(TAU)
+    }
+
+    fn wrapping_add(self, rhs: Self) -> Self {
+        Q16(self.0.wrapping_add(rhs.0))
+    }
+
+    fn wrapping_sub(self, rhs: Self) -> Self {
+        Q16(self.0.wrapping_sub(rhs.0))
+    }
+
+    fn wrapping_mul_int(self, n: i64) -> Self {
+        Q16((self.0 as i64).wrapping_mul(n) as i32)
+    }
+}
+
+/// Serialized as a float so that configs stay human readable
+#[cfg(feature = "serde")]
+impl serde::Serialize for Q16 {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_f64(Q16::to_f64(*self))
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Q16 {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        f64::deserialize(deserializer).map(Q16::from_f64)
+    }
+}
@@ -0,0 +1,78 @@
+use crate::rigid2d::Vector2D;
+use num_traits::Float;
+
+/// A planar range scan, such as the one produced by a 2D lidar.
+/// Beam `i` points along `angle_min + i * angle_increment` in the sensor frame
+#[derive(Debug, Clone)]
+pub struct LaserScan<T: Float> {
+    /// angle of the first beam in radians
+    pub angle_min: T,
+
+    /// angular distance between consecutive beams in radians
+    pub angle_increment: T,
+
+    /// minimum valid range in meters
+    pub range_min: T,
+
+    /// maximum valid range in meters
+    pub range_max: T,
+
+    /// measured ranges in meters, one per beam
+    pub ranges: Vec<T>,
+}
+
+impl<T: Float> LaserScan<T> {
+    /// constructs a new LaserScan from the beam geometry and the measured ranges
+    pub fn new(
+        angle_min: T,
+        angle_increment: T,
+        range_min: T,
+        range_max: T,
+        ranges: Vec<T>,
+    ) -> Self {
+        LaserScan {
+            angle_min,
+            angle_increment,
+            range_min,
+            range_max,
+            ranges,
+        }
+    }
+
+    /// returns the number of beams in the scan
+    pub fn len(&self) -> usize {
+        self.ranges.len()
+    }
+
+    /// returns true if the scan has no beams
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
+    /// returns the angle of the i-th beam in the sensor frame
+    pub fn angle(&self, i: usize) -> T {
+        self.angle_min + T::from(i).unwrap() * self.angle_increment
+    }
+
+    /// returns true if the range is a valid return, i.e. the beam hit something
+    /// between range_min and range_max
+    pub fn is_hit(&self, range: T) -> bool {
+        range.is_finite() && range >= self.range_min && range < self.range_max
+    }
+
+    /// returns the (angle, range) pair of every beam
+    pub fn beams(&self) -> impl Iterator<Item = (T, T)> + '_ {
+        self.ranges
+            .iter()
+            .enumerate()
+            .map(move |(i, r)| (self.angle(i), *r))
+    }
+
+    /// returns the end points of all valid returns in the sensor frame
+    pub fn to_points(&self) -> Vec<Vector2D<T>> {
+        self.beams()
+            .filter(|(_, r)| self.is_hit(*r))
+            .map(|(a, r)| Vector2D::from_polar(r, a))
+            .collect()
+    }
+}
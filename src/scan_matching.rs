@@ -0,0 +1,545 @@
+//! Scan matching for correcting wheel odometry.
+//!
+//! Two scan-to-scan matchers are provided, point-to-point and point-to-line
+//! ICP, along with a multi-resolution correlative matcher that aligns a scan
+//! against an occupancy grid. Every matcher starts from an initial guess,
+//! normally the odometry delta computed by `DiffDrive::forward_kinematics`.
+//!
+//! Covariances are ordered (x, y, theta).
+use crate::linalg::{self, Matrix3};
+use crate::mapping::OccupancyGrid;
+use crate::rigid2d::{Transform2D, Vector2D};
+use crate::scan::LaserScan;
+use anyhow::{self, bail};
+use num_traits::Float;
+
+/// The result of matching a scan
+#[derive(Debug, Clone, Copy)]
+pub struct ScanMatch<T: Float> {
+    /// the refined transform
+    pub transform: Transform2D<T>,
+
+    /// the correction to apply to the initial guess, i.e. guess * correction = transform
+    pub correction: Transform2D<T>,
+
+    /// quality of the match between 0 (no agreement) and 1 (perfect agreement)
+    pub fitness: T,
+
+    /// covariance of the refined transform ordered (x, y, theta)
+    pub covariance: Matrix3<T>,
+
+    /// number of iterations used
+    pub iterations: usize,
+
+    /// true if the matcher converged before running out of iterations
+    pub converged: bool,
+}
+
+/// The error metric minimized by ICP
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IcpMethod {
+    /// minimizes the distance between corresponding points
+    PointToPoint,
+
+    /// minimizes the distance from each point to the line through its correspondence
+    PointToLine,
+}
+
+/// Parameters of the ICP scan matcher
+#[derive(Debug, Clone, Copy)]
+pub struct IcpConfig<T: Float> {
+    /// error metric to minimize
+    pub method: IcpMethod,
+
+    /// maximum number of iterations
+    pub max_iterations: usize,
+
+    /// correspondences further apart than this are rejected
+    pub max_correspondence_distance: T,
+
+    /// iteration stops once an update moves less than this (meters or radians)
+    pub tolerance: T,
+}
+
+impl<T: Float> IcpConfig<T> {
+    /// constructs a new IcpConfig with default limits for the given method
+    pub fn new(method: IcpMethod) -> Self {
+        IcpConfig {
+            method,
+            max_iterations: 50,
+            max_correspondence_distance: T::from(0.5).unwrap(),
+            tolerance: T::from(1e-6).unwrap(),
+        }
+    }
+}
+
+/// Aligns the source points to the target points. The result is the transform
+/// that maps source points into the target frame, so when matching the current
+/// scan (source) against the previous scan (target) the guess is the odometry
+/// delta from the previous pose to the current pose.
+pub fn icp<T: Float>(
+    source: &[Vector2D<T>],
+    target: &[Vector2D<T>],
+    guess: Transform2D<T>,
+    config: &IcpConfig<T>,
+) -> anyhow::Result<ScanMatch<T>> {
+    if source.len() < 3 || target.len() < 3 {
+        bail!("ICP needs at least 3 points in each scan");
+    }
+
+    let mut transform = guess;
+    let mut iterations = 0;
+    let mut converged = false;
+    while iterations < config.max_iterations {
+        iterations += 1;
+        let pairs = correspondences(source, target, &transform, config);
+        if pairs.len() < 3 {
+            bail!("ICP found too few correspondences");
+        }
+        let step = match config.method {
+            IcpMethod::PointToPoint => point_to_point_step(&pairs),
+            IcpMethod::PointToLine => point_to_line_step(&pairs)?,
+        };
+        transform = step * transform;
+        if step.translation().magnitude() < config.tolerance
+            && step.rotation().abs() < config.tolerance
+        {
+            converged = true;
+            break;
+        }
+    }
+
+    let pairs = correspondences(source, target, &transform, config);
+    let covariance = icp_covariance(&pairs, config.method);
+    Ok(ScanMatch {
+        transform,
+        correction: guess.inv() * transform,
+        fitness: T::from(pairs.len()).unwrap() / T::from(source.len()).unwrap(),
+        covariance,
+        iterations,
+        converged,
+    })
+}
+
+/// Aligns the current scan to the previous scan with ICP, starting from the
+/// odometry delta between the two scans
+pub fn match_scans<T: Float>(
+    previous: &LaserScan<T>,
+    current: &LaserScan<T>,
+    odometry_delta: Transform2D<T>,
+    config: &IcpConfig<T>,
+) -> anyhow::Result<ScanMatch<T>> {
+    icp(
+        &current.to_points(),
+        &previous.to_points(),
+        odometry_delta,
+        config,
+    )
+}
+
+/// A matched pair of a transformed source point and its target, along with
+/// the unit normal of the target surface when one could be estimated
+struct Correspondence<T: Float> {
+    source: Vector2D<T>,
+    target: Vector2D<T>,
+    normal: Option<Vector2D<T>>,
+}
+
+fn correspondences<T: Float>(
+    source: &[Vector2D<T>],
+    target: &[Vector2D<T>],
+    transform: &Transform2D<T>,
+    config: &IcpConfig<T>,
+) -> Vec<Correspondence<T>> {
+    let max_dist = config.max_correspondence_distance;
+    source
+        .iter()
+        .filter_map(|p| {
+            let p = transform.apply(*p);
+            let (j, d) = nearest(target, p);
+            if d > max_dist {
+                return None;
+            }
+            // the target points are in scan order, so the surface through the
+            // correspondence is estimated from its closest neighbor in the scan
+            let neighbor = [j.checked_sub(1), Some(j + 1)]
+                .into_iter()
+                .flatten()
+                .filter(|k| *k < target.len())
+                .map(|k| target[k])
+                .filter(|q| q.distance(target[j]) < max_dist)
+                .min_by(|a, b| a.distance(p).partial_cmp(&b.distance(p)).unwrap());
+            let normal = neighbor.and_then(|q| {
+                let dir = Vector2D::new(q.x - target[j].x, q.y - target[j].y);
+                let mag = dir.magnitude();
+                (mag > T::epsilon()).then(|| Vector2D::new(-dir.y / mag, dir.x / mag))
+            });
+            Some(Correspondence {
+                source: p,
+                target: target[j],
+                normal,
+            })
+        })
+        .collect()
+}
+
+/// returns the index of and distance to the point closest to p
+fn nearest<T: Float>(points: &[Vector2D<T>], p: Vector2D<T>) -> (usize, T) {
+    points
+        .iter()
+        .enumerate()
+        .map(|(i, q)| (i, q.distance(p)))
+        .fold(
+            (0, T::infinity()),
+            |best, cur| {
+                if cur.1 < best.1 {
+                    cur
+                } else {
+                    best
+                }
+            },
+        )
+}
+
+/// closed form least squares alignment of the correspondences
+fn point_to_point_step<T: Float>(pairs: &[Correspondence<T>]) -> Transform2D<T> {
+    let n = T::from(pairs.len()).unwrap();
+    let (mut sx, mut sy, mut tx, mut ty) = (T::zero(), T::zero(), T::zero(), T::zero());
+    for c in pairs {
+        sx = sx + c.source.x;
+        sy = sy + c.source.y;
+        tx = tx + c.target.x;
+        ty = ty + c.target.y;
+    }
+    let mu_s = Vector2D::new(sx / n, sy / n);
+    let mu_t = Vector2D::new(tx / n, ty / n);
+
+    let (mut cross, mut dot) = (T::zero(), T::zero());
+    for c in pairs {
+        let s = Vector2D::new(c.source.x - mu_s.x, c.source.y - mu_s.y);
+        let t = Vector2D::new(c.target.x - mu_t.x, c.target.y - mu_t.y);
+        cross = cross + s.x * t.y - s.y * t.x;
+        dot = dot + s.dot(&t);
+    }
+    let angle = cross.atan2(dot);
+    let rotated = Transform2D::new(Vector2D::new(T::zero(), T::zero()), angle).apply(mu_s);
+    Transform2D::new(Vector2D::new(mu_t.x - rotated.x, mu_t.y - rotated.y), angle)
+}
+
+/// linearized least squares alignment of the correspondences to their target lines
+fn point_to_line_step<T: Float>(pairs: &[Correspondence<T>]) -> anyhow::Result<Transform2D<T>> {
+    let mut h = linalg::zeros3();
+    let mut g = [T::zero(); 3];
+    for c in pairs {
+        let n = match c.normal {
+            Some(n) => n,
+            None => continue,
+        };
+        let j = [n.x, n.y, n.y * c.source.x - n.x * c.source.y];
+        let r = n.x * (c.source.x - c.target.x) + n.y * (c.source.y - c.target.y);
+        for a in 0..3 {
+            g[a] = g[a] - j[a] * r;
+            for b in 0..3 {
+                h[a][b] = h[a][b] + j[a] * j[b];
+            }
+        }
+    }
+    match linalg::solve3(&h, &g) {
+        Some(d) => Ok(Transform2D::new(Vector2D::new(d[0], d[1]), d[2])),
+        None => bail!("point-to-line ICP is degenerate, the scan constrains too few directions"),
+    }
+}
+
+/// estimates the covariance as sigma^2 (J^T J)^-1 at the solution
+fn icp_covariance<T: Float>(pairs: &[Correspondence<T>], method: IcpMethod) -> Matrix3<T> {
+    let mut h: Matrix3<T> = linalg::zeros3();
+    let mut sse = T::zero();
+    let mut residuals = 0;
+    let mut add = |j: [T; 3], r: T| {
+        for a in 0..3 {
+            for b in 0..3 {
+                h[a][b] = h[a][b] + j[a] * j[b];
+            }
+        }
+        sse = sse + r * r;
+        residuals += 1;
+    };
+    for c in pairs {
+        let (p, q) = (c.source, c.target);
+        match (method, c.normal) {
+            (IcpMethod::PointToLine, Some(n)) => {
+                add(
+                    [n.x, n.y, n.y * p.x - n.x * p.y],
+                    n.x * (p.x - q.x) + n.y * (p.y - q.y),
+                );
+            }
+            (IcpMethod::PointToLine, None) => {}
+            (IcpMethod::PointToPoint, _) => {
+                add([T::one(), T::zero(), -p.y], p.x - q.x);
+                add([T::zero(), T::one(), p.x], p.y - q.y);
+            }
+        }
+    }
+
+    let dof = residuals.max(4) - 3;
+    let sigma2 = sse / T::from(dof).unwrap();
+    match linalg::inverse3(&h) {
+        Some(inv) => inv.map(|row| row.map(|v| v * sigma2)),
+        None => [[T::infinity(); 3]; 3],
+    }
+}
+
+/// Parameters of the correlative scan matcher
+#[derive(Debug, Clone, Copy)]
+pub struct CorrelativeConfig<T: Float> {
+    /// the search covers +/- this distance around the guess in x and y (meters)
+    pub linear_window: T,
+
+    /// the search covers +/- this angle around the guess (radians)
+    pub angular_window: T,
+
+    /// angular step of the finest search level (radians)
+    pub angular_step: T,
+
+    /// number of resolution levels, each coarser level doubles the cell size
+    pub levels: usize,
+}
+
+impl<T: Float> Default for CorrelativeConfig<T> {
+    fn default() -> Self {
+        CorrelativeConfig {
+            linear_window: T::from(0.3).unwrap(),
+            angular_window: T::from(0.35).unwrap(),
+            angular_step: T::from(0.01).unwrap(),
+            levels: 3,
+        }
+    }
+}
+
+/// returns the number of steps that cover a search window
+fn window_steps<T: Float>(window: T, step: T, name: &str) -> anyhow::Result<i64> {
+    if !step.is_finite() || step <= T::zero() {
+        bail!("the {} search step must be positive", name);
+    }
+    if window.is_nan() || window < T::zero() {
+        bail!("the {} search window must not be negative", name);
+    }
+    match (window / step).ceil().to_i64() {
+        Some(steps) if steps <= i64::from(i32::MAX) => Ok(steps),
+        _ => bail!("the {} search window is too large for its step", name),
+    }
+}
+
+/// Multi-resolution correlative scan matcher. Candidate poses in a window
+/// around the guess are scored by the occupancy of the cells the scan end
+/// points fall in. The search starts on a coarse, max-pooled copy of the grid
+/// and is refined around the best candidate on each finer level.
+pub struct CorrelativeMatcher<T: Float> {
+    config: CorrelativeConfig<T>,
+    resolution: T,
+    origin: Vector2D<T>,
+    width: usize,
+    height: usize,
+
+    /// score maps, level l holds the max over blocks of 2^l x 2^l cells
+    levels: Vec<Vec<T>>,
+}
+
+impl<T: Float> CorrelativeMatcher<T> {
+    /// precomputes the score maps of the grid
+    pub fn new(grid: &OccupancyGrid<T>, config: CorrelativeConfig<T>) -> Self {
+        let (width, height) = (grid.width(), grid.height());
+        let half = T::from(0.5).unwrap();
+        let base: Vec<T> = (0..height)
+            .flat_map(|iy| (0..width).map(move |ix| (ix, iy)))
+            .map(|(ix, iy)| {
+                let p = grid.probability(ix, iy);
+                if p > half {
+                    p
+                } else {
+                    T::zero()
+                }
+            })
+            .collect();
+
+        let mut levels = vec![base];
+        for l in 1..config.levels.max(1) {
+            let prev = &levels[l - 1];
+            let step = 1 << (l - 1);
+            let mut next = prev.clone();
+            for iy in 0..height {
+                for ix in 0..width {
+                    let mut best = prev[iy * width + ix];
+                    for (dx, dy) in [(step, 0), (0, step), (step, step)] {
+                        if ix + dx < width && iy + dy < height {
+                            best = best.max(prev[(iy + dy) * width + ix + dx]);
+                        }
+                    }
+                    next[iy * width + ix] = best;
+                }
+            }
+            levels.push(next);
+        }
+
+        CorrelativeMatcher {
+            config,
+            resolution: grid.resolution(),
+            origin: grid.origin(),
+            width,
+            height,
+            levels,
+        }
+    }
+
+    /// Aligns the scan to the grid. The guess is the pose of the sensor in the
+    /// grid frame, usually the last matched pose composed with the odometry delta.
+    pub fn match_scan(
+        &self,
+        scan: &LaserScan<T>,
+        guess: Transform2D<T>,
+    ) -> anyhow::Result<ScanMatch<T>> {
+        let points = scan.to_points();
+        if points.is_empty() {
+            bail!("scan has no valid returns to match");
+        }
+
+        let window = window_steps(self.config.linear_window, self.resolution, "linear")?;
+        let angle_window = window_steps(
+            self.config.angular_window,
+            self.config.angular_step,
+            "angular",
+        )?;
+
+        // (dx cells, dy cells, angle steps) offsets from the guess
+        let mut best = (0, 0, 0);
+        let (mut lin_range, mut ang_range) = (window, angle_window);
+        let mut candidates = vec![];
+        for level in (0..self.levels.len()).rev() {
+            let stride = 1 << level;
+            candidates.clear();
+            let mut d_angle = -ang_range;
+            while d_angle <= ang_range {
+                let angle = best.2 + d_angle;
+                let rotated = self.rotate(&points, guess, angle);
+                let mut dy = -lin_range;
+                while dy <= lin_range {
+                    let mut dx = -lin_range;
+                    while dx <= lin_range {
+                        let cand = (best.0 + dx, best.1 + dy, angle);
+                        let score = self.score(level, &rotated, cand.0, cand.1);
+                        candidates.push((cand, score));
+                        dx += stride;
+                    }
+                    dy += stride;
+                }
+                d_angle += stride;
+            }
+            let top = candidates
+                .iter()
+                .fold(candidates[0], |a, b| if b.1 > a.1 { *b } else { a });
+            best = top.0;
+            lin_range = stride;
+            ang_range = stride;
+        }
+
+        let transform = self.candidate_transform(guess, best);
+        let fitness = candidates
+            .iter()
+            .map(|c| c.1)
+            .fold(T::zero(), |a, b| a.max(b));
+        Ok(ScanMatch {
+            transform,
+            correction: guess.inv() * transform,
+            fitness,
+            covariance: self.covariance(guess, transform, &candidates),
+            iterations: self.levels.len(),
+            converged: fitness > T::zero(),
+        })
+    }
+
+    /// returns the scan end points rotated by the candidate angle and
+    /// translated to the guess position, in the grid frame
+    fn rotate(
+        &self,
+        points: &[Vector2D<T>],
+        guess: Transform2D<T>,
+        steps: i64,
+    ) -> Vec<Vector2D<T>> {
+        let angle = guess.rotation() + T::from(steps).unwrap() * self.config.angular_step;
+        let tf = Transform2D::new(guess.translation(), angle);
+        points.iter().map(|p| tf.apply(*p)).collect()
+    }
+
+    /// mean score of the points offset by whole cells on the given level
+    fn score(&self, level: usize, points: &[Vector2D<T>], dx: i64, dy: i64) -> T {
+        let map = &self.levels[level];
+        let total = points.iter().fold(T::zero(), |acc, p| {
+            let ix = ((p.x - self.origin.x) / self.resolution)
+                .floor()
+                .to_i64()
+                .unwrap()
+                + dx;
+            let iy = ((p.y - self.origin.y) / self.resolution)
+                .floor()
+                .to_i64()
+                .unwrap()
+                + dy;
+            if ix >= 0 && iy >= 0 && (ix as usize) < self.width && (iy as usize) < self.height {
+                acc + map[iy as usize * self.width + ix as usize]
+            } else {
+                acc
+            }
+        });
+        total / T::from(points.len()).unwrap()
+    }
+
+    fn candidate_transform(&self, guess: Transform2D<T>, cand: (i64, i64, i64)) -> Transform2D<T> {
+        let t = guess.translation();
+        Transform2D::new(
+            Vector2D::new(
+                t.x + T::from(cand.0).unwrap() * self.resolution,
+                t.y + T::from(cand.1).unwrap() * self.resolution,
+            ),
+            guess.rotation() + T::from(cand.2).unwrap() * self.config.angular_step,
+        )
+    }
+
+    /// covariance of the candidates on the finest level weighted by their score
+    fn covariance(
+        &self,
+        guess: Transform2D<T>,
+        best: Transform2D<T>,
+        candidates: &[((i64, i64, i64), T)],
+    ) -> Matrix3<T> {
+        let mut cov = linalg::zeros3();
+        let mut total = T::zero();
+        for (cand, score) in candidates {
+            let tf = self.candidate_transform(guess, *cand);
+            let d = [
+                tf.translation().x - best.translation().x,
+                tf.translation().y - best.translation().y,
+                tf.rotation() - best.rotation(),
+            ];
+            for a in 0..3 {
+                for b in 0..3 {
+                    cov[a][b] = cov[a][b] + *score * d[a] * d[b];
+                }
+            }
+            total = total + *score;
+        }
+
+        // the covariance can not be smaller than the discretization of the search
+        let floor = [self.resolution, self.resolution, self.config.angular_step];
+        for (a, row) in cov.iter_mut().enumerate() {
+            for v in row.iter_mut() {
+                *v = if total > T::zero() {
+                    *v / total
+                } else {
+                    T::infinity()
+                };
+            }
+            row[a] = row[a].max(floor[a] * floor[a] / T::from(12.0).unwrap());
+        }
+        cov
+    }
+}
@@ -0,0 +1,342 @@
+//! A minimal simulator for a differential drive robot with a 2D lidar
+//! driving through a world made of line segments, and one for several
+//! robots sharing an open floor.
+use crate::ddrive::{DiffDrive, DiffDriveConfig, WheelState};
+use crate::rigid2d::{Pose2D, Twist2D, Vector2D};
+use crate::scan::LaserScan;
+use num_traits::Float;
+
+/// A line segment obstacle between two points
+#[derive(Debug, Clone, Copy)]
+pub struct Wall<T: Float> {
+    /// first end point
+    pub a: Vector2D<T>,
+
+    /// second end point
+    pub b: Vector2D<T>,
+}
+
+impl<T: Float> Wall<T> {
+    /// constructs a new Wall between two points
+    pub fn new(a: Vector2D<T>, b: Vector2D<T>) -> Self {
+        Wall { a, b }
+    }
+
+    /// returns the distance along the ray from origin in direction (unit vector)
+    /// to the wall, or None if the ray misses it
+    pub fn intersect(&self, origin: Vector2D<T>, direction: Vector2D<T>) -> Option<T> {
+        let seg = Vector2D::new(self.b.x - self.a.x, self.b.y - self.a.y);
+        let denom = direction.x * seg.y - direction.y * seg.x;
+        if denom.abs() < T::epsilon() {
+            return None;
+        }
+        let w = Vector2D::new(self.a.x - origin.x, self.a.y - origin.y);
+        let t = (w.x * seg.y - w.y * seg.x) / denom;
+        let u = (w.x * direction.y - w.y * direction.x) / denom;
+        if t >= T::zero() && u >= T::zero() && u <= T::one() {
+            Some(t)
+        } else {
+            None
+        }
+    }
+}
+
+/// The static environment the robot drives in
+#[derive(Debug, Clone, Default)]
+pub struct World<T: Float> {
+    walls: Vec<Wall<T>>,
+}
+
+impl<T: Float> World<T> {
+    /// constructs an empty world
+    pub fn new() -> Self {
+        World { walls: vec![] }
+    }
+
+    /// adds a wall between two points
+    pub fn add_wall(&mut self, a: Vector2D<T>, b: Vector2D<T>) {
+        self.walls.push(Wall::new(a, b));
+    }
+
+    /// adds the four walls of an axis aligned rectangle with the given corners
+    pub fn add_rectangle(&mut self, min: Vector2D<T>, max: Vector2D<T>) {
+        let corners = [
+            min,
+            Vector2D::new(max.x, min.y),
+            max,
+            Vector2D::new(min.x, max.y),
+        ];
+        for i in 0..corners.len() {
+            self.add_wall(corners[i], corners[(i + 1) % corners.len()]);
+        }
+    }
+
+    /// returns the walls of the world
+    pub fn walls(&self) -> &[Wall<T>] {
+        &self.walls
+    }
+
+    /// returns the distance to the closest wall along the ray, or None if there is no wall
+    pub fn raycast(&self, origin: Vector2D<T>, angle: T) -> Option<T> {
+        let direction = Vector2D::new(angle.cos(), angle.sin());
+        self.walls
+            .iter()
+            .filter_map(|w| w.intersect(origin, direction))
+            .fold(None, |closest, d| match closest {
+                Some(c) if c <= d => Some(c),
+                _ => Some(d),
+            })
+    }
+}
+
+/// Beam geometry of a simulated 2D lidar mounted at the robot's center
+#[derive(Debug, Clone, Copy)]
+pub struct LidarConfig<T: Float> {
+    /// angle of the first beam in radians
+    pub angle_min: T,
+
+    /// angle of the last beam in radians
+    pub angle_max: T,
+
+    /// number of beams
+    pub num_beams: usize,
+
+    /// minimum valid range in meters
+    pub range_min: T,
+
+    /// maximum valid range in meters
+    pub range_max: T,
+}
+
+impl<T: Float> LidarConfig<T> {
+    /// constructs a new LidarConfig
+    pub fn new(angle_min: T, angle_max: T, num_beams: usize, range_min: T, range_max: T) -> Self {
+        LidarConfig {
+            angle_min,
+            angle_max,
+            num_beams,
+            range_min,
+            range_max,
+        }
+    }
+
+    /// returns the angular distance between consecutive beams
+    pub fn angle_increment(&self) -> T {
+        if self.num_beams < 2 {
+            T::zero()
+        } else {
+            (self.angle_max - self.angle_min) / T::from(self.num_beams - 1).unwrap()
+        }
+    }
+}
+
+/// Simulates a differential drive robot with a lidar in a world of walls.
+/// The robot is driven by wheel angle increments, and its pose comes from
+/// the odometry of the underlying DiffDrive.
+pub struct Simulator<T: Float + Default> {
+    /// the simulated robot
+    robot: DiffDrive<T>,
+
+    /// the environment
+    world: World<T>,
+
+    /// the lidar mounted on the robot
+    lidar: LidarConfig<T>,
+
+    /// accumulated wheel angles in radians
+    wheel_angles: WheelState<T>,
+}
+
+impl<T: Float + Default> Simulator<T> {
+    /// constructs a new Simulator
+    pub fn new(robot: DiffDrive<T>, world: World<T>, lidar: LidarConfig<T>) -> Self {
+        Simulator {
+            robot,
+            world,
+            lidar,
+            wheel_angles: WheelState::default(),
+        }
+    }
+
+    /// returns the simulated robot
+    pub fn robot(&self) -> &DiffDrive<T> {
+        &self.robot
+    }
+
+    /// returns the environment
+    pub fn world(&self) -> &World<T> {
+        &self.world
+    }
+
+    /// returns the current pose of the robot
+    pub fn pose(&self) -> Pose2D<T> {
+        self.robot.pose()
+    }
+
+    /// rotates the wheels by the given angles and returns the new pose
+    pub fn step(&mut self, delta: WheelState<T>) -> Pose2D<T> {
+        self.wheel_angles.left = self.wheel_angles.left + delta.left;
+        self.wheel_angles.right = self.wheel_angles.right + delta.right;
+        self.robot.forward_kinematics(self.wheel_angles)
+    }
+
+    /// simulates a lidar scan from the current pose
+    pub fn scan(&self) -> LaserScan<T> {
+        let pose = self.pose();
+        let origin = Vector2D::new(pose.x, pose.y);
+        let increment = self.lidar.angle_increment();
+        let ranges = (0..self.lidar.num_beams)
+            .map(|i| {
+                let angle = pose.theta + self.lidar.angle_min + T::from(i).unwrap() * increment;
+                match self.world.raycast(origin, angle) {
+                    Some(d) if d < self.lidar.range_max => d,
+                    _ => T::infinity(),
+                }
+            })
+            .collect();
+
+        LaserScan::new(
+            self.lidar.angle_min,
+            increment,
+            self.lidar.range_min,
+            self.lidar.range_max,
+            ranges,
+        )
+    }
+
+    /// runs a scripted drive, stepping the robot by each wheel increment in turn,
+    /// and returns the pose and scan after every step
+    pub fn run(&mut self, script: &[WheelState<T>]) -> Vec<(Pose2D<T>, LaserScan<T>)> {
+        script
+            .iter()
+            .map(|delta| {
+                let pose = self.step(*delta);
+                (pose, self.scan())
+            })
+            .collect()
+    }
+}
+
+/// Simulates several differential drive robots sharing an open floor, each
+/// driven by twists and occupying a disc
+#[derive(Default)]
+pub struct MultiSimulator<T: Float + Default> {
+    /// the simulated robots and the radii of their discs
+    robots: Vec<(DiffDrive<T>, T)>,
+
+    /// velocity of every robot in the world frame over the last step
+    velocities: Vec<Vector2D<T>>,
+}
+
+impl<T: Float + Default> MultiSimulator<T> {
+    /// constructs a simulator without robots
+    pub fn new() -> Self {
+        MultiSimulator {
+            robots: vec![],
+            velocities: vec![],
+        }
+    }
+
+    /// Constructs the scenario of robots evenly spaced on a circle around
+    /// the origin, facing its center, and returns it with the goal of every
+    /// robot, the opposite point of the circle. Driving straight, they all
+    /// meet in the middle.
+    pub fn circle_swap(
+        config: &DiffDriveConfig<T>,
+        count: usize,
+        circle_radius: T,
+        robot_radius: T,
+    ) -> (Self, Vec<Vector2D<T>>) {
+        let mut sim = MultiSimulator::new();
+        let mut goals = vec![];
+        let pi = T::from(core::f64::consts::PI).unwrap();
+        for i in 0..count {
+            let angle = T::from(2.0).unwrap() * pi * T::from(i).unwrap() / T::from(count).unwrap();
+            let (s, c) = angle.sin_cos();
+            let mut robot = config.to_diff_drive();
+            robot.set_pose(Pose2D::new(
+                circle_radius * c,
+                circle_radius * s,
+                angle + pi,
+            ));
+            sim.add_robot(robot, robot_radius);
+            goals.push(Vector2D::new(-circle_radius * c, -circle_radius * s));
+        }
+        (sim, goals)
+    }
+
+    /// adds a robot with a disc of the given radius and returns its index
+    pub fn add_robot(&mut self, robot: DiffDrive<T>, radius: T) -> usize {
+        self.robots.push((robot, radius));
+        self.velocities.push(Vector2D::new(T::zero(), T::zero()));
+        self.robots.len() - 1
+    }
+
+    /// returns the number of robots
+    pub fn len(&self) -> usize {
+        self.robots.len()
+    }
+
+    /// returns true if there are no robots
+    pub fn is_empty(&self) -> bool {
+        self.robots.is_empty()
+    }
+
+    /// returns a simulated robot
+    pub fn robot(&self, index: usize) -> &DiffDrive<T> {
+        &self.robots[index].0
+    }
+
+    /// returns the radius of a robot's disc
+    pub fn radius(&self, index: usize) -> T {
+        self.robots[index].1
+    }
+
+    /// returns the current pose of every robot
+    pub fn poses(&self) -> Vec<Pose2D<T>> {
+        self.robots.iter().map(|(robot, _)| robot.pose()).collect()
+    }
+
+    /// returns the velocity of every robot in the world frame over the
+    /// last step
+    pub fn velocities(&self) -> &[Vector2D<T>] {
+        &self.velocities
+    }
+
+    /// drives every robot with its twist for dt seconds and returns the new
+    /// poses. Twists must not have a y component. Steps with dt <= 0 leave the
+    /// poses and velocities unchanged.
+    pub fn step(&mut self, twists: &[Twist2D<T>], dt: T) -> Vec<Pose2D<T>> {
+        if dt <= T::zero() {
+            return self.poses();
+        }
+        for ((robot, _), (twist, velocity)) in self
+            .robots
+            .iter_mut()
+            .zip(twists.iter().zip(self.velocities.iter_mut()))
+        {
+            let before = robot.pose();
+            let speeds = robot.speeds_from_twist(*twist);
+            let angles = robot.wheel_angles();
+            let after = robot.forward_kinematics(WheelState::new(
+                angles.left + speeds.left * dt,
+                angles.right + speeds.right * dt,
+            ));
+            *velocity = Vector2D::new((after.x - before.x) / dt, (after.y - before.y) / dt);
+        }
+        self.poses()
+    }
+
+    /// returns the smallest gap between the discs of any two robots,
+    /// negative when two overlap
+    pub fn min_clearance(&self) -> T {
+        let mut clearance = T::infinity();
+        for (i, (a, ra)) in self.robots.iter().enumerate() {
+            for (b, rb) in &self.robots[i + 1..] {
+                let (pa, pb) = (a.pose(), b.pose());
+                clearance = clearance.min((pb.x - pa.x).hypot(pb.y - pa.y) - *ra - *rb);
+            }
+        }
+        clearance
+    }
+}
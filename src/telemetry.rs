@@ -0,0 +1,519 @@
+//! A compact, append-only binary log of robot telemetry.
+//!
+//! A log starts with a header (the magic bytes `DDLOG`, a zero padding byte
+//! and a u16 format version) followed by chunks. Each chunk holds a batch of records and ends with a
+//! CRC-32 of its contents, and is written in a single call so that a crash
+//! can at worst leave a partial chunk at the end of the file. The reader
+//! stops at the first incomplete or corrupt chunk and reports the records
+//! before it, and `LogWriter::append` cuts such a tail off before it
+//! continues a log.
+//!
+//! All integers and floats are little endian. A chunk is laid out as
+//!
+//! ```text
+//! u32 payload length | u32 record count | payload | u32 CRC-32 of count and payload
+//! ```
+//!
+//! and each record in the payload as `u8 kind | u32 body length | f64 time | body`,
+//! so readers skip record kinds added by later versions of the format. The
+//! payload length is not covered by the CRC, so readers treat lengths above
+//! `MAX_CHUNK_LEN` as corrupt instead of allocating them.
+use crate::ddrive::{DiffDrive, WheelState};
+use crate::rigid2d::{Pose2D, Twist2D};
+use crate::utils::{crc32, crc32_update};
+use anyhow::{self, bail};
+use std::fs::{File, OpenOptions};
+use std::io::{BufReader, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+/// Magic bytes at the start of every log
+pub const MAGIC: [u8; 5] = *b"DDLOG";
+
+/// Version of the log format written by this crate
+pub const VERSION: u16 = 1;
+
+/// Largest chunk payload in bytes
+pub const MAX_CHUNK_LEN: usize = 1 << 26;
+
+const HEADER_LEN: u64 = 8;
+const KIND_ENCODERS: u8 = 1;
+const KIND_COMMAND: u8 = 2;
+const KIND_POSE: u8 = 3;
+const KIND_SENSOR: u8 = 4;
+
+/// A time-stamped log entry. Times are in seconds.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Record {
+    /// accumulated wheel angles from the encoders in radians
+    Encoders { time: f64, wheels: WheelState<f64> },
+
+    /// twist commanded to the robot
+    Command { time: f64, twist: Twist2D<f64> },
+
+    /// pose estimate of the robot
+    Pose { time: f64, pose: Pose2D<f64> },
+
+    /// raw message from a sensor, identified by its channel name
+    Sensor {
+        time: f64,
+        channel: String,
+        data: Vec<u8>,
+    },
+}
+
+impl Record {
+    /// returns the time stamp of the record
+    pub fn time(&self) -> f64 {
+        match self {
+            Record::Encoders { time, .. }
+            | Record::Command { time, .. }
+            | Record::Pose { time, .. }
+            | Record::Sensor { time, .. } => *time,
+        }
+    }
+
+    fn encode(&self, out: &mut Vec<u8>) {
+        let mut body = vec![];
+        let kind = match self {
+            Record::Encoders { wheels, .. } => {
+                put_f64s(&mut body, &[wheels.left, wheels.right]);
+                KIND_ENCODERS
+            }
+            Record::Command { twist, .. } => {
+                put_f64s(&mut body, &[twist.thetadot, twist.xdot, twist.ydot]);
+                KIND_COMMAND
+            }
+            Record::Pose { pose, .. } => {
+                put_f64s(&mut body, &[pose.x, pose.y, pose.theta]);
+                KIND_POSE
+            }
+            Record::Sensor { channel, data, .. } => {
+                body.extend_from_slice(&(channel.len() as u16).to_le_bytes());
+                body.extend_from_slice(channel.as_bytes());
+                body.extend_from_slice(data);
+                KIND_SENSOR
+            }
+        };
+        out.push(kind);
+        out.extend_from_slice(&(body.len() as u32 + 8).to_le_bytes());
+        out.extend_from_slice(&self.time().to_le_bytes());
+        out.extend_from_slice(&body);
+    }
+
+    /// decodes the record at the start of the bytes and returns it, or None
+    /// for a kind this version does not know, with the number of bytes used
+    fn decode(bytes: &[u8]) -> anyhow::Result<(Option<Record>, usize)> {
+        if bytes.len() < 5 {
+            bail!("record header is cut short");
+        }
+        let kind = bytes[0];
+        let len = u32::from_le_bytes(bytes[1..5].try_into()?) as usize;
+        if len < 8 || bytes.len() < 5 + len {
+            bail!("record body is cut short");
+        }
+        let time = f64::from_le_bytes(bytes[5..13].try_into()?);
+        let body = &bytes[13..5 + len];
+        let floats = |n: usize| -> anyhow::Result<Vec<f64>> {
+            if body.len() != 8 * n {
+                bail!("record of kind {} has the wrong length", kind);
+            }
+            Ok(body
+                .chunks_exact(8)
+                .map(|c| f64::from_le_bytes(c.try_into().unwrap()))
+                .collect())
+        };
+        let record = match kind {
+            KIND_ENCODERS => {
+                let v = floats(2)?;
+                Some(Record::Encoders {
+                    time,
+                    wheels: WheelState::new(v[0], v[1]),
+                })
+            }
+            KIND_COMMAND => {
+                let v = floats(3)?;
+                Some(Record::Command {
+                    time,
+                    twist: Twist2D::new(v[0], v[1], v[2]),
+                })
+            }
+            KIND_POSE => {
+                let v = floats(3)?;
+                Some(Record::Pose {
+                    time,
+                    pose: Pose2D::new(v[0], v[1], v[2]),
+                })
+            }
+            KIND_SENSOR => {
+                if body.len() < 2 {
+                    bail!("sensor record is cut short");
+                }
+                let name_len = u16::from_le_bytes([body[0], body[1]]) as usize;
+                if body.len() < 2 + name_len {
+                    bail!("sensor record is cut short");
+                }
+                Some(Record::Sensor {
+                    time,
+                    channel: String::from_utf8(body[2..2 + name_len].to_vec())?,
+                    data: body[2 + name_len..].to_vec(),
+                })
+            }
+            _ => None,
+        };
+        Ok((record, 5 + len))
+    }
+}
+
+fn put_f64s(out: &mut Vec<u8>, values: &[f64]) {
+    for v in values {
+        out.extend_from_slice(&v.to_le_bytes());
+    }
+}
+
+/// Writes records to a log in CRC-protected chunks. Records only reach the
+/// underlying writer when a chunk fills up or on `flush`, so flush as often
+/// as losing the records since the last flush in a crash would hurt.
+pub struct LogWriter<W: Write> {
+    inner: W,
+
+    /// encoded records of the chunk being built
+    pending: Vec<u8>,
+    pending_records: u32,
+
+    /// size at which the pending chunk is written out
+    chunk_size: usize,
+}
+
+impl<W: Write> LogWriter<W> {
+    /// writes the header of a new log and returns a writer for it
+    pub fn new(mut inner: W) -> anyhow::Result<Self> {
+        inner.write_all(&MAGIC)?;
+        inner.write_all(&[0])?;
+        inner.write_all(&VERSION.to_le_bytes())?;
+        Ok(LogWriter::resume(inner))
+    }
+
+    /// returns a writer that continues a log whose header is already written
+    fn resume(inner: W) -> Self {
+        LogWriter {
+            inner,
+            pending: vec![],
+            pending_records: 0,
+            chunk_size: 4096,
+        }
+    }
+
+    /// sets the number of bytes buffered before a chunk is written. Smaller
+    /// chunks lose fewer records in a crash but add more overhead.
+    pub fn with_chunk_size(mut self, bytes: usize) -> Self {
+        self.chunk_size = bytes;
+        self
+    }
+
+    /// adds a record, writing out the chunk once it is full. Fails for
+    /// records larger than `MAX_CHUNK_LEN`.
+    pub fn write(&mut self, record: &Record) -> anyhow::Result<()> {
+        let start = self.pending.len();
+        record.encode(&mut self.pending);
+        if self.pending.len() - start > MAX_CHUNK_LEN {
+            self.pending.truncate(start);
+            bail!("records are limited to {} bytes", MAX_CHUNK_LEN);
+        }
+        if self.pending.len() > MAX_CHUNK_LEN {
+            // the earlier records go out in a chunk of their own
+            let encoded = self.pending.split_off(start);
+            self.write_chunk()?;
+            self.pending = encoded;
+        }
+        self.pending_records += 1;
+        if self.pending.len() >= self.chunk_size {
+            self.write_chunk()?;
+        }
+        Ok(())
+    }
+
+    /// logs encoder readings
+    pub fn log_encoders(&mut self, time: f64, wheels: WheelState<f64>) -> anyhow::Result<()> {
+        self.write(&Record::Encoders { time, wheels })
+    }
+
+    /// logs a commanded twist
+    pub fn log_command(&mut self, time: f64, twist: Twist2D<f64>) -> anyhow::Result<()> {
+        self.write(&Record::Command { time, twist })
+    }
+
+    /// logs a pose estimate
+    pub fn log_pose(&mut self, time: f64, pose: Pose2D<f64>) -> anyhow::Result<()> {
+        self.write(&Record::Pose { time, pose })
+    }
+
+    /// logs a sensor message
+    pub fn log_sensor(&mut self, time: f64, channel: &str, data: &[u8]) -> anyhow::Result<()> {
+        if channel.len() > u16::MAX as usize {
+            bail!("sensor channel names are limited to {} bytes", u16::MAX);
+        }
+        self.write(&Record::Sensor {
+            time,
+            channel: channel.to_string(),
+            data: data.to_vec(),
+        })
+    }
+
+    /// writes out the pending records and flushes the underlying writer
+    pub fn flush(&mut self) -> anyhow::Result<()> {
+        if self.pending_records > 0 {
+            self.write_chunk()?;
+        }
+        self.inner.flush()?;
+        Ok(())
+    }
+
+    /// flushes the log and returns the underlying writer
+    pub fn into_inner(mut self) -> anyhow::Result<W> {
+        self.flush()?;
+        Ok(self.inner)
+    }
+
+    fn write_chunk(&mut self) -> anyhow::Result<()> {
+        let count = self.pending_records.to_le_bytes();
+        let crc = crc32_update(crc32(&count), &self.pending);
+        let mut chunk = Vec::with_capacity(self.pending.len() + 12);
+        chunk.extend_from_slice(&(self.pending.len() as u32).to_le_bytes());
+        chunk.extend_from_slice(&count);
+        chunk.extend_from_slice(&self.pending);
+        chunk.extend_from_slice(&crc.to_le_bytes());
+        // a single write so that a crash leaves at most one partial chunk
+        self.inner.write_all(&chunk)?;
+        self.pending.clear();
+        self.pending_records = 0;
+        Ok(())
+    }
+}
+
+impl LogWriter<File> {
+    /// creates a new log file, replacing any existing file
+    pub fn create<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
+        LogWriter::new(File::create(path)?)
+    }
+
+    /// Opens a log file to add records to its end, creating it if it does
+    /// not exist. An incomplete or corrupt chunk left at the end by a crash
+    /// is cut off first.
+    pub fn append<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+        if file.metadata()?.len() == 0 {
+            return LogWriter::new(file);
+        }
+        let valid_len = {
+            let mut reader = LogReader::new(BufReader::new(&mut file))?;
+            while reader.next_chunk()?.is_some() {}
+            reader.valid_len()
+        };
+        file.set_len(valid_len)?;
+        file.seek(SeekFrom::End(0))?;
+        Ok(LogWriter::resume(file))
+    }
+}
+
+/// Reads the records of a log chunk by chunk
+pub struct LogReader<R: Read> {
+    inner: R,
+
+    /// format version of the log
+    version: u16,
+
+    /// length of the header and the intact chunks read so far
+    valid_len: u64,
+
+    /// true once an incomplete or corrupt chunk was found
+    truncated: bool,
+}
+
+impl<R: Read> LogReader<R> {
+    /// reads the header of a log, failing if it is not a log or was written
+    /// by a newer version of the format
+    pub fn new(mut inner: R) -> anyhow::Result<Self> {
+        let mut header = [0u8; HEADER_LEN as usize];
+        if inner.read_exact(&mut header).is_err() || header[..5] != MAGIC {
+            bail!("not a diff-drive log");
+        }
+        let version = u16::from_le_bytes([header[6], header[7]]);
+        if version > VERSION {
+            bail!(
+                "log format version {} is newer than the supported version {}",
+                version,
+                VERSION
+            );
+        }
+        Ok(LogReader {
+            inner,
+            version,
+            valid_len: HEADER_LEN,
+            truncated: false,
+        })
+    }
+
+    /// returns the format version of the log
+    pub fn version(&self) -> u16 {
+        self.version
+    }
+
+    /// returns the number of bytes of the log that were read intact
+    pub fn valid_len(&self) -> u64 {
+        self.valid_len
+    }
+
+    /// returns true if reading stopped at an incomplete or corrupt chunk
+    pub fn truncated(&self) -> bool {
+        self.truncated
+    }
+
+    /// Reads the records of the next chunk. Returns None at the end of the
+    /// log, including at an incomplete or corrupt chunk, after which
+    /// `truncated` returns true.
+    pub fn next_chunk(&mut self) -> anyhow::Result<Option<Vec<Record>>> {
+        if self.truncated {
+            return Ok(None);
+        }
+        let mut head = [0u8; 8];
+        match read_full(&mut self.inner, &mut head)? {
+            0 => return Ok(None),
+            8 => {}
+            _ => return self.stop(),
+        }
+        let len = u32::from_le_bytes(head[..4].try_into()?) as usize;
+        if len > MAX_CHUNK_LEN {
+            return self.stop();
+        }
+        let mut rest = vec![0u8; len + 4];
+        if read_full(&mut self.inner, &mut rest)? < rest.len() {
+            return self.stop();
+        }
+        let (payload, crc) = rest.split_at(len);
+        let expected = u32::from_le_bytes(crc.try_into()?);
+        if crc32_update(crc32(&head[4..]), payload) != expected {
+            return self.stop();
+        }
+
+        let count = u32::from_le_bytes(head[4..].try_into()?);
+        let mut records = Vec::with_capacity(count as usize);
+        let mut offset = 0;
+        for _ in 0..count {
+            let (record, used) = Record::decode(&payload[offset..])?;
+            records.extend(record);
+            offset += used;
+        }
+        self.valid_len += 12 + len as u64;
+        Ok(Some(records))
+    }
+
+    /// reads every remaining record into a session
+    pub fn read_session(mut self) -> anyhow::Result<Session> {
+        let mut records = vec![];
+        while let Some(chunk) = self.next_chunk()? {
+            records.extend(chunk);
+        }
+        Ok(Session {
+            version: self.version,
+            records,
+            truncated: self.truncated,
+        })
+    }
+
+    fn stop(&mut self) -> anyhow::Result<Option<Vec<Record>>> {
+        self.truncated = true;
+        Ok(None)
+    }
+}
+
+/// reads until the buffer is full or the input ends, returning the bytes read
+fn read_full<R: Read>(reader: &mut R, buf: &mut [u8]) -> anyhow::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(filled)
+}
+
+/// Receives the records of a session in the order they were logged
+pub trait Replay {
+    fn replay_record(&mut self, record: &Record);
+}
+
+/// Runs the encoder readings through the forward kinematics
+impl Replay for DiffDrive<f64> {
+    fn replay_record(&mut self, record: &Record) {
+        if let Record::Encoders { wheels, .. } = record {
+            self.forward_kinematics(*wheels);
+        }
+    }
+}
+
+impl<F: FnMut(&Record)> Replay for F {
+    fn replay_record(&mut self, record: &Record) {
+        self(record)
+    }
+}
+
+/// All the records of a log
+#[derive(Debug, Clone)]
+pub struct Session {
+    /// format version of the log
+    pub version: u16,
+
+    /// records in the order they were logged
+    pub records: Vec<Record>,
+
+    /// true if the log ended in an incomplete or corrupt chunk
+    pub truncated: bool,
+}
+
+impl Session {
+    /// reads a log file
+    pub fn load<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
+        LogReader::new(BufReader::new(File::open(path)?))?.read_session()
+    }
+
+    /// feeds every record to the consumer in the order they were logged
+    pub fn replay<C: Replay>(&self, consumer: &mut C) {
+        for record in &self.records {
+            consumer.replay_record(record);
+        }
+    }
+
+    /// Replays the encoder readings through the robot's forward kinematics
+    /// and returns the time-stamped pose after each reading. The robot
+    /// should start with zero wheel angles, like the encoders did.
+    pub fn replay_odometry(&self, robot: &mut DiffDrive<f64>) -> Vec<(f64, Pose2D<f64>)> {
+        self.records
+            .iter()
+            .filter_map(|record| match record {
+                Record::Encoders { time, wheels } => {
+                    Some((*time, robot.forward_kinematics(*wheels)))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// returns the logged pose estimates
+    pub fn poses(&self) -> Vec<(f64, Pose2D<f64>)> {
+        self.records
+            .iter()
+            .filter_map(|record| match record {
+                Record::Pose { time, pose } => Some((*time, *pose)),
+                _ => None,
+            })
+            .collect()
+    }
+}
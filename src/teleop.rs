@@ -0,0 +1,185 @@
+//! Mapping of joystick and gamepad axes to robot commands.
+//!
+//! `Teleop` takes axis values normalized to [-1, 1], as read by an operator
+//! console, and shapes each of them with an `AxisCurve`: a deadzone around
+//! the center followed by an expo curve that gives finer control near the
+//! center. The shaped axes are scaled by the maximum speeds of the current
+//! `SpeedMode` and mixed into a `Twist2D` or straight into wheel speeds,
+//! either arcade style (one axis for forward speed and one for turning) or
+//! tank style (one axis per wheel).
+use crate::ddrive::{DiffDrive, WheelState};
+use crate::rigid2d::Twist2D;
+use num_traits::Float;
+
+/// Shaping of a single axis
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AxisCurve<T: Float> {
+    /// axis values closer to the center than this give zero
+    pub deadzone: T,
+
+    /// blend between a linear response (0) and a cubic one (1)
+    pub expo: T,
+}
+
+impl<T: Float> AxisCurve<T> {
+    /// constructs a curve with the given deadzone and expo
+    pub fn new(deadzone: T, expo: T) -> Self {
+        AxisCurve { deadzone, expo }
+    }
+
+    /// Shapes an axis value. The value is clamped to [-1, 1] and the range
+    /// outside the deadzone is stretched back to [0, 1], so the output is
+    /// continuous and still reaches full scale. A NaN or infinite value,
+    /// e.g. from a disconnected joystick, gives zero.
+    pub fn apply(&self, value: T) -> T {
+        if !value.is_finite() {
+            return T::zero();
+        }
+        let value = value.max(-T::one()).min(T::one());
+        let magnitude = value.abs();
+        if magnitude <= self.deadzone || self.deadzone >= T::one() {
+            return T::zero();
+        }
+        let x = (magnitude - self.deadzone) / (T::one() - self.deadzone);
+        let expo = self.expo.max(T::zero()).min(T::one());
+        let shaped = expo * x * x * x + (T::one() - expo) * x;
+        shaped * value.signum()
+    }
+}
+
+impl<T: Float> Default for AxisCurve<T> {
+    /// a 5 % deadzone and a linear response
+    fn default() -> Self {
+        AxisCurve::new(T::from(0.05).unwrap(), T::zero())
+    }
+}
+
+/// How fast the robot may go, usually picked with buttons
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SpeedMode {
+    Slow,
+    #[default]
+    Normal,
+    Turbo,
+}
+
+impl SpeedMode {
+    /// returns the mode selected by a turbo and a slow button, slow wins
+    /// when both are held
+    pub fn from_buttons(turbo: bool, slow: bool) -> Self {
+        match (turbo, slow) {
+            (_, true) => SpeedMode::Slow,
+            (true, false) => SpeedMode::Turbo,
+            (false, false) => SpeedMode::Normal,
+        }
+    }
+}
+
+/// Maps axis values to twists and wheel speeds
+#[derive(Debug, Clone, Copy)]
+pub struct Teleop<T: Float> {
+    /// forward speed in m/s at full stick in turbo mode
+    max_linear: T,
+
+    /// turn rate in rad/s at full stick in turbo mode
+    max_angular: T,
+
+    linear_curve: AxisCurve<T>,
+    angular_curve: AxisCurve<T>,
+
+    /// fraction of the maximum speeds in slow, normal and turbo mode
+    slow_scale: T,
+    normal_scale: T,
+    turbo_scale: T,
+}
+
+impl<T: Float + Default> Teleop<T> {
+    /// Constructs a mapping with the given top speeds, reached in turbo
+    /// mode. Normal mode goes half as fast and slow mode a quarter.
+    pub fn new(max_linear: T, max_angular: T) -> Self {
+        Teleop {
+            max_linear,
+            max_angular,
+            linear_curve: AxisCurve::default(),
+            angular_curve: AxisCurve::default(),
+            slow_scale: T::from(0.25).unwrap(),
+            normal_scale: T::from(0.5).unwrap(),
+            turbo_scale: T::one(),
+        }
+    }
+
+    /// sets the shaping of the forward axis, and of both tank axes
+    pub fn with_linear_curve(mut self, curve: AxisCurve<T>) -> Self {
+        self.linear_curve = curve;
+        self
+    }
+
+    /// sets the shaping of the turn axis
+    pub fn with_angular_curve(mut self, curve: AxisCurve<T>) -> Self {
+        self.angular_curve = curve;
+        self
+    }
+
+    /// sets the fractions of the top speeds used in slow, normal and turbo
+    /// mode
+    pub fn with_mode_scales(mut self, slow: T, normal: T, turbo: T) -> Self {
+        self.slow_scale = slow;
+        self.normal_scale = normal;
+        self.turbo_scale = turbo;
+        self
+    }
+
+    /// returns the fraction of the top speeds used in a mode
+    pub fn scale(&self, mode: SpeedMode) -> T {
+        match mode {
+            SpeedMode::Slow => self.slow_scale,
+            SpeedMode::Normal => self.normal_scale,
+            SpeedMode::Turbo => self.turbo_scale,
+        }
+    }
+
+    /// Maps a forward axis and a turn axis to a twist. Positive forward
+    /// drives forward and positive turn turns left (counterclockwise), so
+    /// the x axis of most sticks needs its sign flipped.
+    pub fn twist(&self, forward: T, turn: T, mode: SpeedMode) -> Twist2D<T> {
+        let scale = self.scale(mode);
+        Twist2D::new(
+            self.angular_curve.apply(turn) * self.max_angular * scale,
+            self.linear_curve.apply(forward) * self.max_linear * scale,
+            T::zero(),
+        )
+    }
+
+    /// Mixes a forward axis and a turn axis into wheel speeds in rad/s. When
+    /// a wheel would go faster than the top forward speed of the mode, both
+    /// wheels are slowed down by the same factor to keep the curvature.
+    pub fn arcade(
+        &self,
+        forward: T,
+        turn: T,
+        mode: SpeedMode,
+        robot: &DiffDrive<T>,
+    ) -> WheelState<T> {
+        let speeds = robot.speeds_from_twist(self.twist(forward, turn, mode));
+        let limit = self.max_linear * self.scale(mode);
+        let fastest = (speeds.left * robot.left_wheel_radius())
+            .abs()
+            .max((speeds.right * robot.right_wheel_radius()).abs());
+        if fastest > limit && fastest > T::zero() {
+            let factor = limit / fastest;
+            WheelState::new(speeds.left * factor, speeds.right * factor)
+        } else {
+            speeds
+        }
+    }
+
+    /// Maps one axis per wheel to wheel speeds in rad/s, with full stick
+    /// driving the wheel at the top forward speed of the mode
+    pub fn tank(&self, left: T, right: T, mode: SpeedMode, robot: &DiffDrive<T>) -> WheelState<T> {
+        let speed = self.max_linear * self.scale(mode);
+        WheelState::new(
+            self.linear_curve.apply(left) * speed / robot.left_wheel_radius(),
+            self.linear_curve.apply(right) * speed / robot.right_wheel_radius(),
+        )
+    }
+}
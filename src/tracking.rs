@@ -0,0 +1,270 @@
+//! Path tracking with a pure pursuit controller.
+//!
+//! The controller steers towards the point of the path one lookahead
+//! distance ahead of the robot, along the circular arc through it. `track`
+//! runs the controller against any base implementing `Kinematics` and reports
+//! how closely the robot followed the path.
+use crate::kinematics::Kinematics;
+use crate::rigid2d::{Pose2D, Twist2D, Vector2D};
+use anyhow::anyhow;
+use num_traits::Float;
+
+/// A pure pursuit path tracking controller
+#[derive(Debug, Clone, Copy)]
+pub struct PurePursuit<T: Float> {
+    /// distance to the point on the path the robot steers towards
+    lookahead: T,
+
+    /// forward speed in m/s
+    speed: T,
+
+    /// largest turn rate in rad/s, the forward speed is lowered to keep
+    /// the curvature when it is reached
+    max_angular_speed: T,
+
+    /// distance from the end of the path at which the goal is reached
+    goal_tolerance: T,
+
+    /// index of the path segment the robot was last closest to
+    progress: usize,
+}
+
+impl<T: Float> PurePursuit<T> {
+    /// constructs a controller with the given lookahead distance and speed
+    pub fn new(lookahead: T, speed: T) -> Self {
+        PurePursuit {
+            lookahead,
+            speed,
+            max_angular_speed: T::infinity(),
+            goal_tolerance: lookahead / T::from(10.0).unwrap(),
+            progress: 0,
+        }
+    }
+
+    /// limits the turn rate
+    pub fn with_max_angular_speed(mut self, max_angular_speed: T) -> Self {
+        self.max_angular_speed = max_angular_speed;
+        self
+    }
+
+    /// sets the distance from the end of the path at which the goal is
+    /// reached, a tenth of the lookahead by default
+    pub fn with_goal_tolerance(mut self, tolerance: T) -> Self {
+        self.goal_tolerance = tolerance;
+        self
+    }
+
+    /// forgets the progress along the path, to start on a new one
+    pub fn reset(&mut self) {
+        self.progress = 0;
+    }
+
+    /// Computes the twist that follows the path from the pose, or None once
+    /// the robot is at the end of the path. Progress along the path only
+    /// moves forward, so paths may cross themselves.
+    pub fn command(&mut self, pose: Pose2D<T>, path: &[Vector2D<T>]) -> Option<Twist2D<T>> {
+        let goal = *path.last()?;
+        let position = Vector2D::new(pose.x, pose.y);
+        if path.len() == 1 {
+            let goal_distance = distance(position, goal);
+            if goal_distance <= self.goal_tolerance {
+                return None;
+            }
+            return Some(self.steer(pose, goal, goal_distance));
+        }
+
+        // closest point on the path within two lookaheads past the last
+        // progress, so that the end of a closed path is not mistaken for
+        // its start
+        let last_segment = path.len() - 2;
+        self.progress = self.progress.min(last_segment);
+        let window = self.lookahead * T::from(2.0).unwrap();
+        let mut closest = (self.progress, T::zero(), T::infinity());
+        let mut searched = T::zero();
+        for i in self.progress..=last_segment {
+            let t = project(position, path[i], path[i + 1]);
+            let d = distance(position, lerp(path[i], path[i + 1], t));
+            if d < closest.2 {
+                closest = (i, t, d);
+            }
+            searched = searched + distance(path[i], path[i + 1]);
+            if searched > window {
+                break;
+            }
+        }
+        self.progress = closest.0;
+
+        let goal_distance = distance(position, goal);
+        if self.progress == last_segment && goal_distance <= self.goal_tolerance {
+            return None;
+        }
+
+        // first point past the closest one that is a lookahead away
+        let mut target = goal;
+        let mut t0 = closest.1;
+        for i in self.progress..=last_segment {
+            let (a, b) = (path[i], path[i + 1]);
+            if distance(position, b) >= self.lookahead {
+                let t = circle_intersection(position, self.lookahead, a, b).max(t0);
+                target = lerp(a, b, t);
+                break;
+            }
+            t0 = T::zero();
+        }
+        Some(self.steer(pose, target, goal_distance))
+    }
+
+    /// twist along the arc from the pose through the target
+    fn steer(&self, pose: Pose2D<T>, target: Vector2D<T>, goal_distance: T) -> Twist2D<T> {
+        let (dx, dy) = (target.x - pose.x, target.y - pose.y);
+        let (sin, cos) = pose.theta.sin_cos();
+        let y = -sin * dx + cos * dy;
+        let d2 = dx * dx + dy * dy;
+        let curvature = if d2 > T::zero() {
+            T::from(2.0).unwrap() * y / d2
+        } else {
+            T::zero()
+        };
+
+        // slow down over the last lookahead so as not to overshoot the goal
+        let ramp = (goal_distance / self.lookahead)
+            .min(T::one())
+            .max(T::from(0.2).unwrap());
+        let mut speed = self.speed * ramp;
+        let mut omega = speed * curvature;
+        if omega.abs() > self.max_angular_speed {
+            omega = self.max_angular_speed * omega.signum();
+            speed = omega / curvature;
+        }
+        Twist2D::new(omega, speed, T::zero())
+    }
+}
+
+/// How well a robot followed a path
+#[derive(Debug, Clone)]
+pub struct TrackingReport<T: Float> {
+    /// pose after every step, starting with the initial pose
+    pub poses: Vec<Pose2D<T>>,
+
+    /// simulated time in seconds
+    pub time: T,
+
+    /// whether the controller reached the end of the path before the timeout
+    pub reached_goal: bool,
+
+    /// root mean square of the distance from the path over all steps
+    pub rms_error: T,
+
+    /// largest distance from the path
+    pub max_error: T,
+
+    /// distance from the end of the path at the end of the run
+    pub goal_error: T,
+}
+
+/// Simulates the controller driving a kinematic robot from the start pose
+/// along the path with the given time step, until the goal is reached or the
+/// timeout passes. Fails if the base can not follow a commanded twist.
+pub fn track<T: Float, K: Kinematics<T>>(
+    robot: &K,
+    start: Pose2D<T>,
+    controller: &mut PurePursuit<T>,
+    path: &[Vector2D<T>],
+    dt: T,
+    timeout: T,
+) -> anyhow::Result<TrackingReport<T>> {
+    let mut poses = vec![start];
+    let mut pose = start;
+    let mut time = T::zero();
+    let mut reached_goal = false;
+    while time < timeout {
+        let twist = match controller.command(pose, path) {
+            Some(twist) => twist,
+            None => {
+                reached_goal = true;
+                break;
+            }
+        };
+        let step = Twist2D::new(twist.thetadot * dt, twist.xdot * dt, twist.ydot * dt);
+        let deltas = robot
+            .wheels_from_twist(&step)
+            .map_err(|e| anyhow!("step {}: {}", poses.len(), e))?;
+        pose = robot.odometry_update(pose, &deltas);
+        poses.push(pose);
+        time = time + dt;
+    }
+
+    let errors: Vec<T> = poses
+        .iter()
+        .map(|p| distance_to_path(Vector2D::new(p.x, p.y), path))
+        .collect();
+    let count = T::from(errors.len()).unwrap();
+    let rms_error = (errors.iter().fold(T::zero(), |a, e| a + *e * *e) / count).sqrt();
+    let max_error = errors.iter().fold(T::zero(), |a, e| a.max(*e));
+    let goal_error = path
+        .last()
+        .map(|g| distance(Vector2D::new(pose.x, pose.y), *g))
+        .unwrap_or_else(T::zero);
+    Ok(TrackingReport {
+        poses,
+        time,
+        reached_goal,
+        rms_error,
+        max_error,
+        goal_error,
+    })
+}
+
+/// returns the distance from a point to the nearest point of a polyline
+pub fn distance_to_path<T: Float>(p: Vector2D<T>, path: &[Vector2D<T>]) -> T {
+    match path {
+        [] => T::infinity(),
+        [single] => distance(p, *single),
+        _ => path
+            .windows(2)
+            .map(|w| distance(p, lerp(w[0], w[1], project(p, w[0], w[1]))))
+            .fold(T::infinity(), T::min),
+    }
+}
+
+fn distance<T: Float>(a: Vector2D<T>, b: Vector2D<T>) -> T {
+    (b.x - a.x).hypot(b.y - a.y)
+}
+
+fn lerp<T: Float>(a: Vector2D<T>, b: Vector2D<T>, t: T) -> Vector2D<T> {
+    Vector2D::new(a.x + (b.x - a.x) * t, a.y + (b.y - a.y) * t)
+}
+
+/// parameter in [0, 1] of the point of segment ab closest to p
+fn project<T: Float>(p: Vector2D<T>, a: Vector2D<T>, b: Vector2D<T>) -> T {
+    let (dx, dy) = (b.x - a.x, b.y - a.y);
+    let length2 = dx * dx + dy * dy;
+    if length2 <= T::zero() {
+        return T::zero();
+    }
+    (((p.x - a.x) * dx + (p.y - a.y) * dy) / length2)
+        .max(T::zero())
+        .min(T::one())
+}
+
+/// parameter of the last point of segment ab at the given distance from the
+/// center, for a segment whose end b is at least that far away
+fn circle_intersection<T: Float>(
+    center: Vector2D<T>,
+    radius: T,
+    a: Vector2D<T>,
+    b: Vector2D<T>,
+) -> T {
+    let (dx, dy) = (b.x - a.x, b.y - a.y);
+    let (fx, fy) = (a.x - center.x, a.y - center.y);
+    let qa = dx * dx + dy * dy;
+    if qa <= T::zero() {
+        return T::one();
+    }
+    let qb = T::from(2.0).unwrap() * (fx * dx + fy * dy);
+    let qc = fx * fx + fy * fy - radius * radius;
+    let discriminant = (qb * qb - T::from(4.0).unwrap() * qa * qc).max(T::zero());
+    ((-qb + discriminant.sqrt()) / (T::from(2.0).unwrap() * qa))
+        .max(T::zero())
+        .min(T::one())
+}
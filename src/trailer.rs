@@ -0,0 +1,247 @@
+//! Kinematics of passive trailers towed by a differential drive tractor.
+//!
+//! Each trailer is hitched at an offset behind the axle of the vehicle in
+//! front of it, and its hitch angle is the heading of that vehicle minus the
+//! heading of the trailer. Hitch angles are propagated whenever the tractor's
+//! odometry is updated, and trailer poses are given at the center of each
+//! trailer's axle.
+use crate::ddrive::{DiffDrive, WheelState};
+use crate::rigid2d::{Pose2D, Transform2D, Twist2D, Vector2D};
+use crate::utils::normalize_angle;
+use num_traits::Float;
+
+/// Geometry of a single passive trailer
+#[derive(Debug, Clone, Copy)]
+pub struct Trailer<T: Float> {
+    /// distance from the axle of the vehicle in front back to the hitch,
+    /// zero when hitched directly above the axle
+    pub hitch_offset: T,
+
+    /// distance from the hitch back to the trailer's axle
+    pub length: T,
+}
+
+impl<T: Float> Trailer<T> {
+    /// constructs a new Trailer
+    pub fn new(hitch_offset: T, length: T) -> Self {
+        Trailer {
+            hitch_offset,
+            length,
+        }
+    }
+}
+
+/// A differential drive tractor towing a chain of trailers
+pub struct TrailerTrain<T: Float + Default> {
+    /// the tractor
+    tractor: DiffDrive<T>,
+
+    /// trailers ordered from the tractor backwards
+    trailers: Vec<Trailer<T>>,
+
+    /// hitch angle of every trailer in radians
+    hitch_angles: Vec<T>,
+
+    /// magnitude of the hitch angle at which a trailer is jackknifed
+    jackknife_limit: T,
+
+    /// accumulated tractor wheel angles in radians
+    wheel_angles: WheelState<T>,
+
+    /// number of integration steps per odometry update
+    substeps: usize,
+}
+
+impl<T: Float + Default> TrailerTrain<T> {
+    /// constructs a train with all trailers aligned behind the tractor
+    pub fn new(tractor: DiffDrive<T>, trailers: Vec<Trailer<T>>) -> Self {
+        let hitch_angles = vec![T::zero(); trailers.len()];
+        TrailerTrain {
+            tractor,
+            trailers,
+            hitch_angles,
+            jackknife_limit: T::from(std::f64::consts::FRAC_PI_2).unwrap(),
+            wheel_angles: WheelState::default(),
+            substeps: 20,
+        }
+    }
+
+    /// sets the hitch angle magnitude at which a trailer counts as jackknifed
+    pub fn with_jackknife_limit(mut self, limit: T) -> Self {
+        self.jackknife_limit = limit;
+        self
+    }
+
+    /// returns the tractor
+    pub fn tractor(&self) -> &DiffDrive<T> {
+        &self.tractor
+    }
+
+    /// returns the trailers
+    pub fn trailers(&self) -> &[Trailer<T>] {
+        &self.trailers
+    }
+
+    /// returns the hitch angle of every trailer
+    pub fn hitch_angles(&self) -> &[T] {
+        &self.hitch_angles
+    }
+
+    /// sets the hitch angle of a trailer
+    pub fn set_hitch_angle(&mut self, trailer: usize, angle: T) {
+        self.hitch_angles[trailer] = angle;
+    }
+
+    /// returns the index of the first jackknifed trailer, if any
+    pub fn jackknifed(&self) -> Option<usize> {
+        self.hitch_angles
+            .iter()
+            .position(|a| a.abs() >= self.jackknife_limit)
+    }
+
+    /// returns the pose of the tractor
+    pub fn pose(&self) -> Pose2D<T> {
+        self.tractor.pose()
+    }
+
+    /// returns the frame of every trailer's axle in the world frame
+    pub fn trailer_transforms(&self) -> Vec<Transform2D<T>> {
+        let zero = T::zero();
+        let mut frame = Transform2D::from_pose(self.tractor.pose());
+        self.trailers
+            .iter()
+            .zip(&self.hitch_angles)
+            .map(|(trailer, angle)| {
+                frame = frame
+                    * Transform2D::new(Vector2D::new(-trailer.hitch_offset, zero), -*angle)
+                    * Transform2D::new(Vector2D::new(-trailer.length, zero), zero);
+                frame
+            })
+            .collect()
+    }
+
+    /// returns the pose of every trailer's axle
+    pub fn trailer_poses(&self) -> Vec<Pose2D<T>> {
+        self.trailer_transforms()
+            .iter()
+            .map(|tf| {
+                let mut pose = tf.to_pose();
+                pose.theta = normalize_angle(pose.theta);
+                pose
+            })
+            .collect()
+    }
+
+    /// computes the forward kinematics of the tractor from its new wheel
+    /// angles and propagates the hitch angles of the trailers
+    pub fn forward_kinematics(&mut self, phi_new: WheelState<T>) -> Pose2D<T> {
+        let delta = WheelState::new(
+            phi_new.left - self.wheel_angles.left,
+            phi_new.right - self.wheel_angles.right,
+        );
+        self.wheel_angles = phi_new;
+        let twist = self.tractor.twist_from_speeds(delta);
+        self.propagate(twist.xdot, twist.thetadot);
+        self.tractor.forward_kinematics(phi_new)
+    }
+
+    /// drives the tractor with a constant twist for dt seconds
+    pub fn drive(&mut self, twist: Twist2D<T>, dt: T) -> Pose2D<T> {
+        let speeds = self.tractor.speeds_from_twist(twist);
+        let phi_new = WheelState::new(
+            self.wheel_angles.left + speeds.left * dt,
+            self.wheel_angles.right + speeds.right * dt,
+        );
+        self.forward_kinematics(phi_new)
+    }
+
+    /// returns the forward and angular velocity of every trailer given those of the tractor
+    pub fn trailer_velocities(&self, v: T, omega: T) -> Vec<(T, T)> {
+        let (mut v, mut omega) = (v, omega);
+        self.trailers
+            .iter()
+            .zip(&self.hitch_angles)
+            .map(|(trailer, phi)| {
+                let (s, c) = (phi.sin(), phi.cos());
+                let m = trailer.hitch_offset;
+                let next_v = v * c + m * omega * s;
+                let next_omega = (v * s - m * omega * c) / trailer.length;
+                v = next_v;
+                omega = next_omega;
+                (v, omega)
+            })
+            .collect()
+    }
+
+    /// integrates the hitch angles over one time unit of constant tractor motion
+    fn propagate(&mut self, v: T, omega: T) {
+        let dt = T::one() / T::from(self.substeps).unwrap();
+        for _ in 0..self.substeps {
+            let velocities = self.trailer_velocities(v, omega);
+            let mut omega_ahead = omega;
+            for (angle, (_, omega_i)) in self.hitch_angles.iter_mut().zip(velocities) {
+                *angle = normalize_angle(*angle + (omega_ahead - omega_i) * dt);
+                omega_ahead = omega_i;
+            }
+        }
+    }
+}
+
+/// Steers a reversing tractor so that its first trailer holds a heading.
+/// The trailer heading error sets a desired hitch angle, and the tractor's
+/// turn rate drives the hitch angle to it, which stabilizes the otherwise
+/// unstable reversing motion.
+#[derive(Debug, Clone, Copy)]
+pub struct ReversingController<T: Float> {
+    /// desired hitch angle per radian of trailer heading error
+    pub heading_gain: T,
+
+    /// convergence rate of the hitch angle to its desired value (1/s)
+    pub hitch_gain: T,
+
+    /// largest hitch angle the controller will command
+    pub max_hitch_angle: T,
+}
+
+impl<T: Float> ReversingController<T> {
+    /// constructs a new ReversingController
+    pub fn new(heading_gain: T, hitch_gain: T, max_hitch_angle: T) -> Self {
+        ReversingController {
+            heading_gain,
+            hitch_gain,
+            max_hitch_angle,
+        }
+    }
+
+    /// computes the tractor twist that drives at the given forward speed
+    /// (negative when reversing) while steering the first trailer towards
+    /// the desired heading
+    pub fn command(&self, train: &TrailerTrain<T>, desired_heading: T, speed: T) -> Twist2D<T>
+    where
+        T: Default,
+    {
+        let trailer = match train.trailers().first() {
+            Some(t) => *t,
+            None => return Twist2D::new(T::zero(), speed, T::zero()),
+        };
+        let heading = train.trailer_poses()[0].theta;
+        let phi = train.hitch_angles()[0];
+        let (m, l) = (trailer.hitch_offset, trailer.length);
+
+        // reversing turns the trailer away from the hitch angle, forward towards it
+        let error = normalize_angle(desired_heading - heading);
+        let direction = if speed < T::zero() {
+            -T::one()
+        } else {
+            T::one()
+        };
+        let phi_desired = (direction * self.heading_gain * error)
+            .max(-self.max_hitch_angle)
+            .min(self.max_hitch_angle);
+
+        // phi_dot = omega (1 + m cos(phi) / l) - v sin(phi) / l
+        let gain = T::one() + m * phi.cos() / l;
+        let omega = (self.hitch_gain * (phi_desired - phi) + speed * phi.sin() / l) / gain;
+        Twist2D::new(omega, speed, T::zero())
+    }
+}
@@ -1,6 +1,7 @@
 use crate::rigid2d::Vector2D;
 use crate::utils::linspace;
-use anyhow;
+use alloc::{vec, vec::Vec};
+use num_traits::Float;
 
 pub struct Path {
     waypoints: Vec<Vector2D<f32>>,
@@ -8,11 +9,34 @@ pub struct Path {
 
 // TODO: implement more basic paths
 impl Path {
+    /// constructs a path through the given waypoints
+    pub fn from_waypoints(waypoints: Vec<Vector2D<f32>>) -> Self {
+        Self { waypoints }
+    }
+
+    /// a straight line of the given length along the x axis
+    pub fn line(length: f32, npoints: usize) -> Self {
+        let waypoints = linspace(0.0, length, npoints)
+            .into_iter()
+            .map(|x| Vector2D::new(x, 0.0))
+            .collect();
+        Self { waypoints }
+    }
+
+    /// a full circle counterclockwise from the origin, centered at (0, radius)
+    pub fn circle(radius: f32, npoints: usize) -> Self {
+        let waypoints = linspace(0.0, 2.0 * core::f32::consts::PI, npoints)
+            .into_iter()
+            .map(|a| Vector2D::new(radius * a.sin(), radius * (1.0 - a.cos())))
+            .collect();
+        Self { waypoints }
+    }
+
     pub fn semi_circle(radius: f32, npoints: usize) -> Self {
         let traj_x = linspace(0.0, radius * 2.0, npoints);
         let mut traj_y: Vec<f32> = vec![];
         for xi in &traj_x {
-            let y = (radius.powf(2.0) - (*xi as f32 - radius).powf(2.0)).sqrt();
+            let y = (radius.powf(2.0) - (*xi - radius).powf(2.0)).sqrt();
             traj_y.push(y);
         }
 
@@ -24,6 +48,7 @@ impl Path {
         Self { waypoints }
     }
 
+    #[cfg(feature = "std")]
     pub fn write_to_csv(&self, filename: &str) -> anyhow::Result<()> {
         let mut traj_file = csv::Writer::from_path(filename)?;
         for i in 0..self.waypoints.len() {
@@ -33,6 +58,26 @@ impl Path {
         Ok(())
     }
 
+    /// reads a path written by `write_to_csv`, one x,y waypoint per line
+    #[cfg(feature = "std")]
+    pub fn read_csv(filename: &str) -> anyhow::Result<Self> {
+        let mut reader = csv::ReaderBuilder::new()
+            .has_headers(false)
+            .from_path(filename)?;
+        let mut waypoints = vec![];
+        for record in reader.records() {
+            let record = record?;
+            if record.len() < 2 {
+                anyhow::bail!("expected x,y on line {}", waypoints.len() + 1);
+            }
+            waypoints.push(Vector2D::new(
+                record[0].trim().parse()?,
+                record[1].trim().parse()?,
+            ));
+        }
+        Ok(Self { waypoints })
+    }
+
     pub fn to_vec(&self) -> Vec<Vector2D<f32>> {
         self.waypoints.clone()
     }
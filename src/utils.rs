@@ -1,43 +1,47 @@
 use crate::rigid2d::Vector2D;
-use num_traits::Float;
-use std::fmt::Display;
+use crate::scalar::Scalar;
+use core::fmt::Display;
+
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
 
 /// Returns true if two floats are almost equal (within epsilon) otherwise false
-pub fn almost_equal<T: Float>(d1: T, d2: T, epsilon: T) -> bool {
+pub fn almost_equal<T: Scalar>(d1: T, d2: T, epsilon: T) -> bool {
     (d1 - d2).abs() < epsilon
 }
 
 /// Converts degrees to radians
-pub fn deg2rad<T: Float>(deg: T) -> T {
-    deg * T::from(std::f64::consts::PI / 180.0).unwrap()
+pub fn deg2rad<T: Scalar>(deg: T) -> T {
+    deg * (T::pi() / T::from_int(180))
 }
 
 /// Converts radians to degrees
-pub fn rad2deg<T: Float>(rad: T) -> T {
-    rad * T::from(180.0 / std::f64::consts::PI).unwrap()
+pub fn rad2deg<T: Scalar>(rad: T) -> T {
+    rad * (T::from_int(180) / T::pi())
 }
 
-pub fn rpm_to_rad_per_sec<T: Float>(speed: T) -> T {
-    speed * T::from((2.0 * std::f64::consts::PI) / 60.0).unwrap()
+pub fn rpm_to_rad_per_sec<T: Scalar>(speed: T) -> T {
+    speed * (T::tau() / T::from_int(60))
 }
 
-pub fn rad_per_sec_to_rpm<T: Float>(speed: T) -> T {
-    speed * T::from(60.0 / (2.0 * std::f64::consts::PI)).unwrap()
+pub fn rad_per_sec_to_rpm<T: Scalar>(speed: T) -> T {
+    speed * (T::from_int(60) / T::tau())
 }
 
 /// Normalizes an angle in radians to be between -pi and pi
-pub fn normalize_angle<T: Float>(rad: T) -> T {
-    let pi = T::from(std::f64::consts::PI).unwrap();
-    if almost_equal(-pi, rad, T::from(1e-6).unwrap()) {
-        return pi;
+pub fn normalize_angle<T: Scalar>(rad: T) -> T {
+    let pi = T::pi();
+    if almost_equal(-pi, rad, T::from_ratio(1, 1_000_000)) {
+        pi
     } else {
-        return rad.sin().atan2(rad.cos());
+        rad.sin().atan2(rad.cos())
     }
 }
 
 /// Creates a linearly spaced vector of floats from start to stop with the given
 /// number of points in between
-pub fn linspace<T: Float>(start: T, stop: T, num_points: usize) -> Vec<T> {
+#[cfg(feature = "alloc")]
+pub fn linspace<T: Scalar>(start: T, stop: T, num_points: usize) -> Vec<T> {
     let step: T = (stop - start) / T::from(num_points - 1).unwrap();
     (0..num_points)
         .map(|i| start + T::from(i).unwrap() * step)
@@ -45,7 +49,8 @@ pub fn linspace<T: Float>(start: T, stop: T, num_points: usize) -> Vec<T> {
 }
 
 /// Creates a vector of floats from the given start to stop, and separated by the step
-pub fn arange<T: Float>(start: T, stop: T, step: T) -> Vec<T> {
+#[cfg(feature = "alloc")]
+pub fn arange<T: Scalar>(start: T, stop: T, step: T) -> Vec<T> {
     let num_points: usize = (((stop - start) / step) + T::from(1.0).unwrap())
         .to_usize()
         .unwrap();
@@ -55,6 +60,152 @@ pub fn arange<T: Float>(start: T, stop: T, step: T) -> Vec<T> {
 }
 
 /// Computes the linear distance between to points
-pub fn distance<T: Float>(a: Vector2D<T>, b: Vector2D<T>) -> T {
-    T::sqrt((b.x - a.x).powf(T::from(2.0).unwrap()) + (b.y - a.y).powf(T::from(2.0).unwrap()))
+pub fn distance<T: Scalar>(a: Vector2D<T>, b: Vector2D<T>) -> T {
+    let (dx, dy) = (b.x - a.x, b.y - a.y);
+    T::sqrt(dx * dx + dy * dy)
+}
+
+/// Computes the CRC-32 (IEEE 802.3) checksum of the bytes
+pub fn crc32(bytes: &[u8]) -> u32 {
+    crc32_update(0, bytes)
+}
+
+/// Continues a CRC-32 checksum over more bytes, starting from the checksum
+/// of the bytes before them (0 for none)
+pub fn crc32_update(crc: u32, bytes: &[u8]) -> u32 {
+    let mut crc = !crc;
+    for byte in bytes {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+const SHA256_K: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+/// Incremental SHA-256 state
+struct Sha256 {
+    state: [u32; 8],
+    block: [u8; 64],
+    block_len: usize,
+    total_len: u64,
+}
+
+impl Sha256 {
+    fn new() -> Self {
+        Sha256 {
+            state: [
+                0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab,
+                0x5be0cd19,
+            ],
+            block: [0; 64],
+            block_len: 0,
+            total_len: 0,
+        }
+    }
+
+    fn update(&mut self, bytes: &[u8]) {
+        self.total_len += bytes.len() as u64;
+        for &byte in bytes {
+            self.block[self.block_len] = byte;
+            self.block_len += 1;
+            if self.block_len == 64 {
+                self.compress();
+                self.block_len = 0;
+            }
+        }
+    }
+
+    fn finish(mut self) -> [u8; 32] {
+        let bits = self.total_len.wrapping_mul(8);
+        self.update(&[0x80]);
+        while self.block_len != 56 {
+            self.update(&[0]);
+        }
+        self.update(&bits.to_be_bytes());
+        let mut digest = [0u8; 32];
+        for (chunk, word) in digest.chunks_exact_mut(4).zip(self.state) {
+            chunk.copy_from_slice(&word.to_be_bytes());
+        }
+        digest
+    }
+
+    fn compress(&mut self) {
+        let mut w = [0u32; 64];
+        for (i, chunk) in self.block.chunks_exact(4).enumerate() {
+            w[i] = u32::from_be_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+        }
+        for i in 16..64 {
+            let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+            let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+            w[i] = w[i - 16]
+                .wrapping_add(s0)
+                .wrapping_add(w[i - 7])
+                .wrapping_add(s1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = self.state;
+        for i in 0..64 {
+            let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+            let ch = (e & f) ^ (!e & g);
+            let t1 = h
+                .wrapping_add(s1)
+                .wrapping_add(ch)
+                .wrapping_add(SHA256_K[i])
+                .wrapping_add(w[i]);
+            let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+            let maj = (a & b) ^ (a & c) ^ (b & c);
+            let t2 = s0.wrapping_add(maj);
+            h = g;
+            g = f;
+            f = e;
+            e = d.wrapping_add(t1);
+            d = c;
+            c = b;
+            b = a;
+            a = t1.wrapping_add(t2);
+        }
+        for (word, add) in self.state.iter_mut().zip([a, b, c, d, e, f, g, h]) {
+            *word = word.wrapping_add(add);
+        }
+    }
+}
+
+/// Computes the SHA-256 digest of the bytes
+pub fn sha256(bytes: &[u8]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(bytes);
+    hasher.finish()
+}
+
+/// Computes the HMAC-SHA256 authentication code of a message with a key
+pub fn hmac_sha256(key: &[u8], message: &[u8]) -> [u8; 32] {
+    let mut block = [0u8; 64];
+    if key.len() > 64 {
+        block[..32].copy_from_slice(&sha256(key));
+    } else {
+        block[..key.len()].copy_from_slice(key);
+    }
+
+    let mut inner = Sha256::new();
+    inner.update(&block.map(|b| b ^ 0x36));
+    inner.update(message);
+    let inner = inner.finish();
+
+    let mut outer = Sha256::new();
+    outer.update(&block.map(|b| b ^ 0x5c));
+    outer.update(&inner);
+    outer.finish()
 }
@@ -0,0 +1,111 @@
+use diff_drive::calibration::{
+    calibrate_from_ground_truth, umbmark, umbmark_square, CalibrationSample, OdometryCalibration,
+    SquareDirection,
+};
+use diff_drive::ddrive::{DiffDrive, WheelState};
+use diff_drive::rigid2d::Vector2D;
+use diff_drive::utils::almost_equal;
+
+const NOMINAL_RADIUS: f64 = 0.05;
+const NOMINAL_SEPARATION: f64 = 0.3;
+
+/// the robot as it really is
+fn actual() -> DiffDrive<f64> {
+    DiffDrive::with_wheel_radii(0.0497, 0.0503, 0.31)
+}
+
+/// drives the robot through the wheel angle increments and returns
+/// the ground-truth samples after every increment
+fn drive(robot: &mut DiffDrive<f64>, script: &[WheelState<f64>]) -> Vec<CalibrationSample<f64>> {
+    let mut angles = WheelState::new(0.0, 0.0);
+    let mut samples = vec![CalibrationSample::new(angles, robot.pose())];
+    for delta in script {
+        angles.left += delta.left;
+        angles.right += delta.right;
+        let pose = robot.forward_kinematics(angles);
+        samples.push(CalibrationSample::new(angles, pose));
+    }
+    samples
+}
+
+#[test]
+fn diffdrive_unequal_wheel_radii() {
+    let robot = DiffDrive::with_wheel_radii(1.0, 2.0, 1.0);
+    let v = robot.twist_from_speeds(WheelState::new(2.0, 1.0));
+    assert!(almost_equal(v.thetadot, 0.0, 1e-12));
+    assert!(almost_equal(v.xdot, 2.0, 1e-12));
+
+    let speeds = robot.speeds_from_twist(v);
+    assert!(almost_equal(speeds.left, 2.0, 1e-12));
+    assert!(almost_equal(speeds.right, 1.0, 1e-12));
+}
+
+#[test]
+fn calibration_round_trips_through_diffdrive() {
+    let calibration = OdometryCalibration::from_diff_drive(&actual());
+    let robot = calibration.to_diff_drive();
+    assert_eq!(robot.left_wheel_radius(), 0.0497);
+    assert_eq!(robot.right_wheel_radius(), 0.0503);
+    assert_eq!(robot.wheel_separation(), 0.31);
+}
+
+#[test]
+fn calibrate_from_ground_truth_log() {
+    let mut script = vec![];
+    for i in 0..200 {
+        let t = i as f64 * 0.1;
+        script.push(WheelState::new(
+            0.5 + 0.3 * t.sin(),
+            0.5 + 0.3 * (0.7 * t).cos(),
+        ));
+    }
+    let samples = drive(&mut actual(), &script);
+    let calibration = calibrate_from_ground_truth(&samples).unwrap();
+
+    assert!(almost_equal(calibration.left_wheel_radius, 0.0497, 1e-9));
+    assert!(almost_equal(calibration.right_wheel_radius, 0.0503, 1e-9));
+    assert!(almost_equal(calibration.wheel_separation, 0.31, 1e-9));
+}
+
+#[test]
+fn calibrate_from_ground_truth_needs_turns() {
+    let samples = drive(&mut actual(), &[WheelState::new(1.0, 1.0); 10]);
+    assert!(calibrate_from_ground_truth(&samples).is_err());
+    assert!(calibrate_from_ground_truth(&samples[..2]).is_err());
+}
+
+#[test]
+fn umbmark_bidirectional_square() {
+    const SIDE: f64 = 4.0;
+    let nominal = DiffDrive::new(NOMINAL_RADIUS, NOMINAL_SEPARATION);
+
+    let run = |direction| {
+        let script = umbmark_square(&nominal, SIDE, direction);
+        let samples = drive(&mut actual(), &script);
+        let end = samples.last().unwrap().pose;
+        Vector2D::new(end.x, end.y)
+    };
+    let cw = vec![run(SquareDirection::Clockwise); 5];
+    let ccw = vec![run(SquareDirection::CounterClockwise); 5];
+
+    let result = umbmark(&nominal, SIDE, &cw, &ccw).unwrap();
+    let calibrated = result.calibration;
+    assert!(result.alpha < 0.0, "a wider robot under-rotates");
+    assert!(result.beta > 0.0, "a larger right wheel curves left");
+    assert!(almost_equal(result.radius_ratio, 0.0503 / 0.0497, 1e-3));
+    assert!(almost_equal(calibrated.wheel_separation, 0.31, 2e-3));
+    assert!(almost_equal(calibrated.left_wheel_radius, 0.0497, 1e-4));
+    assert!(almost_equal(calibrated.right_wheel_radius, 0.0503, 1e-4));
+
+    // the calibrated robot returns much closer to its start
+    let calibrated_robot = calibrated.to_diff_drive();
+    let script = umbmark_square(&calibrated_robot, SIDE, SquareDirection::Clockwise);
+    let end = drive(&mut actual(), &script).last().unwrap().pose;
+    assert!(Vector2D::new(end.x, end.y).magnitude() < cw[0].magnitude() / 10.0);
+}
+
+#[test]
+fn umbmark_needs_both_directions() {
+    let nominal = DiffDrive::new(NOMINAL_RADIUS, NOMINAL_SEPARATION);
+    assert!(umbmark(&nominal, 4.0, &[], &[Vector2D::new(0.0, 0.0)]).is_err());
+}
@@ -0,0 +1,186 @@
+#![cfg(feature = "cli")]
+
+use diff_drive::ddrive::{DiffDrive, WheelParams};
+use diff_drive::mapping::OccupancyGrid;
+use diff_drive::rigid2d::Vector2D;
+use diff_drive::trajectory::Path;
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+fn run(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_diff-drive"))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+fn temp(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("diff_drive_cli_{}", name))
+}
+
+fn write_config(name: &str) -> String {
+    let path = temp(name);
+    DiffDrive::from_wheel_params(
+        WheelParams::new(0.05, 1000.0, 1.0),
+        WheelParams::new(0.05, 1000.0, 1.0),
+        0.3,
+    )
+    .config()
+    .save(&path)
+    .unwrap();
+    path.to_str().unwrap().to_string()
+}
+
+#[test]
+fn cli_generates_and_tracks_paths() {
+    let config = write_config("track_robot.toml");
+    let path_csv = temp("circle.csv");
+    let out = run(&[
+        "gen-path",
+        "circle",
+        "--size",
+        "1.5",
+        "--points",
+        "80",
+        "--output",
+        path_csv.to_str().unwrap(),
+    ]);
+    assert!(out.status.success());
+    let path = Path::read_csv(path_csv.to_str().unwrap()).unwrap();
+    assert_eq!(path.to_vec().len(), 80);
+
+    let json = run(&[
+        "gen-path",
+        "semi-circle",
+        "--points",
+        "5",
+        "--format",
+        "json",
+    ]);
+    let points: Vec<Vector2D<f32>> = serde_json::from_slice(&json.stdout).unwrap();
+    assert_eq!(points.len(), 5);
+    assert_eq!(points[4].x, 2.0);
+
+    let poses_csv = temp("track.csv");
+    let out = run(&[
+        "track",
+        "--config",
+        &config,
+        "--path",
+        path_csv.to_str().unwrap(),
+        "--output",
+        poses_csv.to_str().unwrap(),
+    ]);
+    let report = String::from_utf8(out.stdout).unwrap();
+    assert!(out.status.success(), "{}", report);
+    assert!(report.contains("reached goal: yes"));
+    assert!(report.contains("rms error:"));
+    let poses = std::fs::read_to_string(&poses_csv).unwrap();
+    assert!(poses.starts_with("time,x,y,theta\n"));
+
+    // a timeout that is too short fails the command
+    let out = run(&[
+        "track",
+        "--config",
+        &config,
+        "--path",
+        path_csv.to_str().unwrap(),
+        "--timeout",
+        "1",
+    ]);
+    assert!(!out.status.success());
+    for file in [&path_csv, &poses_csv] {
+        std::fs::remove_file(file).unwrap();
+    }
+}
+
+#[test]
+fn cli_replays_encoder_logs() {
+    let config = write_config("odom_robot.toml");
+    let input = temp("encoders.csv");
+    // 500 ticks on the right wheel more than the left turns the robot by
+    // pi/6 * 0.05 / 0.3 * 2 = 0.5236 rad
+    let mut csv = "time,left,right\n0.0,100,100\n".to_string();
+    for k in 1..=10 {
+        csv += &format!("{},{},{}\n", k as f64 * 0.1, 100 + k * 100, 100 + k * 150);
+    }
+    std::fs::write(&input, csv).unwrap();
+    let out = run(&[
+        "odom",
+        "--config",
+        &config,
+        "--input",
+        input.to_str().unwrap(),
+        "--ticks",
+    ]);
+    std::fs::remove_file(&input).unwrap();
+    assert!(out.status.success());
+    let text = String::from_utf8(out.stdout).unwrap();
+    let lines: Vec<&str> = text.lines().collect();
+    assert_eq!(lines.len(), 12);
+    assert_eq!(lines[1], "0,0,0,0");
+    let last: Vec<f64> = lines[11].split(',').map(|v| v.parse().unwrap()).collect();
+    let expected = 500.0 / 1000.0 * 2.0 * std::f64::consts::PI * 0.05 / 0.3;
+    assert!((last[3] - expected).abs() < 1e-9, "{}", last[3]);
+}
+
+#[test]
+fn cli_plans_on_map_files() {
+    let mut grid = OccupancyGrid::new(30, 30, 0.1, Vector2D::new(-1.0, -1.0));
+    for iy in 0..30 {
+        for ix in 0..30 {
+            let wall = ix == 15 && iy > 5;
+            grid.update_cell(ix, iy, if wall { 10.0 } else { -10.0 });
+        }
+    }
+    let map = temp("map.pgm");
+    grid.write_pgm(map.to_str().unwrap()).unwrap();
+    let plot = temp("plan.svg");
+    let out = run(&[
+        "plan",
+        "--map",
+        map.to_str().unwrap(),
+        "--resolution",
+        "0.1",
+        "--origin",
+        "-1,-1",
+        "--start",
+        "-0.5,1.5",
+        "--goal",
+        "1.5,1.5",
+        "--plot",
+        plot.to_str().unwrap(),
+    ]);
+    assert!(
+        out.status.success(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+    let waypoints: Vec<Vec<f64>> = String::from_utf8(out.stdout)
+        .unwrap()
+        .lines()
+        .map(|l| l.split(',').map(|v| v.parse().unwrap()).collect())
+        .collect();
+    assert_eq!(waypoints[0], vec![-0.5, 1.5]);
+    // around the bottom of the wall
+    assert!(waypoints.iter().any(|p| p[1] < -0.4));
+    assert!(std::fs::read_to_string(&plot).unwrap().contains("<svg"));
+
+    let blocked = run(&[
+        "plan",
+        "--map",
+        map.to_str().unwrap(),
+        "--resolution",
+        "0.1",
+        "--origin",
+        "-1,-1",
+        "--start",
+        "-0.5,1.5",
+        "--goal",
+        "0.5,1.5",
+    ]);
+    assert!(!blocked.status.success());
+    for file in [&map, &plot] {
+        std::fs::remove_file(file).unwrap();
+    }
+}
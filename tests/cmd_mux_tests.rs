@@ -0,0 +1,121 @@
+use diff_drive::cmd_mux::{CommandMux, VelocitySmoother};
+use diff_drive::rigid2d::Twist2D;
+use diff_drive::utils::almost_equal;
+
+fn forward(speed: f64) -> Twist2D<f64> {
+    Twist2D::new(0.0, speed, 0.0)
+}
+
+fn mux() -> CommandMux<f64> {
+    CommandMux::new()
+        .with_source("navigation", 1, 0.5)
+        .with_source("teleop", 5, 0.25)
+        .with_source("safety", 10, f64::INFINITY)
+}
+
+#[test]
+fn higher_priority_takes_over_and_hands_back() {
+    let mut mux = mux();
+    assert_eq!(mux.select(0.0), None);
+    assert_eq!(mux.active(), None);
+
+    mux.submit("navigation", forward(0.3), 0.0).unwrap();
+    assert_eq!(mux.select(0.1), Some(forward(0.3)));
+    assert_eq!(mux.active(), Some("navigation"));
+
+    // teleop preempts navigation while its commands keep coming
+    mux.submit("teleop", forward(0.8), 0.1).unwrap();
+    mux.submit("navigation", forward(0.4), 0.2).unwrap();
+    assert_eq!(mux.select(0.2), Some(forward(0.8)));
+    assert_eq!(mux.active(), Some("teleop"));
+
+    // and hands back once it times out
+    assert_eq!(mux.select(0.4), Some(forward(0.4)));
+    assert_eq!(mux.active(), Some("navigation"));
+
+    // nothing is left once navigation times out as well
+    assert_eq!(mux.select(0.8), None);
+}
+
+#[test]
+fn safety_stop_holds_until_cleared() {
+    let mut mux = mux();
+    mux.submit("teleop", forward(1.0), 0.0).unwrap();
+    mux.submit("safety", forward(0.0), 0.0).unwrap();
+    assert_eq!(mux.select(0.1), Some(forward(0.0)));
+    mux.submit("teleop", forward(1.0), 100.0).unwrap();
+    assert_eq!(mux.select(100.0), Some(forward(0.0)));
+    assert_eq!(mux.active(), Some("safety"));
+
+    mux.clear("safety").unwrap();
+    assert_eq!(mux.select(100.1), Some(forward(1.0)));
+    assert!(mux.submit("planner", forward(1.0), 0.0).is_err());
+    assert!(mux.clear("planner").is_err());
+}
+
+#[test]
+fn smoother_limits_acceleration_and_jerk() {
+    let (dt, accel, jerk) = (0.01, 1.0, 5.0);
+    let mut smoother = VelocitySmoother::new(accel, 2.0).with_jerk_limits(jerk, 10.0);
+    let mut previous = (0.0, 0.0);
+    let mut steps = 0;
+    while !almost_equal(smoother.twist().xdot, 1.0, 1e-9) {
+        let v = smoother.update(forward(1.0), dt).xdot;
+        let a = (v - previous.0) / dt;
+        assert!(a <= accel + 1e-9, "acceleration {} at step {}", a, steps);
+        assert!(a >= -1e-9, "the speed overshot at step {}", steps);
+        assert!((a - previous.1).abs() <= jerk * dt + 1e-9);
+        previous = (v, a);
+        steps += 1;
+        assert!(steps < 1000, "the target was never reached");
+    }
+    // ramping the acceleration up and down takes 0.2 s each, plus 0.8 s at
+    // full acceleration
+    assert!((115..=130).contains(&steps), "{} steps", steps);
+    assert!(smoother.twist().xdot <= 1.0);
+}
+
+#[test]
+fn smoother_follows_mux_hand_over() {
+    let dt = 0.02;
+    let mut mux = mux();
+    let mut smoother = VelocitySmoother::new(0.5, 1.0)
+        .with_deceleration_limits(2.0, 2.0)
+        .with_speed_limits(0.6, 1.0);
+
+    // navigation drives at 1 m/s, capped at 0.6 m/s and ramped up at 0.5 m/s^2
+    // a hand over within the same instant does not jump the speed
+    assert_eq!(smoother.update(forward(2.0), 0.0).xdot, 0.0);
+    assert_eq!(smoother.update(forward(2.0), -dt).xdot, 0.0);
+
+    let mut time = 0.0;
+    let mut speeds = vec![];
+    while time < 2.0 {
+        mux.submit("navigation", forward(1.0), time).unwrap();
+        let target = mux.select(time).unwrap_or(forward(0.0));
+        speeds.push(smoother.update(target, dt).xdot);
+        time += dt;
+    }
+    assert!(almost_equal(speeds[9], 0.1, 1e-9));
+    assert!(almost_equal(*speeds.last().unwrap(), 0.6, 1e-9));
+
+    // the safety stop takes over and the robot brakes at 2 m/s^2
+    mux.submit("safety", forward(0.0), time).unwrap();
+    let target = mux.select(time).unwrap();
+    assert_eq!(mux.active(), Some("safety"));
+    let mut stop_steps = 0;
+    while smoother.update(target, dt).xdot > 0.0 {
+        stop_steps += 1;
+    }
+    assert_eq!(stop_steps, 14);
+
+    // everything times out without the stop, and the smoother sees zero
+    mux.clear("safety").unwrap();
+    assert_eq!(mux.select(time + 1.0), None);
+    smoother.reset(forward(0.2));
+    assert!(almost_equal(
+        smoother.update(forward(0.0), dt).xdot,
+        0.16,
+        1e-9
+    ));
+}
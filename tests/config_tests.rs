@@ -0,0 +1,112 @@
+#![cfg(feature = "config")]
+
+use diff_drive::config::{self, ConfigFormat};
+use diff_drive::ddrive::{DiffDrive, DiffDriveConfig, WheelParams, WheelState};
+use diff_drive::rigid2d::{Pose2D, Transform2D, Vector2D};
+use diff_drive::scalar::Q16;
+use diff_drive::utils::almost_equal;
+
+const TOML: &str = r#"
+wheel_separation = 0.16
+
+[left]
+radius = 0.033
+ticks_per_rev = 4096.0
+gear_ratio = 30.0
+
+[right]
+radius = 0.034
+ticks_per_rev = 4096.0
+gear_ratio = 30.0
+"#;
+
+const YAML: &str = r#"
+wheel_separation: 0.16
+left: {radius: 0.033, ticks_per_rev: 4096.0, gear_ratio: 30.0}
+right: {radius: 0.034, ticks_per_rev: 4096.0, gear_ratio: 30.0}
+"#;
+
+const JSON: &str = r#"{
+  "wheel_separation": 0.16,
+  "left": {"radius": 0.033, "ticks_per_rev": 4096.0, "gear_ratio": 30.0},
+  "right": {"radius": 0.034, "ticks_per_rev": 4096.0, "gear_ratio": 30.0}
+}"#;
+
+fn check(config: &DiffDriveConfig<f64>) {
+    assert_eq!(config.left.radius, 0.033);
+    assert_eq!(config.right.radius, 0.034);
+    assert_eq!(config.left.ticks_per_rev, 4096.0);
+    assert_eq!(config.right.gear_ratio, 30.0);
+    assert_eq!(config.wheel_separation, 0.16);
+    assert_eq!(config.separation_scale, 1.0);
+}
+
+#[test]
+fn config_parses_every_format() {
+    check(&DiffDriveConfig::from_toml(TOML).unwrap());
+    check(&DiffDriveConfig::from_yaml(YAML).unwrap());
+    check(&DiffDriveConfig::from_json(JSON).unwrap());
+
+    let robot = DiffDriveConfig::<f64>::from_toml(TOML)
+        .unwrap()
+        .to_diff_drive();
+    assert_eq!(robot.right_wheel_radius(), 0.034);
+    assert_eq!(robot.left_wheel().gear_ratio, 30.0);
+}
+
+#[test]
+fn config_round_trips_through_files() {
+    let robot = DiffDrive::from_wheel_params(
+        WheelParams::new(0.05, 1024.0, 1.0),
+        WheelParams::new(0.051, 1024.0, 1.0),
+        0.3,
+    )
+    .with_separation_scale(1.02);
+    let dir = std::env::temp_dir();
+    for name in ["robot.toml", "robot.yaml", "robot.json"] {
+        let path = dir.join(format!("diff_drive_config_{}", name));
+        robot.config().save(&path).unwrap();
+        let loaded = DiffDriveConfig::<f64>::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.right.radius, 0.051);
+        assert_eq!(loaded.left.ticks_per_rev, 1024.0);
+        assert_eq!(loaded.separation_scale, 1.02);
+        assert!(almost_equal(
+            loaded.to_diff_drive().wheel_separation(),
+            0.306,
+            1e-12
+        ));
+    }
+}
+
+#[test]
+fn config_serializes_rigid_body_types() {
+    let pose = Pose2D::new(1.0, -2.0, 0.5);
+    let json = serde_json::to_string(&pose).unwrap();
+    assert_eq!(json, r#"{"x":1.0,"y":-2.0,"theta":0.5}"#);
+
+    let tf = Transform2D::new(Vector2D::new(3.0, 4.0), 1.5);
+    let json = serde_json::to_string(&tf).unwrap();
+    assert_eq!(json, r#"{"translation":{"x":3.0,"y":4.0},"rotation":1.5}"#);
+    let back: Transform2D<f64> = serde_json::from_str(&json).unwrap();
+    assert_eq!(back.rotation(), 1.5);
+    assert_eq!(back.translation().y, 4.0);
+
+    // fixed-point values are written as plain numbers
+    let wheels = WheelState::new(Q16::from_f64(0.25), Q16::from_f64(-1.5));
+    let yaml = config::to_string(&wheels, ConfigFormat::Yaml).unwrap();
+    let back: WheelState<Q16> = config::from_str(&yaml, ConfigFormat::Yaml).unwrap();
+    assert_eq!(back.left, Q16::from_f64(0.25));
+    assert_eq!(back.right, Q16::from_f64(-1.5));
+}
+
+#[test]
+fn config_rejects_bad_input() {
+    assert!(ConfigFormat::from_path("robot.ini").is_err());
+    assert_eq!(
+        ConfigFormat::from_path("robot.YML").unwrap(),
+        ConfigFormat::Yaml
+    );
+    assert!(DiffDriveConfig::<f64>::from_toml("wheel_separation = 0.16").is_err());
+    assert!(DiffDriveConfig::<f64>::load("/nonexistent/robot.toml").is_err());
+}
@@ -0,0 +1,90 @@
+use diff_drive::coverage::{CoveragePlanner, Region};
+use diff_drive::geometry::Polygon;
+use diff_drive::rigid2d::{Transform2D, Vector2D};
+
+fn square_with_hole() -> Region<f64> {
+    let hole =
+        Polygon::rectangle(2.0, 2.0).transform(&Transform2D::new(Vector2D::new(5.0, 5.0), 0.0));
+    Region::new(
+        Polygon::rectangle(10.0, 10.0).transform(&Transform2D::new(Vector2D::new(5.0, 5.0), 0.0)),
+    )
+    .with_hole(hole)
+}
+
+#[test]
+fn rectangle_is_one_cell() {
+    let region = Region::new(Polygon::rectangle(4.0, 2.0));
+    let planner = CoveragePlanner::new(0.5).with_overlap(0.2);
+    let cells = planner.decompose(&region).unwrap();
+    assert_eq!(cells.len(), 1);
+    // passes are at most 0.4 m apart across 1.5 m of centerline
+    assert_eq!(cells[0].passes.len(), 5);
+
+    let plan = planner.plan(&region).unwrap();
+    assert!(plan.coverage > 95.0, "coverage {}", plan.coverage);
+    assert!(plan.length > 5.0 * 3.0);
+    assert_eq!(plan.to_path().to_vec().len(), plan.waypoints.len());
+}
+
+#[test]
+fn hole_splits_region_into_cells() {
+    let region = square_with_hole();
+    let planner = CoveragePlanner::new(0.5);
+    let cells = planner.decompose(&region).unwrap();
+    // below, left of, right of and above the hole
+    assert_eq!(cells.len(), 4);
+
+    let plan = planner.plan(&region).unwrap();
+    assert!(plan.coverage > 90.0, "coverage {}", plan.coverage);
+    for p in &plan.waypoints {
+        assert!(region.contains(*p), "{:?} leaves the region", (p.x, p.y));
+    }
+}
+
+#[test]
+fn turnarounds_respect_turning_radius() {
+    let region = Region::new(Polygon::rectangle(6.0, 4.0));
+    for radius in [0.2, 0.6] {
+        let planner = CoveragePlanner::new(0.5)
+            .with_overlap(0.0)
+            .with_turning_radius(radius);
+        let plan = planner.plan(&region).unwrap();
+        // curvature from three consecutive points never exceeds 1 / radius
+        for w in plan.waypoints.windows(3) {
+            let (a, b, c): (Vector2D<f64>, Vector2D<f64>, Vector2D<f64>) = (w[0], w[1], w[2]);
+            let ab = (b.x - a.x).hypot(b.y - a.y);
+            let bc = (c.x - b.x).hypot(c.y - b.y);
+            let ac = (c.x - a.x).hypot(c.y - a.y);
+            let cross = ((b.x - a.x) * (c.y - a.y) - (b.y - a.y) * (c.x - a.x)).abs();
+            if ab < 1e-9 || bc < 1e-9 || ab > 1.0 || bc > 1.0 {
+                continue;
+            }
+            let curvature = 2.0 * cross / (ab * bc * ac);
+            assert!(curvature <= 1.0 / radius + 1e-6, "curvature {}", curvature);
+        }
+        for p in &plan.waypoints {
+            assert!(region.contains(*p) || region.outer.boundary_distance(*p) < 1e-9);
+        }
+    }
+}
+
+#[test]
+fn sweep_angle_and_bad_parameters() {
+    let region = Region::new(Polygon::rectangle(8.0, 2.0));
+    let along = CoveragePlanner::new(0.5).plan(&region).unwrap();
+    let across = CoveragePlanner::new(0.5)
+        .with_sweep_angle(std::f64::consts::FRAC_PI_2)
+        .plan(&region)
+        .unwrap();
+    assert_eq!(along.cells[0].passes.len(), 5);
+    assert_eq!(across.cells[0].passes.len(), 18);
+    let (a, b) = across.cells[0].passes[0];
+    assert!((a.x - b.x).abs() < 1e-9);
+
+    assert!(CoveragePlanner::new(0.0).plan(&region).is_err());
+    assert!(CoveragePlanner::new(0.5)
+        .with_overlap(1.0)
+        .plan(&region)
+        .is_err());
+    assert!(CoveragePlanner::new(5.0).plan(&region).is_err());
+}
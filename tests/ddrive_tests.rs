@@ -1,4 +1,4 @@
-use diff_drive::ddrive::{DiffDrive, WheelState};
+use diff_drive::ddrive::{DiffDrive, EncoderTicks, WheelParams, WheelState};
 use diff_drive::rigid2d::Twist2D;
 use diff_drive::utils::almost_equal;
 use std::f64::consts::PI;
@@ -102,3 +102,59 @@ fn fk_test2() {
     }
     print!("\n\n\n");
 }
+
+#[test]
+fn wheelparams_conversions() {
+    let wheel = WheelParams::new(0.05, 1024.0, 20.0);
+    assert!(almost_equal(
+        wheel.ticks_to_wheel(20.0 * 1024.0),
+        2.0 * PI,
+        1e-12
+    ));
+    assert!(almost_equal(wheel.wheel_to_ticks(PI), 10.0 * 1024.0, 1e-9));
+    assert!(almost_equal(
+        wheel.motor_to_wheel(40.0 * PI),
+        2.0 * PI,
+        1e-12
+    ));
+    assert!(almost_equal(wheel.wheel_to_motor(1.0), 20.0, 1e-12));
+
+    let direct = WheelParams::from_radius(0.05);
+    assert!(almost_equal(direct.ticks_to_wheel(1.5), 1.5, 1e-12));
+}
+
+#[test]
+fn diffdrive_fk_from_ticks_and_motor_angles() {
+    let left = WheelParams::new(1.0, 1000.0, 10.0);
+    let right = WheelParams::new(1.0, 500.0, 5.0);
+
+    // one wheel revolution forward on both sides
+    let mut robot = DiffDrive::from_wheel_params(left, right, 2.0);
+    let pose = robot.forward_kinematics_ticks(EncoderTicks::new(10000, 2500));
+    assert!(almost_equal(pose.x, 2.0 * PI, 1e-9));
+    assert!(almost_equal(pose.theta, 0.0, 1e-9));
+
+    let mut robot = DiffDrive::from_wheel_params(left, right, 2.0);
+    let pose = robot.forward_kinematics_motor(WheelState::new(10.0 * PI, 5.0 * PI));
+    assert!(almost_equal(pose.x, PI, 1e-9));
+}
+
+#[test]
+fn diffdrive_twist_from_ticks_and_motor_speeds() {
+    let left = WheelParams::new(0.05, 2048.0, 30.0);
+    let right = WheelParams::new(0.051, 2048.0, 30.0);
+    let robot = DiffDrive::from_wheel_params(left, right, 0.3).with_separation_scale(1.1);
+    assert!(almost_equal(robot.wheel_separation(), 0.33, 1e-12));
+
+    let v = Twist2D::new(0.4, 0.2, 0.0);
+    let rates = robot.tick_rates_from_twist(Twist2D::new(0.4, 0.2, 0.0));
+    let back = robot.twist_from_tick_rates(rates);
+    assert!(almost_equal(back.thetadot, v.thetadot, 1e-9));
+    assert!(almost_equal(back.xdot, v.xdot, 1e-9));
+
+    let motor = robot.motor_speeds_from_twist(Twist2D::new(0.4, 0.2, 0.0));
+    let wheel = robot.speeds_from_twist(Twist2D::new(0.4, 0.2, 0.0));
+    assert!(almost_equal(motor.left, 30.0 * wheel.left, 1e-9));
+    let back = robot.twist_from_motor_speeds(motor);
+    assert!(almost_equal(back.thetadot, 0.4, 1e-9));
+}
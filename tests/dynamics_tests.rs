@@ -0,0 +1,126 @@
+use diff_drive::ddrive::WheelState;
+use diff_drive::dynamics::{DriveInput, DynamicDiffDrive, DynamicsParams, MotorParams};
+use diff_drive::rigid2d::Twist2D;
+use diff_drive::utils::almost_equal;
+
+fn params() -> DynamicsParams<f64> {
+    DynamicsParams {
+        mass: 10.0,
+        inertia: 0.5,
+        wheel_radius: 0.05,
+        wheel_separation: 0.3,
+        wheel_inertia: 1e-3,
+        motor: MotorParams::new(0.02, 0.02, 1.0, 1e-6, 20.0),
+        coulomb_friction: 0.01,
+        viscous_friction: 1e-3,
+        traction_coefficient: 0.8,
+        gravity: 9.81,
+    }
+}
+
+fn run(robot: &mut DynamicDiffDrive<f64>, input: DriveInput<f64>, seconds: f64, dt: f64) {
+    for _ in 0..(seconds / dt).round() as usize {
+        robot.step(input, dt);
+    }
+}
+
+#[test]
+fn dynamics_reaches_motor_steady_state_speed() {
+    let p = params();
+    let mut robot = DynamicDiffDrive::new(p);
+    run(
+        &mut robot,
+        DriveInput::Voltage(WheelState::new(12.0, 12.0)),
+        2.0,
+        1e-3,
+    );
+
+    // motor torque balances axle friction once the robot stops accelerating
+    let n = p.motor.gear_ratio;
+    let drive = n * p.motor.torque_constant * 12.0 / p.motor.resistance - p.coulomb_friction;
+    let damping = n * n * p.motor.torque_constant * p.motor.back_emf_constant / p.motor.resistance
+        + p.viscous_friction;
+    let expected = drive / damping * p.wheel_radius;
+    assert!(almost_equal(robot.twist().xdot, expected, 1e-6));
+    assert!(almost_equal(robot.twist().thetadot, 0.0, 1e-12));
+    assert!(almost_equal(robot.pose().y, 0.0, 1e-12));
+}
+
+#[test]
+fn dynamics_agrees_with_kinematic_model_without_slip() {
+    let mut robot = DynamicDiffDrive::new(params());
+    let mut kinematic = robot.kinematic();
+    let mut phi = WheelState::new(0.0, 0.0);
+    let dt = 1e-3;
+    for i in 0..3000 {
+        let voltage = if i < 1500 {
+            WheelState::new(3.0, 4.0)
+        } else {
+            WheelState::new(4.0, 4.0)
+        };
+        let before = robot.wheel_speeds();
+        robot.step(DriveInput::Voltage(voltage), dt);
+        let after = robot.wheel_speeds();
+        phi.left += (before.left + after.left) / 2.0 * dt;
+        phi.right += (before.right + after.right) / 2.0 * dt;
+        kinematic.forward_kinematics(phi);
+        assert_eq!(robot.slipping(), (false, false));
+    }
+    let (a, b) = (robot.pose(), kinematic.pose());
+    assert!(a.theta.abs() > 0.1);
+    assert!(almost_equal(a.x, b.x, 1e-9));
+    assert!(almost_equal(a.y, b.y, 1e-9));
+    assert!(almost_equal(a.theta, b.theta, 1e-9));
+}
+
+#[test]
+fn dynamics_wheels_slip_beyond_traction_limit() {
+    let mut p = params();
+    p.traction_coefficient = 0.05;
+    let mut robot = DynamicDiffDrive::new(p);
+    robot.step(DriveInput::Voltage(WheelState::new(24.0, 24.0)), 1e-3);
+    assert_eq!(robot.slipping(), (true, true));
+    assert!(almost_equal(
+        robot.acceleration().0,
+        robot.traction_limit(),
+        1e-12
+    ));
+
+    // the spinning wheels outrun the ground
+    run(
+        &mut robot,
+        DriveInput::Voltage(WheelState::new(24.0, 24.0)),
+        0.1,
+        1e-3,
+    );
+    let speeds = robot.wheel_speeds();
+    assert!(speeds.left * p.wheel_radius > robot.twist().xdot + 0.1);
+}
+
+#[test]
+fn dynamics_stiction_holds_robot_at_rest() {
+    let mut robot = DynamicDiffDrive::new(params());
+    // 0.001 V gives less wheel torque than the Coulomb friction
+    run(
+        &mut robot,
+        DriveInput::Voltage(WheelState::new(0.001, 0.001)),
+        1.0,
+        1e-3,
+    );
+    assert_eq!(robot.twist().xdot, 0.0);
+    assert_eq!(robot.pose().x, 0.0);
+}
+
+#[test]
+fn dynamics_feedforward_tracks_acceleration() {
+    let mut robot = DynamicDiffDrive::new(params());
+    let dt = 1e-3;
+    for _ in 0..1000 {
+        let voltage = robot.feedforward_voltage(&robot.twist(), 0.5, 1.0);
+        robot.step(DriveInput::Voltage(voltage), dt);
+    }
+    let twist: Twist2D<f64> = robot.twist();
+    assert!(almost_equal(twist.xdot, 0.5, 1e-3));
+    assert!(almost_equal(twist.thetadot, 1.0, 1e-3));
+    assert!(robot.max_acceleration(12.0) > 0.5);
+}
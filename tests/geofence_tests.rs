@@ -0,0 +1,215 @@
+use diff_drive::geofence::{FenceAction, FenceOutcome, GeoZone, Geofence, Violation, ZoneKind};
+use diff_drive::geometry::Polygon;
+use diff_drive::rigid2d::{Pose2D, Twist2D, Vector2D};
+use diff_drive::utils::almost_equal;
+
+fn square(x0: f64, y0: f64, x1: f64, y1: f64) -> Polygon<f64> {
+    Polygon::new(vec![
+        Vector2D::new(x0, y0),
+        Vector2D::new(x1, y0),
+        Vector2D::new(x1, y1),
+        Vector2D::new(x0, y1),
+    ])
+}
+
+/// a 10 m square site with a pillar and a slow zone
+fn site() -> Geofence<f64> {
+    Geofence::new(Polygon::rectangle(0.6, 0.4))
+        .with_zone(GeoZone::new(
+            "site",
+            ZoneKind::Inclusion,
+            square(-5.0, -5.0, 5.0, 5.0),
+        ))
+        .with_zone(GeoZone::new(
+            "pillar",
+            ZoneKind::Exclusion,
+            square(3.0, -1.0, 5.0, 1.0),
+        ))
+        .with_zone(GeoZone::new(
+            "loading dock",
+            ZoneKind::SpeedLimit {
+                linear: 0.3,
+                angular: 0.5,
+            },
+            square(-5.0, -5.0, -2.0, 5.0),
+        ))
+}
+
+fn forward(speed: f64) -> Twist2D<f64> {
+    Twist2D::new(0.0, speed, 0.0)
+}
+
+#[test]
+fn checks_footprint_against_zones() {
+    let fence = site();
+    let status = fence.check(Pose2D::new(0.0, 0.0, 0.0));
+    assert!(status.is_allowed());
+    assert!(status.speed_limit.0.is_infinite());
+
+    assert_eq!(
+        fence.check(Pose2D::new(2.8, 0.0, 0.0)).violation,
+        Some(Violation::InsideExclusion("pillar".to_string()))
+    );
+    assert_eq!(
+        fence.check(Pose2D::new(4.8, 3.0, 0.0)).violation,
+        Some(Violation::OutsideInclusion)
+    );
+    // turned sideways the footprint fits
+    assert!(fence
+        .check(Pose2D::new(4.75, 3.0, std::f64::consts::FRAC_PI_2))
+        .is_allowed());
+
+    let slow = fence.check(Pose2D::new(-3.0, 0.0, 0.0));
+    assert!(slow.is_allowed());
+    assert_eq!(slow.speed_limit, (0.3, 0.5));
+}
+
+#[test]
+fn footprint_must_fit_in_one_inclusion_zone() {
+    // two rooms joined at a corner
+    let fence = Geofence::new(Polygon::rectangle(0.6, 0.4))
+        .with_zone(GeoZone::new(
+            "left room",
+            ZoneKind::Inclusion,
+            square(-2.0, -2.0, 0.0, 0.0),
+        ))
+        .with_zone(GeoZone::new(
+            "right room",
+            ZoneKind::Inclusion,
+            square(0.0, 0.0, 2.0, 2.0),
+        ));
+    assert!(fence.check(Pose2D::new(-1.0, -1.0, 0.0)).is_allowed());
+
+    // every corner is in some room, but the footprint straddles both
+    let diagonal = Pose2D::new(0.0, 0.0, std::f64::consts::FRAC_PI_4);
+    assert_eq!(
+        fence.check(diagonal).violation,
+        Some(Violation::OutsideInclusion)
+    );
+
+    // every corner of a triangle is in an L-shaped corridor, but an edge
+    // cuts across its inner corner
+    let footprint = Polygon::new(vec![
+        Vector2D::new(-1.1, -1.1),
+        Vector2D::new(0.6, -1.1),
+        Vector2D::new(0.6, 0.9),
+    ]);
+    let fence = Geofence::new(footprint).with_zone(GeoZone::new(
+        "corridor",
+        ZoneKind::Inclusion,
+        Polygon::new(vec![
+            Vector2D::new(10.0, 0.0),
+            Vector2D::new(14.0, 0.0),
+            Vector2D::new(14.0, 4.0),
+            Vector2D::new(13.0, 4.0),
+            Vector2D::new(13.0, 1.0),
+            Vector2D::new(10.0, 1.0),
+        ]),
+    ));
+    assert_eq!(
+        fence.check(Pose2D::new(13.3, 1.2, 0.0)).violation,
+        Some(Violation::OutsideInclusion)
+    );
+}
+
+#[test]
+fn clamps_or_rejects_commands_leaving_the_allowed_area() {
+    let fence = site();
+    let (command, outcome) = fence.filter(Pose2D::new(0.0, 0.0, 0.0), forward(1.0));
+    assert_eq!(outcome, FenceOutcome::Allowed);
+    assert_eq!(command.xdot, 1.0);
+
+    // the front edge starts at 1.3 m and may move 1.7 m in the 2 s lookahead
+    let (command, outcome) = fence.filter(Pose2D::new(1.0, 0.0, 0.0), forward(1.0));
+    match outcome {
+        FenceOutcome::Clamped { scale, violation } => {
+            assert!(almost_equal(scale, 0.85, 0.03), "scale {}", scale);
+            assert_eq!(violation, Violation::InsideExclusion("pillar".to_string()));
+            assert!(almost_equal(command.xdot, scale, 1e-12));
+        }
+        other => panic!("unexpected outcome {:?}", other),
+    }
+
+    let rejecting = site().with_action(FenceAction::Reject);
+    let (command, outcome) = rejecting.filter(Pose2D::new(1.0, 0.0, 0.0), forward(1.0));
+    assert!(matches!(outcome, FenceOutcome::Rejected { .. }));
+    assert_eq!(command.xdot, 0.0);
+}
+
+#[test]
+fn limits_speed_ahead_of_slow_zones() {
+    let fence = site();
+    let (command, outcome) =
+        fence.filter(Pose2D::new(-1.0, 0.0, std::f64::consts::PI), forward(1.0));
+    assert_eq!(
+        outcome,
+        FenceOutcome::SpeedLimited {
+            zone: "loading dock".to_string()
+        }
+    );
+    assert!(almost_equal(command.xdot, 0.3, 1e-12));
+}
+
+#[test]
+fn only_lets_a_robot_inside_an_exclusion_zone_drive_out() {
+    let fence = site();
+    let pose = Pose2D::new(4.0, 0.0, 0.0);
+    let (command, outcome) = fence.filter(pose, forward(0.5));
+    assert_eq!(
+        outcome,
+        FenceOutcome::Rejected {
+            violation: Violation::InsideExclusion("pillar".to_string())
+        }
+    );
+    assert_eq!(command.xdot, 0.0);
+
+    let (command, outcome) = fence.filter(pose, forward(-1.0));
+    assert_eq!(outcome, FenceOutcome::Allowed);
+    assert_eq!(command.xdot, -1.0);
+}
+
+#[cfg(feature = "geojson")]
+#[test]
+fn loads_zones_from_geojson() {
+    let text = r#"{
+        "type": "FeatureCollection",
+        "features": [
+            {
+                "type": "Feature",
+                "properties": {"kind": "exclusion", "name": "pillar"},
+                "geometry": {
+                    "type": "Polygon",
+                    "coordinates": [[[3, -1], [5, -1], [5, 1], [3, 1], [3, -1]]]
+                }
+            },
+            {
+                "type": "Feature",
+                "properties": {"kind": "speed_limit", "max_linear": 0.3},
+                "geometry": {
+                    "type": "MultiPolygon",
+                    "coordinates": [
+                        [[[0, 0], [1, 0], [1, 1]]],
+                        [[[2, 0], [3, 0], [3, 1]]]
+                    ]
+                }
+            }
+        ]
+    }"#;
+    let zones = Geofence::parse_geojson(text).unwrap();
+    assert_eq!(zones.len(), 3);
+    assert_eq!(zones[0].name, "pillar");
+    assert_eq!(zones[0].kind, ZoneKind::Exclusion);
+    assert_eq!(zones[0].polygon.vertices().len(), 4);
+    assert_eq!(zones[2].name, "zone 1");
+    assert_eq!(
+        zones[2].kind,
+        ZoneKind::SpeedLimit {
+            linear: 0.3,
+            angular: f64::INFINITY
+        }
+    );
+
+    let fence = Geofence::new(Polygon::rectangle(0.6, 0.4)).with_zones(zones);
+    assert!(!fence.check(Pose2D::new(4.0, 0.0, 0.0)).is_allowed());
+    assert!(Geofence::parse_geojson(r#"{"type": "Feature"}"#).is_err());
+}
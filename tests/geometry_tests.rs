@@ -0,0 +1,48 @@
+use diff_drive::geometry::Polygon;
+use diff_drive::rigid2d::{Transform2D, Vector2D};
+use diff_drive::utils::almost_equal;
+
+#[test]
+fn polygon_contains_and_distance() {
+    let square = Polygon::rectangle(2.0, 2.0);
+    assert!(almost_equal(square.area(), 4.0, 1e-12));
+    assert!(square.contains(Vector2D::new(0.5, -0.5)));
+    assert!(square.contains(Vector2D::new(1.0, 0.0)));
+    assert!(!square.contains(Vector2D::new(1.5, 0.0)));
+    assert_eq!(square.distance(Vector2D::new(0.2, 0.3)), 0.0);
+    assert!(almost_equal(
+        square.boundary_distance(Vector2D::new(0.2, 0.3)),
+        0.7,
+        1e-12
+    ));
+    assert!(almost_equal(
+        square.distance(Vector2D::new(4.0, 5.0)),
+        5.0,
+        1e-12
+    ));
+    assert!(almost_equal(square.bounding_radius(), 2.0f64.sqrt(), 1e-12));
+}
+
+#[test]
+fn concave_polygon_and_transform() {
+    // an L shape, with the notch at the top right
+    let l = Polygon::new(vec![
+        Vector2D::new(0.0, 0.0),
+        Vector2D::new(2.0, 0.0),
+        Vector2D::new(2.0, 1.0),
+        Vector2D::new(1.0, 1.0),
+        Vector2D::new(1.0, 2.0),
+        Vector2D::new(0.0, 2.0),
+    ]);
+    assert!(almost_equal(l.area(), 3.0, 1e-12));
+    assert!(l.contains(Vector2D::new(0.5, 1.5)));
+    assert!(!l.contains(Vector2D::new(1.5, 1.5)));
+
+    let moved = l.transform(&Transform2D::new(
+        Vector2D::new(10.0, 0.0),
+        std::f64::consts::FRAC_PI_2,
+    ));
+    assert!(almost_equal(moved.area(), 3.0, 1e-12));
+    assert!(moved.contains(Vector2D::new(9.5, 0.5)));
+    assert!(!moved.contains(Vector2D::new(0.5, 0.5)));
+}
@@ -0,0 +1,88 @@
+use diff_drive::holonomic::{HolonomicDrive, WheelGeometry};
+use diff_drive::kinematics::{Kinematics, Mecanum, Omni3};
+use diff_drive::rigid2d::{Pose2D, Twist2D, Vector2D};
+use diff_drive::utils::almost_equal;
+
+fn assert_twist(a: &Twist2D<f64>, b: &Twist2D<f64>) {
+    assert!(almost_equal(a.thetadot, b.thetadot, 1e-9), "{} != {}", a, b);
+    assert!(almost_equal(a.xdot, b.xdot, 1e-9), "{} != {}", a, b);
+    assert!(almost_equal(a.ydot, b.ydot, 1e-9), "{} != {}", a, b);
+}
+
+#[test]
+fn holonomic_matches_mecanum() {
+    let general = HolonomicDrive::mecanum(0.05, 0.4, 0.3).unwrap();
+    let mecanum = Mecanum::new(0.05, 0.4, 0.3);
+    let twist = Twist2D::new(0.3, 0.5, -0.2);
+
+    let a = general.wheels_from_twist(&twist).unwrap();
+    let b = mecanum.wheels_from_twist(&twist).unwrap();
+    for i in 0..4 {
+        assert!(almost_equal(a[i], b[i], 1e-9));
+    }
+    assert_twist(&general.twist_from_wheels(&a), &twist);
+}
+
+#[test]
+fn holonomic_matches_omni3() {
+    let general = HolonomicDrive::omni(0.03, 0.15, 3).unwrap();
+    let omni = Omni3::new(0.03, 0.15);
+    let twist = Twist2D::new(-0.7, 0.1, 0.4);
+
+    let a = general.wheels_from_twist(&twist).unwrap();
+    let b = omni.wheels_from_twist(&twist).unwrap();
+    for i in 0..3 {
+        assert!(almost_equal(a[i], b[i], 1e-9));
+    }
+}
+
+#[test]
+fn holonomic_least_squares_odometry() {
+    // four omni wheels over-determine the twist
+    let base: HolonomicDrive<f64> = HolonomicDrive::omni(0.03, 0.2, 4).unwrap();
+    let twist = Twist2D::new(0.2, -0.3, 0.6);
+    let mut wheels = base.wheels_from_twist(&twist).unwrap();
+    assert!(base.residual(&wheels) < 1e-9);
+
+    // one wheel slipping shows up in the residual, and is averaged out of the twist
+    wheels[0] += 1.0;
+    assert!(base.residual(&wheels) > 0.1);
+    let estimate = base.twist_from_wheels(&wheels);
+    assert!((estimate.xdot - twist.xdot).abs() < 0.03);
+
+    let pose = base.odometry_update(Pose2D::new(0.0, 0.0, 0.0), &vec![0.0; 4]);
+    assert!(almost_equal(pose.x, 0.0, 1e-12));
+}
+
+#[test]
+fn holonomic_custom_geometry() {
+    // two forward drive wheels and a sideways wheel at the back
+    let wheels = vec![
+        WheelGeometry::new(Vector2D::new(0.0, 0.2), 0.0, 0.0, 0.05),
+        WheelGeometry::new(Vector2D::new(0.0, -0.2), 0.0, 0.0, 0.05),
+        WheelGeometry::new(
+            Vector2D::new(-0.3, 0.0),
+            std::f64::consts::FRAC_PI_2,
+            0.0,
+            0.05,
+        ),
+    ];
+    let base = HolonomicDrive::new(wheels).unwrap();
+    assert_eq!(base.jacobian().len(), 3);
+    let twist = Twist2D::new(0.5, 0.4, 0.1);
+    let speeds = base.wheels_from_twist(&twist).unwrap();
+    assert!(almost_equal(speeds[0], (0.4 - 0.5 * 0.2) / 0.05, 1e-12));
+    assert_twist(&base.twist_from_wheels(&speeds), &twist);
+}
+
+#[test]
+fn holonomic_rejects_degenerate_geometry() {
+    // all wheels driving along x can not move the base sideways
+    let wheels = vec![
+        WheelGeometry::new(Vector2D::new(0.0, 0.2), 0.0, 0.0, 0.05),
+        WheelGeometry::new(Vector2D::new(0.0, -0.2), 0.0, 0.0, 0.05),
+        WheelGeometry::new(Vector2D::new(0.3, 0.0), 0.0, 0.0, 0.05),
+    ];
+    assert!(HolonomicDrive::new(wheels).is_err());
+    assert!(HolonomicDrive::<f64>::omni(0.03, 0.2, 2).is_err());
+}
@@ -0,0 +1,125 @@
+use diff_drive::ddrive::{DiffDrive, WheelState};
+use diff_drive::kinematics::{Ackermann, AckermannState, Kinematics, Mecanum, Omni3, SkidSteer};
+use diff_drive::rigid2d::{Pose2D, Twist2D};
+use diff_drive::utils::almost_equal;
+use std::f64::consts::PI;
+
+fn assert_twist(a: &Twist2D<f64>, b: &Twist2D<f64>) {
+    assert!(almost_equal(a.thetadot, b.thetadot, 1e-9), "{} != {}", a, b);
+    assert!(almost_equal(a.xdot, b.xdot, 1e-9), "{} != {}", a, b);
+    assert!(almost_equal(a.ydot, b.ydot, 1e-9), "{} != {}", a, b);
+}
+
+/// checks that the twist survives a trip through the wheel state
+fn round_trip<K: Kinematics<f64>>(base: &K, twist: Twist2D<f64>) {
+    let wheels = base.wheels_from_twist(&twist).unwrap();
+    assert_twist(&base.twist_from_wheels(&wheels), &twist);
+}
+
+/// a function written once for every base type
+fn drive_straight<K: Kinematics<f64>>(base: &K, distance: f64) -> Pose2D<f64> {
+    let wheels = base
+        .wheels_from_twist(&Twist2D::new(0.0, distance, 0.0))
+        .unwrap();
+    base.odometry_update(Pose2D::new(1.0, 2.0, PI / 2.0), &wheels)
+}
+
+#[test]
+fn diffdrive_kinematics() {
+    let robot = DiffDrive::new(0.05, 0.3);
+    round_trip(&robot, Twist2D::new(0.5, 0.2, 0.0));
+    assert!(robot
+        .wheels_from_twist(&Twist2D::new(0.0, 0.0, 0.1))
+        .is_err());
+
+    let wheels = WheelState::new(PI, 0.0);
+    assert_twist(
+        &robot.twist_from_wheels(&wheels),
+        &robot.twist_from_speeds(wheels),
+    );
+}
+
+#[test]
+fn skid_steer_kinematics() {
+    // without slip a skid-steer base is a differential drive
+    let skid = SkidSteer::new(0.1, 0.5, 1.0);
+    let diff = DiffDrive::new(0.1, 0.5);
+    let wheels = WheelState::new(1.0, 3.0);
+    assert_twist(
+        &skid.twist_from_wheels(&wheels),
+        &diff.twist_from_wheels(&wheels),
+    );
+
+    // slip makes the base turn slower for the same wheel speeds
+    let slipping = SkidSteer::new(0.1, 0.5, 1.5);
+    let twist = slipping.twist_from_wheels(&wheels);
+    assert!(almost_equal(twist.thetadot, 0.4 / 1.5, 1e-12));
+    round_trip(&slipping, Twist2D::new(0.3, 0.5, 0.0));
+
+    // an ICR offset couples turning and sideways motion
+    let offset = SkidSteer::from_icr(0.1, 0.3, -0.35, 0.05);
+    round_trip(&offset, Twist2D::new(0.4, 0.2, -0.02));
+    assert!(offset
+        .wheels_from_twist(&Twist2D::new(0.4, 0.2, 0.0))
+        .is_err());
+}
+
+#[test]
+fn mecanum_kinematics() {
+    let base = Mecanum::new(0.05, 0.4, 0.3);
+    round_trip(&base, Twist2D::new(0.3, 0.5, -0.2));
+
+    // pure sideways motion spins the diagonal wheel pairs in opposite directions
+    let wheels = base
+        .wheels_from_twist(&Twist2D::new(0.0, 0.0, 1.0))
+        .unwrap();
+    assert!(almost_equal(wheels[0], -wheels[1], 1e-12));
+    assert!(almost_equal(wheels[0], wheels[3], 1e-12));
+    assert!(almost_equal(wheels[1], wheels[2], 1e-12));
+}
+
+#[test]
+fn omni_kinematics() {
+    let base = Omni3::new(0.03, 0.15);
+    round_trip(&base, Twist2D::new(-0.7, 0.1, 0.4));
+
+    // spinning in place drives all wheels equally
+    let wheels = base
+        .wheels_from_twist(&Twist2D::new(1.0, 0.0, 0.0))
+        .unwrap();
+    for w in wheels {
+        assert!(almost_equal(w, 5.0, 1e-12));
+    }
+}
+
+#[test]
+fn ackermann_kinematics() {
+    let car = Ackermann::new(0.1, 1.0, 0.6, 0.6);
+    round_trip(&car, Twist2D::new(0.2, 1.0, 0.0));
+
+    let twist = car.twist_from_wheels(&AckermannState::new(10.0, PI / 4.0));
+    assert!(almost_equal(twist.thetadot, 1.0, 1e-12));
+    assert!(car.wheels_from_twist(&Twist2D::new(1.0, 0.0, 0.0)).is_err());
+    assert!(car.wheels_from_twist(&Twist2D::new(5.0, 1.0, 0.0)).is_err());
+    assert!(car.wheels_from_twist(&Twist2D::new(0.0, 1.0, 0.5)).is_err());
+
+    // the inner wheel turns more sharply than the outer wheel
+    let (left, right) = car.front_wheel_angles(0.3);
+    assert!(left > 0.3 && right < 0.3 && right > 0.0);
+}
+
+#[test]
+fn odometry_update_is_shared_by_all_bases() {
+    let poses = [
+        drive_straight(&DiffDrive::new(0.05, 0.3), 2.0),
+        drive_straight(&SkidSteer::new(0.1, 0.5, 1.3), 2.0),
+        drive_straight(&Mecanum::new(0.05, 0.4, 0.3), 2.0),
+        drive_straight(&Omni3::new(0.03, 0.15), 2.0),
+        drive_straight(&Ackermann::new(0.1, 1.0, 0.6, 0.6), 2.0),
+    ];
+    for pose in poses {
+        assert!(almost_equal(pose.x, 1.0, 1e-9));
+        assert!(almost_equal(pose.y, 4.0, 1e-9));
+        assert!(almost_equal(pose.theta, PI / 2.0, 1e-9));
+    }
+}
@@ -0,0 +1,107 @@
+use diff_drive::ddrive::{DiffDrive, WheelState};
+use diff_drive::mapping::{log_odds, probability, CellState, OccupancyGrid};
+use diff_drive::rigid2d::{Pose2D, Vector2D};
+use diff_drive::scan::LaserScan;
+use diff_drive::sim::{LidarConfig, Simulator, World};
+use diff_drive::utils::almost_equal;
+use std::f64::consts::PI;
+
+#[test]
+fn log_odds_round_trip() {
+    assert!(almost_equal(log_odds(0.5), 0.0, 1e-12));
+    assert!(almost_equal(probability(log_odds(0.8)), 0.8, 1e-12));
+}
+
+#[test]
+fn grid_world_to_cell() {
+    let grid = OccupancyGrid::new(10, 20, 0.5, Vector2D::new(-1.0, -2.0));
+    assert_eq!(grid.world_to_cell(Vector2D::new(-1.0, -2.0)), Some((0, 0)));
+    assert_eq!(grid.world_to_cell(Vector2D::new(0.1, 0.1)), Some((2, 4)));
+    assert_eq!(grid.world_to_cell(Vector2D::new(4.1, 0.0)), None);
+    let center = grid.cell_to_world(2, 4);
+    assert!(almost_equal(center.x, 0.25, 1e-12));
+    assert!(almost_equal(center.y, 0.25, 1e-12));
+}
+
+#[test]
+fn grid_single_beam() {
+    let mut grid = OccupancyGrid::new(20, 1, 0.1, Vector2D::new(0.0, 0.0));
+    let scan = LaserScan::new(0.0, 0.0, 0.0, 5.0, vec![1.0]);
+    for _ in 0..5 {
+        grid.integrate_scan(Pose2D::new(0.05, 0.05, 0.0), &scan);
+    }
+    for ix in 0..10 {
+        assert_eq!(grid.state(ix, 0), CellState::Free);
+    }
+    assert_eq!(grid.state(10, 0), CellState::Occupied);
+    assert_eq!(grid.state(11, 0), CellState::Unknown);
+}
+
+#[test]
+fn grid_clamping() {
+    let mut grid = OccupancyGrid::new(1, 1, 1.0, Vector2D::new(0.0, 0.0)).with_clamping(-1.0, 1.0);
+    for _ in 0..100 {
+        grid.update_cell(0, 0, 0.5);
+    }
+    assert!(almost_equal(grid.log_odds(0, 0), 1.0, 1e-12));
+    grid.update_cell(0, 0, -0.5);
+    assert!(almost_equal(grid.log_odds(0, 0), 0.5, 1e-12));
+}
+
+#[test]
+fn grid_from_scripted_drive() {
+    // a 4 x 4 m room with the robot starting in the middle
+    let mut world = World::new();
+    world.add_rectangle(Vector2D::new(0.0, 0.0), Vector2D::new(4.0, 4.0));
+    let lidar = LidarConfig::new(-PI, PI, 360, 0.05, 10.0);
+    let mut robot = DiffDrive::new(0.05, 0.3);
+    robot.set_pose(Pose2D::new(2.0, 2.0, 0.0));
+    let mut sim = Simulator::new(robot, world, lidar);
+
+    // spin in place, then drive forward
+    let mut script = vec![WheelState::new(-0.5, 0.5); 10];
+    script.extend(vec![WheelState::new(1.0, 1.0); 10]);
+
+    let mut grid = OccupancyGrid::new(60, 60, 0.1, Vector2D::new(-1.0, -1.0));
+    for (pose, scan) in sim.run(&script) {
+        grid.integrate_scan(pose, &scan);
+    }
+
+    // the walls are occupied, the room is free and the outside is unknown
+    let wall = grid.world_to_cell(Vector2D::new(4.0, 2.05)).unwrap();
+    assert_eq!(grid.state(wall.0, wall.1), CellState::Occupied);
+    let inside = grid.world_to_cell(Vector2D::new(1.05, 3.05)).unwrap();
+    assert_eq!(grid.state(inside.0, inside.1), CellState::Free);
+    let outside = grid.world_to_cell(Vector2D::new(-0.5, -0.5)).unwrap();
+    assert_eq!(grid.state(outside.0, outside.1), CellState::Unknown);
+
+    let states = grid.states();
+    assert_eq!(states.len(), 3600);
+    assert!(states.contains(&CellState::Occupied));
+
+    let pgm = grid.to_pgm();
+    assert!(pgm.starts_with(b"P5\n60 60\n255\n"));
+    assert_eq!(pgm.len(), b"P5\n60 60\n255\n".len() + 3600);
+    let filename = std::env::temp_dir().join("diff_drive_mapping_test.pgm");
+    grid.write_pgm(filename.to_str().unwrap()).unwrap();
+}
+
+#[test]
+fn grid_ignores_unbounded_beams_and_bad_poses() {
+    let mut grid = OccupancyGrid::new(20, 1, 0.1, Vector2D::new(0.0, 0.0));
+    // a beam without a return and an unlimited range clears to the edge
+    let scan = LaserScan::new(0.0, 0.0, 0.0, f64::INFINITY, vec![f64::INFINITY]);
+    for _ in 0..5 {
+        grid.integrate_scan(Pose2D::new(0.05, 0.05, 0.0), &scan);
+    }
+    for ix in 0..20 {
+        assert_eq!(grid.state(ix, 0), CellState::Free);
+    }
+
+    let before = grid.states();
+    let scan = LaserScan::new(0.0, 0.0, 0.0, 5.0, vec![1.0]);
+    grid.integrate_scan(Pose2D::new(f64::NAN, 0.05, 0.0), &scan);
+    grid.integrate_scan(Pose2D::new(1e30, 0.05, PI), &scan);
+    assert_eq!(grid.states(), before);
+    assert_eq!(grid.world_to_cell(Vector2D::new(f64::NAN, 0.0)), None);
+}
@@ -0,0 +1,100 @@
+#![cfg(feature = "mcap")]
+
+use diff_drive::ddrive::{DiffDrive, WheelState};
+use diff_drive::mcap::{McapLog, McapWriter, MAGIC};
+use diff_drive::rigid2d::{Pose2D, Twist2D};
+
+fn write_run(writer: &mut McapWriter<Vec<u8>>) -> Vec<(f64, Pose2D<f64>)> {
+    let mut robot = DiffDrive::new(0.033, 0.16);
+    let mut poses = vec![];
+    for k in 1..=100 {
+        let t = k as f64 * 0.01;
+        let wheels = WheelState::new(0.2 * k as f64, 0.25 * k as f64);
+        let pose = robot.forward_kinematics(wheels);
+        writer.write_wheels("/wheels", t, &wheels).unwrap();
+        writer
+            .write_twist("/cmd_vel", t, &Twist2D::new(0.1, 0.5, 0.0))
+            .unwrap();
+        writer.write_pose("/odom", t, &pose).unwrap();
+        poses.push((t, pose));
+    }
+    let path: Vec<Pose2D<f64>> = poses.iter().map(|(_, p)| *p).collect();
+    writer.write_path("/plan", 1.5, &path).unwrap();
+    poses
+}
+
+#[test]
+fn mcap_round_trips_streams() {
+    let mut writer = McapWriter::new(vec![]).unwrap();
+    let poses = write_run(&mut writer);
+    let bytes = writer.finish().unwrap();
+    assert_eq!(bytes[..8], MAGIC);
+    assert_eq!(bytes[bytes.len() - 8..], MAGIC);
+
+    let log = McapLog::from_bytes(&bytes).unwrap();
+    assert_eq!(log.topics(), vec!["/wheels", "/cmd_vel", "/odom", "/plan"]);
+    assert_eq!(
+        log.channel("/odom").unwrap().schema_name.as_deref(),
+        Some("diff_drive.Pose2D")
+    );
+
+    let read = log.poses("/odom").unwrap();
+    assert_eq!(read.len(), 100);
+    for ((t, pose), (t_read, pose_read)) in poses.iter().zip(&read) {
+        assert!((t - t_read).abs() < 1e-9);
+        assert_eq!(pose, pose_read);
+    }
+    let wheels = log.wheel_states("/wheels").unwrap();
+    assert_eq!(wheels[9].1, WheelState::new(2.0, 2.5));
+    assert_eq!(log.twists("/cmd_vel").unwrap()[0].1.xdot, 0.5);
+    let paths = log.paths("/plan").unwrap();
+    assert_eq!(paths.len(), 1);
+    assert_eq!(paths[0].1.len(), 100);
+    assert_eq!(paths[0].1[99], poses[99].1);
+}
+
+#[test]
+fn mcap_schemaless_channels_through_a_file() {
+    let path = std::env::temp_dir().join("diff_drive_mcap_schemaless.mcap");
+    let mut writer = McapWriter::new(vec![]).unwrap().schemaless();
+    write_run(&mut writer);
+    std::fs::write(&path, writer.finish().unwrap()).unwrap();
+
+    let log = McapLog::load(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert!(log.channels.iter().all(|c| c.schema_name.is_none()));
+    assert_eq!(log.poses("/odom").unwrap().len(), 100);
+    // sequence numbers count up per channel
+    let sequences: Vec<u32> = log
+        .messages
+        .iter()
+        .filter(|m| m.channel_id == log.channel("/odom").unwrap().id)
+        .map(|m| m.sequence)
+        .collect();
+    assert_eq!(sequences, (0..100).collect::<Vec<u32>>());
+}
+
+#[test]
+fn mcap_rejects_bad_input() {
+    let mut writer = McapWriter::new(vec![]).unwrap();
+    writer
+        .write_pose("/odom", 0.0, &Pose2D::new(0.0, 0.0, 0.0))
+        .unwrap();
+    // a topic keeps the kind it was first written with
+    assert!(writer
+        .write_twist("/odom", 0.1, &Twist2D::new(0.0, 0.0, 0.0))
+        .is_err());
+    assert!(writer
+        .write_pose("/odom", -1.0, &Pose2D::new(0.0, 0.0, 0.0))
+        .is_err());
+    let bytes = writer.finish().unwrap();
+
+    let log = McapLog::from_bytes(&bytes).unwrap();
+    assert!(log.poses("/missing").is_err());
+    assert!(log.twists("/odom").is_err());
+    assert!(McapLog::from_bytes(&bytes[..bytes.len() - 4]).is_err());
+    let mut corrupt = bytes.clone();
+    corrupt[60] ^= 0x01;
+    assert!(McapLog::from_bytes(&corrupt).is_err());
+    assert!(McapLog::from_bytes(b"not an mcap file at all").is_err());
+}
@@ -0,0 +1,129 @@
+use diff_drive::ddrive::{EncoderTicks, WheelState};
+use diff_drive::motor_link::{
+    cobs_decode, cobs_encode, loopback, FramedPort, Message, MockMotorController, MotorLink,
+};
+use std::io::Write;
+
+#[test]
+fn cobs_round_trip() {
+    let long: Vec<u8> = (0..600).map(|i| (i % 255 + 1) as u8).collect();
+    let cases: Vec<Vec<u8>> = vec![
+        vec![],
+        vec![0],
+        vec![0, 0],
+        vec![1, 2, 0, 3],
+        vec![0x11, 0x22, 0x00, 0x33],
+        vec![7; 254],
+        vec![7; 255],
+        long,
+    ];
+    for data in cases {
+        let encoded = cobs_encode(&data);
+        assert!(!encoded.contains(&0), "{:?}", data);
+        assert!(encoded.len() <= data.len() + data.len() / 254 + 2);
+        assert_eq!(cobs_decode(&encoded).unwrap(), data);
+    }
+    assert_eq!(
+        cobs_encode(&[0x11, 0x22, 0x00, 0x33]),
+        [3, 0x11, 0x22, 2, 0x33]
+    );
+    assert!(cobs_decode(&[3, 1]).is_err());
+    assert!(cobs_decode(&[2, 0]).is_err());
+}
+
+#[test]
+fn link_drives_the_mock_controller() {
+    let (host, device) = loopback();
+    let mut link = MotorLink::new(host);
+    let mut controller = MockMotorController::new(device, 100.0);
+    let dt = 0.01;
+    for k in 0..100 {
+        let now = k as f64 * dt;
+        link.send_velocity(WheelState::new(2.0, -1.0), now).unwrap();
+        controller.step(now, dt).unwrap();
+    }
+    link.update(1.0).unwrap();
+    assert!(link.is_alive());
+    assert_eq!(controller.velocity(), WheelState::new(2.0, -1.0));
+    // 100 steps of 10 ms at 2 and -1 rad/s with 100 ticks per radian
+    assert_eq!(link.encoders(), Some(EncoderTicks::new(200, -100)));
+    assert_eq!(link.stats().frames_received, 100);
+    assert_eq!(link.stats().bad_frames, 0);
+    assert_eq!(controller.stats().lost_frames, 0);
+}
+
+#[test]
+fn link_recovers_from_line_noise() {
+    let (mut host, mut device) = loopback();
+    let noise = host.clone();
+    let mut sender = FramedPort::new(&mut host);
+    let mut receiver = FramedPort::new(&mut device);
+    for _ in 0..3 {
+        sender
+            .send(&Message::Velocity(WheelState::new(1.5, 0.5)))
+            .unwrap();
+    }
+    // a flipped bit in the second frame
+    noise.corrupt_outgoing(20, 0x04);
+    sender.send(&Message::Heartbeat).unwrap();
+    let messages = receiver.receive().unwrap();
+    assert_eq!(
+        messages,
+        vec![
+            Message::Velocity(WheelState::new(1.5, 0.5)),
+            Message::Velocity(WheelState::new(1.5, 0.5)),
+            Message::Heartbeat
+        ]
+    );
+    let stats = receiver.stats();
+    assert_eq!(stats.bad_frames, 1);
+    assert_eq!(stats.lost_frames, 1);
+    assert_eq!(stats.frames_received, 3);
+
+    // a long run without a delimiter is dropped rather than buffered, up
+    // to the delimiter of the frame it ran into
+    sender.stream_mut().write_all(&[0x55; 1000]).unwrap();
+    sender.send(&Message::Heartbeat).unwrap();
+    sender.send(&Message::Heartbeat).unwrap();
+    assert_eq!(receiver.receive().unwrap(), vec![Message::Heartbeat]);
+    assert_eq!(receiver.stats().bad_frames, 2);
+}
+
+#[test]
+fn watchdogs_stop_the_wheels_when_the_link_goes_silent() {
+    let (host, device) = loopback();
+    let cable = host.clone();
+    let mut link = MotorLink::new(host).with_watchdog_timeout(0.3);
+    let mut controller = MockMotorController::new(device, 100.0).with_watchdog_timeout(0.3);
+    let dt = 0.01;
+    let mut now = 0.0;
+    link.send_velocity(WheelState::new(1.0, 1.0), now).unwrap();
+    // heartbeats alone keep the controller driving
+    for _ in 0..100 {
+        now += dt;
+        controller.step(now, dt).unwrap();
+        link.update(now).unwrap();
+    }
+    assert!(link.is_alive());
+    assert_eq!(controller.velocity(), WheelState::new(1.0, 1.0));
+
+    cable.set_connected(false);
+    for _ in 0..50 {
+        now += dt;
+        controller.step(now, dt).unwrap();
+        link.update(now).unwrap();
+    }
+    assert!(!link.is_alive());
+    assert_eq!(controller.velocity(), WheelState::new(0.0, 0.0));
+    assert!(link.send_velocity(WheelState::new(1.0, 1.0), now).is_err());
+
+    // once reconnected the host hears the controller again and can drive
+    cable.set_connected(true);
+    now += dt;
+    controller.step(now, dt).unwrap();
+    link.send_velocity(WheelState::new(0.5, 0.5), now).unwrap();
+    assert!(link.is_alive());
+    now += dt;
+    controller.step(now, dt).unwrap();
+    assert_eq!(controller.velocity(), WheelState::new(0.5, 0.5));
+}
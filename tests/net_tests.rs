@@ -0,0 +1,202 @@
+use diff_drive::ddrive::DiffDrive;
+use diff_drive::net::{Packet, TeleopClient, TeleopServer};
+use diff_drive::rigid2d::{Pose2D, Twist2D};
+use std::io::Read;
+use std::net::{TcpStream, UdpSocket};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const KEY: &[u8] = b"correct horse battery staple";
+
+/// polls the server at a fixed time until it has accepted the given number
+/// of commands, returning the twist of the last poll
+fn poll_until_accepted(server: &mut TeleopServer, now: f64, accepted: u64) -> Twist2D<f64> {
+    for _ in 0..200 {
+        let twist = server.poll(now).unwrap();
+        if server.stats().commands_accepted >= accepted {
+            return twist;
+        }
+        thread::sleep(Duration::from_millis(5));
+    }
+    panic!("the server did not receive the command");
+}
+
+/// the system time in microseconds, which sequence numbers start from
+fn clock_micros() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_micros() as u64
+}
+
+#[test]
+fn packets_round_trip_and_are_authenticated() {
+    let command = Packet::Command(Twist2D::new(0.5, 0.2, 0.0));
+    let bytes = command.encode(3, 42, KEY);
+    assert_eq!(Packet::decode(&bytes, KEY).unwrap(), (command, 3, 42));
+    assert!(Packet::decode(&bytes, b"wrong key").is_err());
+
+    let mut tampered = bytes.clone();
+    tampered[12] ^= 0x01;
+    assert!(Packet::decode(&tampered, KEY).is_err());
+    assert!(Packet::decode(&bytes[..20], KEY).is_err());
+}
+
+#[test]
+fn udp_teleop_and_telemetry() {
+    let mut server = TeleopServer::bind("127.0.0.1:0", KEY)
+        .unwrap()
+        .with_deadman_timeout(0.5);
+    let mut client = TeleopClient::udp(server.local_addr().unwrap(), KEY).unwrap();
+
+    let command = Twist2D::new(0.3, 0.1, 0.0);
+    client.send_command(command).unwrap();
+    assert_eq!(poll_until_accepted(&mut server, 1.0, 1), command);
+
+    let mut robot = DiffDrive::new(0.03, 0.1);
+    robot.set_pose(Pose2D::new(1.0, 2.0, 0.5));
+    assert!(server.publish(1.0, &robot, command).unwrap());
+    assert!(!server.publish(1.05, &robot, command).unwrap());
+    let telemetry = client
+        .receive_telemetry(Duration::from_secs(2))
+        .unwrap()
+        .expect("no telemetry");
+    assert_eq!(telemetry.time, 1.0);
+    assert_eq!(telemetry.pose, Pose2D::new(1.0, 2.0, 0.5));
+    assert_eq!(telemetry.twist, command);
+    assert_eq!(telemetry.wheel_speeds, robot.speeds_from_twist(command));
+
+    // the deadman stops the robot once the commands stop
+    assert_eq!(server.poll(1.4).unwrap(), command);
+    assert_eq!(server.poll(1.6).unwrap(), Twist2D::new(0.0, 0.0, 0.0));
+    assert_eq!(server.stats().deadman_stops, 1);
+}
+
+#[test]
+fn tcp_teleop_and_telemetry() {
+    let mut server = TeleopServer::bind("127.0.0.1:0", KEY).unwrap();
+    let mut client = TeleopClient::tcp(server.local_addr().unwrap(), KEY).unwrap();
+
+    let command = Twist2D::new(-0.2, 0.4, 0.0);
+    client.send_command(command).unwrap();
+    assert_eq!(poll_until_accepted(&mut server, 0.0, 1), command);
+    assert_eq!(server.tcp_clients(), 1);
+
+    let robot = DiffDrive::new(0.03, 0.1);
+    for step in 0..3 {
+        server.publish(step as f64 * 0.1, &robot, command).unwrap();
+    }
+    for step in 0..3 {
+        let telemetry = client
+            .receive_telemetry(Duration::from_secs(2))
+            .unwrap()
+            .expect("no telemetry");
+        assert_eq!(telemetry.time, step as f64 * 0.1);
+    }
+    assert_eq!(server.stats().telemetry_sent, 3);
+}
+
+#[test]
+fn rejects_replays_bad_keys_and_fast_commands() {
+    let mut server = TeleopServer::bind("127.0.0.1:0", KEY)
+        .unwrap()
+        .with_command_rate_limit(10.0);
+    let address = server.local_addr().unwrap();
+
+    // a client with the wrong key is ignored
+    let mut intruder = TeleopClient::udp(address, b"guess").unwrap();
+    intruder.send_command(Twist2D::new(0.0, 1.0, 0.0)).unwrap();
+
+    // a recorded command sent twice is only accepted once, also when it is
+    // replayed from another port
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let recorded = Packet::Command(Twist2D::new(0.0, 0.1, 0.0)).encode(5, clock_micros(), KEY);
+    socket.send_to(&recorded, address).unwrap();
+    socket.send_to(&recorded, address).unwrap();
+    let other = UdpSocket::bind("127.0.0.1:0").unwrap();
+    other.send_to(&recorded, address).unwrap();
+
+    // commands faster than 10 Hz are dropped
+    let mut client = TeleopClient::udp(address, KEY).unwrap();
+    client.send_command(Twist2D::new(0.0, 0.2, 0.0)).unwrap();
+    thread::sleep(Duration::from_millis(50));
+    server.poll(1.05).unwrap();
+    client.send_command(Twist2D::new(0.0, 0.3, 0.0)).unwrap();
+    thread::sleep(Duration::from_millis(50));
+    let twist = server.poll(1.2).unwrap();
+
+    let stats = server.stats();
+    assert_eq!(stats.packets_rejected, 4);
+    assert_eq!(stats.commands_accepted, 2);
+    assert_eq!(stats.commands_rate_limited, 1);
+    assert_eq!(twist, Twist2D::new(0.0, 0.3, 0.0));
+}
+
+#[test]
+fn tcp_telemetry_needs_authentication() {
+    let mut server = TeleopServer::bind("127.0.0.1:0", KEY).unwrap();
+    let address = server.local_addr().unwrap();
+    let mut client = TeleopClient::tcp(address, KEY).unwrap();
+    let mut silent = TcpStream::connect(address).unwrap();
+    let mut intruder = TeleopClient::tcp(address, b"guess").unwrap();
+    for _ in 0..200 {
+        server.poll(0.0).unwrap();
+        if server.tcp_clients() == 1 && server.stats().packets_rejected == 1 {
+            break;
+        }
+        thread::sleep(Duration::from_millis(5));
+    }
+    assert_eq!(server.tcp_clients(), 1);
+
+    // only the authenticated client receives telemetry
+    let robot = DiffDrive::new(0.03, 0.1);
+    server
+        .publish(0.0, &robot, Twist2D::new(0.0, 0.0, 0.0))
+        .unwrap();
+    assert_eq!(server.stats().telemetry_sent, 1);
+    assert!(client
+        .receive_telemetry(Duration::from_secs(2))
+        .unwrap()
+        .is_some());
+    assert!(intruder
+        .receive_telemetry(Duration::from_millis(100))
+        .unwrap()
+        .is_none());
+
+    // the others are disconnected once they had time to authenticate
+    server.poll(3.0).unwrap();
+    assert_eq!(server.stats().connections_dropped, 2);
+    silent
+        .set_read_timeout(Some(Duration::from_secs(2)))
+        .unwrap();
+    assert_eq!(silent.read(&mut [0u8; 16]).unwrap(), 0);
+}
+
+#[test]
+fn rejects_replays_to_a_restarted_server() {
+    let command = Packet::Command(Twist2D::new(0.0, 0.1, 0.0));
+    let old = command.encode(5, clock_micros() - 60_000_000, KEY);
+    let mut server = TeleopServer::bind("127.0.0.1:0", KEY).unwrap();
+    let recorded = command.encode(5, clock_micros(), KEY);
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    socket
+        .send_to(&recorded, server.local_addr().unwrap())
+        .unwrap();
+    poll_until_accepted(&mut server, 0.0, 1);
+
+    // the server restarts on the same port and the packets are replayed
+    let address = server.local_addr().unwrap();
+    drop(server);
+    let mut server = TeleopServer::bind(address, KEY).unwrap();
+    socket.send_to(&recorded, address).unwrap();
+    socket.send_to(&old, address).unwrap();
+    for _ in 0..200 {
+        server.poll(0.0).unwrap();
+        if server.stats().packets_rejected == 2 {
+            break;
+        }
+        thread::sleep(Duration::from_millis(5));
+    }
+    assert_eq!(server.stats().packets_rejected, 2);
+    assert_eq!(server.stats().commands_accepted, 0);
+}
@@ -0,0 +1,123 @@
+use diff_drive::ddrive::{DiffDrive, DiffDriveConfig, WheelParams};
+use diff_drive::orca::{Orca, OrcaAgent};
+use diff_drive::rigid2d::{Pose2D, Twist2D, Vector2D};
+use diff_drive::sim::MultiSimulator;
+use diff_drive::utils::{almost_equal, normalize_angle};
+
+#[test]
+fn free_agent_keeps_preferred_velocity() {
+    let orca = Orca::new(1.0);
+    let agent = OrcaAgent::new(
+        Pose2D::new(0.0, 0.0, std::f64::consts::FRAC_PI_2),
+        Vector2D::new(0.0, 0.0),
+        Twist2D::new(0.0, 0.5, 0.0),
+        0.3,
+    );
+    let v = orca.velocities(&[agent]);
+    assert!(almost_equal(v[0].x, 0.0, 1e-9));
+    assert!(almost_equal(v[0].y, 0.5, 1e-9));
+
+    // the preferred speed is capped at the top speed
+    let fast = OrcaAgent {
+        preferred: Twist2D::new(0.0, 3.0, 0.0),
+        ..agent
+    };
+    let v = orca.velocities(&[fast]);
+    assert!(almost_equal(v[0].x.hypot(v[0].y), 1.0, 1e-9));
+}
+
+#[test]
+fn head_on_holonomic_agents_pass() {
+    let orca = Orca::new(1.0).with_time_horizon(3.0);
+    let mut agents = vec![
+        OrcaAgent::new(
+            Pose2D::new(-2.0, 0.01, 0.0),
+            Vector2D::new(0.0, 0.0),
+            Twist2D::new(0.0, 0.0, 0.0),
+            0.25,
+        ),
+        OrcaAgent::new(
+            Pose2D::new(2.0, 0.0, 0.0),
+            Vector2D::new(0.0, 0.0),
+            Twist2D::new(0.0, 0.0, 0.0),
+            0.25,
+        ),
+    ];
+    let goals = [Vector2D::new(2.0, 0.0), Vector2D::new(-2.0, 0.0)];
+    let dt = 0.1;
+    for _ in 0..100 {
+        for (agent, goal) in agents.iter_mut().zip(goals) {
+            let (dx, dy): (f64, f64) = (goal.x - agent.pose.x, goal.y - agent.pose.y);
+            let distance = dx.hypot(dy);
+            let speed = distance.min(1.0) / distance.max(1e-9);
+            agent.preferred = Twist2D::new(0.0, dx * speed, dy * speed);
+        }
+        let twists = orca.twists(&agents);
+        let velocities = orca.velocities(&agents);
+        for ((agent, twist), v) in agents.iter_mut().zip(twists).zip(velocities) {
+            // with zero heading the body and world frames agree
+            assert!(almost_equal(twist.xdot, v.x, 1e-9));
+            agent.pose.x += v.x * dt;
+            agent.pose.y += v.y * dt;
+            agent.velocity = v;
+        }
+        let gap = (agents[1].pose.x - agents[0].pose.x).hypot(agents[1].pose.y - agents[0].pose.y);
+        assert!(gap >= 0.5 - 1e-3, "gap {}", gap);
+    }
+    for (agent, goal) in agents.iter().zip(goals) {
+        assert!((agent.pose.x - goal.x).hypot(agent.pose.y - goal.y) < 0.05);
+    }
+}
+
+#[test]
+fn differential_drive_robots_swap_on_a_circle() {
+    let config = DiffDriveConfig::new(WheelParams::from_radius(0.05), 0.3);
+    let (mut sim, goals) = MultiSimulator::circle_swap(&config, 8, 3.0, 0.2);
+    let max_wheel_speed = 16.0;
+    let robot: DiffDrive<f64> = config.to_diff_drive();
+    let orca = Orca::new(0.6)
+        .with_time_horizon(3.0)
+        .with_differential_drive(&robot, max_wheel_speed, 0.05, 0.5);
+
+    let dt = 0.05;
+    let mut min_clearance = f64::INFINITY;
+    for _ in 0..1200 {
+        let agents: Vec<OrcaAgent<f64>> = sim
+            .poses()
+            .iter()
+            .zip(sim.velocities())
+            .zip(&goals)
+            .map(|((pose, velocity), goal)| {
+                let (dx, dy) = (goal.x - pose.x, goal.y - pose.y);
+                // keep a little to the right until close to the goal, as
+                // robots meeting in a perfectly symmetric crowd jam
+                let offset = -0.3 * dx.hypot(dy).min(1.0);
+                let heading = normalize_angle(dy.atan2(dx) + offset - pose.theta);
+                let speed = 0.6_f64.min(dx.hypot(dy)) * heading.cos().max(0.0);
+                OrcaAgent::new(
+                    *pose,
+                    *velocity,
+                    Twist2D::new(2.0 * heading, speed, 0.0),
+                    0.2,
+                )
+            })
+            .collect();
+        let twists = orca.twists(&agents);
+        for twist in &twists {
+            let speeds = robot.speeds_from_twist(*twist);
+            assert!(speeds.left.abs() <= max_wheel_speed + 1e-9);
+            assert!(speeds.right.abs() <= max_wheel_speed + 1e-9);
+        }
+        sim.step(&twists, dt);
+        min_clearance = min_clearance.min(sim.min_clearance());
+    }
+    assert!(
+        min_clearance > 0.0,
+        "robots collided, clearance {}",
+        min_clearance
+    );
+    for (pose, goal) in sim.poses().iter().zip(&goals) {
+        let miss = (goal.x - pose.x).hypot(goal.y - pose.y);
+        assert!(miss < 0.1, "robot missed its goal by {}", miss);
+    }
+}
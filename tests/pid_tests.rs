@@ -0,0 +1,157 @@
+use diff_drive::ddrive::WheelState;
+use diff_drive::dynamics::{DriveInput, DynamicDiffDrive, DynamicsParams, MotorParams};
+use diff_drive::pid::{AntiWindup, Pid, RelayAutotuner, TuningRule, WheelPid};
+use diff_drive::utils::almost_equal;
+use std::collections::VecDeque;
+
+fn robot() -> DynamicDiffDrive<f64> {
+    DynamicDiffDrive::new(DynamicsParams {
+        mass: 10.0,
+        inertia: 0.5,
+        wheel_radius: 0.05,
+        wheel_separation: 0.3,
+        wheel_inertia: 1e-3,
+        motor: MotorParams::new(0.02, 0.02, 1.0, 1e-6, 20.0),
+        coulomb_friction: 0.01,
+        viscous_friction: 1e-3,
+        traction_coefficient: 0.8,
+        gravity: 9.81,
+    })
+}
+
+#[test]
+fn pid_proportional_and_feedforward() {
+    let mut pid = Pid::new(2.0, 0.0, 0.0).with_feedforward(0.5);
+    assert!(almost_equal(
+        pid.update(4.0, 1.0, 0.01),
+        2.0 * 3.0 + 0.5 * 4.0,
+        1e-12
+    ));
+
+    let mut clamped = Pid::new(2.0, 0.0, 0.0).with_output_limits(-1.0, 1.0);
+    assert_eq!(clamped.update(4.0, 1.0, 0.01), 1.0);
+    assert_eq!(clamped.update(-4.0, 1.0, 0.01), -1.0);
+}
+
+#[test]
+fn pid_derivative_acts_on_filtered_measurement() {
+    let mut pid = Pid::new(0.0, 0.0, 1.0).with_derivative_filter(0.1);
+    pid.update(0.0, 0.0, 0.01);
+    // a setpoint step does not kick the derivative
+    assert_eq!(pid.update(10.0, 0.0, 0.01), 0.0);
+
+    // a measurement ramp of 1 unit/s converges to -kd through the filter
+    let first = pid.update(10.0, 0.01, 0.01);
+    assert!(first < 0.0 && first > -0.2);
+    let mut out = first;
+    for i in 2..200 {
+        out = pid.update(10.0, 0.01 * i as f64, 0.01);
+    }
+    assert!(almost_equal(out, -1.0, 1e-6));
+}
+
+#[test]
+fn pid_ignores_empty_time_steps() {
+    let mut pid = Pid::new(2.0, 1.0, 0.1);
+    let mut reference = pid;
+    let out = pid.update(1.0, 0.0, 0.01);
+    assert_eq!(reference.update(1.0, 0.0, 0.01), out);
+    assert_eq!(pid.update(1.0, 0.5, 0.0), out);
+    assert_eq!(pid.update(1.0, 0.5, -0.01), out);
+    assert_eq!(pid.update(1.0, 0.2, 0.01), reference.update(1.0, 0.2, 0.01));
+}
+
+#[test]
+fn pid_anti_windup_limits_integral() {
+    let run = |anti_windup| {
+        let mut pid = Pid::new(0.5, 2.0, 0.0)
+            .with_output_limits(-1.0, 1.0)
+            .with_anti_windup(anti_windup);
+        for _ in 0..500 {
+            pid.update(10.0, 0.0, 0.01);
+        }
+        pid
+    };
+    let plain = run(AntiWindup::None);
+    let conditional = run(AntiWindup::ConditionalIntegration);
+    let back = run(AntiWindup::BackCalculation(5.0));
+    assert!(almost_equal(plain.integral(), 100.0, 1e-9));
+    assert!(conditional.integral() < 1.0);
+    assert!(back.integral() < 1.0);
+    assert_eq!(conditional.output(), 1.0);
+    assert_eq!(back.output(), 1.0);
+}
+
+#[test]
+fn pid_bumpless_transfer() {
+    let mut pid = Pid::new(1.5, 3.0, 0.2).with_feedforward(0.1);
+    pid.update(2.0, 1.0, 0.01);
+    pid.set_manual(4.0);
+    assert!(pid.is_manual());
+    assert_eq!(pid.update(2.0, 1.5, 0.01), 4.0);
+
+    pid.set_auto(2.0, 1.5);
+    let out = pid.update(2.0, 1.5, 0.01);
+    assert!(almost_equal(out, 4.0, 1e-9));
+
+    // gain changes do not bump the output either
+    let before = pid.update(2.0, 1.5, 0.01);
+    let integral = pid.integral();
+    pid.set_gains(1.5, 6.0, 0.2);
+    assert_eq!(pid.integral(), integral);
+    assert!(almost_equal(pid.update(2.0, 1.5, 0.01), before, 0.1));
+
+    // so do changes of the proportional and derivative gains
+    let mut unchanged = pid;
+    pid.set_gains(3.0, 6.0, 0.5);
+    let expected = unchanged.update(2.0, 1.5, 0.01);
+    assert!(almost_equal(pid.update(2.0, 1.5, 0.01), expected, 1e-9));
+}
+
+#[test]
+fn pid_relay_autotune_on_motor_model() {
+    let dt = 1e-3;
+    let mut plant = robot();
+    // the wheel speed estimate lags the wheel by 20 ms
+    let mut delay: VecDeque<f64> = VecDeque::from(vec![0.0; 20]);
+    let setpoint = 20.0;
+    let tuner = RelayAutotuner::new(6.0, 3.0, 0.2);
+    let result = tuner
+        .tune(setpoint, dt, 20.0, |v| {
+            plant.step(DriveInput::Voltage(WheelState::new(v, v)), dt);
+            delay.push_back(plant.wheel_speeds().left);
+            delay.pop_front().unwrap()
+        })
+        .unwrap();
+    assert!(result.ultimate_gain > 0.0);
+    assert!(result.ultimate_period > 0.04 && result.ultimate_period < 1.0);
+
+    // the tuned loop settles on a new setpoint
+    let pid = result
+        .pid(TuningRule::ZieglerNicholsPi)
+        .with_output_limits(-12.0, 12.0)
+        .with_anti_windup(AntiWindup::ConditionalIntegration);
+    let mut control = WheelPid::new(pid);
+    let mut plant = robot();
+    let mut delay: VecDeque<WheelState<f64>> = VecDeque::from(vec![WheelState::default(); 20]);
+    let target = WheelState::new(15.0, 25.0);
+    for _ in 0..5000 {
+        let measured = delay.pop_front().unwrap();
+        let voltage = control.update(target, measured, dt);
+        plant.step(DriveInput::Voltage(voltage), dt);
+        delay.push_back(plant.wheel_speeds());
+    }
+    let speeds = plant.wheel_speeds();
+    assert!(almost_equal(speeds.left, 15.0, 0.05));
+    assert!(almost_equal(speeds.right, 25.0, 0.05));
+}
+
+#[test]
+fn pid_autotune_fails_without_oscillation() {
+    let tuner = RelayAutotuner::new(0.0, 1.0, 0.1);
+    assert!(tuner.tune(1.0, 0.01, 1.0, |_| 0.0).is_err());
+
+    let mut tuner = RelayAutotuner::new(0.0, 1.0, 0.1);
+    tuner.measure_cycles = 0;
+    assert!(tuner.tune(0.0, 0.01, 1.0, |u| u).is_err());
+}
@@ -0,0 +1,108 @@
+use diff_drive::mapping::{CellState, OccupancyGrid};
+use diff_drive::planning::{path_length, GridPlanner};
+use diff_drive::rigid2d::Vector2D;
+use diff_drive::utils::almost_equal;
+
+/// a free 40 x 20 grid of 0.1 m cells with a wall at x = 2 m that leaves a
+/// gap above y = 1.5 m
+fn walled_grid() -> OccupancyGrid<f64> {
+    let mut grid = OccupancyGrid::new(40, 20, 0.1, Vector2D::new(0.0, 0.0));
+    for iy in 0..20 {
+        for ix in 0..40 {
+            let delta = if ix == 20 && iy < 15 { 10.0 } else { -10.0 };
+            grid.update_cell(ix, iy, delta);
+        }
+    }
+    grid
+}
+
+#[test]
+fn planner_goes_around_walls() {
+    let grid = walled_grid();
+    let start = Vector2D::new(0.55, 0.55);
+    let goal = Vector2D::new(3.55, 0.55);
+    let path = GridPlanner::new(0.15).plan(&grid, start, goal).unwrap();
+    assert_eq!(path.first().unwrap().x, 0.55);
+    assert_eq!(path.last().unwrap().x, 3.55);
+
+    // through the gap, keeping the robot radius from the end of the wall
+    let top = path.iter().map(|p| p.y).fold(f64::MIN, f64::max);
+    assert!(top > 1.65, "{}", top);
+    let planner = GridPlanner::new(0.15);
+    let traversable = planner.traversable(&grid);
+    for p in &path {
+        let (ix, iy) = grid.world_to_cell(*p).unwrap();
+        assert!(traversable[iy * 40 + ix]);
+    }
+    // about two straight legs over the corner of the inflated wall
+    let length = path_length(&path);
+    assert!(length > 3.8 && length < 4.2, "{}", length);
+}
+
+#[test]
+fn planner_reports_impossible_requests() {
+    let mut grid = walled_grid();
+    for iy in 15..20 {
+        grid.update_cell(20, iy, 20.0);
+    }
+    let planner = GridPlanner::new(0.1);
+    let start = Vector2D::new(0.55, 0.55);
+    assert!(planner
+        .plan(&grid, start, Vector2D::new(3.55, 0.55))
+        .is_err());
+    assert!(planner
+        .plan(&grid, start, Vector2D::new(9.0, 0.55))
+        .is_err());
+    assert!(planner
+        .plan(&grid, start, Vector2D::new(2.05, 0.55))
+        .is_err());
+
+    // unknown space is avoided unless allowed
+    let unknown = OccupancyGrid::new(10, 10, 0.1, Vector2D::new(0.0, 0.0));
+    let (a, b) = (Vector2D::new(0.15, 0.15), Vector2D::new(0.85, 0.85));
+    assert!(planner.plan(&unknown, a, b).is_err());
+    let path = planner
+        .with_unknown_allowed(true)
+        .plan(&unknown, a, b)
+        .unwrap();
+    assert!(almost_equal(path_length(&path), 0.7 * 2f64.sqrt(), 1e-9));
+}
+
+#[test]
+fn grid_round_trips_through_pgm() {
+    let mut grid = walled_grid();
+    for ix in 0..5 {
+        grid.update_cell(ix, 0, 10.0);
+        // back from the clamped free value to even odds
+        grid.update_cell(ix, 1, 2.0);
+    }
+    let loaded = OccupancyGrid::from_pgm(&grid.to_pgm(), 0.1, Vector2D::new(0.0, 0.0)).unwrap();
+    assert_eq!((loaded.width(), loaded.height()), (40, 20));
+    assert_eq!(loaded.states(), grid.states());
+    assert_eq!(loaded.state(20, 0), CellState::Occupied);
+    assert_eq!(loaded.state(0, 1), CellState::Unknown);
+
+    // comments in the header, as written by other tools
+    let mut pgm = b"P5\n# made by hand\n2 1\n255\n".to_vec();
+    pgm.extend_from_slice(&[0, 255]);
+    let small = OccupancyGrid::from_pgm(&pgm, 0.5, Vector2D::new(0.0, 0.0)).unwrap();
+    assert_eq!(small.states(), vec![CellState::Occupied, CellState::Free]);
+    assert!(
+        OccupancyGrid::<f64>::from_pgm(b"P2\n2 1\n255\n0 0", 0.5, Vector2D::new(0.0, 0.0)).is_err()
+    );
+    assert!(
+        OccupancyGrid::<f64>::from_pgm(b"P5\n2 2\n255\n\0", 0.5, Vector2D::new(0.0, 0.0)).is_err()
+    );
+
+    // a header whose size overflows or does not match the pixels is
+    // rejected before anything is allocated
+    for header in [
+        "P5\n18446744073709551615 2\n255\n",
+        "P5\n4294967296 4294967296\n255\n",
+        "P5\n1 1\n255\n",
+    ] {
+        let mut pgm = header.as_bytes().to_vec();
+        pgm.extend_from_slice(&[0, 255]);
+        assert!(OccupancyGrid::<f64>::from_pgm(&pgm, 0.5, Vector2D::new(0.0, 0.0)).is_err());
+    }
+}
@@ -0,0 +1,158 @@
+use diff_drive::ddrive::{DiffDrive, WheelState};
+use diff_drive::mapping::OccupancyGrid;
+use diff_drive::plot::{Color, Image, Plot};
+use diff_drive::rigid2d::{Pose2D, Vector2D};
+use diff_drive::scan::LaserScan;
+use diff_drive::trajectory::Path;
+use std::f64::consts::PI;
+
+fn odometry_trace() -> Vec<Pose2D<f64>> {
+    let mut robot = DiffDrive::new(0.033, 0.16);
+    (1..=200)
+        .map(|k| robot.forward_kinematics(WheelState::new(0.5 * k as f64, 0.55 * k as f64)))
+        .collect()
+}
+
+fn example_plot() -> Plot {
+    let mut grid = OccupancyGrid::new(40, 30, 0.1, Vector2D::new(-0.5, -0.5));
+    let scan = LaserScan::new(0.0, PI / 2.0, PI / 40.0, 5.0, vec![2.0; 21]);
+    for _ in 0..5 {
+        grid.integrate_scan(Pose2D::new(0.0, 0.0, 0.0), &scan);
+    }
+    let trace = odometry_trace();
+    let rollouts: Vec<Vec<Pose2D<f64>>> = (-2..=2)
+        .map(|i| {
+            (0..10)
+                .map(|k| {
+                    let s = k as f64 * 0.05;
+                    let w = i as f64 * 0.5;
+                    Pose2D::new(1.0 + s * (w * s).cos(), 1.0 + s * (w * s).sin(), w * s)
+                })
+                .collect()
+        })
+        .collect();
+
+    let mut plot = Plot::new(480, 360).with_title("odometry & map");
+    plot.add_grid(&grid)
+        .add_path(&Path::semi_circle(1.0, 30), "reference")
+        .add_pose_trace(&trace, 10, "odometry")
+        .add_footprint(*trace.last().unwrap(), 0.2, 0.16, "robot")
+        .add_rollouts(&rollouts, Some(3), "rollouts")
+        .add_line(
+            &[Vector2D::new(0.0, 2.0), Vector2D::new(1.0, 2.0)],
+            Color::BLACK,
+            "",
+        );
+    plot
+}
+
+/// decodes a PNG with stored deflate blocks, as written by the crate
+fn decode_png(png: &[u8]) -> (u32, u32, Vec<u8>) {
+    assert_eq!(
+        png[..8],
+        [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n']
+    );
+    let (mut offset, mut width, mut height, mut zlib) = (8, 0, 0, vec![]);
+    while offset < png.len() {
+        let len = u32::from_be_bytes(png[offset..offset + 4].try_into().unwrap()) as usize;
+        let kind = &png[offset + 4..offset + 8];
+        let data = &png[offset + 8..offset + 8 + len];
+        let crc = u32::from_be_bytes(png[offset + 8 + len..offset + 12 + len].try_into().unwrap());
+        assert_eq!(
+            diff_drive::utils::crc32(&png[offset + 4..offset + 8 + len]),
+            crc
+        );
+        match kind {
+            b"IHDR" => {
+                width = u32::from_be_bytes(data[..4].try_into().unwrap());
+                height = u32::from_be_bytes(data[4..8].try_into().unwrap());
+                assert_eq!(data[8..10], [8, 2]);
+            }
+            b"IDAT" => zlib.extend_from_slice(data),
+            _ => {}
+        }
+        offset += 12 + len;
+    }
+    let mut raw = vec![];
+    let mut at = 2;
+    loop {
+        let last = zlib[at] & 1 == 1;
+        let len = u16::from_le_bytes([zlib[at + 1], zlib[at + 2]]) as usize;
+        raw.extend_from_slice(&zlib[at + 5..at + 5 + len]);
+        at += 5 + len;
+        if last {
+            break;
+        }
+    }
+    (width, height, raw)
+}
+
+#[test]
+fn plot_svg_has_every_item() {
+    let svg = example_plot().to_svg();
+    assert!(svg.starts_with("<svg"));
+    assert!(svg.trim_end().ends_with("</svg>"));
+    assert!(svg.contains(r#"width="480" height="360""#));
+    assert!(svg.contains("odometry &amp; map"));
+    for label in [
+        "reference",
+        "odometry",
+        "robot",
+        "rollouts",
+        "rollouts (chosen)",
+    ] {
+        assert!(svg.contains(&format!(">{}</text>", label)), "{}", label);
+    }
+    assert!(svg.contains("stroke-dasharray"));
+    assert!(svg.contains("x [m]"));
+    assert!(svg.contains(" m</text>"));
+    // occupied cells of the grid
+    assert!(svg.contains(r##"fill="#000000""##));
+    assert!(!svg.contains("NaN"));
+}
+
+#[test]
+fn plot_png_matches_rendered_image() {
+    let plot = example_plot();
+    let image = plot.render();
+    let (width, height, raw) = decode_png(&plot.to_png());
+    assert_eq!((width, height), (480, 360));
+    assert_eq!(raw.len(), (3 * width as usize + 1) * height as usize);
+    for (y, row) in raw.chunks(3 * width as usize + 1).enumerate() {
+        assert_eq!(row[0], 0);
+        for x in [0, 100, 240, 479] {
+            let c = image.pixel(x, y as u32);
+            assert_eq!(row[1 + 3 * x as usize..4 + 3 * x as usize], [c.r, c.g, c.b]);
+        }
+    }
+
+    let count = |color: Color| {
+        (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .filter(|&(x, y)| image.pixel(x, y) == color)
+            .count()
+    };
+    assert!(count(Color::PALETTE[0]) > 50, "reference path");
+    assert!(count(Color::PALETTE[1]) > 100, "odometry trace");
+    assert!(count(Color::BLACK) > 500, "axes, text and occupied cells");
+    assert_eq!(image.pixel(0, 0), Color::WHITE);
+}
+
+#[test]
+fn plot_saves_by_extension() {
+    let dir = std::env::temp_dir();
+    let plot = example_plot();
+    for name in ["plot.svg", "plot.png"] {
+        let path = dir.join(format!("diff_drive_{}", name));
+        plot.save(&path).unwrap();
+        assert!(std::fs::metadata(&path).unwrap().len() > 1000);
+        std::fs::remove_file(&path).unwrap();
+    }
+    assert!(plot.save(dir.join("plot.bmp")).is_err());
+
+    // an empty plot still renders axes around a default window
+    let empty = Plot::new(100, 80);
+    assert!(empty.to_svg().contains("<polyline"));
+    let image: Image = empty.render();
+    assert_eq!((image.width(), image.height()), (100, 80));
+}
@@ -0,0 +1,230 @@
+use diff_drive::linalg::{identity3, Matrix3};
+use diff_drive::pose_graph::{OptimizerConfig, PoseGraph, RobustKernel, Solver};
+use diff_drive::rigid2d::{Pose2D, Transform2D, Vector2D};
+use diff_drive::utils::almost_equal;
+use std::f64::consts::PI;
+use std::io::Cursor;
+
+fn information() -> Matrix3<f64> {
+    let mut info = identity3();
+    info[2][2] = 10.0;
+    info
+}
+
+/// the true poses of a robot driving around a 2 x 2 m square
+fn square() -> Vec<Pose2D<f64>> {
+    let mut poses = vec![];
+    for side in 0..4 {
+        for step in 0..4 {
+            let mut tf = Transform2D::new(Vector2D::new(0.0, 0.0), 0.0);
+            for _ in 0..side {
+                tf = tf * Transform2D::new(Vector2D::new(2.0, 0.0), PI / 2.0);
+            }
+            tf = tf * Transform2D::new(Vector2D::new(0.5 * step as f64, 0.0), 0.0);
+            poses.push(tf.to_pose());
+        }
+    }
+    poses
+}
+
+fn relative(a: Pose2D<f64>, b: Pose2D<f64>) -> Transform2D<f64> {
+    Transform2D::from_pose(a).inv() * Transform2D::from_pose(b)
+}
+
+/// builds a graph with drifting odometry edges and a loop closure back to the start
+fn drifting_square() -> (PoseGraph<f64>, Vec<Pose2D<f64>>) {
+    let truth = square();
+    let mut graph = PoseGraph::new();
+    let drift = Transform2D::new(Vector2D::new(0.02, 0.01), 0.03);
+
+    let mut estimate = Transform2D::from_pose(truth[0]);
+    graph.add_node(truth[0]);
+    for i in 1..truth.len() {
+        let odom = relative(truth[i - 1], truth[i]) * drift;
+        estimate = estimate * odom;
+        graph.add_node(estimate.to_pose());
+        graph.add_edge(i - 1, i, odom, information()).unwrap();
+    }
+    let last = truth.len() - 1;
+    graph
+        .add_edge(last, 0, relative(truth[last], truth[0]), information())
+        .unwrap();
+    (graph, truth)
+}
+
+fn max_error(graph: &PoseGraph<f64>, truth: &[Pose2D<f64>]) -> f64 {
+    graph
+        .nodes()
+        .iter()
+        .zip(truth)
+        .map(|(a, b)| Vector2D::new(a.x, a.y).distance(Vector2D::new(b.x, b.y)))
+        .fold(0.0, f64::max)
+}
+
+#[test]
+fn robust_kernel_weights() {
+    assert_eq!(RobustKernel::None.weight(100.0), 1.0);
+    assert_eq!(RobustKernel::Huber(1.0).weight(0.25), 1.0);
+    assert!(almost_equal(
+        RobustKernel::Huber(1.0).weight(4.0),
+        0.5,
+        1e-12
+    ));
+    assert!(almost_equal(
+        RobustKernel::Cauchy(1.0).weight(1.0),
+        0.5,
+        1e-12
+    ));
+    assert!(RobustKernel::Cauchy(1.0).cost(100.0) < RobustKernel::Huber(1.0).cost(100.0));
+}
+
+#[test]
+fn pose_graph_gauss_newton_and_levenberg_marquardt() {
+    for solver in [Solver::GaussNewton, Solver::LevenbergMarquardt] {
+        let (mut graph, truth) = drifting_square();
+        let before = max_error(&graph, &truth);
+        let config = OptimizerConfig {
+            solver,
+            ..OptimizerConfig::default()
+        };
+        let summary = graph.optimize(&config).unwrap();
+        assert!(summary.final_cost < summary.initial_cost);
+        assert!(max_error(&graph, &truth) < before / 4.0);
+        assert_eq!(graph.nodes()[0].x, 0.0);
+    }
+}
+
+#[test]
+fn pose_graph_converged_only_on_tolerance() {
+    let (mut graph, _) = drifting_square();
+    let config = OptimizerConfig {
+        max_iterations: 50,
+        tolerance: -1.0,
+        ..OptimizerConfig::default()
+    };
+    let summary = graph.optimize(&config).unwrap();
+    assert_eq!(summary.iterations, 50);
+    assert!(!summary.converged);
+    assert!(!summary.stalled);
+}
+
+#[test]
+fn pose_graph_exact_measurements() {
+    let truth = square();
+    let mut graph = PoseGraph::new();
+    for pose in &truth {
+        graph.add_node(Pose2D::new(pose.x + 0.1, pose.y - 0.1, pose.theta + 0.05));
+    }
+    graph.set_fixed(0, false);
+    graph.add_node(truth[0]);
+    graph.set_fixed(truth.len(), true);
+    graph
+        .add_edge(truth.len(), 0, relative(truth[0], truth[0]), information())
+        .unwrap();
+    for i in 1..truth.len() {
+        graph
+            .add_edge(i - 1, i, relative(truth[i - 1], truth[i]), information())
+            .unwrap();
+    }
+    let summary = graph.optimize(&OptimizerConfig::default()).unwrap();
+    assert!(summary.final_cost < 1e-12);
+    assert!(summary.converged && !summary.stalled);
+    assert!(max_error(&graph, &truth) < 1e-6);
+}
+
+#[test]
+fn pose_graph_robust_kernel_rejects_bad_loop_closure() {
+    let bad_closure = |graph: &mut PoseGraph<f64>| {
+        let wrong = Transform2D::new(Vector2D::new(1.5, -1.0), 1.0);
+        graph.add_edge(8, 0, wrong, information()).unwrap();
+    };
+
+    let (mut plain, truth) = drifting_square();
+    bad_closure(&mut plain);
+    plain.optimize(&OptimizerConfig::default()).unwrap();
+
+    let (mut robust, _) = drifting_square();
+    bad_closure(&mut robust);
+    let config = OptimizerConfig {
+        kernel: RobustKernel::Cauchy(0.5),
+        ..OptimizerConfig::default()
+    };
+    robust.optimize(&config).unwrap();
+
+    let (mut good, _) = drifting_square();
+    good.optimize(&OptimizerConfig::default()).unwrap();
+
+    let robust_error = max_error(&robust, &truth);
+    assert!(robust_error < max_error(&plain, &truth));
+    assert!(robust_error < 2.0 * max_error(&good, &truth));
+}
+
+#[test]
+fn pose_graph_add_edge_checks_nodes() {
+    let mut graph = PoseGraph::new();
+    graph.add_node(Pose2D::new(0.0, 0.0, 0.0));
+    let tf = Transform2D::new(Vector2D::new(1.0, 0.0), 0.0);
+    assert!(graph.add_edge(0, 1, tf, information()).is_err());
+}
+
+#[test]
+fn pose_graph_g2o_round_trip() {
+    let text = "\
+# a tiny graph
+VERTEX_SE2 10 0 0 0
+VERTEX_SE2 11 1.1 0.1 0.05
+VERTEX_SE2 12 2 0.2 0.1
+EDGE_SE2 10 11 1 0 0 100 0 0 100 0 1000
+EDGE_SE2 11 12 1 0 0 100 0 0 100 0 1000
+EDGE_SE2 10 12 2 0 0 100 0 0 100 0 1000
+FIX 10
+";
+    let mut graph: PoseGraph<f64> = PoseGraph::from_g2o(Cursor::new(text)).unwrap();
+    assert_eq!(graph.nodes().len(), 3);
+    assert_eq!(graph.edges().len(), 3);
+    assert_eq!(graph.edges()[2].from, 0);
+    assert_eq!(graph.edges()[2].information[2][2], 1000.0);
+
+    graph.optimize(&OptimizerConfig::default()).unwrap();
+    assert!(almost_equal(graph.nodes()[1].x, 1.0, 1e-6));
+    assert!(almost_equal(graph.nodes()[2].y, 0.0, 1e-6));
+
+    let mut out = vec![];
+    graph.to_g2o(&mut out).unwrap();
+    let reread: PoseGraph<f64> = PoseGraph::from_g2o(Cursor::new(out)).unwrap();
+    assert_eq!(reread.nodes().len(), 3);
+    assert_eq!(reread.edges().len(), 3);
+    assert!(almost_equal(reread.nodes()[2].x, graph.nodes()[2].x, 1e-9));
+
+    let filename = std::env::temp_dir().join("diff_drive_pose_graph_test.g2o");
+    let filename = filename.to_str().unwrap();
+    graph.write_g2o(filename).unwrap();
+    assert_eq!(
+        PoseGraph::<f64>::read_g2o(filename).unwrap().edges().len(),
+        3
+    );
+}
+
+#[test]
+fn pose_graph_g2o_errors() {
+    let unknown = "VERTEX_SE2 0 0 0 0\nEDGE_SE2 0 5 1 0 0 1 0 0 1 0 1\n";
+    assert!(PoseGraph::<f64>::from_g2o(Cursor::new(unknown)).is_err());
+    let unsupported = "VERTEX_XY 0 1 2\n";
+    assert!(PoseGraph::<f64>::from_g2o(Cursor::new(unsupported)).is_err());
+    let duplicate = "VERTEX_SE2 0 0 0 0\nVERTEX_SE2 0 1 0 0\n";
+    assert!(PoseGraph::<f64>::from_g2o(Cursor::new(duplicate)).is_err());
+
+    // ids are integers, not rounded or clamped floats
+    for bad in [
+        "VERTEX_SE2 -1 0 0 0\n",
+        "VERTEX_SE2 1.5 0 0 0\n",
+        "VERTEX_SE2 0 0 0 0\nVERTEX_SE2 1 1 0 0\nEDGE_SE2 0 0.9 1 0 0 1 0 0 1 0 1\n",
+        "VERTEX_SE2 0 0 0 0\nFIX -0\n",
+    ] {
+        let result = PoseGraph::<f64>::from_g2o(Cursor::new(bad));
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("invalid vertex id"));
+    }
+}
@@ -0,0 +1,92 @@
+use diff_drive::geometry::Polygon;
+use diff_drive::rigid2d::{Transform2D, Twist2D, Vector2D};
+use diff_drive::safety::{SafetySupervisor, Zone};
+use diff_drive::scan::LaserScan;
+use diff_drive::utils::almost_equal;
+
+fn forward(speed: f64) -> Twist2D<f64> {
+    Twist2D::new(0.0, speed, 0.0)
+}
+
+/// a 0.6 m by 0.4 m robot with one obstacle point
+fn supervisor(obstacle: Vector2D<f64>) -> SafetySupervisor<f64> {
+    let mut supervisor = SafetySupervisor::new(Polygon::rectangle(0.6, 0.4));
+    supervisor.set_obstacles(vec![obstacle]);
+    supervisor
+}
+
+#[test]
+fn stops_for_close_obstacles_but_lets_the_robot_back_away() {
+    let supervisor = supervisor(Vector2D::new(0.5, 0.0));
+    let report = supervisor.limit(forward(0.5));
+    assert_eq!(report.zone, Zone::Protective);
+    assert_eq!(report.command.xdot, 0.0);
+    assert!(almost_equal(report.time_to_collision.unwrap(), 0.3, 0.02));
+    assert!(report.is_limited());
+    assert!(report
+        .to_string()
+        .starts_with("stopped in the protective zone"));
+
+    let report = supervisor.limit(forward(-0.5));
+    assert_eq!(report.zone, Zone::Clear);
+    assert_eq!(report.command.xdot, -0.5);
+    assert_eq!(report.to_string(), "clear");
+    assert_eq!(supervisor.limit(forward(0.0)).zone, Zone::Clear);
+}
+
+#[test]
+fn stops_for_obstacles_on_the_bumper_and_invalid_commands() {
+    // on the front edge and just inside it
+    for x in [0.3, 0.25] {
+        let supervisor = supervisor(Vector2D::new(x, 0.0));
+        let report = supervisor.limit(forward(0.5));
+        assert_eq!(report.zone, Zone::Protective);
+        assert_eq!(report.time_to_collision, Some(0.0));
+        assert_eq!(supervisor.limit(forward(-0.5)).zone, Zone::Clear);
+    }
+
+    let supervisor = supervisor(Vector2D::new(5.0, 0.0));
+    let report = supervisor.limit(Twist2D::new(0.0, f64::NAN, 0.0));
+    assert_eq!(report.zone, Zone::Protective);
+    assert_eq!(report.command, Twist2D::new(0.0, 0.0, 0.0));
+    assert_eq!(report.to_string(), "stopped, the command is invalid");
+}
+
+#[test]
+fn zones_grow_with_speed() {
+    let supervisor = supervisor(Vector2D::new(1.5, 0.0));
+    assert_eq!(supervisor.limit(forward(0.2)).zone, Zone::Clear);
+
+    // the footprint reaches the margin after 1.15 m
+    let report = supervisor.limit(forward(1.0));
+    assert_eq!(report.zone, Zone::Warning);
+    assert!(almost_equal(report.scale, 0.2 + 0.8 * 0.65, 0.02));
+    assert!(almost_equal(report.command.xdot, report.scale, 1e-12));
+
+    assert_eq!(supervisor.limit(forward(2.0)).zone, Zone::Warning);
+    assert_eq!(supervisor.limit(forward(3.0)).zone, Zone::Protective);
+
+    // braking at 2 m/s^2 from 1 m/s pushes both horizons out by 0.5 s
+    let braking = supervisor.clone().with_deceleration(2.0);
+    let report = braking.limit(forward(1.0));
+    assert_eq!(report.zone, Zone::Warning);
+    assert!(almost_equal(report.scale, 0.2 + 0.8 * 0.15, 0.02));
+}
+
+#[test]
+fn checks_turns_against_a_scan() {
+    // one beam to the left from a sensor 0.2 m ahead of the center, hitting
+    // 0.1 m beside the robot
+    let scan = LaserScan::new(std::f64::consts::FRAC_PI_2, 0.1, 0.05, 10.0, vec![0.3]);
+    let mut supervisor = SafetySupervisor::new(Polygon::rectangle(0.6, 0.4));
+    supervisor.set_scan(&scan, Transform2D::new(Vector2D::new(0.2, 0.0), 0.0));
+    let obstacle = supervisor.obstacles()[0];
+    assert!(almost_equal(obstacle.x, 0.2, 1e-12));
+    assert!(almost_equal(obstacle.y, 0.3, 1e-12));
+
+    // driving past it is fine, turning into it is not
+    assert_eq!(supervisor.limit(forward(0.5)).zone, Zone::Clear);
+    let report = supervisor.limit(Twist2D::new(1.0, 0.0, 0.0));
+    assert_eq!(report.zone, Zone::Protective);
+    assert!(almost_equal(report.obstacle.unwrap().y, 0.3, 1e-12));
+}
@@ -0,0 +1,186 @@
+use diff_drive::ddrive::{DiffDrive, EncoderTicks, WheelParams, WheelState};
+use diff_drive::rigid2d::{Transform2D, Twist2D, Vector2D};
+use diff_drive::scalar::{Scalar, Q16};
+use diff_drive::utils::{almost_equal, normalize_angle};
+
+fn q(x: f64) -> Q16 {
+    Q16::from_f64(x)
+}
+
+#[test]
+fn q16_arithmetic() {
+    assert_eq!(q(1.5).to_bits(), 3 << 15);
+    assert_eq!(q(1.5) + q(2.25), q(3.75));
+    assert_eq!(q(1.5) - q(2.25), q(-0.75));
+    assert_eq!(q(1.5) * q(-2.25), q(-3.375));
+    assert_eq!(q(3.375) / q(1.5), q(2.25));
+    assert_eq!(q(7.5) % q(2.0), q(1.5));
+    assert_eq!(Q16::from_int(3), q(3.0));
+    assert!(almost_equal(q(1.0 / 3.0).to_f64(), 1.0 / 3.0, 1e-5));
+
+    // saturation instead of overflow
+    assert_eq!(q(30000.0) + q(30000.0), Q16::MAX);
+    assert_eq!(q(-200.0) * q(200.0), Q16::MIN);
+    assert_eq!(q(1.0) / q(0.0), Q16::MAX);
+    assert_eq!(q(1e9), Q16::MAX);
+
+    // num_traits conversions
+    let two: Q16 = num_traits::NumCast::from(2).unwrap();
+    assert_eq!(two, q(2.0));
+    assert!(<Q16 as num_traits::NumCast>::from(1e6).is_none());
+    assert_eq!(format!("{}", q(-0.5)), "-0.5");
+}
+
+#[test]
+fn q16_trig_accuracy() {
+    let mut worst: f64 = 0.0;
+    for i in -2000..=2000 {
+        let x = i as f64 * 0.005;
+        worst = worst
+            .max((q(x).sin().to_f64() - q(x).to_f64().sin()).abs())
+            .max((q(x).cos().to_f64() - q(x).to_f64().cos()).abs());
+    }
+    assert!(worst < 3e-5, "sin/cos error {}", worst);
+
+    let mut worst: f64 = 0.0;
+    for i in -40..=40 {
+        for j in -40..=40 {
+            let (y, x) = (q(i as f64 * 0.25), q(j as f64 * 0.25));
+            let expected = y.to_f64().atan2(x.to_f64());
+            let error = (y.atan2(x).to_f64() - expected).abs();
+            worst = worst.max(error);
+        }
+    }
+    assert!(worst < 3e-5, "atan2 error {}", worst);
+
+    for x in [0.0, 1e-4, 0.5, 2.0, 100.0, 30000.0] {
+        let expected = q(x).to_f64().sqrt();
+        assert!(almost_equal(q(x).sqrt().to_f64(), expected, 3e-5));
+    }
+    assert!(almost_equal(q(0.5).acos().to_f64(), 0.5f64.acos(), 1e-4));
+}
+
+#[test]
+fn q16_rigid_body_math() {
+    let v = Vector2D::new(q(3.0), q(4.0));
+    assert_eq!(v.magnitude(), q(5.0));
+    assert!(almost_equal(
+        normalize_angle(q(7.0)).to_f64(),
+        7.0 - 2.0 * std::f64::consts::PI,
+        1e-4
+    ));
+
+    let twists = [
+        (0.0, 1.0, 0.0),
+        (0.003, 0.01, 0.0),
+        (-1.2, 0.7, 0.2),
+        (2.5, -0.3, 0.4),
+    ];
+    let start = Transform2D::new(Vector2D::new(1.0, -2.0), 0.4);
+    let start_q = Transform2D::new(Vector2D::new(q(1.0), q(-2.0)), q(0.4));
+    for (w, x, y) in twists {
+        let tf = start * start.integrate_twist(Twist2D::new(w, x, y));
+        let tf_q = start_q * start_q.integrate_twist(Twist2D::new(q(w), q(x), q(y)));
+        assert!(almost_equal(
+            tf_q.translation().x.to_f64(),
+            tf.translation().x,
+            1e-4
+        ));
+        assert!(almost_equal(
+            tf_q.translation().y.to_f64(),
+            tf.translation().y,
+            1e-4
+        ));
+        assert!(almost_equal(tf_q.rotation().to_f64(), tf.rotation(), 1e-4));
+    }
+}
+
+#[test]
+fn q16_long_odometry_run_matches_f64() {
+    // both robots use the parameters as Q16 represents them
+    let (radius, separation) = (q(0.033), q(0.16));
+    let mut robot = DiffDrive::new(radius.to_f64(), separation.to_f64());
+    let mut robot_q = DiffDrive::new(radius, separation);
+    let (mut left, mut right) = (0.0, 0.0);
+    let mut worst: f64 = 0.0;
+    let mut travelled = 0.0;
+    let mut worst_heading: f64 = 0.0;
+
+    // 20000 encoder updates of a robot weaving around at 100 Hz
+    for k in 0..20000 {
+        let t = k as f64 * 0.01;
+        left += 0.1 + 0.08 * (t / 3.0).sin();
+        right += 0.1 + 0.08 * (t / 5.0).cos();
+        let before = robot.pose();
+        let pose = robot.forward_kinematics(WheelState::new(left, right));
+        let pose_q = robot_q.forward_kinematics(WheelState::new(q(left), q(right)));
+        travelled += ((pose.x - before.x).powi(2) + (pose.y - before.y).powi(2)).sqrt();
+
+        let error =
+            ((pose_q.x.to_f64() - pose.x).powi(2) + (pose_q.y.to_f64() - pose.y).powi(2)).sqrt();
+        worst = worst.max(error);
+        let heading = normalize_angle(pose_q.theta.to_f64() - pose.theta).abs();
+        worst_heading = worst_heading.max(heading);
+    }
+    assert!(travelled > 50.0);
+    // rounding each wheel increment to 2^-16 makes the fixed-point pose
+    // random walk away from the f64 one, by about 4 cm and 0.01 rad here
+    assert!(worst < 0.1, "position error {}", worst);
+    assert!(worst_heading < 0.02, "heading error {}", worst_heading);
+}
+
+#[test]
+fn q16_odometry_wraps_wheel_angles() {
+    assert!(almost_equal(Q16::pi().to_f64(), std::f64::consts::PI, 1e-5));
+    assert!(almost_equal(
+        Q16::tau().to_f64(),
+        std::f64::consts::TAU,
+        1e-5
+    ));
+    assert_eq!(Q16::from_ratio(1, 10_000), q(0.0001));
+    assert_eq!(Q16::MAX.wrapping_add(Q16::EPSILON), Q16::MIN);
+
+    // the wheels turn well past the 32768 rad the fixed-point angle can hold
+    let (radius, separation) = (q(0.0625), q(0.25));
+    let mut robot = DiffDrive::new(radius.to_f64(), separation.to_f64());
+    let mut robot_q = DiffDrive::new(radius, separation);
+    let (mut left, mut right) = (0.0, 0.0);
+    let (mut left_q, mut right_q) = (q(0.0), q(0.0));
+    for _ in 0..100_000 {
+        left += 0.5;
+        right += 0.5;
+        left_q = left_q.wrapping_add(q(0.5));
+        right_q = right_q.wrapping_add(q(0.5));
+        robot.forward_kinematics(WheelState::new(left, right));
+        robot_q.forward_kinematics(WheelState::new(left_q, right_q));
+    }
+    assert!(left > 32768.0);
+    let (pose, pose_q) = (robot.pose(), robot_q.pose());
+    assert!(almost_equal(pose.x, 3125.0, 1e-6));
+    assert!(almost_equal(pose_q.x.to_f64(), pose.x, 1e-3));
+    assert!(almost_equal(pose_q.y.to_f64(), 0.0, 1e-3));
+}
+
+#[test]
+fn q16_odometry_from_long_encoder_counts() {
+    let wheel = WheelParams::new(0.0625, 1024.0, 1.0);
+    let wheel_q = WheelParams::new(q(0.0625), q(1024.0), q(1.0));
+    assert!(almost_equal(
+        wheel_q.count_to_wheel(6000).to_f64(),
+        wheel.count_to_wheel(6000),
+        1e-4
+    ));
+
+    // about 5900 wheel turns, well past 32768 ticks and 32768 rad
+    let mut robot = DiffDrive::from_wheel_params(wheel, wheel, 0.25);
+    let mut robot_q = DiffDrive::from_wheel_params(wheel_q, wheel_q, q(0.25));
+    for k in 1..=6000 {
+        let ticks = EncoderTicks::new(1000 * k, 1000 * k);
+        robot.forward_kinematics_ticks(ticks);
+        robot_q.forward_kinematics_ticks(ticks);
+    }
+    let (pose, pose_q) = (robot.pose(), robot_q.pose());
+    assert!(pose.x > 2000.0);
+    assert!(almost_equal(pose_q.x.to_f64(), pose.x, 0.05));
+    assert!(almost_equal(pose_q.y.to_f64(), 0.0, 0.05));
+}
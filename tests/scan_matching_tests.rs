@@ -0,0 +1,130 @@
+use diff_drive::ddrive::DiffDrive;
+use diff_drive::mapping::OccupancyGrid;
+use diff_drive::pose_graph::PoseGraph;
+use diff_drive::rigid2d::{Pose2D, Transform2D, Vector2D};
+use diff_drive::scan::LaserScan;
+use diff_drive::scan_matching::{
+    icp, match_scans, CorrelativeConfig, CorrelativeMatcher, IcpConfig, IcpMethod,
+};
+use diff_drive::sim::{LidarConfig, Simulator, World};
+use diff_drive::utils::almost_equal;
+use std::f64::consts::PI;
+
+/// a room with an obstacle so that scans constrain every direction
+fn world() -> World<f64> {
+    let mut world = World::new();
+    world.add_rectangle(Vector2D::new(0.0, 0.0), Vector2D::new(6.0, 4.0));
+    world.add_rectangle(Vector2D::new(4.0, 2.5), Vector2D::new(4.5, 3.2));
+    world
+}
+
+fn scan_at(pose: Pose2D<f64>) -> LaserScan<f64> {
+    let lidar = LidarConfig::new(-PI, PI * 0.995, 360, 0.05, 10.0);
+    let mut robot = DiffDrive::new(0.05, 0.3);
+    robot.set_pose(pose);
+    Simulator::new(robot, world(), lidar).scan()
+}
+
+fn assert_transform(tf: Transform2D<f64>, expected: Transform2D<f64>, tol: f64) {
+    assert!(
+        almost_equal(tf.translation().x, expected.translation().x, tol),
+        "x: {} != {}",
+        tf.translation().x,
+        expected.translation().x
+    );
+    assert!(almost_equal(
+        tf.translation().y,
+        expected.translation().y,
+        tol
+    ));
+    assert!(almost_equal(tf.rotation(), expected.rotation(), tol));
+}
+
+#[test]
+fn transform2d_inverse_and_apply() {
+    let tf = Transform2D::new(Vector2D::new(1.0, 2.0), 0.7);
+    let p = tf.apply(Vector2D::new(0.3, -0.4));
+    let q = tf.inv().apply(p);
+    assert!(almost_equal(q.x, 0.3, 1e-12));
+    assert!(almost_equal(q.y, -0.4, 1e-12));
+    let id = tf * tf.inv();
+    assert!(almost_equal(id.translation().magnitude(), 0.0, 1e-12));
+    assert!(almost_equal(id.rotation(), 0.0, 1e-12));
+}
+
+#[test]
+fn icp_point_to_point() {
+    let prev = Pose2D::new(2.0, 2.0, 0.1);
+    let curr = Pose2D::new(2.15, 1.95, 0.18);
+    let truth = Transform2D::from_pose(prev).inv() * Transform2D::from_pose(curr);
+
+    // odometry that drifted from the truth
+    let guess = truth * Transform2D::new(Vector2D::new(0.04, -0.03), 0.03);
+    let config = IcpConfig::new(IcpMethod::PointToPoint);
+    let result = match_scans(&scan_at(prev), &scan_at(curr), guess, &config).unwrap();
+
+    assert_transform(result.transform, truth, 2e-2);
+    assert_transform(guess * result.correction, result.transform, 1e-9);
+    assert!(result.fitness > 0.9);
+    assert!(result.covariance[0][0] > 0.0 && result.covariance[2][2] > 0.0);
+}
+
+#[test]
+fn icp_point_to_line() {
+    let prev = Pose2D::new(2.0, 2.0, 0.0);
+    let curr = Pose2D::new(2.2, 2.1, -0.1);
+    let truth = Transform2D::from_pose(prev).inv() * Transform2D::from_pose(curr);
+    let guess = truth * Transform2D::new(Vector2D::new(-0.05, 0.05), -0.04);
+    let config = IcpConfig::new(IcpMethod::PointToLine);
+    let result = match_scans(&scan_at(prev), &scan_at(curr), guess, &config).unwrap();
+
+    assert!(result.converged);
+    assert_transform(result.transform, truth, 1e-3);
+    assert!(result.fitness > 0.9);
+
+    // a confident match has a tiny covariance, which still inverts
+    let mut graph = PoseGraph::new();
+    let (a, b) = (graph.add_node(prev), graph.add_node(curr));
+    graph.add_scan_match(a, b, &result).unwrap();
+    assert!(graph.edges()[0].information[0][0] > 1e6);
+}
+
+#[test]
+fn icp_rejects_too_few_points() {
+    let points = vec![Vector2D::new(0.0, 0.0), Vector2D::new(1.0, 0.0)];
+    let guess = Transform2D::new(Vector2D::new(0.0, 0.0), 0.0);
+    let config = IcpConfig::new(IcpMethod::PointToPoint);
+    assert!(icp(&points, &points, guess, &config).is_err());
+}
+
+#[test]
+fn correlative_matcher() {
+    // build a map from a few known poses
+    let mut grid = OccupancyGrid::new(140, 100, 0.05, Vector2D::new(-0.5, -0.5));
+    for pose in [Pose2D::new(2.0, 2.0, 0.0), Pose2D::new(3.0, 1.5, 1.0)] {
+        grid.integrate_scan(pose, &scan_at(pose));
+    }
+
+    let truth = Pose2D::new(2.5, 1.8, 0.2);
+    let guess = Transform2D::from_pose(Pose2D::new(2.62, 1.7, 0.3));
+    let matcher = CorrelativeMatcher::new(&grid, CorrelativeConfig::default());
+    let result = matcher.match_scan(&scan_at(truth), guess).unwrap();
+
+    assert_transform(result.transform, Transform2D::from_pose(truth), 0.05);
+    assert!(result.fitness > 0.5);
+    for i in 0..3 {
+        assert!(result.covariance[i][i] > 0.0);
+    }
+
+    // an invalid search config is an error, not a panic
+    let scan = scan_at(truth);
+    for (angular_step, angular_window) in [(0.0, 0.35), (0.01, f64::NAN), (f64::NAN, 0.35)] {
+        let config = CorrelativeConfig {
+            angular_step,
+            angular_window,
+            ..CorrelativeConfig::default()
+        };
+        let matcher = CorrelativeMatcher::new(&grid, config);
+        assert!(matcher.match_scan(&scan, guess).is_err());
+    }
+}
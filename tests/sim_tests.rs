@@ -0,0 +1,81 @@
+use diff_drive::ddrive::{DiffDrive, DiffDriveConfig, WheelParams, WheelState};
+use diff_drive::rigid2d::{Pose2D, Twist2D, Vector2D};
+use diff_drive::sim::{LidarConfig, MultiSimulator, Simulator, World};
+use diff_drive::utils::almost_equal;
+use std::f64::consts::PI;
+
+#[test]
+fn world_raycast() {
+    let mut world = World::new();
+    world.add_rectangle(Vector2D::new(0.0, 0.0), Vector2D::new(4.0, 2.0));
+    let origin = Vector2D::new(1.0, 1.0);
+    assert!(almost_equal(world.raycast(origin, 0.0).unwrap(), 3.0, 1e-9));
+    assert!(almost_equal(
+        world.raycast(origin, PI / 2.0).unwrap(),
+        1.0,
+        1e-9
+    ));
+    assert!(almost_equal(world.raycast(origin, PI).unwrap(), 1.0, 1e-9));
+    assert!(World::<f64>::new().raycast(origin, 0.0).is_none());
+}
+
+#[test]
+fn simulator_step_and_scan() {
+    let mut world = World::new();
+    world.add_wall(Vector2D::new(3.0, -5.0), Vector2D::new(3.0, 5.0));
+    let lidar = LidarConfig::new(-PI / 2.0, PI / 2.0, 3, 0.1, 5.0);
+    let mut sim = Simulator::new(DiffDrive::new(0.1, 0.5), world, lidar);
+
+    let pose = sim.step(WheelState::new(10.0, 10.0));
+    assert!(almost_equal(pose.x, 1.0, 1e-9));
+
+    let scan = sim.scan();
+    assert_eq!(scan.len(), 3);
+    assert!(scan.ranges[0].is_infinite());
+    assert!(almost_equal(scan.ranges[1], 2.0, 1e-9));
+    assert!(scan.ranges[2].is_infinite());
+    assert_eq!(scan.to_points().len(), 1);
+}
+
+#[test]
+fn simulator_run() {
+    let lidar = LidarConfig::new(0.0, 0.0, 1, 0.1, 5.0);
+    let mut robot = DiffDrive::new(0.1, 0.5);
+    robot.set_pose(Pose2D::new(1.0, 1.0, 0.0));
+    let mut sim = Simulator::new(robot, World::new(), lidar);
+    let frames = sim.run(&[WheelState::new(1.0, 1.0); 5]);
+    assert_eq!(frames.len(), 5);
+    assert!(almost_equal(frames[4].0.x, 1.5, 1e-9));
+    assert!(almost_equal(sim.pose().y, 1.0, 1e-9));
+}
+
+#[test]
+fn multi_simulator_circle_swap() {
+    let config = DiffDriveConfig::new(WheelParams::from_radius(0.05), 0.3);
+    let (mut sim, goals) = MultiSimulator::circle_swap(&config, 4, 2.0, 0.2);
+    assert_eq!(sim.len(), 4);
+    assert!(almost_equal(goals[0].x, -2.0, 1e-9));
+    assert!(almost_equal(sim.poses()[1].theta, 1.5 * PI, 1e-9));
+    assert!(almost_equal(
+        sim.min_clearance(),
+        8.0_f64.sqrt() - 0.4,
+        1e-9
+    ));
+
+    // driving straight for a second, every robot gets 0.5 m closer
+    let twists = vec![Twist2D::new(0.0, 0.5, 0.0); 4];
+    for _ in 0..10 {
+        sim.step(&twists, 0.1);
+    }
+    let poses = sim.poses();
+    assert!(almost_equal(poses[0].x, 1.5, 1e-9));
+    assert!(almost_equal(poses[2].x, -1.5, 1e-9));
+    assert!(almost_equal(sim.velocities()[0].x, -0.5, 1e-9));
+    assert!(almost_equal(sim.velocities()[0].y, 0.0, 1e-9));
+
+    // an empty time step keeps the poses and velocities
+    let poses = sim.step(&twists, 0.0);
+    assert!(almost_equal(poses[0].x, 1.5, 1e-9));
+    assert!(almost_equal(sim.velocities()[0].x, -0.5, 1e-9));
+    assert!(sim.velocities()[0].y.is_finite());
+}
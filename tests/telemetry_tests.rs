@@ -0,0 +1,152 @@
+use diff_drive::ddrive::{DiffDrive, WheelState};
+use diff_drive::rigid2d::{Pose2D, Twist2D};
+use diff_drive::telemetry::{LogReader, LogWriter, Record, Session, VERSION};
+use std::fs;
+use std::io::Cursor;
+
+fn temp_log(name: &str) -> std::path::PathBuf {
+    std::env::temp_dir().join(format!("diff_drive_telemetry_{}.ddlog", name))
+}
+
+fn wheels(k: usize) -> WheelState<f64> {
+    let t = k as f64 * 0.01;
+    WheelState::new(0.3 * k as f64 + (t * 2.0).sin(), 0.35 * k as f64)
+}
+
+#[test]
+fn telemetry_round_trip() {
+    let mut writer = LogWriter::new(vec![]).unwrap().with_chunk_size(64);
+    writer
+        .log_encoders(0.0, WheelState::new(0.5, -0.5))
+        .unwrap();
+    writer
+        .log_command(0.1, Twist2D::new(0.2, 0.5, 0.0))
+        .unwrap();
+    writer.log_pose(0.2, Pose2D::new(1.0, 2.0, 0.3)).unwrap();
+    writer.log_sensor(0.3, "imu", &[1, 2, 3, 4]).unwrap();
+    let bytes = writer.into_inner().unwrap();
+
+    let reader = LogReader::new(Cursor::new(bytes)).unwrap();
+    assert_eq!(reader.version(), VERSION);
+    let session = reader.read_session().unwrap();
+    assert!(!session.truncated);
+    assert_eq!(session.records.len(), 4);
+    assert_eq!(
+        session.records[0],
+        Record::Encoders {
+            time: 0.0,
+            wheels: WheelState::new(0.5, -0.5)
+        }
+    );
+    assert_eq!(session.poses(), vec![(0.2, Pose2D::new(1.0, 2.0, 0.3))]);
+    assert_eq!(
+        session.records[3],
+        Record::Sensor {
+            time: 0.3,
+            channel: "imu".to_string(),
+            data: vec![1, 2, 3, 4]
+        }
+    );
+
+    assert!(LogReader::new(Cursor::new(b"not a log".to_vec())).is_err());
+    let mut newer = b"DDLOG\0".to_vec();
+    newer.extend_from_slice(&(VERSION + 1).to_le_bytes());
+    assert!(LogReader::new(Cursor::new(newer)).is_err());
+}
+
+#[test]
+fn telemetry_stops_at_truncated_or_corrupt_chunk() {
+    let mut writer = LogWriter::new(vec![]).unwrap().with_chunk_size(1);
+    for k in 0..3 {
+        writer.log_encoders(k as f64, wheels(k)).unwrap();
+    }
+    let bytes = writer.into_inner().unwrap();
+
+    // a crash part way through the last chunk
+    let cut = bytes[..bytes.len() - 5].to_vec();
+    let session = LogReader::new(Cursor::new(cut))
+        .unwrap()
+        .read_session()
+        .unwrap();
+    assert!(session.truncated);
+    assert_eq!(session.records.len(), 2);
+
+    // a flipped bit in the second chunk hides it and everything after it
+    let chunk_len = (bytes.len() - 8) / 3;
+    let mut corrupt = bytes.clone();
+    corrupt[8 + chunk_len + 20] ^= 0x10;
+    let mut reader = LogReader::new(Cursor::new(corrupt)).unwrap();
+    assert_eq!(reader.next_chunk().unwrap().unwrap().len(), 1);
+    assert!(reader.next_chunk().unwrap().is_none());
+    assert!(reader.truncated());
+    assert_eq!(reader.valid_len(), 8 + chunk_len as u64);
+
+    // a corrupt length is not allocated
+    let mut huge = bytes.clone();
+    huge[8 + chunk_len..8 + chunk_len + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+    let mut reader = LogReader::new(Cursor::new(huge)).unwrap();
+    assert_eq!(reader.next_chunk().unwrap().unwrap().len(), 1);
+    assert!(reader.next_chunk().unwrap().is_none());
+    assert!(reader.truncated());
+}
+
+#[test]
+fn telemetry_append_recovers_after_crash() {
+    let path = temp_log("append");
+    let mut writer = LogWriter::create(&path).unwrap().with_chunk_size(1);
+    for k in 0..3 {
+        writer.log_encoders(k as f64, wheels(k)).unwrap();
+    }
+    drop(writer.into_inner().unwrap());
+
+    // simulate a crash that left half a chunk behind
+    let mut bytes = fs::read(&path).unwrap();
+    let full = bytes.len();
+    bytes.extend_from_within(8..20);
+    fs::write(&path, &bytes).unwrap();
+    assert!(Session::load(&path).unwrap().truncated);
+
+    let mut writer = LogWriter::append(&path).unwrap();
+    assert_eq!(fs::metadata(&path).unwrap().len(), full as u64);
+    writer.log_encoders(3.0, wheels(3)).unwrap();
+    writer.flush().unwrap();
+
+    let session = Session::load(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+    assert!(!session.truncated);
+    let times: Vec<f64> = session.records.iter().map(|r| r.time()).collect();
+    assert_eq!(times, vec![0.0, 1.0, 2.0, 3.0]);
+}
+
+#[test]
+fn telemetry_replay_matches_live_odometry() {
+    let path = temp_log("replay");
+    let mut live = DiffDrive::new(0.033, 0.16);
+    let mut writer = LogWriter::create(&path).unwrap().with_chunk_size(256);
+    for k in 1..500 {
+        let t = k as f64 * 0.01;
+        writer.log_encoders(t, wheels(k)).unwrap();
+        let pose = live.forward_kinematics(wheels(k));
+        writer.log_pose(t, pose).unwrap();
+    }
+    writer.flush().unwrap();
+    let session = Session::load(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+
+    // replaying through a fresh robot reproduces the live poses exactly
+    let mut robot = DiffDrive::new(0.033, 0.16);
+    assert_eq!(session.replay_odometry(&mut robot), session.poses());
+    assert_eq!(robot.pose(), live.pose());
+
+    let mut replayed = DiffDrive::new(0.033, 0.16);
+    session.replay(&mut replayed);
+    assert_eq!(replayed.pose(), live.pose());
+
+    let mut count = 0;
+    session.replay(&mut |record: &Record| {
+        if let Record::Pose { .. } = record {
+            count += 1;
+        }
+    });
+    assert_eq!(count, 499);
+}
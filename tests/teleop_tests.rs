@@ -0,0 +1,74 @@
+use diff_drive::ddrive::DiffDrive;
+use diff_drive::teleop::{AxisCurve, SpeedMode, Teleop};
+use diff_drive::utils::almost_equal;
+
+#[test]
+fn axis_curve_deadzone_and_expo() {
+    let curve = AxisCurve::new(0.1, 0.0);
+    assert_eq!(curve.apply(0.05), 0.0);
+    assert_eq!(curve.apply(-0.1), 0.0);
+    assert!(almost_equal(curve.apply(0.55), 0.5, 1e-12));
+    assert!(almost_equal(curve.apply(-1.0), -1.0, 1e-12));
+    assert!(almost_equal(curve.apply(3.0), 1.0, 1e-12));
+    assert_eq!(curve.apply(f64::NAN), 0.0);
+    assert_eq!(curve.apply(f64::NEG_INFINITY), 0.0);
+
+    // expo softens the middle of the range but keeps the ends
+    let expo = AxisCurve::new(0.0, 0.6);
+    assert!(almost_equal(
+        expo.apply(0.5),
+        0.6 * 0.125 + 0.4 * 0.5,
+        1e-12
+    ));
+    assert!(almost_equal(expo.apply(1.0), 1.0, 1e-12));
+    assert!(expo.apply(-0.5) < 0.0);
+}
+
+#[test]
+fn speed_modes_scale_the_twist() {
+    let teleop = Teleop::new(2.0, 4.0).with_linear_curve(AxisCurve::new(0.0, 0.0));
+    assert_eq!(SpeedMode::from_buttons(true, true), SpeedMode::Slow);
+    assert_eq!(SpeedMode::from_buttons(true, false), SpeedMode::Turbo);
+    assert_eq!(SpeedMode::from_buttons(false, false), SpeedMode::Normal);
+
+    let turbo = teleop.twist(1.0, 0.0, SpeedMode::Turbo);
+    assert_eq!((turbo.xdot, turbo.thetadot), (2.0, 0.0));
+    assert_eq!(teleop.twist(1.0, 0.0, SpeedMode::Normal).xdot, 1.0);
+    assert_eq!(teleop.twist(1.0, 0.0, SpeedMode::Slow).xdot, 0.5);
+    assert_eq!(teleop.twist(0.0, -1.0, SpeedMode::Turbo).thetadot, -4.0);
+}
+
+#[test]
+fn arcade_mixing_keeps_curvature_within_limits() {
+    let robot = DiffDrive::new(0.05, 0.4);
+    let teleop = Teleop::new(1.0, 5.0)
+        .with_linear_curve(AxisCurve::new(0.0, 0.0))
+        .with_angular_curve(AxisCurve::new(0.0, 0.0));
+
+    // straight ahead at the top speed
+    let straight = teleop.arcade(1.0, 0.0, SpeedMode::Turbo, &robot);
+    assert!(almost_equal(straight.left, 20.0, 1e-9));
+    assert!(almost_equal(straight.right, 20.0, 1e-9));
+
+    // full forward and full turn would need 2 m/s on the outer wheel
+    let turning = teleop.arcade(1.0, 1.0, SpeedMode::Turbo, &robot);
+    assert!(almost_equal(turning.right * 0.05, 1.0, 1e-9));
+    let twist = robot.twist_from_speeds(turning);
+    assert!(almost_equal(twist.thetadot / twist.xdot, 5.0, 1e-9));
+
+    // spinning in place
+    let spin = teleop.arcade(0.0, 1.0, SpeedMode::Turbo, &robot);
+    assert!(almost_equal(spin.left, -spin.right, 1e-9));
+}
+
+#[test]
+fn tank_mixing_drives_each_wheel() {
+    let robot = DiffDrive::new(0.05, 0.4);
+    let teleop = Teleop::new(1.0, 5.0).with_linear_curve(AxisCurve::new(0.2, 0.0));
+    let speeds = teleop.tank(1.0, 0.1, SpeedMode::Normal, &robot);
+    assert!(almost_equal(speeds.left, 10.0, 1e-9));
+    assert_eq!(speeds.right, 0.0);
+    let reverse = teleop.tank(-0.6, -1.0, SpeedMode::Turbo, &robot);
+    assert!(almost_equal(reverse.left, -10.0, 1e-9));
+    assert!(almost_equal(reverse.right, -20.0, 1e-9));
+}
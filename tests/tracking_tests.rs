@@ -0,0 +1,97 @@
+use diff_drive::ddrive::DiffDrive;
+use diff_drive::kinematics::{Ackermann, Mecanum};
+use diff_drive::rigid2d::{Pose2D, Vector2D};
+use diff_drive::tracking::{distance_to_path, track, PurePursuit};
+use diff_drive::trajectory::Path;
+use std::f64::consts::PI;
+
+fn waypoints(path: &Path) -> Vec<Vector2D<f64>> {
+    path.to_vec()
+        .iter()
+        .map(|p| Vector2D::new(p.x as f64, p.y as f64))
+        .collect()
+}
+
+#[test]
+fn pure_pursuit_follows_a_circle() {
+    let path = waypoints(&Path::circle(1.0, 100));
+    let robot = DiffDrive::new(0.033, 0.16);
+    let mut controller = PurePursuit::new(0.2, 0.3);
+    let start = Pose2D::new(0.0, 0.0, 0.0);
+    let report = track(&robot, start, &mut controller, &path, 0.02, 60.0).unwrap();
+    assert!(report.reached_goal);
+    // one lap of 2 pi m at up to 0.3 m/s
+    assert!(
+        report.time > 2.0 * PI / 0.3 && report.time < 30.0,
+        "{}",
+        report.time
+    );
+    // pure pursuit cuts the inside of curves by about L^2 / 2R
+    assert!(report.max_error < 0.03, "{}", report.max_error);
+    assert!(report.rms_error < report.max_error);
+    assert!(report.goal_error <= 0.02);
+}
+
+#[test]
+fn pure_pursuit_recovers_from_an_offset_start() {
+    let path = waypoints(&Path::line(3.0, 10));
+    let robot = DiffDrive::new(0.033, 0.16);
+    let start = Pose2D::new(-0.3, 0.5, -PI / 2.0);
+    let mut controller = PurePursuit::new(0.3, 0.25).with_max_angular_speed(1.0);
+    let report = track(&robot, start, &mut controller, &path, 0.02, 60.0).unwrap();
+    assert!(report.reached_goal);
+    let end = report.poses.last().unwrap();
+    assert!(end.theta.abs() < 0.1, "{}", end.theta);
+    assert!(report.max_error > 0.49);
+
+    // the second half of the run is on the line
+    let half = report.poses.len() / 2;
+    for pose in &report.poses[half..] {
+        assert!(distance_to_path(Vector2D::new(pose.x, pose.y), &path) < 0.01);
+    }
+
+    // the turn rate limit holds
+    for pair in report.poses.windows(2) {
+        let turn = (pair[1].theta - pair[0].theta).abs();
+        assert!(turn <= 1.0 * 0.02 + 1e-9);
+    }
+}
+
+#[test]
+fn pure_pursuit_times_out_and_stops_at_the_goal() {
+    let path = waypoints(&Path::line(5.0, 2));
+    let robot = DiffDrive::new(0.033, 0.16);
+    let mut controller = PurePursuit::new(0.3, 0.5);
+    let start = Pose2D::new(0.0, 0.0, 0.0);
+    let report = track(&robot, start, &mut controller, &path, 0.05, 2.0).unwrap();
+    assert!(!report.reached_goal);
+    assert!(report.goal_error > 3.9);
+
+    // continuing the same run reaches the goal and then stops commanding
+    let start = *report.poses.last().unwrap();
+    let report = track(&robot, start, &mut controller, &path, 0.05, 60.0).unwrap();
+    assert!(report.reached_goal);
+    let end = *report.poses.last().unwrap();
+    assert!(controller.command(end, &path).is_none());
+    assert!(controller.command(end, &[]).is_none());
+}
+
+#[test]
+fn pure_pursuit_drives_other_bases() {
+    let path = waypoints(&Path::circle(1.0, 100));
+    let start = Pose2D::new(0.0, 0.0, 0.0);
+    let mecanum = Mecanum::new(0.05, 0.3, 0.3);
+    let mut controller = PurePursuit::new(0.2, 0.3);
+    let report = track(&mecanum, start, &mut controller, &path, 0.02, 60.0).unwrap();
+    assert!(report.reached_goal);
+    assert!(report.max_error < 0.03, "{}", report.max_error);
+
+    // a car can follow the circle, but not turn sharper than its steering allows
+    let car = Ackermann::new(0.05, 0.3, 0.2, 0.6);
+    controller.reset();
+    let report = track(&car, start, &mut controller, &path, 0.02, 60.0).unwrap();
+    assert!(report.reached_goal);
+    let sharp = Ackermann::new(0.05, 0.3, 0.2, 0.1);
+    controller.reset();
+    assert!(track(&sharp, start, &mut controller, &path, 0.02, 60.0).is_err());
+}
@@ -0,0 +1,84 @@
+use diff_drive::ddrive::{DiffDrive, WheelState};
+use diff_drive::rigid2d::{Twist2D, Vector2D};
+use diff_drive::trailer::{ReversingController, Trailer, TrailerTrain};
+use diff_drive::utils::almost_equal;
+use std::f64::consts::PI;
+
+fn train(trailers: Vec<Trailer<f64>>) -> TrailerTrain<f64> {
+    TrailerTrain::new(DiffDrive::new(0.1, 0.5), trailers)
+}
+
+#[test]
+fn trailer_poses_follow_the_hitch() {
+    let mut train = train(vec![Trailer::new(0.3, 1.0), Trailer::new(0.2, 0.8)]);
+    let poses = train.trailer_poses();
+    assert!(almost_equal(poses[0].x, -1.3, 1e-12));
+    assert!(almost_equal(poses[1].x, -2.3, 1e-12));
+
+    train.set_hitch_angle(0, PI / 2.0);
+    let poses = train.trailer_poses();
+    assert!(almost_equal(poses[0].x, -0.3, 1e-12));
+    assert!(almost_equal(poses[0].y, 1.0, 1e-12));
+    assert!(almost_equal(poses[0].theta, -PI / 2.0, 1e-12));
+}
+
+#[test]
+fn trailer_straight_drive_keeps_alignment() {
+    let mut train = train(vec![Trailer::new(0.3, 1.0)]);
+    let pose = train.forward_kinematics(WheelState::new(10.0, 10.0));
+    assert!(almost_equal(pose.x, 1.0, 1e-12));
+    assert!(almost_equal(train.hitch_angles()[0], 0.0, 1e-12));
+    assert!(almost_equal(train.trailer_poses()[0].x, -0.3, 1e-12));
+}
+
+#[test]
+fn trailer_settles_behind_a_turning_tractor() {
+    // driving forward on a circle, the hitch angle converges to its steady state
+    let mut train = train(vec![Trailer::new(0.0, 1.0)]);
+    for _ in 0..2000 {
+        train.drive(Twist2D::new(0.2, 0.5, 0.0), 0.05);
+    }
+    // with an on-axle hitch the trailer axle tracks a circle of radius sqrt(R^2 - L^2)
+    let radius: f64 = 0.5 / 0.2;
+    let expected = (1.0 / radius).asin();
+    assert!(almost_equal(train.hitch_angles()[0], expected, 1e-3));
+    assert!(train.jackknifed().is_none());
+
+    let (v, omega) = train.trailer_velocities(0.5, 0.2)[0];
+    assert!(almost_equal(omega, 0.2, 1e-3));
+    assert!(v < 0.5);
+}
+
+#[test]
+fn trailer_jackknifes_when_reversing_uncontrolled() {
+    let mut train = train(vec![Trailer::new(0.3, 1.0)]).with_jackknife_limit(1.2);
+    train.set_hitch_angle(0, 0.05);
+    for _ in 0..400 {
+        train.drive(Twist2D::new(0.0, -0.5, 0.0), 0.05);
+        if train.jackknifed().is_some() {
+            break;
+        }
+    }
+    assert_eq!(train.jackknifed(), Some(0));
+}
+
+#[test]
+fn reversing_controller_stabilizes_trailer_heading() {
+    let controller = ReversingController::new(1.0, 2.0, 0.6);
+    let mut train = train(vec![Trailer::new(0.3, 1.0)]).with_jackknife_limit(1.2);
+    train.set_hitch_angle(0, 0.3);
+
+    let desired = 0.4;
+    let mut max_hitch: f64 = 0.0;
+    for _ in 0..800 {
+        let cmd = controller.command(&train, desired, -0.3);
+        train.drive(cmd, 0.05);
+        max_hitch = max_hitch.max(train.hitch_angles()[0].abs());
+        assert!(train.jackknifed().is_none());
+    }
+    assert!(max_hitch < 1.0);
+    assert!(almost_equal(train.trailer_poses()[0].theta, desired, 1e-2));
+    assert!(almost_equal(train.hitch_angles()[0], 0.0, 1e-2));
+    let pose = train.pose();
+    assert!(Vector2D::new(pose.x, pose.y).magnitude() > 5.0);
+}
@@ -1,6 +1,7 @@
 use diff_drive::rigid2d::Vector2D;
 use diff_drive::utils::{
-    almost_equal, arange, distance, linspace, rad_per_sec_to_rpm, rpm_to_rad_per_sec,
+    almost_equal, arange, distance, hmac_sha256, linspace, rad_per_sec_to_rpm, rpm_to_rad_per_sec,
+    sha256,
 };
 
 #[test]
@@ -46,3 +47,31 @@ fn test_arange() {
     let x = arange(0.0, 11.0, 1.0);
     println!("arange (0,11,1): {:?}", x);
 }
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[test]
+fn test_sha256_and_hmac() {
+    assert_eq!(
+        hex(&sha256(b"abc")),
+        "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+    );
+    assert_eq!(
+        hex(&sha256(&[b'a'; 1000])),
+        "41edece42d63e8d9bf515a9ba6932e1c20cbc9f5a5d134645adb5db1b9737ea3"
+    );
+    // RFC 4231 test cases 2 and 6
+    assert_eq!(
+        hex(&hmac_sha256(b"Jefe", b"what do ya want for nothing?")),
+        "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+    );
+    assert_eq!(
+        hex(&hmac_sha256(
+            &[0xaa; 131],
+            b"Test Using Larger Than Block-Size Key - Hash Key First"
+        )),
+        "60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54"
+    );
+}